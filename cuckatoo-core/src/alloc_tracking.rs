@@ -0,0 +1,191 @@
+//! Allocation tracking for Cuckatoo pipeline stages
+//!
+//! With the `alloc-track` feature enabled, a counting global allocator is
+//! installed that records bytes allocated and live/peak live bytes. Stages
+//! can then be measured with `track_allocations` (or as phases of a
+//! `PerformanceTimer`). Without the feature, all queries return None.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Total bytes allocated since startup
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Number of allocations since startup
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+/// Currently live bytes
+static LIVE_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Highest live bytes since startup or the last peak reset
+static PEAK_LIVE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Global allocator wrapping the system allocator with allocation counters
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn record_alloc(size: usize) {
+        let size = size as u64;
+        ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_LIVE_BYTES.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn record_dealloc(size: usize) {
+        LIVE_BYTES.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+}
+
+// SAFETY: all allocation work is delegated to the system allocator
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::record_dealloc(layout.size());
+            Self::record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[cfg(feature = "alloc-track")]
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Allocation counters of the whole process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationStats {
+    /// Total bytes allocated since startup
+    pub allocated_bytes: u64,
+    /// Number of allocations since startup
+    pub allocations: u64,
+    /// Currently live bytes
+    pub live_bytes: u64,
+    /// Highest live bytes seen
+    pub peak_live_bytes: u64,
+}
+
+/// Allocations made by a tracked stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StageAllocations {
+    /// Bytes allocated during the stage (including memory freed again)
+    pub allocated_bytes: u64,
+    /// Number of allocations during the stage
+    pub allocations: u64,
+    /// Peak live bytes during the stage above the live bytes at its start
+    pub peak_live_bytes: u64,
+}
+
+/// Check if the counting allocator is installed
+pub fn allocation_tracking_enabled() -> bool {
+    cfg!(feature = "alloc-track")
+}
+
+/// Get the process allocation counters
+///
+/// Returns None unless the `alloc-track` feature is enabled.
+pub fn allocation_stats() -> Option<AllocationStats> {
+    if !allocation_tracking_enabled() {
+        return None;
+    }
+
+    Some(AllocationStats {
+        allocated_bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        live_bytes: LIVE_BYTES.load(Ordering::Relaxed),
+        peak_live_bytes: PEAK_LIVE_BYTES.load(Ordering::Relaxed),
+    })
+}
+
+/// Allocation counters captured at the start of a stage
+#[derive(Debug, Clone, Copy)]
+pub struct StageTracker {
+    /// Counters at the start of the stage
+    start: AllocationStats,
+}
+
+impl StageTracker {
+    /// Start tracking a stage
+    ///
+    /// The peak is reset to the current live bytes so the stage's own peak
+    /// can be measured; `finish` restores the enclosing peak. Stages may be
+    /// nested but must finish in reverse order of starting.
+    pub fn start() -> Option<Self> {
+        let start = allocation_stats()?;
+        PEAK_LIVE_BYTES.store(start.live_bytes, Ordering::Relaxed);
+        Some(Self { start })
+    }
+
+    /// Finish the stage and get its allocations
+    pub fn finish(self) -> StageAllocations {
+        let end = allocation_stats().unwrap_or(self.start);
+        PEAK_LIVE_BYTES.fetch_max(self.start.peak_live_bytes, Ordering::Relaxed);
+
+        StageAllocations {
+            allocated_bytes: end.allocated_bytes - self.start.allocated_bytes,
+            allocations: end.allocations - self.start.allocations,
+            peak_live_bytes: end.peak_live_bytes.saturating_sub(self.start.live_bytes),
+        }
+    }
+}
+
+/// Run a stage and measure its allocations
+///
+/// The allocations are None unless the `alloc-track` feature is enabled.
+/// Allocations from other threads running at the same time are included.
+pub fn track_allocations<F, R>(f: F) -> (R, Option<StageAllocations>)
+where
+    F: FnOnce() -> R,
+{
+    let tracker = StageTracker::start();
+    let result = f();
+    (result, tracker.map(StageTracker::finish))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracking_matches_feature() {
+        assert_eq!(allocation_stats().is_some(), cfg!(feature = "alloc-track"));
+
+        let (buffer, allocations) = track_allocations(|| vec![0u64; 1 << 16]);
+        assert_eq!(buffer.len(), 1 << 16);
+        assert_eq!(allocations.is_some(), cfg!(feature = "alloc-track"));
+    }
+
+    #[cfg(feature = "alloc-track")]
+    #[test]
+    fn test_stage_peak_live_bytes() {
+        let (_, outer) = track_allocations(|| {
+            let (buffer, inner) = track_allocations(|| vec![1u8; 1 << 20]);
+            assert_eq!(buffer.len(), 1 << 20);
+            let inner = inner.unwrap();
+            assert!(inner.allocated_bytes >= 1 << 20);
+            assert!(inner.peak_live_bytes >= 1 << 20);
+        });
+
+        // The inner stage's peak is part of the enclosing stage's peak
+        assert!(outer.unwrap().peak_live_bytes >= 1 << 20);
+    }
+}
@@ -0,0 +1,639 @@
+//! Array-indexed cycle finder
+//!
+//! Runs the search of the C++ `getCuckatooSolution`: edges are added one
+//! at a time, and every edge whose two endpoints already have other edges
+//! starts a depth-first walk for a 42-cycle through it, alternating
+//! between edges meeting at a U node pair and at a V node pair. An edge
+//! reaching node `x` continues from the edges at `x ^ 1`, the other node
+//! of its pair, the way `verify_proof_nonces` walks a proof, so the
+//! cycles found are valid proofs. Where `HashCycleFinder` finds each
+//! node's connection list through a `HashMap`, this finder gives every
+//! node a dense slot up front (by sorting) and threads the lists through
+//! flat arrays: a head edge per slot and a next edge per edge. The walk
+//! itself never hashes or allocates.
+//!
+//! A walk from an edge only uses the edges added before it, so every cycle
+//! is found from its highest-index edge. That lets the parallel search
+//! split the starting edges across threads: each builds the lists itself
+//! and walks from its share of the edges only, and the first thread to
+//! find a cycle tells the others to stop.
+//!
+//! `find_cycle_in_bitmap` builds the lists straight from a trimmer's edges
+//! bitmap, hashing the surviving edges' nodes as it goes, so the search
+//! needs no `Vec<Edge>` copy of the survivors.
+//!
+//! `push_edge` takes the edges one at a time instead, giving nodes slots
+//! as they come, and walks from each as soon as it's added, so a trimmer's
+//! final pass can feed the finder while it hashes the survivors and stop
+//! at the first cycle. `finish` returns that cycle and ends the graph.
+
+use crate::cancel::BudgetMeter;
+use crate::hashing::for_each_word_node;
+use crate::types::assert_cycle_length;
+use crate::{CancelToken, CuckatooError, CycleSearchStats, Edge, Node, Result, SearchBudget, SipHasher, SOLUTION_SIZE};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// End of a connection list
+const NONE: u32 = u32::MAX;
+
+/// Connection lists of one partition's nodes
+///
+/// Every node has a slot: the dense id of its pair (`node >> 1`) shifted
+/// left once, with the node's lowest bit. The other node of the pair is
+/// at `slot ^ 1`.
+#[derive(Default)]
+struct Partition {
+    /// Slot of every edge's endpoint
+    slots: Vec<u32>,
+    /// Newest edge at each slot
+    heads: Vec<u32>,
+    /// Next older edge at the same slot, per edge
+    next: Vec<u32>,
+    /// Whether each pair is on the current path
+    visited: Vec<bool>,
+}
+
+impl Partition {
+    /// Give `nodes` dense slots and reset the lists
+    fn build(&mut self, nodes: impl Iterator<Item = u64>) {
+        self.build_from(nodes.zip(0..).collect());
+    }
+
+    /// Give the nodes of `(node, edge)` pairs covering edges 0 to
+    /// `sorted.len()` dense slots and reset the lists
+    fn build_from(&mut self, mut sorted: Vec<(u64, u32)>) {
+        sorted.sort_unstable();
+
+        self.slots.clear();
+        self.slots.resize(sorted.len(), 0);
+        let mut pairs = 0;
+        for (i, &(node, edge)) in sorted.iter().enumerate() {
+            if i > 0 && node >> 1 != sorted[i - 1].0 >> 1 {
+                pairs += 1;
+            }
+            self.slots[edge as usize] = pairs << 1 | (node & 1) as u32;
+        }
+        let pairs = if sorted.is_empty() { 0 } else { pairs as usize + 1 };
+        self.heads.clear();
+        self.heads.resize(2 * pairs, NONE);
+        self.next.clear();
+        self.next.resize(self.slots.len(), NONE);
+        self.visited.clear();
+        self.visited.resize(pairs, false);
+    }
+
+    /// Add `edge` to its slot's list
+    fn insert(&mut self, edge: u32) {
+        let slot = self.slots[edge as usize] as usize;
+        self.next[edge as usize] = self.heads[slot];
+        self.heads[slot] = edge;
+    }
+
+    /// Add an edge at `slot` (of the next pair id for a new pair) to the
+    /// lists, returning the edge
+    fn push(&mut self, slot: u32) -> u32 {
+        let edge = self.slots.len() as u32;
+        self.slots.push(slot);
+        self.next.push(NONE);
+        if slot as usize >> 1 == self.visited.len() {
+            self.heads.extend([NONE; 2]);
+            self.visited.push(false);
+        }
+        self.insert(edge);
+        edge
+    }
+
+    /// Slot of `edge`
+    fn slot(&self, edge: u32) -> usize {
+        self.slots[edge as usize] as usize
+    }
+
+    /// Whether the other node of the pair of `edge`'s node has edges
+    fn has_partner_edges(&self, edge: u32) -> bool {
+        self.heads[self.slot(edge) ^ 1] != NONE
+    }
+}
+
+/// Graph fed to the finder by `push_edge`
+#[derive(Default)]
+struct Stream {
+    /// Dense ids of the U node pairs seen so far
+    u_ids: HashMap<u64, u32>,
+    /// Dense ids of the V node pairs seen so far
+    v_ids: HashMap<u64, u32>,
+    /// Caller's index of every edge pushed
+    indices: Vec<u64>,
+    /// Indices of the cycle found, ascending
+    cycle: Option<Vec<u64>>,
+}
+
+/// Cycle finder with array-indexed connection lists
+pub struct ArrayCycleFinder {
+    u: Partition,
+    v: Partition,
+    /// Edges of the current walk, the starting edge first
+    path: Vec<u32>,
+    /// Number of edges in the cycles searched for
+    cycle_length: usize,
+    stats: CycleSearchStats,
+    /// Token stopping the search between edges
+    cancel: Option<CancelToken>,
+    /// Limits on the work of each search
+    budget: SearchBudget,
+    /// Budget left to the running search
+    meter: BudgetMeter,
+    /// Graph being pushed, until `finish`
+    stream: Option<Stream>,
+}
+
+impl Default for ArrayCycleFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArrayCycleFinder {
+    /// Create a new cycle finder
+    pub fn new() -> Self {
+        Self {
+            u: Partition::default(),
+            v: Partition::default(),
+            path: Vec::new(),
+            cycle_length: SOLUTION_SIZE,
+            stats: CycleSearchStats::default(),
+            cancel: None,
+            budget: SearchBudget::default(),
+            meter: BudgetMeter::default(),
+            stream: None,
+        }
+    }
+
+    /// Search for cycles of `length` edges instead of 42
+    ///
+    /// Panics if `length` is odd or outside 4 to `MAX_CYCLE_LENGTH`.
+    pub fn with_cycle_length(mut self, length: usize) -> Self {
+        assert_cycle_length(length);
+        self.cycle_length = length;
+        self
+    }
+
+    /// Number of edges in the cycles searched for
+    pub fn cycle_length(&self) -> usize {
+        self.cycle_length
+    }
+
+    /// Stop searching with `Cancelled` soon after `token` is cancelled
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Fail searches with `BudgetExceeded` once they go over `budget`
+    ///
+    /// In a parallel search every thread gets the whole budget.
+    pub fn with_budget(mut self, budget: SearchBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Work done by the last search (reset by `find_cycle`)
+    pub fn stats(&self) -> CycleSearchStats {
+        self.stats
+    }
+
+    /// Find a cycle of `cycle_length` edges, returning the indices into
+    /// `edges` of its edges in ascending order
+    pub fn find_cycle(&mut self, edges: &[Edge]) -> Result<Option<Vec<usize>>> {
+        Ok(self.search(edges, |_| true, false, &AtomicBool::new(false))?.pop())
+    }
+
+    /// Find a cycle like `find_cycle`, walking from the edges on `threads`
+    /// threads
+    ///
+    /// All threads stop once one finds a cycle, so with several cycles in
+    /// the graph any of them may be returned. `stats` adds up the work of
+    /// all threads.
+    pub fn find_cycle_parallel(&mut self, edges: &[Edge], threads: usize) -> Result<Option<Vec<usize>>> {
+        Ok(self.search_parallel(edges, threads, false)?.pop())
+    }
+
+    /// Find the cycles of `cycle_length` edges on `threads` threads, at
+    /// most one per highest-index edge, sorted
+    pub fn find_all_cycles_parallel(&mut self, edges: &[Edge], threads: usize) -> Result<Vec<Vec<usize>>> {
+        let mut cycles = self.search_parallel(edges, threads, true)?;
+        cycles.sort_unstable();
+        Ok(cycles)
+    }
+
+    /// Split the starting edges across `threads` finders, interleaved so
+    /// each gets a share of the later, better connected edges
+    fn search_parallel(&mut self, edges: &[Edge], threads: usize, all: bool) -> Result<Vec<Vec<usize>>> {
+        let threads = threads.clamp(1, edges.len().max(1));
+        let stop = AtomicBool::new(false);
+        if threads == 1 {
+            return self.search(edges, |_| true, all, &stop);
+        }
+
+        let results: Vec<(Result<Vec<Vec<usize>>>, CycleSearchStats)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|thread| {
+                    let mut finder = Self::new().with_cycle_length(self.cycle_length);
+                    finder.cancel = self.cancel.clone();
+                    finder.budget = self.budget;
+                    let stop = &stop;
+                    scope.spawn(move || {
+                        let found = finder.search(edges, |edge| edge as usize % threads == thread, all, stop);
+                        (found, finder.stats)
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|worker| worker.join().expect("cycle search thread panicked")).collect()
+        });
+
+        self.stats = CycleSearchStats::default();
+        let mut cycles = Vec::new();
+        for (found, stats) in results {
+            self.stats.candidate_pairs += stats.candidate_pairs;
+            self.stats.traversal_steps += stats.traversal_steps;
+            self.stats.max_chain_length = self.stats.max_chain_length.max(stats.max_chain_length);
+            cycles.extend(found?);
+        }
+        if !all {
+            cycles.truncate(1);
+        }
+        Ok(cycles)
+    }
+
+    /// Add the edges one at a time, walking from those `starts` accepts;
+    /// returns the first cycle found, or every one if `all`, and stops
+    /// early once `stop` is set (setting it on finding a cycle unless `all`)
+    fn search(&mut self, edges: &[Edge], starts: impl Fn(u32) -> bool, all: bool, stop: &AtomicBool) -> Result<Vec<Vec<usize>>> {
+        if edges.len() >= NONE as usize {
+            return Err(CuckatooError::InternalError(format!("Too many edges for the cycle finder: {}", edges.len())));
+        }
+        self.u.build(edges.iter().map(|edge| edge.u.value()));
+        self.v.build(edges.iter().map(|edge| edge.v.value()));
+        self.walk(edges.len() as u32, starts, all, stop)
+    }
+
+    /// Search the edges left set in `edges_bitmap` (edge `i` at bit `i % 64`
+    /// of word `i / 64`, as the trimmers leave it) for a cycle of
+    /// `cycle_length` edges, hashing their nodes with `hasher`
+    ///
+    /// Returns the edge indices of the cycle, which are its proof nonces,
+    /// in ascending order. Only the dense node ids are kept while walking,
+    /// so the surviving edges are never materialized as `Edge`s.
+    pub fn find_cycle_in_bitmap<H: SipHasher>(&mut self, edges_bitmap: &[u64], hasher: &H, edge_bits: u32) -> Result<Option<Vec<u64>>> {
+        let count: u64 = edges_bitmap.iter().map(|word| u64::from(word.count_ones())).sum();
+        if count >= u64::from(NONE) {
+            return Err(CuckatooError::InternalError(format!("Too many edges for the cycle finder: {}", count)));
+        }
+        for (uorv, partition) in [(0, &mut self.u), (1, &mut self.v)] {
+            let mut nodes = Vec::with_capacity(count as usize);
+            for (word_index, &word) in edges_bitmap.iter().enumerate() {
+                for_each_word_node(hasher, edge_bits, word_index, word, uorv, |_, node| {
+                    nodes.push((node, nodes.len() as u32));
+                });
+            }
+            partition.build_from(nodes);
+        }
+
+        let Some(positions) = self.walk(count as u32, |_| true, false, &AtomicBool::new(false))?.pop() else {
+            return Ok(None);
+        };
+        // Map the ascending positions among the set bits back to edge indices
+        let mut positions = positions.into_iter().peekable();
+        let mut indices = Vec::with_capacity(self.cycle_length);
+        let mut seen = 0;
+        for (word_index, &word) in edges_bitmap.iter().enumerate() {
+            let mut unit = word;
+            while unit != 0 && positions.peek().is_some() {
+                if positions.peek() == Some(&seen) {
+                    indices.push(word_index as u64 * 64 + u64::from(unit.trailing_zeros()));
+                    positions.next();
+                }
+                seen += 1;
+                unit &= unit - 1;
+            }
+        }
+        Ok(Some(indices))
+    }
+
+    /// Add the edge from `u` to `v` to the graph being pushed, walking from
+    /// it for a cycle through the edges pushed before it; returns whether
+    /// a cycle has been found
+    ///
+    /// The first edge after `new` or `finish` starts a new graph. `index`
+    /// identifies the edge in the cycle `finish` returns, e.g. its nonce.
+    /// Once a cycle is found further edges are ignored, so the caller can
+    /// stop pushing. An error ends the graph.
+    pub fn push_edge(&mut self, u: Node, v: Node, index: u64) -> Result<bool> {
+        self.push_stream_edge(u.value(), v.value(), index).inspect_err(|_| self.stream = None)
+    }
+
+    /// Add an edge to the pushed graph (see `push_edge`)
+    fn push_stream_edge(&mut self, u: u64, v: u64, index: u64) -> Result<bool> {
+        if self.stream.is_none() {
+            self.u.build_from(Vec::new());
+            self.v.build_from(Vec::new());
+            self.stats = CycleSearchStats::default();
+            self.meter = self.budget.start();
+        }
+        let stream = self.stream.get_or_insert_with(Stream::default);
+        if stream.cycle.is_some() {
+            return Ok(true);
+        }
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(CuckatooError::Cancelled);
+        }
+        if stream.indices.len() >= NONE as usize - 1 {
+            return Err(CuckatooError::InternalError(format!("Too many edges for the cycle finder: {}", stream.indices.len() + 1)));
+        }
+        let slot = |ids: &mut HashMap<u64, u32>, node: u64| {
+            let next = ids.len() as u32;
+            *ids.entry(node >> 1).or_insert(next) << 1 | (node & 1) as u32
+        };
+        let (u_slot, v_slot) = (slot(&mut stream.u_ids, u), slot(&mut stream.v_ids, v));
+        stream.indices.push(index);
+        let edge = self.u.push(u_slot);
+        self.v.push(v_slot);
+        if !self.u.has_partner_edges(edge) || !self.v.has_partner_edges(edge) || !self.walk_from(edge)? {
+            return Ok(false);
+        }
+        if let Some(stream) = &mut self.stream {
+            let mut cycle: Vec<u64> = self.path.iter().map(|&edge| stream.indices[edge as usize]).collect();
+            cycle.sort_unstable();
+            stream.cycle = Some(cycle);
+        }
+        Ok(true)
+    }
+
+    /// End the graph fed by `push_edge`, returning the indices of the cycle
+    /// of `cycle_length` edges found in it, in ascending order
+    pub fn finish(&mut self) -> Option<Vec<u64>> {
+        self.stream.take().and_then(|stream| stream.cycle)
+    }
+
+    /// Add edges 0 to `count` one at a time to the built partitions (see
+    /// `search`)
+    fn walk(&mut self, count: u32, starts: impl Fn(u32) -> bool, all: bool, stop: &AtomicBool) -> Result<Vec<Vec<usize>>> {
+        self.stream = None;
+        self.stats = CycleSearchStats::default();
+        self.meter = self.budget.start();
+
+        let mut cycles = Vec::new();
+        for edge in 0..count {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(CuckatooError::Cancelled);
+            }
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            self.u.insert(edge);
+            self.v.insert(edge);
+            if !starts(edge) || !self.u.has_partner_edges(edge) || !self.v.has_partner_edges(edge) {
+                continue;
+            }
+
+            if self.walk_from(edge)? {
+                let mut solution: Vec<usize> = self.path.iter().map(|&edge| edge as usize).collect();
+                solution.sort_unstable();
+                cycles.push(solution);
+                if !all {
+                    stop.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+        Ok(cycles)
+    }
+
+    /// Walk from `edge`, the newest edge, for a cycle through it, leaving
+    /// the cycle's edges in `path` if one is found
+    fn walk_from(&mut self, edge: u32) -> Result<bool> {
+        self.stats.candidate_pairs += 1;
+        self.path.clear();
+        self.path.push(edge);
+        let (u, v) = (self.u.slot(edge) >> 1, self.v.slot(edge) >> 1);
+        self.u.visited[u] = true;
+        self.v.visited[v] = true;
+        let found = self.search_first_partition(edge);
+        self.u.visited[u] = false;
+        self.v.visited[v] = false;
+        if self.meter.is_exceeded() {
+            return Err(CuckatooError::BudgetExceeded);
+        }
+        Ok(found)
+    }
+
+    /// Extend the path with the edges at the other node of the U pair of
+    /// `from`, continuing from their V nodes or closing the cycle at the
+    /// other node of the starting edge's V pair
+    fn search_first_partition(&mut self, from: u32) -> bool {
+        if self.record_step() {
+            return false;
+        }
+        let root = self.v.slot(self.path[0]);
+        let mut edge = self.u.heads[self.u.slot(from) ^ 1];
+        while edge != NONE {
+            let v = self.v.slot(edge);
+            if v == root ^ 1 {
+                if self.path.len() == self.cycle_length - 1 {
+                    self.path.push(edge);
+                    return true;
+                }
+            } else if !self.v.visited[v >> 1] && self.path.len() < self.cycle_length - 1 && self.v.has_partner_edges(edge) {
+                self.v.visited[v >> 1] = true;
+                self.path.push(edge);
+                if self.search_second_partition(edge) {
+                    return true;
+                }
+                self.path.pop();
+                self.v.visited[v >> 1] = false;
+            }
+            edge = self.u.next[edge as usize];
+        }
+        false
+    }
+
+    /// Extend the path with the edges at the other node of the V pair of
+    /// `from`, continuing from their U nodes
+    fn search_second_partition(&mut self, from: u32) -> bool {
+        if self.record_step() {
+            return false;
+        }
+        let mut edge = self.v.heads[self.v.slot(from) ^ 1];
+        while edge != NONE {
+            let u = self.u.slot(edge) >> 1;
+            if !self.u.visited[u] && self.u.has_partner_edges(edge) {
+                self.u.visited[u] = true;
+                self.path.push(edge);
+                if self.search_first_partition(edge) {
+                    return true;
+                }
+                self.path.pop();
+                self.u.visited[u] = false;
+            }
+            edge = self.v.next[edge as usize];
+        }
+        false
+    }
+
+    /// Count a node visited at the current path length, returning whether
+    /// the search went over budget
+    fn record_step(&mut self) -> bool {
+        self.stats.traversal_steps += 1;
+        self.stats.max_chain_length = self.stats.max_chain_length.max(self.path.len() as u32);
+        self.meter.step()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{KNOWN_PROOF, KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_KEYS};
+    use crate::{Node, SipHash};
+
+    #[test]
+    fn test_array_cycle_finder_finds_known_proof() {
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let indices: Vec<u64> = (0..1 << KNOWN_PROOF_EDGE_BITS).collect();
+        let graph = siphash.edges_for_indices(&indices, KNOWN_PROOF_EDGE_BITS);
+        let mut finder = ArrayCycleFinder::new();
+        let solution = finder.find_cycle(&graph).unwrap().unwrap();
+        assert_eq!(solution, KNOWN_PROOF.map(|index| index as usize));
+        assert!(finder.stats().candidate_pairs > 0);
+        assert_eq!(finder.stats().max_chain_length, SOLUTION_SIZE as u32 - 1);
+
+        // Not without one of its edges
+        let mut broken = graph.clone();
+        broken.remove(KNOWN_PROOF[7] as usize);
+        assert_eq!(finder.find_cycle(&broken).unwrap(), None);
+
+        let token = CancelToken::new();
+        token.cancel();
+        let mut cancelled = ArrayCycleFinder::new().with_cancel_token(token);
+        assert!(matches!(cancelled.find_cycle(&graph), Err(CuckatooError::Cancelled)));
+    }
+
+    #[test]
+    fn test_array_cycle_finder_cycle_length() {
+        // A 12-cycle through U pairs 0..6 and V pairs 0..6, among edges of
+        // nodes with no other edges
+        let mut edges: Vec<Edge> = (0..6u64)
+            .flat_map(|i| [(2 * i, 2 * i), (2 * ((i + 1) % 6) + 1, 2 * i + 1)])
+            .map(|(u, v)| Edge::new(Node::new(u), Node::new(v)))
+            .collect();
+        edges.extend((100..110u64).map(|i| Edge::new(Node::new(i), Node::new(i))));
+        edges.rotate_left(5);
+
+        let mut finder = ArrayCycleFinder::new().with_cycle_length(12);
+        assert_eq!(finder.cycle_length(), 12);
+        let solution = finder.find_cycle(&edges).unwrap().unwrap();
+        assert_eq!(solution.len(), 12);
+        assert!(solution.iter().all(|&index| edges[index].u.value() < 100));
+        assert_eq!(ArrayCycleFinder::new().find_cycle(&edges).unwrap(), None);
+        assert_eq!(ArrayCycleFinder::new().with_cycle_length(10).find_cycle(&edges).unwrap(), None);
+        assert!(std::panic::catch_unwind(|| ArrayCycleFinder::new().with_cycle_length(13)).is_err());
+    }
+
+    #[test]
+    fn test_parallel_search_matches_sequential() {
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let indices: Vec<u64> = (0..1 << KNOWN_PROOF_EDGE_BITS).collect();
+        let graph = siphash.edges_for_indices(&indices, KNOWN_PROOF_EDGE_BITS);
+        let mut sequential = ArrayCycleFinder::new();
+        let expected = sequential.find_cycle(&graph).unwrap();
+        let mut finder = ArrayCycleFinder::new();
+        for threads in [1, 2, 3] {
+            assert_eq!(finder.find_cycle_parallel(&graph, threads).unwrap(), expected);
+            assert_eq!(finder.find_all_cycles_parallel(&graph, threads).unwrap(), vec![expected.clone().unwrap()]);
+        }
+        assert!(finder.stats().candidate_pairs > 0);
+
+        // Two disjoint 4-cycles, found together or one at a time
+        let square = |base: u64| {
+            [(0, 0), (3, 1), (2, 2), (1, 3)].map(|(u, v)| Edge::new(Node::new(base + u), Node::new(base + v)))
+        };
+        let edges: Vec<Edge> = square(0).into_iter().chain(square(100)).collect();
+        let mut finder = ArrayCycleFinder::new().with_cycle_length(4);
+        assert_eq!(finder.find_all_cycles_parallel(&edges, 2).unwrap(), vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        assert_eq!(finder.find_cycle_parallel(&edges, 4).unwrap().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_find_cycle_in_bitmap() {
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let mut bitmap = vec![u64::MAX; (1 << KNOWN_PROOF_EDGE_BITS) / 64];
+        let mut finder = ArrayCycleFinder::new();
+        let solution = finder.find_cycle_in_bitmap(&bitmap, &siphash, KNOWN_PROOF_EDGE_BITS).unwrap();
+        assert_eq!(solution, Some(KNOWN_PROOF.to_vec()));
+
+        // Not without one of its edges
+        bitmap[KNOWN_PROOF[7] as usize / 64] &= !(1 << (KNOWN_PROOF[7] % 64));
+        assert_eq!(finder.find_cycle_in_bitmap(&bitmap, &siphash, KNOWN_PROOF_EDGE_BITS).unwrap(), None);
+
+        // Same cycles as searching the trimmed edges
+        let mut trimmer = crate::BitmapTrimmer::new(10);
+        let mut found = 0;
+        for seed in 0..64 {
+            let siphash = SipHash::with_key(crate::SipKeys::from_header(b"bitmap cycle finder", seed));
+            let edges = trimmer.trim_edges(&siphash, 4).unwrap();
+            let indices = crate::trim_output::edge_indices(trimmer.edges_bitmap());
+            let mut finder = ArrayCycleFinder::new().with_cycle_length(4);
+            let expected = finder.find_cycle(&edges).unwrap().map(|cycle| cycle.iter().map(|&position| indices[position]).collect());
+            found += expected.is_some() as usize;
+            assert_eq!(finder.find_cycle_in_bitmap(trimmer.edges_bitmap(), &siphash, 10).unwrap(), expected);
+        }
+        assert!(found > 0);
+    }
+
+    #[test]
+    fn test_push_edge() {
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let indices: Vec<u64> = (0..1 << KNOWN_PROOF_EDGE_BITS).collect();
+        let graph = siphash.edges_for_indices(&indices, KNOWN_PROOF_EDGE_BITS);
+        let mut finder = ArrayCycleFinder::new();
+        let mut pushed = 0;
+        for (index, edge) in graph.iter().enumerate() {
+            pushed += 1;
+            if finder.push_edge(edge.u, edge.v, index as u64 + 1000).unwrap() {
+                break;
+            }
+        }
+        // Found at the proof's highest edge, with the caller's indices
+        assert_eq!(pushed, KNOWN_PROOF[SOLUTION_SIZE - 1] + 1);
+        assert_eq!(finder.finish(), Some(KNOWN_PROOF.map(|index| index + 1000).to_vec()));
+        assert_eq!(finder.finish(), None);
+
+        // A new graph after `finish`, without one of the proof's edges
+        for (index, edge) in graph.iter().enumerate().filter(|&(index, _)| index as u64 != KNOWN_PROOF[7]) {
+            assert!(!finder.push_edge(edge.u, edge.v, index as u64).unwrap());
+        }
+        assert_eq!(finder.finish(), None);
+
+        // The known proof, found as the trimmer's final pass hashes it
+        let mut trimmer = crate::BitmapTrimmer::new(KNOWN_PROOF_EDGE_BITS);
+        let mut finder = ArrayCycleFinder::new();
+        assert_eq!(trimmer.trim_and_search(&siphash, 90, &mut finder).unwrap(), Some(KNOWN_PROOF.to_vec()));
+
+        // Same cycles as searching the trimmed bitmap
+        let mut trimmer = crate::BitmapTrimmer::new(10);
+        let mut found = 0;
+        for seed in 0..64 {
+            let siphash = SipHash::with_key(crate::SipKeys::from_header(b"streaming cycle finder", seed));
+            let mut finder = ArrayCycleFinder::new().with_cycle_length(4);
+            let cycle = trimmer.trim_and_search(&siphash, 4, &mut finder).unwrap();
+            found += cycle.is_some() as usize;
+            assert_eq!(finder.find_cycle_in_bitmap(trimmer.edges_bitmap(), &siphash, 10).unwrap(), cycle);
+        }
+        assert!(found > 0);
+
+        let token = CancelToken::new();
+        token.cancel();
+        let mut cancelled = ArrayCycleFinder::new().with_cancel_token(token);
+        assert!(matches!(cancelled.push_edge(graph[0].u, graph[0].v, 0), Err(CuckatooError::Cancelled)));
+        assert_eq!(cancelled.finish(), None);
+    }
+}
@@ -0,0 +1,168 @@
+//! Comparing a run against an earlier one
+//!
+//! The miner's `--json` run summary doubles as a baseline: loaded back as a
+//! `RunBaseline`, each stage time and count of a new run is compared with
+//! the old value, so the effect of a configuration or hardware change can
+//! be read off as "trimming 12.0% faster" or "surviving edges +40".
+
+use crate::{format_seconds, CuckatooError, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Fields of an earlier run summary
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunBaseline {
+    fields: HashMap<String, String>,
+}
+
+impl RunBaseline {
+    /// Parse a run summary written with `--json`
+    ///
+    /// Only flat objects are understood; array values (such as the keys)
+    /// are kept as their raw text.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let invalid = |message: String| CuckatooError::InternalError(format!("Invalid run summary: {}", message));
+        let body = json
+            .trim()
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+            .ok_or_else(|| invalid("expected a JSON object".to_string()))?;
+
+        let mut fields = HashMap::new();
+        for field in top_level_fields(body).into_iter().map(str::trim).filter(|field| !field.is_empty()) {
+            let (name, value) = field.split_once(':').ok_or_else(|| invalid(format!("no ':' in {:?}", field)))?;
+            fields.insert(name.trim().trim_matches('"').to_string(), value.trim().trim_matches('"').to_string());
+        }
+        Ok(Self { fields })
+    }
+
+    /// Load a run summary file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|error| {
+            CuckatooError::InternalError(format!("Cannot read run summary {}: {}", path.display(), error))
+        })?;
+        Self::from_json(&text)
+    }
+
+    /// Raw value of a field, without quotes
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+
+    /// Numeric value of a field
+    pub fn number(&self, name: &str) -> Option<f64> {
+        self.field(name)?.parse().ok()
+    }
+
+    /// Compare a stage time in seconds with the baseline's `field`
+    ///
+    /// None if the baseline has no such time, e.g. because it was written
+    /// in deterministic mode.
+    pub fn compare_time(&self, label: &str, field: &str, seconds: f64) -> Option<Delta> {
+        Some(Delta { label: label.to_string(), kind: DeltaKind::Time, baseline: self.number(field)?, current: seconds })
+    }
+
+    /// Compare a count with the baseline's `field`
+    pub fn compare_count(&self, label: &str, field: &str, count: u64) -> Option<Delta> {
+        Some(Delta { label: label.to_string(), kind: DeltaKind::Count, baseline: self.number(field)?, current: count as f64 })
+    }
+}
+
+/// Split the body of a JSON object on the commas between its fields,
+/// skipping commas inside strings and arrays
+fn top_level_fields(body: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let (mut depth, mut in_string, mut start) = (0usize, false, 0);
+    for (index, character) in body.char_indices() {
+        match character {
+            '"' => in_string = !in_string,
+            '[' | '{' if !in_string => depth += 1,
+            ']' | '}' if !in_string => depth = depth.saturating_sub(1),
+            ',' if !in_string && depth == 0 => {
+                fields.push(&body[start..index]);
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    fields.push(&body[start..]);
+    fields
+}
+
+/// How a compared value is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaKind {
+    /// Seconds, where less is faster
+    Time,
+    /// A count such as the surviving edges
+    Count,
+}
+
+/// A value of this run next to the baseline's
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    /// What was compared, e.g. `Trimming time`
+    pub label: String,
+    /// How the values are read
+    pub kind: DeltaKind,
+    /// Value of the baseline run
+    pub baseline: f64,
+    /// Value of this run
+    pub current: f64,
+}
+
+impl Delta {
+    /// Relative change from the baseline (-0.25 is 25% less), None for a
+    /// zero baseline
+    pub fn relative_change(&self) -> Option<f64> {
+        (self.baseline != 0.0).then(|| (self.current - self.baseline) / self.baseline)
+    }
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            DeltaKind::Time => {
+                write!(f, "{}: {} -> {}", self.label, format_seconds(self.baseline), format_seconds(self.current))?;
+                match self.relative_change() {
+                    Some(change) if change < 0.0 => write!(f, " ({:.1}% faster)", -change * 100.0),
+                    Some(change) if change > 0.0 => write!(f, " ({:.1}% slower)", change * 100.0),
+                    _ => write!(f, " (unchanged)"),
+                }
+            },
+            DeltaKind::Count => {
+                write!(f, "{}: {} -> {}", self.label, self.baseline, self.current)?;
+                let difference = self.current - self.baseline;
+                if difference == 0.0 {
+                    write!(f, " (unchanged)")
+                } else {
+                    write!(f, " ({:+})", difference)
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_with_run_summary() {
+        let json = "{\"edge_bits\":12,\"mode\":\"lean\",\"keys\":[\"0x01\",\"0x02\"],\"surviving_edges\":1344,\"trimming_time\":0.500000000}\n";
+        let baseline = RunBaseline::from_json(json).unwrap();
+        assert_eq!(baseline.field("mode"), Some("lean"));
+        assert_eq!(baseline.field("keys"), Some("[\"0x01\",\"0x02\"]"));
+        assert_eq!(baseline.number("edge_bits"), Some(12.0));
+
+        let trimming = baseline.compare_time("Trimming time", "trimming_time", 0.4).unwrap();
+        assert!((trimming.relative_change().unwrap() + 0.2).abs() < 1e-9);
+        assert!(trimming.to_string().ends_with("(20.0% faster)"));
+        assert!(baseline.compare_time("Searching time", "searching_time", 1.0).is_none());
+
+        assert_eq!(baseline.compare_count("Surviving edges", "surviving_edges", 1300).unwrap().to_string(), "Surviving edges: 1344 -> 1300 (-44)");
+        assert!(baseline.compare_count("Surviving edges", "surviving_edges", 1344).unwrap().to_string().ends_with("(unchanged)"));
+        assert!(RunBaseline::from_json("edge_bits=12").is_err());
+    }
+}
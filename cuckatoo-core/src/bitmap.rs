@@ -1,21 +1,214 @@
-use crate::constants::*;
+//! Bitmaps of nodes and edges
+//!
+//! `Bitmap` is for single-threaded use; `AtomicBitmap` lets several
+//! trimming threads set and test bits of the same bitmap without locks.
+//! Either can keep its words in a disk-backed mapping (with the `mmap`
+//! feature on Unix) when they don't fit in RAM.
+
+use crate::huge_pages::AlignedWords;
+#[cfg(all(unix, feature = "mmap"))]
+use crate::mmap::MappedWords;
+use crate::Result;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Bitmap unit width in bits
+const BITMAP_UNIT_WIDTH: usize = 64;
+
+/// Words a thread counts at least when `AtomicBitmap::count_set_bits` splits
+/// the bitmap, so small bitmaps aren't worth a thread each
+const MIN_WORDS_PER_COUNT_THREAD: usize = 1 << 16;
+
+/// Words that may live in a mapped file or a zeroed allocation
+///
+/// # Safety
+///
+/// All-zero bytes must be a valid value, since new words read as zeros.
+pub(crate) unsafe trait ZeroableWord: Sized {
+    /// The all-zero word
+    fn zero() -> Self;
+}
+
+// SAFETY: zero is a valid u64 and a clear AtomicU64
+unsafe impl ZeroableWord for u64 {
+    fn zero() -> Self {
+        0
+    }
+}
+
+// SAFETY: as for u64
+unsafe impl ZeroableWord for AtomicU64 {
+    fn zero() -> Self {
+        AtomicU64::new(0)
+    }
+}
+
+/// How the words of a disk-backed bitmap will be accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    /// Walked in order, like the edges bitmap (read ahead aggressively)
+    Sequential,
+    /// Hit at random, like the nodes bitmap (don't read ahead)
+    Random,
+}
+
+/// Words of a bitmap, on the heap, on huge pages or in a disk-backed
+/// mapping
+pub(crate) enum Words<T> {
+    Heap(Vec<T>),
+    Aligned(AlignedWords<T>),
+    #[cfg(all(target_os = "linux", feature = "mmap"))]
+    HugeTlb(MappedWords<T>),
+    #[cfg(all(unix, feature = "mmap"))]
+    Mapped(MappedWords<T>),
+}
+
+impl<T> Words<T> {
+    /// Map `len` zeroed words backed by a temp file in `dir`
+    #[cfg(all(unix, feature = "mmap"))]
+    pub(crate) fn disk_backed(len: usize, dir: &Path, access: Access) -> Result<Self>
+    where
+        T: ZeroableWord,
+    {
+        if len == 0 {
+            return Ok(Self::Heap(Vec::new()));
+        }
+        MappedWords::new(len, dir, access).map(Self::Mapped)
+    }
+
+    /// Disk-backed words need the `mmap` feature on a Unix target
+    #[cfg(not(all(unix, feature = "mmap")))]
+    pub(crate) fn disk_backed(len: usize, dir: &Path, _access: Access) -> Result<Self> {
+        Err(crate::CuckatooError::MemoryError(format!(
+            "Cannot back {} bitmap words with a file in {}: built without the mmap feature on a Unix target",
+            len, dir.display()
+        )))
+    }
+
+    /// Allocate `len` zeroed words on the heap without writing them
+    ///
+    /// The pages are first touched by whichever thread uses them first,
+    /// which on NUMA hosts places them on that thread's node.
+    pub(crate) fn zeroed(len: usize) -> Self
+    where
+        T: ZeroableWord,
+    {
+        let layout = match std::alloc::Layout::array::<T>(len) {
+            Ok(layout) if layout.size() > 0 => layout,
+            _ => return Self::Heap((0..len).map(|_| T::zero()).collect()),
+        };
+        // SAFETY: the layout has a non-zero size
+        let words = unsafe { std::alloc::alloc_zeroed(layout) }.cast::<T>();
+        if words.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        // SAFETY: `len` words allocated by the global allocator with the
+        // layout of a `Vec<T>` of that capacity, and zero words are valid
+        Self::Heap(unsafe { Vec::from_raw_parts(words, len, len) })
+    }
+
+    /// Allocate `len` zeroed words on huge pages, falling back to 2 MiB
+    /// aligned words and then to ordinary ones (see `huge_pages`)
+    pub(crate) fn huge_pages(len: usize) -> Self
+    where
+        T: ZeroableWord,
+    {
+        #[cfg(all(target_os = "linux", feature = "mmap"))]
+        if let Some(words) = MappedWords::huge_pages(len) {
+            return Self::HugeTlb(words);
+        }
+        match AlignedWords::new(len) {
+            Some(words) => Self::Aligned(words),
+            None => Self::Heap((0..len).map(|_| T::zero()).collect()),
+        }
+    }
+
+    /// Check if the words live in a disk-backed mapping
+    pub(crate) fn is_disk_backed(&self) -> bool {
+        #[cfg(all(unix, feature = "mmap"))]
+        if let Self::Mapped(_) = self {
+            return true;
+        }
+        false
+    }
+
+    /// Check if the words are on explicit huge pages, or on aligned
+    /// memory advised for transparent ones
+    pub(crate) fn is_huge_page_backed(&self) -> bool {
+        match self {
+            Self::Aligned(words) => words.is_advised(),
+            #[cfg(all(target_os = "linux", feature = "mmap"))]
+            Self::HugeTlb(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T> Default for Words<T> {
+    fn default() -> Self {
+        Self::Heap(Vec::new())
+    }
+}
+
+impl<T> std::ops::Deref for Words<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Heap(words) => words,
+            Self::Aligned(words) => words,
+            #[cfg(all(target_os = "linux", feature = "mmap"))]
+            Self::HugeTlb(words) => words,
+            #[cfg(all(unix, feature = "mmap"))]
+            Self::Mapped(words) => words,
+        }
+    }
+}
+
+impl<T> std::ops::DerefMut for Words<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Self::Heap(words) => words,
+            Self::Aligned(words) => words,
+            #[cfg(all(target_os = "linux", feature = "mmap"))]
+            Self::HugeTlb(words) => words,
+            #[cfg(all(unix, feature = "mmap"))]
+            Self::Mapped(words) => words,
+        }
+    }
+}
 
 /// A bitmap for efficient bit operations
 pub struct Bitmap {
-    buffer: Vec<u64>,
+    buffer: Words<u64>,
     size: u64,
 }
 
 impl Bitmap {
     /// Create a new bitmap with the specified size
     pub fn new(size: u64) -> Self {
-        let buffer_size = (size + BITMAP_UNIT_WIDTH as u64 - 1) / BITMAP_UNIT_WIDTH as u64;
+        let buffer_size = size.div_ceil(BITMAP_UNIT_WIDTH as u64);
         Self {
-            buffer: vec![0; buffer_size as usize],
+            buffer: Words::Heap(vec![0; buffer_size as usize]),
             size,
         }
     }
 
+    /// Create a new bitmap whose words live in a temp file in `dir`,
+    /// mapped for sequential access
+    pub fn new_disk_backed(size: u64, dir: &Path) -> Result<Self> {
+        let buffer_size = crate::checked_usize(size.div_ceil(BITMAP_UNIT_WIDTH as u64), "Bitmap size")?;
+        Ok(Self {
+            buffer: Words::disk_backed(buffer_size, dir, Access::Sequential)?,
+            size,
+        })
+    }
+
+    /// Check if the bitmap's words live in a temp file
+    pub fn is_disk_backed(&self) -> bool {
+        self.buffer.is_disk_backed()
+    }
+
     /// Set a bit at the specified index
     pub fn set_bit(&mut self, index: u64) {
         if index < self.size {
@@ -47,14 +240,14 @@ impl Bitmap {
 
     /// Set all bits
     pub fn set_all_bits(&mut self) {
-        for word in &mut self.buffer {
+        for word in self.buffer.iter_mut() {
             *word = u64::MAX;
         }
     }
 
     /// Clear all bits
     pub fn clear_all_bits(&mut self) {
-        for word in &mut self.buffer {
+        for word in self.buffer.iter_mut() {
             *word = 0;
         }
     }
@@ -85,3 +278,185 @@ impl Default for Bitmap {
         Self::new(0)
     }
 }
+
+/// A bitmap whose bits can be set and tested from several threads at once
+///
+/// Bits are set with atomic `fetch_or`s, so concurrent sets never lose each
+/// other's bits. Ordering is relaxed: a thread that needs the bits set by
+/// others must synchronize with them first, e.g. by joining them.
+pub struct AtomicBitmap {
+    buffer: Words<AtomicU64>,
+    size: u64,
+}
+
+impl AtomicBitmap {
+    /// Create a new bitmap with the specified size, all bits clear
+    pub fn new(size: u64) -> Self {
+        let buffer_size = size.div_ceil(BITMAP_UNIT_WIDTH as u64);
+        Self {
+            buffer: Words::zeroed(buffer_size as usize),
+            size,
+        }
+    }
+
+    /// Create a new bitmap on huge pages where possible (see
+    /// `Words::huge_pages`), all bits clear
+    pub fn new_huge_pages(size: u64) -> Self {
+        let buffer_size = size.div_ceil(BITMAP_UNIT_WIDTH as u64);
+        Self {
+            buffer: Words::huge_pages(buffer_size as usize),
+            size,
+        }
+    }
+
+    /// Create a new bitmap whose words live in a temp file in `dir`,
+    /// mapped for random access
+    pub fn new_disk_backed(size: u64, dir: &Path) -> Result<Self> {
+        let buffer_size = crate::checked_usize(size.div_ceil(BITMAP_UNIT_WIDTH as u64), "Bitmap size")?;
+        Ok(Self {
+            buffer: Words::disk_backed(buffer_size, dir, Access::Random)?,
+            size,
+        })
+    }
+
+    /// Check if the bitmap's words live in a temp file
+    pub fn is_disk_backed(&self) -> bool {
+        self.buffer.is_disk_backed()
+    }
+
+    /// Check if the bitmap's words are on huge pages
+    pub fn is_huge_page_backed(&self) -> bool {
+        self.buffer.is_huge_page_backed()
+    }
+
+    /// Set a bit at the specified index, returning whether it was already set
+    pub fn set_bit(&self, index: u64) -> bool {
+        if index < self.size {
+            let (word_index, mask) = Self::locate(index);
+            self.fetch_or_word(word_index, mask) & mask != 0
+        } else {
+            false
+        }
+    }
+
+    /// Set a bit without atomic instructions, for single-threaded phases
+    pub fn set_bit_mut(&mut self, index: u64) {
+        if index < self.size {
+            let (word_index, mask) = Self::locate(index);
+            *self.buffer[word_index].get_mut() |= mask;
+        }
+    }
+
+    /// Check if a bit is set at the specified index
+    pub fn test_bit(&self, index: u64) -> bool {
+        if index < self.size {
+            let (word_index, mask) = Self::locate(index);
+            self.buffer[word_index].load(Ordering::Relaxed) & mask != 0
+        } else {
+            false
+        }
+    }
+
+    /// Set the bits of `mask` in word `word_index`, returning the word's
+    /// previous value
+    ///
+    /// Panics if the word is out of range.
+    pub fn fetch_or_word(&self, word_index: usize, mask: u64) -> u64 {
+        self.buffer[word_index].fetch_or(mask, Ordering::Relaxed)
+    }
+
+    /// Clear all bits
+    pub fn clear_all_bits(&mut self) {
+        for word in self.buffer.iter_mut() {
+            *word.get_mut() = 0;
+        }
+    }
+
+    /// Get the words of the bitmap, for threads clearing their own ranges
+    pub(crate) fn words_mut(&mut self) -> &mut [AtomicU64] {
+        &mut self.buffer
+    }
+
+    /// Get the size of the bitmap
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Number of 64-bit words
+    pub fn words(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Count the number of set bits
+    ///
+    /// Large bitmaps are counted on all available cores.
+    pub fn count_set_bits(&self) -> u64 {
+        let count = |words: &[AtomicU64]| words.iter().map(|word| word.load(Ordering::Relaxed).count_ones() as u64).sum::<u64>();
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .min(self.buffer.len() / MIN_WORDS_PER_COUNT_THREAD);
+        if threads <= 1 {
+            return count(&self.buffer);
+        }
+
+        let per_thread = self.buffer.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let counts: Vec<_> = self.buffer.chunks(per_thread).map(|words| scope.spawn(move || count(words))).collect();
+            counts.into_iter().map(|handle| handle.join().expect("counting thread panicked")).sum()
+        })
+    }
+
+    /// Word index and bit mask of a bit
+    fn locate(index: u64) -> (usize, u64) {
+        let word_index = (index / BITMAP_UNIT_WIDTH as u64) as usize;
+        let bit_index = (index % BITMAP_UNIT_WIDTH as u64) as u32;
+        (word_index, 1u64 << bit_index)
+    }
+}
+
+impl Default for AtomicBitmap {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_atomic_bitmap_concurrent_sets() {
+        const THREADS: u64 = 8;
+        let bitmap = AtomicBitmap::new(4096 + 3);
+        let first_sets = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let (bitmap, first_sets) = (&bitmap, &first_sets);
+                scope.spawn(move || {
+                    // Interleaved bits, so every word is written by every thread
+                    for index in (thread..4096).step_by(THREADS as usize) {
+                        assert!(!bitmap.set_bit(index));
+                    }
+                    // Every thread races to set the same bit; only one sets it first
+                    if !bitmap.set_bit(4096) {
+                        first_sets.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(first_sets.load(Ordering::Relaxed), 1);
+        assert_eq!(bitmap.count_set_bits(), 4097);
+        assert!((0..=4096).all(|index| bitmap.test_bit(index)));
+        assert!(!bitmap.test_bit(4097));
+        assert!(!bitmap.set_bit(1 << 20));
+        assert_eq!(bitmap.fetch_or_word(64, 0b100), 0b1);
+
+        let mut bitmap = bitmap;
+        bitmap.clear_all_bits();
+        bitmap.set_bit_mut(5);
+        assert_eq!(bitmap.count_set_bits(), 1);
+        assert_eq!(bitmap.words(), 65);
+    }
+}
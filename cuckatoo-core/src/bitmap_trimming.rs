@@ -5,37 +5,370 @@
 //! - Generates edges only after trimming
 //! - Implements the 4-step trimming process
 
-use crate::{Node, Edge, Result};
-use crate::hashing::SipHash;
+use crate::{ArrayCycleFinder, Config, Edge, MemoryEstimate, Node, NumaPolicy, Result, TrimmingMode, CuckatooError, checked_usize, CancelToken, AdaptiveRoundController, AdaptiveTrimResult, AtomicBitmap, EarlyExit, PerformanceTimer, SipKeys, TrimCheckpoint, TrimOutput};
+use crate::bitmap::{Access, Words};
+use crate::hashing::{for_each_word_node, SipHasher, SIPHASH_BLOCK_EDGES};
+use crate::metrics::{MetricsRegistry, DEFAULT_TIME_BUCKETS};
+use crate::cancel::check_cancelled;
+use crate::checkpoint::CheckpointSink;
+use crate::progress::{report_round, ProgressObserver};
+use crate::numa::{pin_current_thread, NumaTopology};
+use crate::trim_output::edge_indices;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Edges bitmap words trimmed between checks of the cancel token by default
+/// (a few milliseconds of hashing)
+pub const DEFAULT_CHUNK_WORDS: usize = 1 << 12;
 
 /// Bitmap-based trimmer matching C++ implementation
+/// 
+/// With more than one thread each worker owns a contiguous range of the
+/// edges bitmap and sets node bits with atomic `fetch_or`s, so the
+/// survivors don't depend on the thread count. Steps run in chunks of
+/// edges bitmap words, checking the cancel token (if any) between chunks.
+/// 
+/// Rounds alternate between the partitions: the first marks and trims the
+/// U endpoints, the second the V endpoints, the third the U endpoints
+/// again and so on (see `round_side`). One nodes bitmap serves both
+/// partitions: steps one and three clear it before marking the endpoints
+/// of the round's side, so it never holds nodes of both at once and
+/// behaves exactly like separate U and V bitmaps at half the memory.
 pub struct BitmapTrimmer {
     edge_bits: u32,
     number_of_edges: u64,
-    node_mask: u64,
-    edges_bitmap: Vec<u64>,
-    nodes_bitmap: Vec<u64>,
+    edges_bitmap: Words<u64>,
+    nodes_bitmap: AtomicBitmap,
+    threads: usize,
+    numa: Option<NumaTopology>,
+    chunk_words: usize,
+    cancel: Option<CancelToken>,
+    early_exit: EarlyExit,
+    rounds_run: u32,
+    progress: Option<Box<dyn ProgressObserver>>,
+    survivor_curve: Option<Vec<u64>>,
+    keys: Option<SipKeys>,
+    checkpoint_rounds: u32,
+    checkpoint_sink: Option<CheckpointSink>,
+    metrics: Option<MetricsRegistry>,
 }
 
 impl BitmapTrimmer {
     /// Create a new bitmap trimmer
+    /// 
+    /// Panics if the bitmaps can't be addressed on this target; use
+    /// `try_new` to get an error instead.
     pub fn new(edge_bits: u32) -> Self {
-        let number_of_edges = 1 << edge_bits;
-        let node_mask = number_of_edges - 1;
+        Self::try_new(edge_bits).unwrap_or_else(|e| panic!("{}", e))
+    }
+    
+    /// Create a new bitmap trimmer, checking that its bitmaps can be addressed
+    pub fn try_new(edge_bits: u32) -> Result<Self> {
+        if edge_bits >= u64::BITS {
+            return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let number_of_edges: u64 = 1 << edge_bits;
         
         // Calculate bitmap sizes (64 bits per u64)
-        let edges_bitmap_size = ((number_of_edges + 63) / 64) as usize;
-        let nodes_bitmap_size = ((number_of_edges + 63) / 64) as usize;
+        let edges_bitmap_size = checked_usize(number_of_edges.div_ceil(64), "Edges bitmap size")?;
+        checked_usize(number_of_edges.div_ceil(64), "Nodes bitmap size")?;
+        
+        Ok(Self {
+            edges_bitmap: Words::Heap(vec![0; edges_bitmap_size]),
+            nodes_bitmap: AtomicBitmap::new(number_of_edges),
+            ..Self::unallocated(edge_bits, number_of_edges)
+        })
+    }
+    
+    /// Create a new bitmap trimmer whose bitmaps take at most
+    /// `memory_budget` bytes of RAM, backing the rest with temp files in
+    /// `spill_dir`
+    /// 
+    /// The edges bitmap, which every step walks in order, goes to disk
+    /// first; the nodes bitmap, hit at random, follows only if it doesn't
+    /// fit in the budget on its own. Trimming is the same either way, just
+    /// slower from disk.
+    pub fn try_new_with_budget(edge_bits: u32, memory_budget: u64, spill_dir: &Path) -> Result<Self> {
+        if edge_bits >= u64::BITS {
+            return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let number_of_edges: u64 = 1 << edge_bits;
+        let bitmap_words = number_of_edges.div_ceil(64);
+        let bitmap_bytes = bitmap_words.saturating_mul(8);
+        if bitmap_bytes.saturating_mul(2) <= memory_budget {
+            return Self::try_new(edge_bits);
+        }
+        
+        let edges_bitmap = Words::disk_backed(checked_usize(bitmap_words, "Edges bitmap size")?, spill_dir, Access::Sequential)?;
+        let nodes_bitmap = match bitmap_bytes <= memory_budget {
+            true => AtomicBitmap::new(number_of_edges),
+            false => AtomicBitmap::new_disk_backed(number_of_edges, spill_dir)?,
+        };
+        Ok(Self {
+            edges_bitmap,
+            nodes_bitmap,
+            ..Self::unallocated(edge_bits, number_of_edges)
+        })
+    }
+    
+    /// Create a new bitmap trimmer with its bitmaps on huge pages where
+    /// the system has them, falling back to ordinary pages
+    /// 
+    /// `is_huge_page_backed` tells whether the huge pages were granted.
+    pub fn try_new_with_huge_pages(edge_bits: u32) -> Result<Self> {
+        if edge_bits >= u64::BITS {
+            return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let number_of_edges: u64 = 1 << edge_bits;
+        let edges_bitmap_size = checked_usize(number_of_edges.div_ceil(64), "Edges bitmap size")?;
         
+        Ok(Self {
+            edges_bitmap: Words::huge_pages(edges_bitmap_size),
+            nodes_bitmap: AtomicBitmap::new_huge_pages(number_of_edges),
+            ..Self::unallocated(edge_bits, number_of_edges)
+        })
+    }
+    
+    /// Create a trimmer for a configuration's graphs
+    /// 
+    /// Bitmaps over the configured bitmap memory budget are disk-backed
+    /// (see `try_new_with_budget`); otherwise they go on huge pages if the
+    /// configuration asks for them.
+    pub fn for_config(config: &Config) -> Result<Self> {
+        let bitmaps = MemoryEstimate::new(config.edge_bits, TrimmingMode::Lean).trimming;
+        match config.bitmap_memory {
+            Some(budget) if bitmaps > budget => {
+                let spill_dir = config.spill_dir.clone().unwrap_or_else(std::env::temp_dir);
+                Self::try_new_with_budget(config.edge_bits, budget, &spill_dir)
+            },
+            _ if config.use_huge_pages => Self::try_new_with_huge_pages(config.edge_bits),
+            _ => Self::try_new(config.edge_bits),
+        }
+    }
+    
+    /// A trimmer with default options and empty bitmaps
+    fn unallocated(edge_bits: u32, number_of_edges: u64) -> Self {
         Self {
             edge_bits,
             number_of_edges,
-            node_mask,
-            edges_bitmap: vec![0; edges_bitmap_size],
-            nodes_bitmap: vec![0; nodes_bitmap_size],
+            edges_bitmap: Words::default(),
+            nodes_bitmap: AtomicBitmap::new(0),
+            threads: 1,
+            numa: None,
+            chunk_words: DEFAULT_CHUNK_WORDS,
+            cancel: None,
+            early_exit: EarlyExit::default(),
+            rounds_run: 0,
+            progress: None,
+            survivor_curve: None,
+            keys: None,
+            checkpoint_rounds: 0,
+            checkpoint_sink: None,
+            metrics: None,
+        }
+    }
+    
+    /// Edge bits of the graphs the trimmer's bitmaps are sized for
+    pub fn edge_bits(&self) -> u32 {
+        self.edge_bits
+    }
+    
+    /// Check if any of the trimmer's bitmaps live in a temp file
+    pub fn is_disk_backed(&self) -> bool {
+        self.edges_bitmap.is_disk_backed() || self.nodes_bitmap.is_disk_backed()
+    }
+    
+    /// Check if both of the trimmer's bitmaps are on huge pages
+    pub fn is_huge_page_backed(&self) -> bool {
+        self.edges_bitmap.is_huge_page_backed() && self.nodes_bitmap.is_huge_page_backed()
+    }
+    
+    /// Edges bitmap of the last trim: edge `i` survived if bit `i % 64` of
+    /// word `i / 64` is set (see `ArrayCycleFinder::find_cycle_in_bitmap`)
+    pub fn edges_bitmap(&self) -> &[u64] {
+        &self.edges_bitmap
+    }
+    
+    /// Forget the last trim and set the trimmer up for the graph of `keys`,
+    /// keeping its bitmaps
+    /// 
+    /// Every edge is marked alive again, which also touches the bitmap's
+    /// pages ahead of the first round. `resume_trim_edges` trims from this
+    /// state like `trim_edges` would.
+    pub fn reset_for_keys(&mut self, keys: SipKeys) {
+        self.fill_edges_bitmap();
+        self.keys = Some(keys);
+        self.start_rounds();
+    }
+    
+    /// Keep only the bitmaps, dropping the builder options, observers and
+    /// the state of the last trim
+    pub(crate) fn recycle(self) -> Self {
+        Self {
+            edges_bitmap: self.edges_bitmap,
+            nodes_bitmap: self.nodes_bitmap,
+            ..Self::unallocated(self.edge_bits, self.number_of_edges)
+        }
+    }
+    
+    /// Split each trimming step across `threads` threads (1, the default,
+    /// trims on the calling thread)
+    /// 
+    /// Only hashers that can be shared between threads (see
+    /// `SipHasher::as_sync`) are split; others trim on the calling thread.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+    
+    /// Number of threads trimming steps are split across
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+    
+    /// Place the trimming threads and their bitmap ranges on NUMA nodes
+    /// (see `NumaPolicy`)
+    /// 
+    /// With `NumaPolicy::Local` on a host with several nodes, the workers
+    /// of each step are pinned to the nodes in turn, and they fill the
+    /// edges bitmap and clear the nodes bitmap range by range, so the
+    /// kernel puts each range's pages on the node of the worker walking it.
+    /// Only trims split across threads (see `with_threads`) are placed.
+    pub fn with_numa_policy(self, policy: NumaPolicy) -> Self {
+        match policy {
+            NumaPolicy::Local => match NumaTopology::detect() {
+                Some(topology) => self.with_numa_topology(topology),
+                None => self,
+            },
+            NumaPolicy::Off => Self { numa: None, ..self },
         }
     }
     
+    /// Place the trimming threads on the nodes of `topology`
+    pub(crate) fn with_numa_topology(mut self, topology: NumaTopology) -> Self {
+        self.numa = Some(topology);
+        self
+    }
+    
+    /// Number of NUMA nodes the trimming threads are placed on (1 unless
+    /// placed with `with_numa_policy`)
+    pub fn numa_nodes(&self) -> usize {
+        self.numa.as_ref().map_or(1, NumaTopology::nodes)
+    }
+    
+    /// Check the cancel token every `chunk_words` edges bitmap words (64 edges
+    /// each) of a step, per thread
+    /// 
+    /// Smaller chunks stop sooner at the cost of more flag checks.
+    pub fn with_chunk_words(mut self, chunk_words: usize) -> Self {
+        self.chunk_words = chunk_words.max(1);
+        self
+    }
+    
+    /// Stop trimming with `Cancelled` soon after `token` is cancelled,
+    /// e.g. when a new job preempts the current one
+    /// 
+    /// The token is checked between chunks (see `with_chunk_words`), so it
+    /// takes effect within milliseconds rather than at the end of a round.
+    /// Later trims fail too until the token is reset.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+    
+    /// End fixed-round trims early once any of the `early_exit` conditions
+    /// is met
+    pub fn with_early_exit(mut self, early_exit: EarlyExit) -> Self {
+        self.early_exit = early_exit;
+        self
+    }
+    
+    /// Number of rounds the last trim ran
+    pub fn rounds_run(&self) -> u32 {
+        self.rounds_run
+    }
+    
+    /// Report every finished round to `observer`
+    /// 
+    /// Counting the surviving edges for the report takes a pass over the
+    /// edges bitmap per round.
+    pub fn with_progress(mut self, observer: impl ProgressObserver + 'static) -> Self {
+        self.progress = Some(Box::new(observer));
+        self
+    }
+    
+    /// Record the surviving edges after every round (see `survivor_curve`)
+    /// 
+    /// Like progress reports, this takes a pass over the edges bitmap per
+    /// round. With metrics enabled, the curve is also published as the
+    /// `cuckatoo_round_surviving_edges` series.
+    pub fn with_survivor_curve(mut self) -> Self {
+        self.survivor_curve = Some(Vec::new());
+        self
+    }
+    
+    /// Surviving edges after each round of the last trim, empty unless
+    /// enabled with `with_survivor_curve`
+    /// 
+    /// The curve is the quickest health check of a trim: survivors should
+    /// drop steeply over the first rounds and then level off. A curve that
+    /// stays flat or collapses to zero points at bad keys, the wrong
+    /// `edge_bits` or broken trimming.
+    pub fn survivor_curve(&self) -> &[u64] {
+        self.survivor_curve.as_deref().unwrap_or_default()
+    }
+    
+    /// Pass a checkpoint (see `save_checkpoint`) to `sink` after every
+    /// `every_rounds` rounds of fixed-round trims
+    /// 
+    /// Writing the bytes somewhere durable lets a restarted miner resume
+    /// the trim with `restore_checkpoint` instead of starting over.
+    pub fn with_checkpoints(mut self, every_rounds: u32, sink: impl FnMut(&[u8]) + Send + 'static) -> Self {
+        self.checkpoint_rounds = every_rounds.max(1);
+        self.checkpoint_sink = Some(Box::new(sink));
+        self
+    }
+    
+    /// Encode the state of the current or last trim: its keys, the rounds
+    /// finished and the edges bitmap they left
+    /// 
+    /// Taken after a cancelled trim, the checkpoint resumes at the
+    /// interrupted round. Fails if the trimmer hasn't trimmed yet.
+    pub fn save_checkpoint(&self) -> Result<Vec<u8>> {
+        let keys = self.keys.ok_or_else(|| CuckatooError::TrimmingError("No trim to checkpoint".to_string()))?;
+        let checkpoint = TrimCheckpoint {
+            edge_bits: self.edge_bits,
+            rounds_run: self.rounds_run,
+            keys,
+            edges_bitmap: self.edges_bitmap.to_vec(),
+        };
+        Ok(checkpoint.to_bytes())
+    }
+    
+    /// Load a checkpoint written by `save_checkpoint` for
+    /// `resume_trim_edges`
+    /// 
+    /// Fails if the checkpoint is invalid or of a different graph size.
+    pub fn restore_checkpoint(&mut self, bytes: &[u8]) -> Result<()> {
+        let checkpoint = TrimCheckpoint::from_bytes(bytes)?;
+        if checkpoint.edge_bits != self.edge_bits || checkpoint.edges_bitmap.len() != self.edges_bitmap.len() {
+            return Err(CuckatooError::TrimmingError(format!(
+                "Checkpoint of EDGE_BITS={} can't be resumed by a trimmer of EDGE_BITS={}",
+                checkpoint.edge_bits, self.edge_bits
+            )));
+        }
+        self.edges_bitmap.copy_from_slice(&checkpoint.edges_bitmap);
+        self.rounds_run = checkpoint.rounds_run;
+        self.keys = Some(checkpoint.keys);
+        Ok(())
+    }
+    
+    /// Record trimming metrics into the given registry
+    pub fn with_metrics(mut self, registry: MetricsRegistry) -> Self {
+        self.metrics = Some(registry);
+        self
+    }
+    
     /// Perform lean trimming matching C++ implementation
     /// 
     /// This implements the exact same algorithm as the C++ lean trimming:
@@ -43,33 +376,346 @@ impl BitmapTrimmer {
     /// 2. Step one: Generate all possible edge indices in edges bitmap
     /// 3. Step two: Trim edges based on node pairs
     /// 4. Repeat steps 3-4 for multiple rounds
-    pub fn trim_edges(&mut self, siphash: &SipHash, trimming_rounds: u32) -> Result<Vec<Edge>> {
+    pub fn trim_edges<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<Vec<Edge>> {
+        self.run_trim_edges(siphash, trimming_rounds)
+            .inspect_err(|error| self.record_error(error))
+    }
+    
+    /// Lean trimming with a fixed number of rounds (see `trim_edges`)
+    fn run_trim_edges<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<Vec<Edge>> {
+        let start_time = Instant::now();
+        self.trim_bitmap(siphash, trimming_rounds, start_time)?;
+        
+        // Generate final edges from surviving bits in edges bitmap
+        let edges = self.generate_final_edges(siphash)?;
+        self.record_metrics(self.rounds_run, edges.len(), start_time.elapsed());
+        Ok(edges)
+    }
+    
+    /// Perform lean trimming like `trim_edges`, then feed the surviving
+    /// edges to `finder` (see `ArrayCycleFinder::push_edge`) as the final
+    /// pass hashes them, instead of collecting them
+    /// 
+    /// Returns the edge indices, which are the proof nonces, of the first
+    /// cycle of the finder's `cycle_length` edges in ascending order.
+    /// Hashing stops at the bitmap word where the cycle is found.
+    pub fn trim_and_search<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32, finder: &mut ArrayCycleFinder) -> Result<Option<Vec<u64>>> {
+        self.run_trim_and_search(siphash, trimming_rounds, finder)
+            .inspect_err(|error| self.record_error(error))
+    }
+    
+    /// Lean trimming feeding a cycle finder (see `trim_and_search`)
+    fn run_trim_and_search<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32, finder: &mut ArrayCycleFinder) -> Result<Option<Vec<u64>>> {
+        let start_time = Instant::now();
+        self.trim_bitmap(siphash, trimming_rounds, start_time)?;
+        self.record_metrics(self.rounds_run, self.surviving_edges_count() as usize, start_time.elapsed());
+        
+        // Drop any graph left unfinished in the finder
+        finder.finish();
+        self.for_each_final_edge(siphash, |index, edge| finder.push_edge(edge.u, edge.v, index))?;
+        Ok(finder.finish())
+    }
+    
+    /// Perform lean trimming like `trim_edges`, returning the indices of
+    /// the surviving edges with the trim's statistics
+    /// 
+    /// The survivors are counted after every round for
+    /// `TrimOutput::per_round_survivors`, whether or not the survivor curve
+    /// is enabled, but not hashed into edges (see `TrimOutput::edges`).
+    pub fn trim<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<TrimOutput> {
+        let enabled_curve = self.survivor_curve.replace(Vec::new()).is_some();
+        let result = self.run_trim(siphash, trimming_rounds);
+        let per_round_survivors = self.survivor_curve.take().unwrap_or_default();
+        if enabled_curve {
+            self.survivor_curve = Some(per_round_survivors.clone());
+        }
+        
+        let (surviving_edge_indices, elapsed) = result.inspect_err(|error| self.record_error(error))?;
+        Ok(TrimOutput {
+            surviving_edge_indices,
+            rounds_run: self.rounds_run,
+            per_round_survivors,
+            elapsed,
+        })
+    }
+    
+    /// Lean trimming to edge indices (see `trim`)
+    fn run_trim<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<(Vec<u64>, Duration)> {
+        let start_time = Instant::now();
+        self.trim_bitmap(siphash, trimming_rounds, start_time)?;
+        
+        let indices = edge_indices(&self.edges_bitmap);
+        let elapsed = start_time.elapsed();
+        self.record_metrics(self.rounds_run, indices.len(), elapsed);
+        Ok((indices, elapsed))
+    }
+    
+    /// Fill the edges bitmap and run up to `trimming_rounds` rounds on it
+    fn trim_bitmap<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32, start_time: Instant) -> Result<()> {
         // Step 1: Generate all possible edge indices in edges bitmap
         self.generate_edges_bitmap(siphash)?;
+        self.keys = Some(siphash.keys());
+        self.start_rounds();
+        self.run_rounds(siphash, trimming_rounds, self.number_of_edges, start_time)
+    }
+    
+    /// Finish the trim of a restored checkpoint, running the rounds left
+    /// up to `trimming_rounds`
+    /// 
+    /// `siphash` must hash the checkpoint's graph. The survivor curve only
+    /// covers the resumed rounds.
+    pub fn resume_trim_edges<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<Vec<Edge>> {
+        self.run_resume_trim_edges(siphash, trimming_rounds)
+            .inspect_err(|error| self.record_error(error))
+    }
+    
+    /// Resumed fixed-round trimming (see `resume_trim_edges`)
+    fn run_resume_trim_edges<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<Vec<Edge>> {
+        let start_time = Instant::now();
+        match self.keys {
+            Some(keys) if keys == siphash.keys() => {},
+            Some(keys) => {
+                return Err(CuckatooError::TrimmingError(format!(
+                    "Checkpoint of keys {} can't be resumed with keys {}", keys, siphash.keys()
+                )))
+            },
+            None => return Err(CuckatooError::TrimmingError("No checkpoint to resume".to_string())),
+        }
+        
+        if let Some(curve) = &mut self.survivor_curve {
+            curve.clear();
+        }
+        let survivors = self.surviving_edges_count();
+        self.run_rounds(siphash, trimming_rounds, survivors, start_time)?;
         
-        // Perform trimming rounds
+        let edges = self.generate_final_edges(siphash)?;
+        self.record_metrics(self.rounds_run, edges.len(), start_time.elapsed());
+        Ok(edges)
+    }
+    
+    /// Run the rounds after `rounds_run` up to `trimming_rounds`, or until
+    /// an early-exit condition is met
+    fn run_rounds<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32, mut survivors: u64, start_time: Instant) -> Result<()> {
+        for round in self.rounds_run..trimming_rounds {
+            self.trim_round(siphash, round)?;
+            self.rounds_run = round + 1;
+            if self.end_round(&mut survivors, start_time)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Perform lean trimming with the number of rounds chosen by the controller
+    /// 
+    /// Rounds are run until the controller reports that the marginal edge
+    /// reduction dropped below its threshold or its round cap was reached.
+    pub fn trim_edges_adaptive<H: SipHasher>(
+        &mut self,
+        siphash: &H,
+        controller: &mut AdaptiveRoundController,
+    ) -> Result<AdaptiveTrimResult> {
+        self.run_trim_edges_adaptive(siphash, controller)
+            .inspect_err(|error| self.record_error(error))
+    }
+    
+    /// Lean trimming with controller-chosen rounds (see `trim_edges_adaptive`)
+    fn run_trim_edges_adaptive<H: SipHasher>(
+        &mut self,
+        siphash: &H,
+        controller: &mut AdaptiveRoundController,
+    ) -> Result<AdaptiveTrimResult> {
+        let start_time = Instant::now();
+        self.generate_edges_bitmap(siphash)?;
+        self.keys = Some(siphash.keys());
+        
+        controller.start(self.surviving_edges_count());
+        self.start_rounds();
+        let mut round = 0;
+        while controller.should_continue() {
+            self.trim_round(siphash, round)?;
+            round += 1;
+            
+            let survivors = self.surviving_edges_count();
+            if let Some(curve) = &mut self.survivor_curve {
+                curve.push(survivors);
+            }
+            report_round(&mut self.progress, round, survivors, start_time)?;
+            if !controller.record_round(survivors) {
+                break;
+            }
+        }
+        
+        self.rounds_run = controller.rounds_run();
+        let edges = self.generate_final_edges(siphash)?;
+        self.record_metrics(self.rounds_run, edges.len(), start_time.elapsed());
+        Ok(AdaptiveTrimResult {
+            edges,
+            rounds: controller.rounds_run(),
+        })
+    }
+    
+    /// Perform lean trimming while recording per-round and per-step phases
+    /// 
+    /// Phases are recorded as `trimming > round N > step ...` sub-phases
+    /// of whichever phase is currently running on the timer.
+    pub fn trim_edges_timed<H: SipHasher>(
+        &mut self,
+        siphash: &H,
+        trimming_rounds: u32,
+        timer: &mut PerformanceTimer,
+    ) -> Result<Vec<Edge>> {
+        self.run_trim_edges_timed(siphash, trimming_rounds, timer)
+            .inspect_err(|error| self.record_error(error))
+    }
+    
+    /// Lean trimming recording phases on the timer (see `trim_edges_timed`)
+    fn run_trim_edges_timed<H: SipHasher>(
+        &mut self,
+        siphash: &H,
+        trimming_rounds: u32,
+        timer: &mut PerformanceTimer,
+    ) -> Result<Vec<Edge>> {
+        let start_time = Instant::now();
+        timer.start_phase("trimming");
+        self.generate_edges_bitmap(siphash)?;
+        self.keys = Some(siphash.keys());
+        
+        let mut survivors = self.number_of_edges;
+        self.start_rounds();
         for round in 0..trimming_rounds {
+            let round_phase = format!("round {}", round + 1);
+            timer.start_phase(&round_phase);
+            crate::profile_scope!(crate::profiling::TRIM_ROUND, round = round + 1);
             if round == 0 {
-                // First round: steps 1 and 2
+                timer.start_phase("step one");
                 self.trim_edges_step_one(siphash)?;
+                timer.end_phase("step one")?;
+                timer.start_phase("step two");
                 self.trim_edges_step_two(siphash)?;
+                timer.end_phase("step two")?;
             } else {
-                // Subsequent rounds: steps 3 and 4
-                self.trim_edges_step_three(siphash)?;
-                self.trim_edges_step_four(siphash)?;
+                let uorv = round_side(round);
+                timer.start_phase("step three");
+                self.trim_edges_step_three(siphash, uorv)?;
+                timer.end_phase("step three")?;
+                timer.start_phase("step four");
+                self.trim_edges_step_four(siphash, uorv)?;
+                timer.end_phase("step four")?;
+            }
+            timer.end_phase(&round_phase)?;
+            self.rounds_run = round + 1;
+            if self.end_round(&mut survivors, start_time)? {
+                break;
             }
         }
         
-        // Generate final edges from surviving bits in edges bitmap
-        self.generate_final_edges(siphash)
+        timer.start_phase("edge generation");
+        let edges = self.generate_final_edges(siphash)?;
+        timer.end_phase("edge generation")?;
+        timer.end_phase("trimming")?;
+        
+        self.record_metrics(self.rounds_run, edges.len(), start_time.elapsed());
+        Ok(edges)
+    }
+    
+    /// Record a completed trim in the metrics registry, if any
+    fn record_metrics(&self, rounds: u32, surviving_edges: usize, elapsed: Duration) {
+        if let Some(registry) = &self.metrics {
+            registry.counter("cuckatoo_trimmed_graphs_total").inc();
+            registry.counter("cuckatoo_trimming_rounds_total").add(rounds as u64);
+            registry.gauge("cuckatoo_trimming_rounds").set(rounds as f64);
+            registry.gauge("cuckatoo_surviving_edges").set(surviving_edges as f64);
+            registry.histogram("cuckatoo_trimming_seconds", &DEFAULT_TIME_BUCKETS).observe(elapsed.as_secs_f64());
+            if let Some(curve) = &self.survivor_curve {
+                registry.record_survivor_curve(curve);
+            }
+        }
+    }
+    
+    /// Count a failed trim in the metrics registry, if any
+    fn record_error(&self, error: &CuckatooError) {
+        if let Some(registry) = &self.metrics {
+            registry.record_error(error);
+        }
+    }
+    
+    /// Get the number of edges currently surviving in the edges bitmap
+    pub fn surviving_edges_count(&self) -> u64 {
+        self.edges_bitmap.iter().map(|&x| x.count_ones() as u64).sum()
+    }
+    
+    /// Reset the round count and survivor curve for a new trim
+    fn start_rounds(&mut self) {
+        self.rounds_run = 0;
+        if let Some(curve) = &mut self.survivor_curve {
+            curve.clear();
+        }
+    }
+    
+    /// Write a checkpoint if one is due, report the latest round to the
+    /// progress observer and survivor curve and check the early-exit
+    /// conditions, updating `survivors` to the edges it left
+    /// 
+    /// Returns whether trimming should stop.
+    fn end_round(&mut self, survivors: &mut u64, start_time: Instant) -> Result<bool> {
+        if self.checkpoint_sink.is_some() && self.rounds_run.is_multiple_of(self.checkpoint_rounds) {
+            let bytes = self.save_checkpoint()?;
+            if let Some(sink) = &mut self.checkpoint_sink {
+                sink(&bytes);
+            }
+        }
+        if !self.early_exit.is_enabled() && self.progress.is_none() && self.survivor_curve.is_none() {
+            return Ok(false);
+        }
+        let previous = std::mem::replace(survivors, self.surviving_edges_count());
+        if let Some(curve) = &mut self.survivor_curve {
+            curve.push(*survivors);
+        }
+        report_round(&mut self.progress, self.rounds_run, *survivors, start_time)?;
+        Ok(self.early_exit.should_stop(self.rounds_run, previous, *survivors))
+    }
+    
+    /// Perform a single trimming round
+    fn trim_round<H: SipHasher>(&mut self, siphash: &H, round: u32) -> Result<()> {
+        crate::profile_scope!(crate::profiling::TRIM_ROUND, round = round + 1);
+        
+        if round == 0 {
+            // First round: steps 1 and 2
+            self.trim_edges_step_one(siphash)?;
+            self.trim_edges_step_two(siphash)
+        } else {
+            // Later rounds: steps 3 and 4, on the V endpoints in odd
+            // rounds and the U endpoints in even ones
+            let uorv = round_side(round);
+            self.trim_edges_step_three(siphash, uorv)?;
+            self.trim_edges_step_four(siphash, uorv)
+        }
     }
     
     /// Step 1: Generate all possible edge indices in edges bitmap
     /// This matches C++ trimEdgesStepOne
-    fn generate_edges_bitmap(&mut self, _siphash: &SipHash) -> Result<()> {
-        // Set all bits in edges bitmap (all edges are initially present)
-        for i in 0..self.edges_bitmap.len() {
-            self.edges_bitmap[i] = u64::MAX;
+    fn generate_edges_bitmap<H: SipHasher>(&mut self, _siphash: &H) -> Result<()> {
+        self.fill_edges_bitmap();
+        
+        tracing::debug!(
+            edges = self.number_of_edges,
+            bits_set = self.surviving_edges_count(),
+            "Filled edges bitmap"
+        );
+        
+        Ok(())
+    }
+    
+    /// Mark every edge of the graph alive in the edges bitmap
+    fn fill_edges_bitmap(&mut self) {
+        // Set all bits in edges bitmap (all edges are initially present),
+        // on NUMA hosts from the worker that will trim each range
+        match (&self.numa, self.threads) {
+            (Some(numa), threads) if threads > 1 => {
+                for_each_range(&mut self.edges_bitmap, threads, Some(numa), |_, words| words.fill(u64::MAX));
+            },
+            _ => self.edges_bitmap.fill(u64::MAX),
         }
         
         // Clear any excess bits beyond number_of_edges
@@ -79,285 +725,214 @@ impl BitmapTrimmer {
             let mask = (1u64 << (64 - excess_bits)) - 1;
             self.edges_bitmap[last_index] &= mask;
         }
-        
-        // Debug: Print initial edges bitmap state
-        println!("DEBUG: Initial edges bitmap has {} bits set", 
-                 self.edges_bitmap.iter().map(|&x| x.count_ones()).sum::<u32>());
-        println!("DEBUG: Number of edges: {}", self.number_of_edges);
-        
-        Ok(())
     }
     
     /// Step 1: Clear nodes bitmap and generate nodes for all edges
     /// This matches C++ trimEdgesStepOne
-    fn trim_edges_step_one(&mut self, siphash: &SipHash) -> Result<()> {
-        // Clear nodes bitmap
-        self.nodes_bitmap.fill(0);
-        
-        // Go through all edges in the edges bitmap
-        for (bitmap_index, &bitmap_unit) in self.edges_bitmap.iter().enumerate() {
-            if bitmap_unit == 0 {
-                continue;
-            }
-            
-            // Go through all set bits in the unit
-            let mut unit = bitmap_unit;
-            let mut bit_index = 0;
-            while unit != 0 {
-                let bit_pos = unit.trailing_zeros() as u8;
-                let edge_index = (bitmap_index * 64 + bit_index * 64 + bit_pos as usize) as u64;
-                
-                if edge_index < self.number_of_edges {
-                    // Get edge's first node using SipHash
-                    let node = self.siphash24(siphash, edge_index * 2);
-                    
-                    // Enable node in nodes bitmap
-                    Self::set_bit_in_bitmap(&mut self.nodes_bitmap, node.value());
-                }
-                
-                // Clear the bit and continue
-                unit &= unit - 1;
-                bit_index += 1;
-            }
-        }
+    fn trim_edges_step_one<H: SipHasher>(&mut self, siphash: &H) -> Result<()> {
+        // Enable the first node of every edge in the edges bitmap
+        self.mark_nodes(siphash, 0)?;
         
-        // Debug: Print nodes bitmap state after step one
-        println!("DEBUG: After step one, nodes bitmap has {} bits set", 
-                 self.nodes_bitmap.iter().map(|&x| x.count_ones()).sum::<u32>());
+        tracing::trace!(nodes_set = self.nodes_bitmap.count_set_bits(), "Marked U nodes");
         
         Ok(())
     }
     
     /// Step 2: Trim edges based on node pairs
     /// This matches C++ trimEdgesStepTwo
-    fn trim_edges_step_two(&mut self, siphash: &SipHash) -> Result<()> {
-        // Go through all edges in the edges bitmap
-        for bitmap_index in 0..self.edges_bitmap.len() {
-            if self.edges_bitmap[bitmap_index] == 0 {
-                continue;
-            }
-            
-            let mut new_unit = 0u64;
-            let mut bit_index = 0;
-            let mut unit = self.edges_bitmap[bitmap_index];
-            
-            // Go through all set bits in the unit
-            while unit != 0 {
-                let bit_pos = unit.trailing_zeros() as u8;
-                let edge_index = (bitmap_index * 64 + bit_index * 64 + bit_pos as usize) as u64;
-                
-                if edge_index < self.number_of_edges {
-                    // Get edge's first node using SipHash
-                    let node = self.siphash24(siphash, edge_index * 2);
-                    
-                    // Check if node has a pair in the nodes bitmap
-                    if Self::is_bit_set_in_bitmap(&self.nodes_bitmap, node.value() ^ 1) {
-                        // Enable edge
-                        new_unit |= 1u64 << bit_pos;
-                    }
-                }
-                
-                // Clear the bit and continue
-                unit &= unit - 1;
-                bit_index += 1;
-            }
-            
-            self.edges_bitmap[bitmap_index] = new_unit;
-        }
-        
-        Ok(())
+    fn trim_edges_step_two<H: SipHasher>(&mut self, siphash: &H) -> Result<()> {
+        self.keep_paired_edges(siphash, 0)
     }
     
-    /// Step 3: Clear nodes bitmap and generate nodes for surviving edges
+    /// Step 3: Clear nodes bitmap and generate nodes `uorv` for surviving
+    /// edges
     /// This matches C++ trimEdgesStepThree
-    fn trim_edges_step_three(&mut self, siphash: &SipHash) -> Result<()> {
-        // Clear nodes bitmap
-        self.nodes_bitmap.fill(0);
-        
-        // Go through all surviving edges in the edges bitmap
-        for (bitmap_index, &bitmap_unit) in self.edges_bitmap.iter().enumerate() {
-            if bitmap_unit == 0 {
-                continue;
-            }
-            
-            // Go through all set bits in the unit
-            let mut unit = bitmap_unit;
-            let mut bit_index = 0;
-            while unit != 0 {
-                let bit_pos = unit.trailing_zeros() as u8;
-                let edge_index = (bitmap_index * 64 + bit_index * 64 + bit_pos as usize) as u64;
-                
-                if edge_index < self.number_of_edges {
-                    // Get edge's second node using SipHash
-                    let node = self.siphash24(siphash, edge_index * 2 + 1);
-                    
-                    // Enable node in nodes bitmap
-                    Self::set_bit_in_bitmap(&mut self.nodes_bitmap, node.value());
-                }
-                
-                // Clear the bit and continue
-                unit &= unit - 1;
-                bit_index += 1;
-            }
-        }
-        
-        Ok(())
+    fn trim_edges_step_three<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
+        // Enable the node of that side of every surviving edge
+        self.mark_nodes(siphash, uorv)
     }
     
-    /// Step 4: Trim edges based on node pairs (second partition)
+    /// Step 4: Trim edges based on node pairs (partition `uorv`)
     /// This matches C++ trimEdgesStepFour
-    fn trim_edges_step_four(&mut self, siphash: &SipHash) -> Result<()> {
-        // Go through all edges in the edges bitmap
-        for bitmap_index in 0..self.edges_bitmap.len() {
-            if self.edges_bitmap[bitmap_index] == 0 {
-                continue;
-            }
-            
-            let mut new_unit = 0u64;
-            let mut bit_index = 0;
-            let mut unit = self.edges_bitmap[bitmap_index];
-            
-            // Go through all set bits in the unit
-            while unit != 0 {
-                let bit_pos = unit.trailing_zeros() as u8;
-                let edge_index = (bitmap_index * 64 + bit_index * 64 + bit_pos as usize) as u64;
-                
-                if edge_index < self.number_of_edges {
-                    // Get edge's second node using SipHash
-                    let node = self.siphash24(siphash, edge_index * 2 + 1);
-                    
-                    // Check if node has a pair in the nodes bitmap
-                    if Self::is_bit_set_in_bitmap(&self.nodes_bitmap, node.value() ^ 1) {
-                        // Enable edge
-                        new_unit |= 1u64 << bit_pos;
-                    }
-                }
-                
-                // Clear the bit and continue
-                unit &= unit - 1;
-                bit_index += 1;
-            }
-            
-            self.edges_bitmap[bitmap_index] = new_unit;
+    fn trim_edges_step_four<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
+        self.keep_paired_edges(siphash, uorv)
+    }
+    
+    /// Clear the nodes bitmap and set the node `uorv` of every surviving
+    /// edge (steps one and three)
+    fn mark_nodes<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
+        let (edge_bits, chunk_words, cancel, numa) = (self.edge_bits, self.chunk_words, self.cancel.as_ref(), self.numa.as_ref());
+        match (numa, self.threads) {
+            // Each node's workers clear (and so first touch) a share of the nodes bitmap
+            (Some(numa), threads) if threads > 1 => {
+                for_each_range(self.nodes_bitmap.words_mut(), threads, Some(numa), |_, words| {
+                    words.iter_mut().for_each(|word| *word.get_mut() = 0);
+                });
+            },
+            _ => self.nodes_bitmap.clear_all_bits(),
         }
-        
-        Ok(())
+        match shared_hasher(siphash, self.threads) {
+            Some(hasher) => {
+                let nodes_bitmap = &self.nodes_bitmap;
+                for_each_range(&mut self.edges_bitmap, self.threads, numa, |first_word, words| {
+                    for_each_chunk(first_word, words, chunk_words, cancel, |first_word, words| {
+                        mark_words(&hasher, edge_bits, first_word, words, uorv, |node| {
+                            nodes_bitmap.set_bit(node);
+                        });
+                    });
+                });
+            },
+            None => {
+                let nodes_bitmap = &mut self.nodes_bitmap;
+                for_each_chunk(0, &mut self.edges_bitmap, chunk_words, cancel, |first_word, words| {
+                    mark_words(siphash, edge_bits, first_word, words, uorv, |node| nodes_bitmap.set_bit_mut(node));
+                });
+            },
+        }
+        check_cancelled(cancel)
+    }
+    
+    /// Keep only the edges whose node `uorv` has a pair in the nodes bitmap
+    /// (steps two and four)
+    fn keep_paired_edges<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
+        let (edge_bits, nodes_bitmap) = (self.edge_bits, &self.nodes_bitmap);
+        let (chunk_words, cancel, numa) = (self.chunk_words, self.cancel.as_ref(), self.numa.as_ref());
+        match shared_hasher(siphash, self.threads) {
+            Some(hasher) => for_each_range(&mut self.edges_bitmap, self.threads, numa, |first_word, words| {
+                for_each_chunk(first_word, words, chunk_words, cancel, |first_word, words| {
+                    keep_paired_words(&hasher, edge_bits, first_word, words, nodes_bitmap, uorv);
+                });
+            }),
+            None => for_each_chunk(0, &mut self.edges_bitmap, chunk_words, cancel, |first_word, words| {
+                keep_paired_words(siphash, edge_bits, first_word, words, nodes_bitmap, uorv);
+            }),
+        }
+        check_cancelled(cancel)
     }
     
     /// Generate final edges from surviving bits in edges bitmap
     /// This matches C++ edge generation after trimming
-    fn generate_final_edges(&self, siphash: &SipHash) -> Result<Vec<Edge>> {
+    fn generate_final_edges<H: SipHasher>(&self, siphash: &H) -> Result<Vec<Edge>> {
+        crate::profile_scope!(crate::profiling::EDGE_GEN);
         let mut edges = Vec::new();
-        
-        // Go through all surviving edges in the edges bitmap
+        self.for_each_final_edge(siphash, |_, edge| {
+            edges.push(edge);
+            Ok(false)
+        })?;
+        Ok(edges)
+    }
+    
+    /// Pass every surviving edge with its index to `visit`, in order, until
+    /// it returns true or fails
+    fn for_each_final_edge<H: SipHasher>(&self, siphash: &H, mut visit: impl FnMut(u64, Edge) -> Result<bool>) -> Result<()> {
+        // Hash both nodes of each word's surviving edges (in order like C++)
+        let mut first_nodes = [0u64; SIPHASH_BLOCK_EDGES];
+        let mut done = Ok(false);
         for (bitmap_index, &bitmap_unit) in self.edges_bitmap.iter().enumerate() {
-            if bitmap_unit == 0 {
-                continue;
-            }
-            
-            // Go through all set bits in the unit
-            let mut unit = bitmap_unit;
-            let mut bit_index = 0;
-            while unit != 0 {
-                let bit_pos = unit.trailing_zeros() as u8;
-                let edge_index = (bitmap_index * 64 + bit_index * 64 + bit_pos as usize) as u64;
-                
-                if edge_index < self.number_of_edges {
-                    // Generate edge's nodes using SipHash
-                    let u = self.siphash24(siphash, edge_index * 2);
-                    let v = self.siphash24(siphash, edge_index * 2 + 1);
-                    
-                    // Create edge (preserve order like C++)
-                    let edge = Edge::new(u, v);
-                    edges.push(edge);
+            for_each_word_node(siphash, self.edge_bits, bitmap_index, bitmap_unit, 0, |bit, node| {
+                first_nodes[bit] = node;
+            });
+            for_each_word_node(siphash, self.edge_bits, bitmap_index, bitmap_unit, 1, |bit, node| {
+                if matches!(done, Ok(false)) {
+                    let index = (bitmap_index * SIPHASH_BLOCK_EDGES + bit) as u64;
+                    done = visit(index, Edge::new(Node::new(first_nodes[bit]), Node::new(node)));
                 }
-                
-                // Clear the bit and continue
-                unit &= unit - 1;
-                bit_index += 1;
+            });
+            if !matches!(done, Ok(false)) {
+                break;
             }
         }
-        
-        Ok(edges)
+        done.map(|_| ())
     }
-    
-    /// SipHash-2-4 implementation matching C++ version
-    fn siphash24(&self, siphash: &SipHash, nonce: u64) -> Node {
-        // Use the same SipHash implementation as the main hashing module
-        let key = siphash.get_key();
-        let node_value = if self.edge_bits == 32 {
-            self.siphash24_internal(key, nonce)
-        } else {
-            self.siphash24_internal(key, nonce) & self.node_mask
-        };
-        
-        Node::new(node_value)
-    }
-    
-    /// Internal SipHash-2-4 implementation
-    fn siphash24_internal(&self, key: [u64; 4], nonce: u64) -> u64 {
-        // Initialize states with keys
-        let mut states = key;
-        
-        // Perform hash on states (exactly like C++ implementation)
-        states[3] ^= nonce;
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        states[0] ^= nonce;
-        states[2] ^= 255;
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        
-        // Get node from states
-        states[0] ^ states[1] ^ states[2] ^ states[3]
-    }
-    
-    /// SipRound implementation matching C++ version
-    fn sip_round(&self, states: &mut [u64; 4]) {
-        // Perform SipRound on states (exactly like C++ implementation)
-        states[0] = states[0].wrapping_add(states[1]);
-        states[1] = states[1].rotate_left(13);
-        states[1] ^= states[0];
-        states[0] = states[0].rotate_left(32);
-        states[2] = states[2].wrapping_add(states[3]);
-        states[3] = states[3].rotate_left(16);
-        states[3] ^= states[2];
-        states[0] = states[0].wrapping_add(states[3]);
-        states[3] = states[3].rotate_left(21);
-        states[3] ^= states[0];
-        states[2] = states[2].wrapping_add(states[1]);
-        states[1] = states[1].rotate_left(17);
-        states[1] ^= states[2];
-        states[2] = states[2].rotate_left(32);
-    }
-    
-    /// Set bit in bitmap
-    fn set_bit_in_bitmap(bitmap: &mut [u64], index: u64) {
-        let word_index = (index / 64) as usize;
-        let bit_index = (index % 64) as u8;
-        if word_index < bitmap.len() {
-            bitmap[word_index] |= 1u64 << bit_index;
-        }
-    }
-    
-    /// Check if bit is set in bitmap
-    fn is_bit_set_in_bitmap(bitmap: &[u64], index: u64) -> bool {
-        let word_index = (index / 64) as usize;
-        let bit_index = (index % 64) as u8;
-        if word_index < bitmap.len() {
-            (bitmap[word_index] & (1u64 << bit_index)) != 0
-        } else {
-            false
+}
+
+/// Side (0 for U, 1 for V) a lean trimming round trims: the sides
+/// alternate, starting with U, as trimming the same side again removes
+/// nothing
+pub(crate) fn round_side(round: u32) -> u64 {
+    u64::from(round & 1)
+}
+
+/// The hasher to share between `threads` workers, if there is more than
+/// one and the hasher can be shared
+fn shared_hasher<H: SipHasher>(siphash: &H, threads: usize) -> Option<&(dyn SipHasher + Sync)> {
+    if threads > 1 {
+        siphash.as_sync()
+    } else {
+        None
+    }
+}
+
+/// Run `work(first_word, words)` on `threads` contiguous ranges of a
+/// bitmap, each on its own scoped thread, pinned to the CPUs of its node
+/// if `numa` is given
+fn for_each_range<T: Send>(bitmap: &mut [T], threads: usize, numa: Option<&NumaTopology>, work: impl Fn(usize, &mut [T]) + Sync) {
+    let per_thread = bitmap.len().div_ceil(threads).max(1);
+    let ranges = bitmap.len().div_ceil(per_thread);
+    let work = &work;
+    std::thread::scope(|scope| {
+        for (index, words) in bitmap.chunks_mut(per_thread).enumerate() {
+            scope.spawn(move || {
+                if let Some(numa) = numa {
+                    pin_current_thread(numa.cpus_for_worker(index, ranges));
+                }
+                work(index * per_thread, words)
+            });
         }
+    });
+}
+
+/// Run `work(first_word, words)` on chunks of `chunk_words` words of a
+/// range starting at word `first_word`, skipping the rest once `cancel` is
+/// cancelled
+fn for_each_chunk(
+    first_word: usize,
+    words: &mut [u64],
+    chunk_words: usize,
+    cancel: Option<&CancelToken>,
+    mut work: impl FnMut(usize, &mut [u64]),
+) {
+    for (index, chunk) in words.chunks_mut(chunk_words).enumerate() {
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            return;
+        }
+        work(first_word + index * chunk_words, chunk);
+    }
+}
+
+/// Call `set(node)` with the node `uorv` of every edge of the edges bitmap
+/// words starting at word `first_word`
+fn mark_words<H: SipHasher>(hasher: &H, edge_bits: u32, first_word: usize, words: &[u64], uorv: u64, mut set: impl FnMut(u64)) {
+    for (offset, &unit) in words.iter().enumerate() {
+        for_each_word_node(hasher, edge_bits, first_word + offset, unit, uorv, |_, node| set(node));
+    }
+}
+
+/// Clear the edges of the words starting at word `first_word` whose node
+/// `uorv` has no pair in the nodes bitmap
+fn keep_paired_words<H: SipHasher>(
+    hasher: &H,
+    edge_bits: u32,
+    first_word: usize,
+    words: &mut [u64],
+    nodes_bitmap: &AtomicBitmap,
+    uorv: u64,
+) {
+    for (offset, unit) in words.iter_mut().enumerate() {
+        let mut new_unit = 0u64;
+        for_each_word_node(hasher, edge_bits, first_word + offset, *unit, uorv, |bit, node| {
+            if nodes_bitmap.test_bit(node ^ 1) {
+                new_unit |= 1u64 << bit;
+            }
+        });
+        *unit = new_unit;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Header;
+    use crate::{Header, SipHash, SipKeys, DEFAULT_MIN_REDUCTION, DEFAULT_PLATEAU_ROUNDS};
 
     #[test]
     fn test_bitmap_trimmer_basic() {
@@ -374,17 +949,253 @@ mod tests {
         assert!(edges.len() < 1024); // Should be trimmed down
     }
     
+    #[test]
+    fn test_trimming_matches_oracle() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"oracle", 7));
+        crate::trim_oracle::assert_matches_oracle(&siphash, 10, 6, |rounds| {
+            BitmapTrimmer::new(10).trim_edges(&siphash, rounds).unwrap()
+        });
+    }
+    
+    #[test]
+    fn test_shared_nodes_bitmap_matches_partition_degrees() {
+        // The oracle tracks U and V nodes separately, so rounds on both
+        // sides of a larger graph check that the shared bitmap never mixes
+        // the partitions
+        let siphash = SipHash::with_key(SipKeys::from_header(b"partitions", 1));
+        crate::trim_oracle::assert_matches_oracle(&siphash, 13, 10, |rounds| {
+            BitmapTrimmer::new(13).trim_edges(&siphash, rounds).unwrap()
+        });
+    }
+    
+    #[test]
+    fn test_survivors_fall_to_two_core() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"survival", 1));
+        let mut trimmer = BitmapTrimmer::new(16).with_survivor_curve();
+        trimmer.trim_edges(&siphash, 200).unwrap();
+        let curve = trimmer.survivor_curve().to_vec();
+        
+        // The C++ lean miner keeps an edge in round one when the partner of
+        // its U endpoint has an edge, with probability 1 - 1/e, and in round
+        // two likewise on the V side among the round one survivors
+        let edges = (1u64 << 16) as f64;
+        let first = 1.0 - (-1.0f64).exp();
+        let second = first * (1.0 - (-first).exp());
+        assert!((curve[0] as f64 / edges - first).abs() < 0.01, "round 1 kept {}", curve[0]);
+        assert!((curve[1] as f64 / edges - second).abs() < 0.01, "round 2 kept {}", curve[1]);
+        
+        // Every round removes edges until a round on one side removes none,
+        // after which neither side has anything left to trim
+        let settled = curve.windows(2).position(|pair| pair[1] == pair[0]).expect("trimming never settled");
+        assert!(settled > 10, "trimming stalled after round {}", settled + 1);
+        assert!(curve[..=settled].windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(curve[settled..].iter().all(|&survivors| survivors == curve[settled]));
+        
+        let all: Vec<Edge> = (0..1u64 << 16).map(|index| siphash.hash_pair(index, 16)).collect();
+        let survivors: Vec<usize> = (0..all.len())
+            .filter(|&index| trimmer.edges_bitmap()[index / 64] >> (index % 64) & 1 == 1)
+            .collect();
+        assert_eq!(survivors, crate::graph_stats::two_core(&all));
+    }
+    
+    #[test]
+    fn test_bitmap_trimmer_try_new() {
+        assert!(BitmapTrimmer::try_new(12).is_ok());
+        assert!(matches!(BitmapTrimmer::try_new(64), Err(CuckatooError::InvalidEdgeBits(64))));
+        assert_eq!(checked_usize(1 << 20, "Edge count").unwrap(), 1 << 20);
+        if usize::BITS < 64 {
+            assert!(matches!(checked_usize(1 << 40, "Edge count"), Err(CuckatooError::MemoryError(_))));
+        }
+    }
+    
+    #[test]
+    fn test_threaded_trimming_matches_single_thread() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"threads", 2));
+        let expected = BitmapTrimmer::new(12).trim_edges(&siphash, 9).unwrap();
+        for threads in [2, 3, 8] {
+            let mut trimmer = BitmapTrimmer::new(12).with_threads(threads);
+            assert_eq!(trimmer.trim_edges(&siphash, 9).unwrap(), expected, "{} threads", threads);
+        }
+        // More threads than bitmap words
+        let small = BitmapTrimmer::new(6).trim_edges(&siphash, 3).unwrap();
+        assert_eq!(BitmapTrimmer::new(6).with_threads(4).trim_edges(&siphash, 3).unwrap(), small);
+    }
+    
+    #[test]
+    fn test_chunked_trimming_stops() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"chunks", 4));
+        let expected = BitmapTrimmer::new(12).trim_edges(&siphash, 5).unwrap();
+        for (chunk_words, threads) in [(1, 1), (5, 3)] {
+            let mut trimmer = BitmapTrimmer::new(12).with_chunk_words(chunk_words).with_threads(threads);
+            assert_eq!(trimmer.trim_edges(&siphash, 5).unwrap(), expected);
+        }
+        
+        let token = CancelToken::new();
+        token.cancel();
+        let mut trimmer = BitmapTrimmer::new(12).with_chunk_words(1).with_cancel_token(token.clone());
+        assert!(matches!(trimmer.trim_edges(&siphash, 5), Err(CuckatooError::Cancelled)));
+        token.reset();
+        assert_eq!(trimmer.trim_edges(&siphash, 5).unwrap(), expected);
+    }
+    
     #[test]
     fn test_bitmap_operations() {
         let _trimmer = BitmapTrimmer::new(10);
-        let mut bitmap = vec![0u64; 2];
+        let mut bitmap = AtomicBitmap::new(128);
         
         // Test setting and checking bits
-        BitmapTrimmer::set_bit_in_bitmap(&mut bitmap, 0);
-        assert!(BitmapTrimmer::is_bit_set_in_bitmap(&bitmap, 0));
-        assert!(!BitmapTrimmer::is_bit_set_in_bitmap(&bitmap, 1));
+        bitmap.set_bit_mut(0);
+        assert!(bitmap.test_bit(0));
+        assert!(!bitmap.test_bit(1));
+        
+        bitmap.set_bit_mut(65);
+        assert!(bitmap.test_bit(65));
+    }
+    
+    #[test]
+    fn test_timed_trimming_records_phases() {
+        let header = Header::new(&[0u8; 238]);
+        let siphash = SipHash::new_from_header(&header, 0);
+        let mut timer = PerformanceTimer::new();
+        
+        let timed = BitmapTrimmer::new(10).trim_edges_timed(&siphash, 3, &mut timer).unwrap();
+        let plain = BitmapTrimmer::new(10).trim_edges(&siphash, 3).unwrap();
+        assert_eq!(timed, plain);
+        
+        assert_eq!(timer.get_phase_count("trimming > round 1 > step two"), Some(1));
+        assert_eq!(timer.get_phase_count("trimming > round 3 > step four"), Some(1));
+        assert!(timer.get_phase_duration("trimming > round 2 > step one").is_none());
+    }
+    
+    #[test]
+    fn test_trimming_metrics() {
+        let header = Header::new(&[0u8; 238]);
+        let siphash = SipHash::new_from_header(&header, 0);
+        let registry = MetricsRegistry::new();
+        
+        let mut trimmer = BitmapTrimmer::new(10).with_metrics(registry.clone());
+        let edges = trimmer.trim_edges(&siphash, 5).unwrap();
+        trimmer.trim_edges(&siphash, 5).unwrap();
+        
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("cuckatoo_trimmed_graphs_total"), Some(2));
+        assert_eq!(snapshot.counter("cuckatoo_trimming_rounds_total"), Some(10));
+        assert_eq!(snapshot.gauge("cuckatoo_surviving_edges"), Some(edges.len() as f64));
+        assert_eq!(snapshot.histogram("cuckatoo_trimming_seconds").unwrap().count, 2);
+    }
+    
+    #[test]
+    fn test_early_exit() {
+        let header = Header::new(&[0u8; 238]);
+        let siphash = SipHash::new_from_header(&header, 0);
+        let registry = MetricsRegistry::new();
+        let full = BitmapTrimmer::new(10).trim_edges(&siphash, 90).unwrap();
+        
+        // A plateau ends trimming without changing the survivors
+        let mut trimmer = BitmapTrimmer::new(10)
+            .with_early_exit(EarlyExit::new().stop_on_plateau())
+            .with_metrics(registry.clone());
+        assert_eq!(trimmer.trim_edges(&siphash, 90).unwrap(), full);
+        let rounds = trimmer.rounds_run();
+        assert!(rounds < 90);
+        assert_eq!(registry.snapshot().counter("cuckatoo_trimming_rounds_total"), Some(rounds as u64));
+        assert_eq!(registry.snapshot().gauge("cuckatoo_trimming_rounds"), Some(rounds as f64));
+        
+        let mut trimmer = BitmapTrimmer::new(10).with_early_exit(EarlyExit::new().stop_when_edges_below(512));
+        let edges = trimmer.trim_edges(&siphash, 90).unwrap();
+        assert!(edges.len() < 512);
+        assert_eq!(edges, BitmapTrimmer::new(10).trim_edges(&siphash, trimmer.rounds_run()).unwrap());
+    }
+
+    #[test]
+    fn test_early_exit_deep_in_trimming() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"early exit", 6));
+        let full = BitmapTrimmer::new(14).trim_edges(&siphash, 90).unwrap();
+        
+        // Stops at the first round below the threshold, many rounds in
+        let mut trimmer = BitmapTrimmer::new(14).with_early_exit(EarlyExit::new().stop_when_edges_below(200));
+        let edges = trimmer.trim_edges(&siphash, 90).unwrap();
+        let rounds = trimmer.rounds_run();
+        assert!(edges.len() < 200 && edges.len() > full.len());
+        assert!(rounds > 10 && rounds < 90, "stopped after {} rounds", rounds);
+        assert!(BitmapTrimmer::new(14).trim_edges(&siphash, rounds - 1).unwrap().len() >= 200);
+        
+        // The plateau stop only comes once both sides are fully trimmed
+        let mut trimmer = BitmapTrimmer::new(14).with_early_exit(EarlyExit::new().stop_on_plateau());
+        assert_eq!(trimmer.trim_edges(&siphash, 90).unwrap(), full);
+        assert!(trimmer.rounds_run() > rounds && trimmer.rounds_run() < 90);
+    }
+
+    #[test]
+    fn test_survivor_curve() {
+        let header = Header::new(&[0u8; 238]);
+        let siphash = SipHash::new_from_header(&header, 0);
+        let registry = MetricsRegistry::new();
+        assert!(BitmapTrimmer::new(10).survivor_curve().is_empty());
+        
+        let mut trimmer = BitmapTrimmer::new(10).with_survivor_curve().with_metrics(registry.clone());
+        let edges = trimmer.trim_edges(&siphash, 6).unwrap();
+        let curve = trimmer.survivor_curve().to_vec();
+        assert_eq!(curve.len(), 6);
+        assert!(curve.windows(2).all(|pair| pair[1] <= pair[0]));
+        // Every round trims the side the previous one didn't, so the curve
+        // keeps falling past the first two rounds
+        assert!(curve[1..5].windows(2).all(|pair| pair[1] < pair[0]), "{:?}", curve);
+        assert_eq!(curve[5], edges.len() as u64);
+        assert_eq!(registry.snapshot().survivor_curve(), curve);
+        
+        // A shorter trim replaces the whole series
+        trimmer.trim_edges(&siphash, 2).unwrap();
+        assert_eq!(trimmer.survivor_curve(), &curve[..2]);
+        assert_eq!(registry.snapshot().survivor_curve(), &curve[..2]);
+        assert!(registry.to_prometheus().contains("cuckatoo_round_surviving_edges{round=\"2\"}"));
+    }
+
+    #[test]
+    fn test_adaptive_trimming() {
+        let header = Header::new(&[0u8; 238]);
+        let siphash = SipHash::new_from_header(&header, 0);
+        let mut controller = AdaptiveRoundController::new(0.005, 90);
+        
+        let result = BitmapTrimmer::new(10).trim_edges_adaptive(&siphash, &mut controller).unwrap();
+        assert!(result.rounds >= 1 && result.rounds <= 90);
+        
+        // Same number of fixed rounds should produce the same survivors
+        let fixed = BitmapTrimmer::new(10).trim_edges(&siphash, result.rounds).unwrap();
+        assert_eq!(result.edges, fixed);
+    }
+
+    #[test]
+    fn test_target_survivors_reached() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"target", 5));
+        let mut config = Config::new(14);
+        config.target_survivors = Some(10);
+        let mut controller = config.round_controller().unwrap();
+        let result = BitmapTrimmer::new(14).trim_edges_adaptive(&siphash, &mut controller).unwrap();
+        
+        // The controller stops at the first round leaving at most 10 × 42
+        // edges, long before the cap
+        let target = 10 * crate::SOLUTION_SIZE;
+        assert!(result.edges.len() <= target, "{} edges after {} rounds", result.edges.len(), result.rounds);
+        assert!(result.rounds > 2 && result.rounds < config.trimming_rounds);
+        assert!(BitmapTrimmer::new(14).trim_edges(&siphash, result.rounds - 1).unwrap().len() > target);
+    }
+    
+    #[test]
+    fn test_auto_rounds_stop_at_plateau() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"plateau", 3));
+        let mut controller = AdaptiveRoundController::auto(90);
+        let result = BitmapTrimmer::new(14).trim_edges_adaptive(&siphash, &mut controller).unwrap();
+        assert!(result.rounds > 10 && result.rounds < 90, "stopped after {} rounds", result.rounds);
         
-        BitmapTrimmer::set_bit_in_bitmap(&mut bitmap, 65);
-        assert!(BitmapTrimmer::is_bit_set_in_bitmap(&bitmap, 65));
+        // The last DEFAULT_PLATEAU_ROUNDS rounds each removed less than the
+        // minimum reduction, and the round before them more
+        let mut trimmer = BitmapTrimmer::new(14).with_survivor_curve();
+        assert_eq!(trimmer.trim_edges(&siphash, result.rounds).unwrap(), result.edges);
+        let curve: Vec<u64> = std::iter::once(1 << 14).chain(trimmer.survivor_curve().iter().copied()).collect();
+        let reductions: Vec<f64> = curve.windows(2).map(|pair| (pair[0] - pair[1]) as f64 / pair[0] as f64).collect();
+        let plateau = reductions.len() - DEFAULT_PLATEAU_ROUNDS as usize;
+        assert!(reductions[plateau..].iter().all(|&reduction| reduction < DEFAULT_MIN_REDUCTION), "{:?}", reductions);
+        assert!(reductions[plateau - 1] >= DEFAULT_MIN_REDUCTION, "{:?}", reductions);
     }
 }
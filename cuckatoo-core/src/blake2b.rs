@@ -1,37 +1,243 @@
-// Simplified Blake2b implementation for Cuckatoo
-// This is a minimal working version that generates SipHash keys from header+nonce
-
-/// Simplified Blake2b hash function
-/// This generates 4 u64 values that can be used as SipHash keys
-pub fn blake2b(header: &[u8], nonce: u64) -> [u64; 4] {
-    // For now, use a simple hash-based approach to generate keys
-    // This is not the full Blake2b implementation but generates deterministic keys
-    
-    let mut key = [0u64; 4];
-    
-    // Use the header and nonce to generate deterministic keys
-    let mut hash_state = 0u64;
-    
-    // Mix in header bytes
-    for &byte in header {
-        hash_state = hash_state.wrapping_mul(0x9e3779b97f4a7c15u64);
-        hash_state ^= byte as u64;
-        hash_state = hash_state.rotate_left(13);
+//! Blake2b (RFC 7693) for Cuckatoo key derivation
+//!
+//! The SipHash keys are the Blake2b hash of the header with the nonce, with
+//! a 32-byte output like the C++ reference miner and Grin. Note that a
+//! 32-byte Blake2b output is not a truncated Blake2b-512 digest: the output
+//! length is part of the parameter block.
+//!
+//! Grin and the C++ reference miner feed Blake2b differently (see
+//! `KeyProvenance`). Both are `KeyDerivation`s, chosen at run time through
+//! `Config::key_schedule`, so parity tests against either lineage run from
+//! the same build.
+
+use crate::endian::{header_with_nonce, read_u64_le, KEYS_SIZE};
+use crate::{CuckatooError, KeyProvenance, SipKeys};
+use std::fmt;
+
+/// Size of a Blake2b block in bytes
+const BLOCK_SIZE: usize = 128;
+
+/// Largest Blake2b output in bytes
+pub const MAX_OUTPUT_SIZE: usize = 64;
+
+/// Initialization vector (the SHA-512 IV)
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+/// Message word permutation of each round (rounds 10 and 11 reuse 0 and 1)
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Derive the SipHash keys of a header and nonce with Blake2b
+pub fn blake2b(header: &[u8], nonce: u64) -> SipKeys {
+    SipKeys::from_header(header, nonce)
+}
+
+/// Derivation of the SipHash keys of a header and nonce
+pub trait KeyDerivation: Send + Sync {
+    /// Derive the keys of `header` with `nonce`
+    fn derive_keys(&self, header: &[u8], nonce: u64) -> SipKeys;
+}
+
+/// Grin's derivation: Blake2b of the header with the nonce appended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GrinBlake2b;
+
+impl KeyDerivation for GrinBlake2b {
+    fn derive_keys(&self, header: &[u8], nonce: u64) -> SipKeys {
+        SipKeys::from_header(header, nonce)
     }
-    
-    // Mix in nonce
-    hash_state = hash_state.wrapping_mul(0x9e3779b97f4a7c15u64);
-    hash_state ^= nonce;
-    hash_state = hash_state.rotate_left(13);
-    
-    // Generate 4 keys from the hash state
-    for i in 0..4 {
-        hash_state = hash_state.wrapping_mul(0x9e3779b97f4a7c15u64);
-        hash_state = hash_state.rotate_left(13);
-        key[i] = hash_state;
+}
+
+/// The C++ reference miner's derivation: Blake2b of the header with the
+/// low 32 bits of the nonce written over its last four bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReferenceBlake2b;
+
+impl KeyDerivation for ReferenceBlake2b {
+    fn derive_keys(&self, header: &[u8], nonce: u64) -> SipKeys {
+        SipKeys::from_header_reference(header, nonce)
     }
-    
-    key
+}
+
+/// Fixed keys of a test graph, whatever the header and nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestSeedKeys(pub [u64; 4]);
+
+impl KeyDerivation for TestSeedKeys {
+    fn derive_keys(&self, _header: &[u8], _nonce: u64) -> SipKeys {
+        SipKeys::test_seed(self.0)
+    }
+}
+
+/// Key derivation selected in the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeySchedule {
+    /// Blake2b the way Grin does (see `GrinBlake2b`)
+    #[default]
+    Grin,
+    /// Blake2b the way the C++ reference miner does (see `ReferenceBlake2b`)
+    ReferenceMiner,
+    /// The same fixed keys for every header and nonce (see `TestSeedKeys`)
+    TestSeed([u64; 4]),
+}
+
+impl KeyDerivation for KeySchedule {
+    fn derive_keys(&self, header: &[u8], nonce: u64) -> SipKeys {
+        match *self {
+            KeySchedule::Grin => GrinBlake2b.derive_keys(header, nonce),
+            KeySchedule::ReferenceMiner => ReferenceBlake2b.derive_keys(header, nonce),
+            KeySchedule::TestSeed(words) => TestSeedKeys(words).derive_keys(header, nonce),
+        }
+    }
+}
+
+impl fmt::Display for KeySchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeySchedule::Grin => write!(f, "{}", KeyProvenance::Grin),
+            KeySchedule::ReferenceMiner => write!(f, "{}", KeyProvenance::ReferenceMiner),
+            KeySchedule::TestSeed([k0, k1, k2, k3]) => {
+                write!(f, "{}:0x{:016x},0x{:016x},0x{:016x},0x{:016x}", KeyProvenance::TestSeed, k0, k1, k2, k3)
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for KeySchedule {
+    type Err = CuckatooError;
+
+    /// Parse `grin-blake2b`, `reference-miner` or `test-seed:<k0>,<k1>,<k2>,<k3>`
+    /// (keys in hex, with or without `0x`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CuckatooError::InternalError(format!("Unknown key derivation: {}", s));
+        match s.split_once(':') {
+            None => match s.parse::<KeyProvenance>()? {
+                KeyProvenance::Grin => Ok(KeySchedule::Grin),
+                KeyProvenance::ReferenceMiner => Ok(KeySchedule::ReferenceMiner),
+                KeyProvenance::TestSeed => Err(invalid()),
+            },
+            Some(("test-seed", keys)) => {
+                let words = keys
+                    .split(',')
+                    .map(|key| {
+                        let key = key.trim();
+                        u64::from_str_radix(key.strip_prefix("0x").unwrap_or(key), 16).map_err(|_| invalid())
+                    })
+                    .collect::<Result<Vec<u64>, _>>()?;
+                Ok(KeySchedule::TestSeed(words.try_into().map_err(|_| invalid())?))
+            },
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+/// 32-byte Blake2b digest of the header with the nonce appended
+/// (big-endian), the bytes of the SipHash keys
+pub fn blake2b_digest(header: &[u8], nonce: u64) -> [u8; KEYS_SIZE] {
+    blake2b_bytes(&header_with_nonce(header, nonce))
+}
+
+/// 32-byte Blake2b digest of a byte string
+pub fn blake2b_bytes(bytes: &[u8]) -> [u8; KEYS_SIZE] {
+    let mut digest = [0u8; KEYS_SIZE];
+    digest.copy_from_slice(&blake2b_hash(bytes, KEYS_SIZE)[..KEYS_SIZE]);
+    digest
+}
+
+/// 64-byte Blake2b-512 digest of a byte string
+pub fn blake2b_512(bytes: &[u8]) -> [u8; MAX_OUTPUT_SIZE] {
+    blake2b_hash(bytes, MAX_OUTPUT_SIZE)
+}
+
+/// Unkeyed Blake2b with an `output_size`-byte output (1 to 64)
+///
+/// Only the first `output_size` bytes of the result are the digest; the
+/// rest are zero.
+pub fn blake2b_hash(bytes: &[u8], output_size: usize) -> [u8; MAX_OUTPUT_SIZE] {
+    assert!((1..=MAX_OUTPUT_SIZE).contains(&output_size), "Blake2b output size must be 1-64 bytes");
+
+    // Parameter block: digest length, no key, fanout 1, depth 1
+    let mut state = IV;
+    state[0] ^= 0x0101_0000 ^ output_size as u64;
+
+    // Every block but the last is compressed as it is; the last one (empty
+    // input still has one) is zero-padded and flagged
+    let mut counter: u128 = 0;
+    let full_blocks = bytes.len().saturating_sub(1) / BLOCK_SIZE;
+    for block in bytes.chunks_exact(BLOCK_SIZE).take(full_blocks) {
+        counter += BLOCK_SIZE as u128;
+        compress(&mut state, block, counter, false);
+    }
+    let rest = &bytes[full_blocks * BLOCK_SIZE..];
+    let mut last = [0u8; BLOCK_SIZE];
+    last[..rest.len()].copy_from_slice(rest);
+    counter += rest.len() as u128;
+    compress(&mut state, &last, counter, true);
+
+    let mut output = [0u8; MAX_OUTPUT_SIZE];
+    for (chunk, word) in output.chunks_exact_mut(8).zip(state) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    output[output_size..].fill(0);
+    output
+}
+
+/// Compression function F
+fn compress(state: &mut [u64; 8], block: &[u8], counter: u128, last: bool) {
+    let mut message = [0u64; 16];
+    for (i, word) in message.iter_mut().enumerate() {
+        *word = read_u64_le(block, i * 8).expect("full block");
+    }
+
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(state);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= counter as u64;
+    v[13] ^= (counter >> 64) as u64;
+    if last {
+        v[14] = !v[14];
+    }
+
+    for round in 0..12 {
+        let s = &SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, message[s[0]], message[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, message[s[2]], message[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, message[s[4]], message[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, message[s[6]], message[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, message[s[8]], message[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, message[s[10]], message[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, message[s[12]], message[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, message[s[14]], message[s[15]]);
+    }
+
+    for i in 0..8 {
+        state[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+/// Mixing function G
+fn mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
 }
 
 #[cfg(test)]
@@ -45,8 +251,8 @@ mod tests {
         let result = blake2b(header, nonce);
         
         // Basic test - just ensure it doesn't panic and returns 4 u64s
-        assert_eq!(result.len(), 4);
-        assert!(result.iter().any(|&x| x != 0)); // At least one non-zero value
+        assert_eq!(result.words().len(), 4);
+        assert!(result.words().iter().any(|&x| x != 0)); // At least one non-zero value
     }
     
     #[test]
@@ -72,4 +278,62 @@ mod tests {
         // Different inputs should produce different outputs
         assert_ne!(result1, result2);
     }
+    
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_rfc_7693_vectors() {
+        assert_eq!(
+            hex(&blake2b_512(b"abc")),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+        assert_eq!(
+            hex(&blake2b_512(b"")),
+            "786a02f742015903c6c6fd852552d272912f4740e15847618a86e217f71f5419\
+             d25e1031afee585313896444934eb04b903a685b1448b755d56f701afe9be2ce"
+        );
+        // 32-byte outputs, as used for the SipHash keys
+        assert_eq!(hex(&blake2b_bytes(b"abc")), "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319");
+        assert_eq!(hex(&blake2b_bytes(b"")), "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8");
+    }
+
+    #[test]
+    fn test_multi_block_input() {
+        // 128 bytes is exactly one (last) block, 129 and 256 need two
+        let bytes: Vec<u8> = (0..=255u8).collect();
+        let digests = [blake2b_512(&bytes[..128]), blake2b_512(&bytes[..129]), blake2b_512(&bytes)];
+        assert_ne!(digests[0], digests[1]);
+        assert_ne!(digests[1], digests[2]);
+        // Trailing zeros are hashed, not dropped
+        assert_ne!(blake2b_512(&[0u8; 128]), blake2b_512(&[0u8; 129]));
+    }
+
+    #[test]
+    fn test_blake2b_keys_are_little_endian_digest() {
+        let digest = blake2b_digest(b"test header", 12345);
+        let keys = blake2b(b"test header", 12345);
+        assert_eq!(keys.words()[0].to_le_bytes(), digest[..8]);
+        assert_eq!(keys.words()[3].to_le_bytes(), digest[24..]);
+    }
+
+    #[test]
+    fn test_key_schedules() {
+        let header = [3u8; 32];
+        assert_eq!(KeySchedule::default().derive_keys(&header, 9), blake2b(&header, 9));
+        assert_eq!(KeySchedule::ReferenceMiner.derive_keys(&header, 9), SipKeys::from_header_reference(&header, 9));
+
+        let seed = KeySchedule::TestSeed([1, 2, 3, 0xffff]);
+        assert_eq!(seed.derive_keys(&header, 9), seed.derive_keys(b"other", 10));
+        assert_eq!(seed.derive_keys(&header, 9).provenance(), KeyProvenance::TestSeed);
+
+        for schedule in [KeySchedule::Grin, KeySchedule::ReferenceMiner, seed] {
+            assert_eq!(schedule.to_string().parse::<KeySchedule>().unwrap(), schedule);
+        }
+        assert_eq!("test-seed:1,2,3,ffff".parse::<KeySchedule>().unwrap(), seed);
+        assert!("test-seed".parse::<KeySchedule>().is_err());
+        assert!("test-seed:1,2,3".parse::<KeySchedule>().is_err());
+    }
 }
\ No newline at end of file
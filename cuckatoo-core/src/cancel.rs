@@ -0,0 +1,186 @@
+//! Cooperative cancellation
+//!
+//! A `CancelToken` is a cheap, clonable handle to a shared flag. The caller
+//! keeps one clone and hands others to trimmers and cycle finders, which
+//! check it between chunks of work and fail with
+//! `CuckatooError::Cancelled` soon after it is set, e.g. when a new job
+//! makes the graph being solved pointless.
+//!
+//! A `SearchBudget` stops a cycle search on its own once it has walked too
+//! long, failing with `CuckatooError::BudgetExceeded`.
+
+use crate::{CuckatooError, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Traversal steps between checks of a search's deadline
+const DEADLINE_CHECK_STEPS: u64 = 1024;
+
+/// Shared flag asking long-running work to stop
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a token sharing an existing flag
+    pub fn from_flag(flag: Arc<AtomicBool>) -> Self {
+        Self { flag }
+    }
+
+    /// Ask the work holding a clone of this token to stop
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Clear the flag so the token can be used for the next piece of work
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    /// Check if the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// Fail with `Cancelled` if the token was cancelled
+    pub fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(CuckatooError::Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Fail with `Cancelled` if there is a token and it was cancelled
+pub(crate) fn check_cancelled(token: Option<&CancelToken>) -> Result<()> {
+    token.map_or(Ok(()), CancelToken::check)
+}
+
+/// Limits on the work of one cycle search
+///
+/// On adversarial or very dense graphs a path search can walk for minutes;
+/// one over budget gives up with `BudgetExceeded`. The default is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchBudget {
+    /// Traversal steps allowed (see `CycleSearchStats::traversal_steps`)
+    pub max_steps: Option<u64>,
+    /// Time allowed, checked every 1024 steps
+    pub max_duration: Option<Duration>,
+}
+
+impl SearchBudget {
+    /// Allow `steps` traversal steps
+    pub fn with_max_steps(mut self, steps: u64) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    /// Allow `duration` of searching
+    pub fn with_max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Start spending the budget on a search
+    pub(crate) fn start(&self) -> BudgetMeter {
+        BudgetMeter {
+            max_steps: self.max_steps,
+            deadline: self.max_duration.and_then(|duration| Instant::now().checked_add(duration)),
+            steps: 0,
+            exceeded: false,
+        }
+    }
+}
+
+/// Budget left to a running search
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BudgetMeter {
+    max_steps: Option<u64>,
+    deadline: Option<Instant>,
+    steps: u64,
+    exceeded: bool,
+}
+
+impl BudgetMeter {
+    /// Count a traversal step, returning whether the budget is exceeded
+    pub(crate) fn step(&mut self) -> bool {
+        if !self.exceeded {
+            self.steps += 1;
+            self.exceeded = self.max_steps.is_some_and(|max_steps| self.steps > max_steps)
+                || (self.steps.is_multiple_of(DEADLINE_CHECK_STEPS) && self.deadline.is_some_and(|deadline| Instant::now() >= deadline));
+        }
+        self.exceeded
+    }
+
+    /// Whether the budget ran out
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_KEYS};
+    use crate::{ArrayCycleFinder, BitmapTrimmer, CycleVerifier, ExactSipHash, ExactTrimmer, HashCycleFinder, SipHash, SipKeys};
+
+    #[test]
+    fn test_cancelled_work_fails() {
+        let token = CancelToken::new();
+        assert!(token.check().is_ok());
+
+        let keys = SipKeys::from_header(b"cancel", 4);
+        let siphash = SipHash::with_key(keys);
+        let mut bitmap = BitmapTrimmer::new(12).with_cancel_token(token.clone());
+        let mut exact = ExactTrimmer::new(12).with_node_buffer(1 << 20).with_cancel_token(token.clone());
+        let mut verifier = CycleVerifier::new().with_cancel_token(token.clone());
+        let survivors = bitmap.trim_edges(&siphash, 5).unwrap();
+
+        token.cancel();
+        assert!(matches!(bitmap.trim_edges(&siphash, 5), Err(CuckatooError::Cancelled)));
+        assert!(matches!(exact.trim_edges(&ExactSipHash::new(keys, 12), 5), Err(CuckatooError::Cancelled)));
+        assert!(matches!(verifier.find_42_cycle(&survivors), Err(CuckatooError::Cancelled)));
+
+        // Cancelled trims leave nothing behind for the next one
+        token.reset();
+        assert_eq!(exact.trim_edges(&ExactSipHash::new(keys, 12), 5).unwrap(), survivors);
+        assert_eq!(bitmap.trim_edges(&siphash, 5).unwrap(), survivors);
+        assert!(verifier.find_42_cycle(&survivors).is_ok());
+    }
+
+    #[test]
+    fn test_search_budget() {
+        let indices: Vec<u64> = (0..1 << KNOWN_PROOF_EDGE_BITS).collect();
+        let graph = SipHash::with_key(KNOWN_PROOF_KEYS).edges_for_indices(&indices, KNOWN_PROOF_EDGE_BITS);
+        let mut verifier = CycleVerifier::new();
+        assert!(verifier.find_42_cycle(&graph).unwrap().is_some());
+        let steps = verifier.search_stats().traversal_steps;
+
+        let enough = SearchBudget::default().with_max_steps(steps).with_max_duration(Duration::from_secs(600));
+        assert!(CycleVerifier::new().with_budget(enough).find_42_cycle(&graph).unwrap().is_some());
+        let error = CycleVerifier::new().with_budget(SearchBudget::default().with_max_steps(steps - 1)).find_42_cycle(&graph).unwrap_err();
+        assert!(matches!(error, CuckatooError::BudgetExceeded));
+        assert_eq!(error.kind(), "budget");
+
+        // The deadline is checked every DEADLINE_CHECK_STEPS steps
+        let mut finder = ArrayCycleFinder::new();
+        assert!(finder.find_cycle(&graph).unwrap().is_some());
+        assert!(finder.stats().traversal_steps > DEADLINE_CHECK_STEPS);
+        let mut finder = ArrayCycleFinder::new().with_budget(SearchBudget::default().with_max_duration(Duration::ZERO));
+        assert!(matches!(finder.find_cycle(&graph), Err(CuckatooError::BudgetExceeded)));
+
+        let mut finder = HashCycleFinder::new().with_budget(SearchBudget::default().with_max_steps(10));
+        assert!(matches!(finder.find_cycle(&graph), Err(CuckatooError::BudgetExceeded)));
+        assert!(finder.was_budget_exceeded());
+        let mut finder = HashCycleFinder::new().with_budget(SearchBudget::default().with_max_steps(u64::MAX));
+        assert!(finder.find_cycle(&graph).is_ok());
+        assert!(!finder.was_budget_exceeded());
+    }
+}
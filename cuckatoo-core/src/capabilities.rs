@@ -0,0 +1,238 @@
+//! Startup capability report
+//!
+//! What a run is actually going to use: the CPU features found at runtime,
+//! the hashing backend and cycle finder picked from them, the memory the
+//! configuration needs and the configuration itself after command line
+//! parsing and memory budget fallbacks. Printed as text at startup, or as
+//! one JSON line to paste into a support thread.
+
+use crate::{
+    allocation_tracking_enabled, format_memory_size, profiling, warnings_to_json, Config, ConfigWarning, CycleVerifier,
+    MemoryEstimate, NumaPolicy, SipHash, SolverStrategy,
+};
+use std::fmt;
+
+/// Capabilities of this build and machine, and the effective configuration
+#[derive(Debug, Clone)]
+pub struct CapabilityReport {
+    /// Crate version
+    pub version: &'static str,
+    /// Target architecture and operating system, e.g. `x86_64-linux`
+    pub target: String,
+    /// CPU features detected at runtime that the miner can use
+    pub cpu_features: Vec<&'static str>,
+    /// Batched SipHash backend (`avx2`, `sse2`, `simd128` or `scalar`)
+    pub hash_backend: &'static str,
+    /// Cycle finder searching the trimmed graphs
+    pub cycle_finder: &'static str,
+    /// Hardware threads available to the process
+    pub threads: usize,
+    /// Optional crate features compiled in
+    pub build_features: Vec<&'static str>,
+    /// Memory one graph of the configuration needs
+    pub memory: MemoryEstimate,
+    /// Effective configuration
+    pub config: Config,
+    /// Pre-flight warnings about the configuration
+    pub warnings: Vec<ConfigWarning>,
+}
+
+impl CapabilityReport {
+    /// Report on this machine for an (already validated) configuration
+    pub fn new(config: &Config) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            target: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+            cpu_features: cpu_features(),
+            hash_backend: SipHash::simd_backend(),
+            cycle_finder: CycleVerifier::NAME,
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            build_features: build_features(),
+            memory: MemoryEstimate::for_config(config),
+            config: config.clone(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Include the configuration's pre-flight warnings (see `Preflight`)
+    pub fn with_warnings(mut self, warnings: Vec<ConfigWarning>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Format the report as a single-line JSON object
+    pub fn to_json(&self) -> String {
+        let strings = |items: &[&str]| items.iter().map(|item| format!("\"{}\"", item)).collect::<Vec<_>>().join(",");
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        let config = &self.config;
+
+        let fields = [
+            format!("\"version\":\"{}\"", self.version),
+            format!("\"target\":\"{}\"", self.target),
+            format!("\"cpu_features\":[{}]", strings(&self.cpu_features)),
+            format!("\"hash_backend\":\"{}\"", self.hash_backend),
+            format!("\"cycle_finder\":\"{}\"", self.cycle_finder),
+            format!("\"threads\":{}", self.threads),
+            format!("\"build_features\":[{}]", strings(&self.build_features)),
+            format!(
+                "\"memory\":{{\"edges\":{},\"trimming\":{},\"search\":{},\"total\":{}}}",
+                self.memory.edges, self.memory.trimming, self.memory.search, self.memory.total()
+            ),
+            format!("\"edge_bits\":{}", config.edge_bits),
+            format!("\"mode\":\"{}\"", config.mode),
+            format!("\"trimming_rounds\":{}", config.trimming_rounds),
+            format!("\"edge_threads\":{}", config.threads),
+            format!("\"numa_policy\":\"{}\"", config.numa_policy),
+            format!("\"solver_strategy\":\"{}\"", config.solver_strategy),
+            format!("\"adaptive_threshold\":{}", optional(config.adaptive_threshold.map(|t| t.to_string()))),
+            format!("\"plateau_rounds\":{}", config.plateau_rounds),
+            format!("\"target_survivors\":{}", optional(config.target_survivors.map(|k| k.to_string()))),
+            format!("\"deterministic\":{}", config.deterministic),
+            format!("\"tuning\":{}", config.tuning),
+            format!("\"validation_policy\":\"{}\"", config.validation_policy),
+            format!("\"key_derivation\":\"{}\"", config.key_schedule),
+            format!("\"nonce_schedule\":\"{}\"", config.nonce_schedule),
+            format!("\"submission_policy\":\"{}\"", config.submission_policy),
+            format!("\"stale_share_grace\":{}", config.stale_share_grace.as_secs_f64()),
+            format!("\"max_memory\":{}", optional(config.max_memory.map(|bytes| bytes.to_string()))),
+            format!("\"use_huge_pages\":{}", config.use_huge_pages),
+            format!("\"bitmap_memory\":{}", optional(config.bitmap_memory.map(|bytes| bytes.to_string()))),
+            format!("\"endpoint_cache\":{}", optional(config.endpoint_cache.map(|bytes| bytes.to_string()))),
+            format!("\"warnings\":{}", warnings_to_json(&self.warnings)),
+        ];
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[&str]| if items.is_empty() { "none".to_string() } else { items.join(" ") };
+        let config = &self.config;
+
+        writeln!(f, "cuckatoo {} ({})", self.version, self.target)?;
+        writeln!(f, "  CPU features:   {}", list(&self.cpu_features))?;
+        writeln!(f, "  Hash backend:   {}", self.hash_backend)?;
+        writeln!(f, "  Cycle finder:   {}", self.cycle_finder)?;
+        writeln!(f, "  Threads:        {}", self.threads)?;
+        writeln!(f, "  Build features: {}", list(&self.build_features))?;
+        writeln!(f, "  Memory:         {}", self.memory)?;
+        if let Some(budget) = config.max_memory {
+            writeln!(f, "  Memory budget:  {}", format_memory_size(budget))?;
+        }
+        if let Some(bytes) = config.bitmap_memory {
+            writeln!(f, "  Bitmap memory:  {}", format_memory_size(bytes))?;
+        }
+        if let Some(bytes) = config.endpoint_cache {
+            writeln!(f, "  Endpoint cache: {}", format_memory_size(bytes))?;
+        }
+        write!(
+            f,
+            "  Parameters:     EDGE_BITS={} mode={} rounds={} edge threads={} validation={} keys={} nonces={} submission={} stale grace={:?}",
+            config.edge_bits, config.mode, config.trimming_rounds, config.threads, config.validation_policy,
+            config.key_schedule, config.nonce_schedule, config.submission_policy, config.stale_share_grace
+        )?;
+        if let Some(threshold) = config.adaptive_threshold {
+            write!(f, " adaptive={:.2}%", threshold * 100.0)?;
+            if config.plateau_rounds > 1 {
+                write!(f, " for {} rounds", config.plateau_rounds)?;
+            }
+        }
+        if let Some(multiple) = config.target_survivors {
+            write!(f, " target survivors={}x", multiple)?;
+        }
+        if config.numa_policy != NumaPolicy::Off {
+            write!(f, " numa={}", config.numa_policy)?;
+        }
+        if config.solver_strategy != SolverStrategy::Cpp {
+            write!(f, " solver={}", config.solver_strategy)?;
+        }
+        if config.deterministic {
+            write!(f, " deterministic")?;
+        }
+        for warning in &self.warnings {
+            write!(f, "\n  Warning:        {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// CPU features relevant to mining that this CPU supports
+fn cpu_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        let detected = [
+            ("sse2", is_x86_feature_detected!("sse2")),
+            ("sse4.1", is_x86_feature_detected!("sse4.1")),
+            ("avx2", is_x86_feature_detected!("avx2")),
+            ("avx512f", is_x86_feature_detected!("avx512f")),
+            ("bmi2", is_x86_feature_detected!("bmi2")),
+            ("popcnt", is_x86_feature_detected!("popcnt")),
+        ];
+        features.extend(detected.iter().filter(|(_, found)| *found).map(|(name, _)| *name));
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        features.push("neon");
+    }
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    features.push("simd128");
+    features
+}
+
+/// Optional crate features this build was compiled with
+fn build_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "resource-usage") {
+        features.push("resource-usage");
+    }
+    if cfg!(feature = "mmap") {
+        features.push("mmap");
+    }
+    if allocation_tracking_enabled() {
+        features.push("alloc-track");
+    }
+    if profiling::profiling_enabled() {
+        features.push("profiling");
+    }
+    if cfg!(feature = "opencl") {
+        features.push("opencl");
+    }
+    if cfg!(feature = "wgpu") {
+        features.push("wgpu");
+    }
+    if cfg!(feature = "sqlite") {
+        features.push("sqlite");
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_reflects_config() {
+        let mut config = Config::new(16);
+        config.endpoint_cache = Some(1 << 20);
+        let report = CapabilityReport::new(&config);
+
+        assert_eq!(report.hash_backend, SipHash::simd_backend());
+        assert!(report.threads >= 1);
+        assert_eq!(report.memory, MemoryEstimate::for_config(&config));
+        #[cfg(target_arch = "x86_64")]
+        assert!(report.cpu_features.contains(&"sse2"));
+
+        let text = report.to_string();
+        assert!(text.contains("EDGE_BITS=16"));
+        assert!(text.contains("Endpoint cache: 1.00 MiB"), "{}", text);
+
+        let json = report.to_json();
+        assert!(!json.contains('\n'));
+        assert!(json.contains("\"edge_bits\":16"));
+        assert!(json.contains("\"endpoint_cache\":1048576"));
+        assert!(json.contains("\"max_memory\":null"));
+        assert!(json.contains("\"warnings\":[]"));
+        assert!(json.contains(&format!("\"hash_backend\":\"{}\"", report.hash_backend)));
+    }
+}
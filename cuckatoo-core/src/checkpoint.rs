@@ -0,0 +1,147 @@
+//! Trimming checkpoints
+//!
+//! Trimming a large graph takes tens of seconds, most of it in the first
+//! rounds. A `TrimCheckpoint` holds everything needed to carry on with a
+//! trim: the graph's keys, the rounds already run and the edges bitmap
+//! they left. `BitmapTrimmer` writes checkpoints between rounds and resumes
+//! from them, so a miner restarted mid-trim picks up where it stopped.
+//!
+//! The byte form is a small header (magic, version, edge bits, rounds,
+//! key derivation and keys) followed by the bitmap words, all
+//! little-endian.
+
+use crate::endian::{keys_to_le_bytes, read_u64_le, KEYS_SIZE};
+use crate::{CuckatooError, KeyProvenance, Result, SipKeys};
+
+/// Magic bytes opening a checkpoint
+const MAGIC: &[u8; 4] = b"CKTC";
+
+/// Version of the byte form
+const VERSION: u32 = 1;
+
+/// Bytes before the bitmap words
+const HEADER_SIZE: usize = 4 + 4 + 4 + 4 + 1 + KEYS_SIZE + 8;
+
+/// Receiver of the checkpoints written during a trim
+pub(crate) type CheckpointSink = Box<dyn FnMut(&[u8]) + Send>;
+
+/// State of a trim between two rounds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrimCheckpoint {
+    /// Edge bits of the graph
+    pub edge_bits: u32,
+    /// Rounds finished so far
+    pub rounds_run: u32,
+    /// Keys of the graph being trimmed
+    pub keys: SipKeys,
+    /// Surviving edges, one bit per edge
+    pub edges_bitmap: Vec<u64>,
+}
+
+impl TrimCheckpoint {
+    /// Encode the checkpoint
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.edges_bitmap.len() * 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.edge_bits.to_le_bytes());
+        bytes.extend_from_slice(&self.rounds_run.to_le_bytes());
+        bytes.push(provenance_byte(self.keys.provenance()));
+        bytes.extend_from_slice(&keys_to_le_bytes(&self.keys.words()));
+        bytes.extend_from_slice(&(self.edges_bitmap.len() as u64).to_le_bytes());
+        for word in &self.edges_bitmap {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decode a checkpoint written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let invalid = |message: &str| CuckatooError::InternalError(format!("Invalid trimming checkpoint: {}", message));
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+            return Err(invalid("not a checkpoint"));
+        }
+        let read_u32 = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("4-byte field"));
+        let version = read_u32(4);
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        let provenance = provenance_from_byte(bytes[16]).ok_or_else(|| invalid("unknown key derivation"))?;
+        let keys: &[u8; KEYS_SIZE] = bytes[17..17 + KEYS_SIZE].try_into().expect("keys field");
+        let words = read_u64_le(bytes, 17 + KEYS_SIZE).expect("word count field");
+        if words.checked_mul(8) != Some((bytes.len() - HEADER_SIZE) as u64) {
+            return Err(invalid("truncated edges bitmap"));
+        }
+
+        Ok(Self {
+            edge_bits: read_u32(8),
+            rounds_run: read_u32(12),
+            keys: SipKeys::from_le_bytes(keys, provenance),
+            edges_bitmap: bytes[HEADER_SIZE..]
+                .chunks_exact(8)
+                .map(|word| u64::from_le_bytes(word.try_into().expect("8-byte word")))
+                .collect(),
+        })
+    }
+}
+
+/// Byte recording how the keys were derived
+fn provenance_byte(provenance: KeyProvenance) -> u8 {
+    match provenance {
+        KeyProvenance::Grin => 0,
+        KeyProvenance::ReferenceMiner => 1,
+        KeyProvenance::TestSeed => 2,
+    }
+}
+
+/// Key derivation recorded by `provenance_byte`
+fn provenance_from_byte(byte: u8) -> Option<KeyProvenance> {
+    match byte {
+        0 => Some(KeyProvenance::Grin),
+        1 => Some(KeyProvenance::ReferenceMiner),
+        2 => Some(KeyProvenance::TestSeed),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitmapTrimmer, CancelToken, SipHash};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let keys = SipKeys::from_header_reference(b"checkpoint", 3);
+        let siphash = SipHash::with_key(keys);
+        let full = BitmapTrimmer::new(10).trim_edges(&siphash, 8).unwrap();
+
+        // Keep the checkpoint written after round 3 and stop the trim there
+        let saved = Arc::new(Mutex::new(Vec::new()));
+        let token = CancelToken::new();
+        let (sink, stop) = (Arc::clone(&saved), token.clone());
+        let mut trimmer = BitmapTrimmer::new(10).with_cancel_token(token).with_checkpoints(3, move |bytes: &[u8]| {
+            *sink.lock().unwrap() = bytes.to_vec();
+            stop.cancel();
+        });
+        assert!(matches!(trimmer.trim_edges(&siphash, 8), Err(CuckatooError::Cancelled)));
+
+        let bytes = saved.lock().unwrap().clone();
+        let checkpoint = TrimCheckpoint::from_bytes(&bytes).unwrap();
+        assert_eq!((checkpoint.edge_bits, checkpoint.rounds_run, checkpoint.keys), (10, 3, keys));
+        assert_eq!(checkpoint.to_bytes(), bytes);
+
+        let mut resumed = BitmapTrimmer::new(10);
+        resumed.restore_checkpoint(&bytes).unwrap();
+        assert_eq!(resumed.resume_trim_edges(&siphash, 8).unwrap(), full);
+        assert_eq!(resumed.rounds_run(), 8);
+
+        // Checkpoints only resume the graph and size they were taken from
+        resumed.restore_checkpoint(&bytes).unwrap();
+        let other = SipHash::with_key(SipKeys::from_header(b"checkpoint", 3));
+        assert!(resumed.resume_trim_edges(&other, 8).is_err());
+        assert!(BitmapTrimmer::new(11).restore_checkpoint(&bytes).is_err());
+        assert!(TrimCheckpoint::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//! Consensus verification of Cuckatoo proofs
+//!
+//! Checks a proof the way Grin nodes do (Tromp's `verify`): recompute the
+//! endpoints of each proof nonce from the SipHash keys and walk them as a
+//! single cycle. Nodes come in pairs, `2k` and `2k + 1` on the same side,
+//! and an edge reaching one node of a pair continues from the edge at the
+//! other one. This is independent of the trimmers and cycle finders, and
+//! is the only verifier compiled in `verify-only` builds.
+
+use crate::{CuckatooError, Edge, Result, SipKeys, ValidationPolicy, SOLUTION_SIZE};
+use crate::hashing::SipHash;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Reason a proof was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyFailure {
+    /// Edge bits outside the range of the validation policy
+    InvalidEdgeBits(u32),
+    /// The proof doesn't have `SOLUTION_SIZE` nonces
+    WrongLength(usize),
+    /// A nonce is not below 2^edge_bits
+    NonceTooBig(u64),
+    /// Nonces are not strictly ascending
+    NotAscending,
+    /// The same nonce appears twice in a row
+    DuplicateNonce(u64),
+    /// Two nonces have the same endpoints (a multi-edge), which simpler
+    /// cycle checkers can mistake for part of a cycle
+    DuplicateEdge { first: u64, second: u64 },
+    /// The endpoints don't pair up, first at the edge of proof nonce `index`
+    EndpointsMismatch { index: usize },
+    /// A node pair has more than two proof endpoints
+    Branch,
+    /// A node pair has only one proof endpoint, or two at the same node
+    DeadEnd,
+    /// The edges form a cycle of the wrong length
+    WrongCycleLength(usize),
+}
+
+impl fmt::Display for VerifyFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyFailure::InvalidEdgeBits(bits) => write!(f, "Invalid edge bits: {}", bits),
+            VerifyFailure::WrongLength(length) => write!(f, "Proof has {} nonces, expected {}", length, SOLUTION_SIZE),
+            VerifyFailure::NonceTooBig(nonce) => write!(f, "Nonce {} is too big", nonce),
+            VerifyFailure::NotAscending => write!(f, "Nonces are not ascending"),
+            VerifyFailure::DuplicateNonce(nonce) => write!(f, "Nonce {} appears twice", nonce),
+            VerifyFailure::DuplicateEdge { first, second } => {
+                write!(f, "Nonces {} and {} are duplicate edges", first, second)
+            },
+            VerifyFailure::EndpointsMismatch { index } => write!(f, "Endpoints don't match up at proof nonce {}", index),
+            VerifyFailure::Branch => write!(f, "Branch in cycle"),
+            VerifyFailure::DeadEnd => write!(f, "Cycle dead ends"),
+            VerifyFailure::WrongCycleLength(length) => {
+                write!(f, "Cycle has length {}, expected {}", length, SOLUTION_SIZE)
+            },
+        }
+    }
+}
+
+impl From<VerifyFailure> for CuckatooError {
+    fn from(failure: VerifyFailure) -> Self {
+        match failure {
+            VerifyFailure::InvalidEdgeBits(bits) => CuckatooError::InvalidEdgeBits(bits),
+            failure => CuckatooError::VerificationError(failure.to_string()),
+        }
+    }
+}
+
+/// Verify a proof of the graph of `header` and `nonce`, deriving its keys
+/// the way Grin nodes do
+///
+/// The proof nonces must be strictly ascending, distinct edges below
+/// 2^edge_bits whose endpoints form a single 42-cycle; see
+/// `check_proof_nonces` for the reason a proof is rejected.
+pub fn verify_proof(header: &[u8], nonce: u64, proof_nonces: &[u64], edge_bits: u32) -> Result<()> {
+    verify_proof_nonces(&SipKeys::from_header(header, nonce), edge_bits, proof_nonces)
+}
+
+/// Verify that proof nonces form a single 42-cycle in the graph of `keys`
+///
+/// The nonces must be strictly ascending edge indices below 2^edge_bits.
+pub fn verify_proof_nonces(keys: &SipKeys, edge_bits: u32, nonces: &[u64]) -> Result<()> {
+    check_proof_nonces(keys, edge_bits, nonces).map_err(CuckatooError::from)
+}
+
+/// Check proof nonces like `verify_proof_nonces`, returning why a proof
+/// was rejected
+pub fn check_proof_nonces(keys: &SipKeys, edge_bits: u32, nonces: &[u64]) -> std::result::Result<(), VerifyFailure> {
+    check_proof_nonces_with_policy(keys, edge_bits, nonces, ValidationPolicy::Consensus)
+}
+
+/// Check proof nonces like `check_proof_nonces`, accepting the edge bits
+/// of `policy`
+///
+/// With `ValidationPolicy::Research` this checks the proofs of graphs
+/// outside the consensus range, e.g. EDGE_BITS=33 experiments.
+pub fn check_proof_nonces_with_policy(
+    keys: &SipKeys,
+    edge_bits: u32,
+    nonces: &[u64],
+    policy: ValidationPolicy,
+) -> std::result::Result<(), VerifyFailure> {
+    if !policy.edge_bits_range().contains(&edge_bits) {
+        return Err(VerifyFailure::InvalidEdgeBits(edge_bits));
+    }
+    if nonces.len() != SOLUTION_SIZE {
+        return Err(VerifyFailure::WrongLength(nonces.len()));
+    }
+
+    let number_of_edges = 1u64 << edge_bits;
+    for (i, &nonce) in nonces.iter().enumerate() {
+        if nonce >= number_of_edges {
+            return Err(VerifyFailure::NonceTooBig(nonce));
+        }
+        if i > 0 && nonce == nonces[i - 1] {
+            return Err(VerifyFailure::DuplicateNonce(nonce));
+        }
+        if i > 0 && nonce < nonces[i - 1] {
+            return Err(VerifyFailure::NotAscending);
+        }
+    }
+
+    let edges = SipHash::with_key(*keys).edges_for_indices(nonces, edge_bits);
+    if let Some(&(first, second)) = duplicate_edges(&edges).first() {
+        return Err(VerifyFailure::DuplicateEdge { first: nonces[first], second: nonces[second] });
+    }
+
+    // Endpoints as [u0, v0, u1, v1, ...]
+    let endpoints: Vec<u64> = edges.iter().flat_map(|edge| [edge.u.value(), edge.v.value()]).collect();
+
+    // A cycle uses both nodes of each of its SOLUTION_SIZE / 2 pairs per
+    // side, and the two nodes of a pair differ only in the lowest bit
+    let parity = (SOLUTION_SIZE as u64 / 2) & 1;
+    let (xor_u, xor_v) = edges.iter().fold((parity, parity), |(u, v), edge| (u ^ edge.u.value(), v ^ edge.v.value()));
+    if xor_u | xor_v != 0 {
+        // Some node is used by a different number of edges than the other
+        // node of its pair
+        let count = |side: fn(&Edge) -> u64, node| edges.iter().filter(|&edge| side(edge) == node).count();
+        let unpaired = |side: fn(&Edge) -> u64, edge| count(side, side(edge)) != count(side, side(edge) ^ 1);
+        let index = edges
+            .iter()
+            .position(|edge| unpaired(|edge| edge.u.value(), edge) || unpaired(|edge| edge.v.value(), edge))
+            .unwrap_or(0);
+        return Err(VerifyFailure::EndpointsMismatch { index });
+    }
+
+    // Walk the cycle, alternating between the U and V endpoints of each edge
+    let mut length = 0;
+    let mut i = 0;
+    loop {
+        // Find the unique other endpoint in the pair of endpoint i (same
+        // partition), which must be the other node of the pair
+        let mut next = i;
+        let mut k = i;
+        loop {
+            k = (k + 2) % endpoints.len();
+            if k == i {
+                break;
+            }
+            if endpoints[k] >> 1 == endpoints[i] >> 1 {
+                if next != i {
+                    return Err(VerifyFailure::Branch);
+                }
+                next = k;
+            }
+        }
+        if next == i || endpoints[next] == endpoints[i] {
+            return Err(VerifyFailure::DeadEnd);
+        }
+
+        // Continue from the other endpoint of that edge
+        i = next ^ 1;
+        length += 1;
+        if i == 0 {
+            break;
+        }
+    }
+
+    if length != SOLUTION_SIZE {
+        return Err(VerifyFailure::WrongCycleLength(length));
+    }
+    Ok(())
+}
+
+/// Find edges with the same endpoints as an earlier edge
+///
+/// Returns `(first, duplicate)` index pairs in order of the duplicates.
+/// Cuckatoo graphs may contain such multi-edges, so this can also be run
+/// over a generated graph before cycle finding.
+pub fn duplicate_edges(edges: &[Edge]) -> Vec<(usize, usize)> {
+    let mut first_index: HashMap<Edge, usize> = HashMap::with_capacity(edges.len());
+    edges
+        .iter()
+        .enumerate()
+        .filter_map(|(index, edge)| match first_index.get(edge) {
+            Some(&first) => Some((first, index)),
+            None => {
+                first_index.insert(*edge, index);
+                None
+            },
+        })
+        .collect()
+}
+
+/// Independently re-verify a solution reported by a cycle finder
+///
+/// A finder producing a proof the consensus verifier rejects is a bug, so
+/// the failure is returned as `InternalError` with the graph seed (keys and
+/// edge bits) and proof needed to reproduce it. Edge bits were already
+/// checked against the run's validation policy, so any the research policy
+/// accepts are verified here.
+pub fn reverify_solution(keys: &SipKeys, edge_bits: u32, nonces: &[u64]) -> Result<()> {
+    check_proof_nonces_with_policy(keys, edge_bits, nonces, ValidationPolicy::Research).map_err(|error| {
+        CuckatooError::InternalError(format!(
+            "Found solution failed consensus verification ({}); graph seed: EDGE_BITS={} keys={} proof={:?}",
+            error, edge_bits, keys, nonces
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{
+        GRIN_PROOF, GRIN_PROOF_EDGE_BITS, GRIN_PROOF_HEADER, GRIN_PROOF_NONCE, KNOWN_PROOF, KNOWN_PROOF_KEYS as KNOWN_KEYS,
+    };
+
+    #[test]
+    fn test_accepts_known_proof() {
+        assert!(verify_proof_nonces(&KNOWN_KEYS, 12, &KNOWN_PROOF).is_ok());
+
+        // The same nonces don't form a cycle in a different graph
+        let mut words = KNOWN_KEYS.words();
+        words[1] ^= 1;
+        let other_keys = SipKeys::test_seed(words);
+        assert!(verify_proof_nonces(&other_keys, 12, &KNOWN_PROOF).is_err());
+    }
+
+    #[test]
+    fn test_verify_proof_of_header() {
+        // A 42-cycle in the EDGE_BITS=12 graph of this header and nonce
+        let proof = [
+            59, 86, 140, 150, 216, 232, 241, 466, 470, 472, 524, 544, 551, 825, 940, 1272, 1462, 1796, 1839, 2062, 2227,
+            2532, 2630, 2639, 2646, 2720, 2791, 2896, 2919, 2970, 3002, 3062, 3282, 3293, 3404, 3459, 3720, 3835, 3879, 3950, 3965, 4075,
+        ];
+        assert!(verify_proof(b"verify proof", 277, &proof, 12).is_ok());
+        assert!(verify_proof(b"verify proof", 278, &proof, 12).is_err());
+        assert!(verify_proof(b"verify proof", 277, &proof[1..], 12).is_err());
+        assert!(matches!(verify_proof(b"verify proof", 277, &proof, 64), Err(CuckatooError::InvalidEdgeBits(64))));
+    }
+
+    #[test]
+    fn test_accepts_grin_proof() {
+        assert!(verify_proof(&GRIN_PROOF_HEADER, GRIN_PROOF_NONCE, &GRIN_PROOF, GRIN_PROOF_EDGE_BITS).is_ok());
+
+        let mut tampered = GRIN_PROOF;
+        tampered[0] += 1;
+        assert!(verify_proof(&GRIN_PROOF_HEADER, GRIN_PROOF_NONCE, &tampered, GRIN_PROOF_EDGE_BITS).is_err());
+        assert!(verify_proof(&GRIN_PROOF_HEADER, GRIN_PROOF_NONCE + 1, &GRIN_PROOF, GRIN_PROOF_EDGE_BITS).is_err());
+    }
+
+    #[test]
+    fn test_reverify_reports_graph_seed() {
+        assert!(reverify_solution(&KNOWN_KEYS, 12, &KNOWN_PROOF).is_ok());
+
+        let mut bad_proof = KNOWN_PROOF;
+        bad_proof[0] = 100;
+        match reverify_solution(&KNOWN_KEYS, 12, &bad_proof) {
+            Err(CuckatooError::InternalError(message)) => {
+                assert!(message.contains("EDGE_BITS=12"));
+                assert!(message.contains("0x2835d134238e603b"));
+                assert!(message.contains("(grin-blake2b)"));
+            },
+            other => panic!("Expected an internal error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rejects_malformed_proofs() {
+        let keys = SipHash::default().get_key();
+        let ascending: Vec<u64> = (0..SOLUTION_SIZE as u64).collect();
+
+        assert!(verify_proof_nonces(&keys, 12, &ascending[..41]).is_err());
+        assert!(verify_proof_nonces(&keys, 40, &ascending).is_err());
+
+        let mut descending = ascending.clone();
+        descending.swap(0, 1);
+        assert!(verify_proof_nonces(&keys, 12, &descending).is_err());
+
+        let mut too_big = ascending.clone();
+        too_big[41] = 1 << 12;
+        assert!(verify_proof_nonces(&keys, 12, &too_big).is_err());
+
+        // Consecutive edges of a random graph don't form a cycle
+        assert!(verify_proof_nonces(&keys, 12, &ascending).is_err());
+    }
+
+    #[test]
+    fn test_check_reports_failure_kind() {
+        let keys = SipHash::default().get_key();
+        let ascending: Vec<u64> = (0..SOLUTION_SIZE as u64).collect();
+        assert_eq!(check_proof_nonces(&keys, 12, &ascending[..41]), Err(VerifyFailure::WrongLength(41)));
+        assert_eq!(check_proof_nonces(&keys, 40, &ascending), Err(VerifyFailure::InvalidEdgeBits(40)));
+        assert!(matches!(verify_proof_nonces(&keys, 40, &ascending), Err(CuckatooError::InvalidEdgeBits(40))));
+
+        // Research graphs get past the edge bits check to the cycle check
+        let failure = check_proof_nonces_with_policy(&keys, 40, &ascending, ValidationPolicy::Research);
+        assert!(!matches!(failure, Err(VerifyFailure::InvalidEdgeBits(_))), "{:?}", failure);
+    }
+
+    #[test]
+    fn test_duplicate_edges() {
+        let edge = |u, v| Edge::new(crate::Node::new(u), crate::Node::new(v));
+        let edges = [edge(1, 2), edge(3, 4), edge(1, 2), edge(2, 1), edge(1, 2)];
+        assert_eq!(duplicate_edges(&edges), vec![(0, 2), (0, 4)]);
+        assert!(duplicate_edges(&edges[..2]).is_empty());
+
+        // Edges 125 and 1007 of this graph have the same endpoints
+        let keys = SipKeys::test_seed([0x736f6d6570736576, 0x646f72616e646f6d, 0x6c7967656e657261, 0x7465646279746573]);
+        let mut nonces: Vec<u64> = (0..40).collect();
+        nonces.extend([125, 1007]);
+        assert_eq!(
+            check_proof_nonces(&keys, 12, &nonces),
+            Err(VerifyFailure::DuplicateEdge { first: 125, second: 1007 })
+        );
+    }
+
+    #[test]
+    fn test_check_reports_failure_detail() {
+        let mut repeated = KNOWN_PROOF;
+        repeated[20] = repeated[19];
+        assert_eq!(check_proof_nonces(&KNOWN_KEYS, 12, &repeated), Err(VerifyFailure::DuplicateNonce(KNOWN_PROOF[19])));
+
+        // Swapping in an edge leaves the edges at the other nodes of the
+        // old edge's pairs and the new edge with unmatched endpoints
+        let edges = SipHash::with_key(KNOWN_KEYS).edges_for_indices(&KNOWN_PROOF, 12);
+        let mut replaced = KNOWN_PROOF;
+        replaced[41] = KNOWN_PROOF[41] + 1;
+        let neighbours = (0..41).filter(|&i| edges[i].u.value() == edges[41].u.value() ^ 1 || edges[i].v.value() == edges[41].v.value() ^ 1);
+        match check_proof_nonces(&KNOWN_KEYS, 12, &replaced) {
+            Err(VerifyFailure::EndpointsMismatch { index }) => assert_eq!(Some(index), neighbours.min()),
+            other => panic!("Expected an endpoints mismatch, got {:?}", other),
+        }
+        assert!(VerifyFailure::EndpointsMismatch { index: 3 }.to_string().contains("proof nonce 3"));
+    }
+}
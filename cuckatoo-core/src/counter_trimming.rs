@@ -0,0 +1,218 @@
+//! Lean trimming with 2-bit degree counters
+//!
+//! Classic lean miners keep a 2-bit saturating degree counter per node
+//! instead of a presence bit. `CounterTrimmer` counts the live edges at
+//! every node of the round's side (0, 1, or 2 and more) and keeps an edge
+//! while the partner `node ^ 1` of its endpoint has at least one, as a
+//! cycle entering a node leaves from its partner. The counters pack 32
+//! nodes per word, twice the memory of `BitmapTrimmer`'s nodes bitmap.
+//!
+//! A partner with a single edge can still continue a cycle, so no higher
+//! degree threshold trims without losing cycle edges, and the counter
+//! trimmer keeps exactly the bitmap trimmer's survivors.
+//! `compare_lean_trimmers` in the timing module measures the two side by
+//! side.
+
+use crate::cancel::check_cancelled;
+use crate::bitmap_trimming::round_side;
+use crate::hashing::{for_each_word_node, SipHasher, SIPHASH_BLOCK_EDGES};
+use crate::trim_output::edge_indices;
+use crate::{checked_usize, CancelToken, CuckatooError, Edge, Node, Result, TrimOutput};
+use std::time::Instant;
+
+/// 2-bit counters per counters word
+const COUNTERS_PER_WORD: u64 = 32;
+
+/// Degree at which the counters saturate
+const MAX_NODE_DEGREE: u64 = 2;
+
+/// Degree a partner node needs for the edges at its node to survive
+const MIN_PARTNER_DEGREE: u64 = 1;
+
+/// Lean trimmer counting node degrees in packed 2-bit counters
+///
+/// Rounds follow `BitmapTrimmer`'s schedule, alternating between the U
+/// and V endpoints starting with U.
+pub struct CounterTrimmer {
+    edge_bits: u32,
+    number_of_edges: u64,
+    edges_bitmap: Vec<u64>,
+    counters: Vec<u64>,
+    cancel: Option<CancelToken>,
+    rounds_run: u32,
+}
+
+impl CounterTrimmer {
+    /// Create a new counter trimmer
+    ///
+    /// Panics if the bitmap and counters can't be addressed on this
+    /// target; use `try_new` to get an error instead.
+    pub fn new(edge_bits: u32) -> Self {
+        Self::try_new(edge_bits).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a new counter trimmer, checking that its bitmap and counters
+    /// can be addressed
+    pub fn try_new(edge_bits: u32) -> Result<Self> {
+        if edge_bits >= u64::BITS {
+            return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let number_of_edges: u64 = 1 << edge_bits;
+
+        Ok(Self {
+            edge_bits,
+            number_of_edges,
+            edges_bitmap: vec![0; checked_usize(number_of_edges.div_ceil(64), "Edges bitmap size")?],
+            counters: vec![0; checked_usize(number_of_edges.div_ceil(COUNTERS_PER_WORD), "Degree counters size")?],
+            cancel: None,
+            rounds_run: 0,
+        })
+    }
+
+    /// Stop trimming with `CuckatooError::Cancelled` once `token` is
+    /// cancelled (checked between steps)
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Number of rounds the last trim ran
+    pub fn rounds_run(&self) -> u32 {
+        self.rounds_run
+    }
+
+    /// Get the number of edges currently surviving in the edges bitmap
+    pub fn surviving_edges_count(&self) -> u64 {
+        self.edges_bitmap.iter().map(|&x| x.count_ones() as u64).sum()
+    }
+
+    /// Trim the graph of `siphash` for `trimming_rounds` rounds and return
+    /// the surviving edges
+    pub fn trim_edges<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<Vec<Edge>> {
+        self.run_rounds(siphash, trimming_rounds, None)?;
+        Ok(self.generate_final_edges(siphash))
+    }
+
+    /// Trim the graph of `siphash` for `trimming_rounds` rounds and return
+    /// the indices of the surviving edges with the trim's statistics
+    pub fn trim<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<TrimOutput> {
+        let start_time = Instant::now();
+        let mut per_round_survivors = Vec::new();
+        self.run_rounds(siphash, trimming_rounds, Some(&mut per_round_survivors))?;
+        Ok(TrimOutput {
+            surviving_edge_indices: edge_indices(&self.edges_bitmap),
+            rounds_run: self.rounds_run,
+            per_round_survivors,
+            elapsed: start_time.elapsed(),
+        })
+    }
+
+    /// Fill the edges bitmap and run `trimming_rounds` rounds on it,
+    /// pushing the survivors of each round to `curve` if given
+    fn run_rounds<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32, mut curve: Option<&mut Vec<u64>>) -> Result<()> {
+        self.fill_edges_bitmap();
+        self.rounds_run = 0;
+        for round in 0..trimming_rounds {
+            crate::profile_scope!(crate::profiling::TRIM_ROUND, round = round + 1);
+            let uorv = round_side(round);
+            self.count_nodes(siphash, uorv)?;
+            self.keep_partnered_edges(siphash, uorv)?;
+            self.rounds_run = round + 1;
+            if let Some(curve) = curve.as_deref_mut() {
+                curve.push(self.surviving_edges_count());
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark every edge of the graph alive in the edges bitmap
+    fn fill_edges_bitmap(&mut self) {
+        self.edges_bitmap.fill(u64::MAX);
+        let excess_bits = (self.edges_bitmap.len() * 64) as u64 - self.number_of_edges;
+        if let (Some(last), true) = (self.edges_bitmap.last_mut(), excess_bits > 0) {
+            *last &= (1u64 << (64 - excess_bits)) - 1;
+        }
+    }
+
+    /// Clear the counters and count the live edges whose endpoint `uorv`
+    /// is each node
+    fn count_nodes<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
+        self.counters.fill(0);
+        let counters = &mut self.counters;
+        for (word_index, &unit) in self.edges_bitmap.iter().enumerate() {
+            for_each_word_node(siphash, self.edge_bits, word_index, unit, uorv, |_, node| {
+                let (word, shift) = locate(node);
+                if (counters[word] >> shift) & 3 < MAX_NODE_DEGREE {
+                    counters[word] += 1 << shift;
+                }
+            });
+        }
+        check_cancelled(self.cancel.as_ref())
+    }
+
+    /// Keep only the edges whose endpoint `uorv` has a partner node with
+    /// live edges
+    fn keep_partnered_edges<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
+        let counters = &self.counters;
+        for (word_index, unit) in self.edges_bitmap.iter_mut().enumerate() {
+            let mut new_unit = 0u64;
+            for_each_word_node(siphash, self.edge_bits, word_index, *unit, uorv, |bit, node| {
+                let (word, shift) = locate(node ^ 1);
+                if (counters[word] >> shift) & 3 >= MIN_PARTNER_DEGREE {
+                    new_unit |= 1u64 << bit;
+                }
+            });
+            *unit = new_unit;
+        }
+        check_cancelled(self.cancel.as_ref())
+    }
+
+    /// Generate the surviving edges from the edges bitmap
+    fn generate_final_edges<H: SipHasher>(&self, siphash: &H) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        let mut first_nodes = [0u64; SIPHASH_BLOCK_EDGES];
+        for (word_index, &unit) in self.edges_bitmap.iter().enumerate() {
+            for_each_word_node(siphash, self.edge_bits, word_index, unit, 0, |bit, node| {
+                first_nodes[bit] = node;
+            });
+            for_each_word_node(siphash, self.edge_bits, word_index, unit, 1, |bit, node| {
+                edges.push(Edge::new(Node::new(first_nodes[bit]), Node::new(node)));
+            });
+        }
+        edges
+    }
+}
+
+/// Counters word index and bit shift of a node's counter
+fn locate(node: u64) -> (usize, u32) {
+    ((node / COUNTERS_PER_WORD) as usize, ((node % COUNTERS_PER_WORD) * 2) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitmapTrimmer, CycleVerifier, SipHash, SipKeys};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_counter_trimming_keeps_bitmap_survivors() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"counters", 9));
+        for rounds in [1, 2, 3, 4, 10, 30] {
+            let bitmap = BitmapTrimmer::new(12).trim_edges(&siphash, rounds).unwrap();
+            let mut trimmer = CounterTrimmer::new(12);
+            let counter = trimmer.trim_edges(&siphash, rounds).unwrap();
+            assert_eq!(trimmer.rounds_run(), rounds);
+            assert_eq!(trimmer.surviving_edges_count(), counter.len() as u64);
+            let kept: HashSet<Edge> = bitmap.iter().copied().collect();
+            assert!(counter.iter().all(|edge| kept.contains(edge)), "round {}", rounds);
+            assert_eq!(counter, bitmap, "round {}", rounds);
+        }
+
+        // Both leave the cycle finder the same cycles
+        let counter = CounterTrimmer::new(12).trim_edges(&siphash, 10).unwrap();
+        let bitmap = BitmapTrimmer::new(12).trim_edges(&siphash, 10).unwrap();
+        let mut verifier = CycleVerifier::new();
+        assert_eq!(verifier.find_42_cycle(&counter).unwrap().is_some(), verifier.find_42_cycle(&bitmap).unwrap().is_some());
+        assert!(CounterTrimmer::try_new(64).is_err());
+    }
+}
@@ -1,72 +1,90 @@
 //! Exact C++ getCuckatooSolution algorithm implementation
-//! 
+//!
 //! This implements the exact same cycle finding algorithm as the C++ version,
 //! including the exact data structures, loop structure, and logic flow.
+//!
+//! The C++ code keeps the node connection links of every edge next to each
+//! other in one preallocated array, the first partition's link followed by
+//! the second partition's, and steps from one to the other with pointer
+//! arithmetic (`nodeConnection + 1` and `nodeConnection - 1`). Here the links
+//! live in the same kind of arena and refer to each other by index: a link's
+//! previous link is an index into the arena, and its edge's other link is
+//! the index next to it.
 
-use crate::{SOLUTION_SIZE, EDGE_NUMBER_OF_COMPONENTS};
+use crate::types::assert_cycle_length;
+use crate::{CuckatooError, Edge, Result, SOLUTION_SIZE, EDGE_NUMBER_OF_COMPONENTS};
 use std::collections::HashMap;
 
 /// Node connection link matching C++ CuckatooNodeConnectionsLink exactly
-#[derive(Clone, Debug)]
+///
+/// Links are stored in an arena two per edge: the first partition's link
+/// at an even index and the second partition's right after it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct CuckatooNodeConnectionsLink {
-    pub previous_node_connection_link: Option<Box<CuckatooNodeConnectionsLink>>,
+    /// Arena index of the previous connection of the same node
+    pub previous_node_connection_link: Option<usize>,
     pub node: u32,
     pub edge_index: u32,
 }
 
-/// Hash table matching C++ HashTable template
+/// Hash table matching C++ HashTable template, mapping nodes to the arena
+/// index of their newest connection
+#[derive(Default)]
 pub struct HashTable {
-    data: HashMap<u32, CuckatooNodeConnectionsLink>,
+    data: HashMap<u32, usize>,
 }
 
 impl HashTable {
     pub fn new() -> Self {
-        Self {
-            data: HashMap::new(),
-        }
+        Self::default()
     }
-    
+
     pub fn clear(&mut self) {
         self.data.clear();
     }
-    
+
     pub fn contains(&self, key: u32) -> bool {
         self.data.contains_key(&key)
     }
-    
-    pub fn get(&self, key: u32) -> Option<&CuckatooNodeConnectionsLink> {
-        self.data.get(&key)
+
+    pub fn get(&self, key: u32) -> Option<usize> {
+        self.data.get(&key).copied()
     }
-    
-    pub fn replace(&mut self, key: u32, new_link: &CuckatooNodeConnectionsLink) -> Option<CuckatooNodeConnectionsLink> {
-        self.data.insert(key, new_link.clone())
+
+    /// Make `new_link` the newest connection of `key`, returning the
+    /// previous newest
+    pub fn replace(&mut self, key: u32, new_link: usize) -> Option<usize> {
+        self.data.insert(key, new_link)
     }
 }
 
 /// Visited node pairs hash table
+#[derive(Default)]
 pub struct VisitedNodePairs {
     data: HashMap<u64, u32>,
 }
 
 impl VisitedNodePairs {
     pub fn new() -> Self {
-        Self {
-            data: HashMap::new(),
-        }
+        Self::default()
     }
-    
+
     pub fn clear(&mut self) {
         self.data.clear();
     }
-    
+
     pub fn contains(&self, key: u64) -> bool {
         self.data.contains_key(&key)
     }
-    
+
     pub fn set_unique(&mut self, key: u64, value: u32) {
         self.data.insert(key, value);
     }
-    
+
+    pub fn remove(&mut self, key: u64) {
+        self.data.remove(&key);
+    }
+
     pub fn get_values(&self, solution: &mut [u32]) {
         let mut i = 0;
         for &value in self.data.values() {
@@ -86,6 +104,14 @@ pub struct CppCycleFinder {
     cuckatoo_u_visited_node_pairs: VisitedNodePairs,
     cuckatoo_v_visited_node_pairs: VisitedNodePairs,
     cuckatoo_root_node: u32,
+    /// Number of edges in the cycles searched for
+    cycle_length: usize,
+}
+
+impl Default for CppCycleFinder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CppCycleFinder {
@@ -96,9 +122,24 @@ impl CppCycleFinder {
             cuckatoo_u_visited_node_pairs: VisitedNodePairs::new(),
             cuckatoo_v_visited_node_pairs: VisitedNodePairs::new(),
             cuckatoo_root_node: 0,
+            cycle_length: SOLUTION_SIZE,
         }
     }
-    
+
+    /// Search for cycles of `length` edges instead of 42
+    ///
+    /// Panics if `length` is odd or outside 4 to `MAX_CYCLE_LENGTH`.
+    pub fn with_cycle_length(mut self, length: usize) -> Self {
+        assert_cycle_length(length);
+        self.cycle_length = length;
+        self
+    }
+
+    /// Number of edges in the cycles searched for
+    pub fn cycle_length(&self) -> usize {
+        self.cycle_length
+    }
+
     /// Initialize thread-local global variables (matching C++ initializeCuckatooThreadLocalGlobalVariables)
     pub fn initialize_cuckatoo_thread_local_global_variables(&mut self) -> bool {
         // Reset thread local global variables
@@ -107,347 +148,391 @@ impl CppCycleFinder {
         self.cuckatoo_u_visited_node_pairs.clear();
         self.cuckatoo_v_visited_node_pairs.clear();
         self.cuckatoo_root_node = 0;
-        
+
         true
     }
-    
+
     /// Get cuckatoo solution (matching C++ getCuckatooSolution exactly)
-    pub fn get_cuckatoo_solution(&mut self, solution: &mut [u32; SOLUTION_SIZE], 
-                                node_connections: &mut [CuckatooNodeConnectionsLink], 
-                                edges: &[u32], 
+    ///
+    /// `node_connections` is the links arena, at least two links per edge.
+    /// On success `solution` holds the `cycle_length` edge indices of the cycle.
+    pub fn get_cuckatoo_solution(&mut self, solution: &mut Vec<u32>,
+                                node_connections: &mut [CuckatooNodeConnectionsLink],
+                                edges: &[u32],
                                 number_of_edges: u64) -> bool {
-        
+        solution.clear();
+        solution.resize(self.cycle_length, 0);
+        let last = self.cycle_length as u8 - 1;
+        let half = self.cycle_length / 2;
+
         // Go through all edges (matching C++ loop exactly)
         let mut node_connections_index = 0;
         let mut edges_index = 0;
-        
+
         while node_connections_index < (number_of_edges * 2) as usize {
             // Get edge's index and nodes (matching C++ exactly)
-            let index = &edges[edges_index];
+            let index = edges[edges_index];
             let node = edges[edges_index + 1];
             self.cuckatoo_root_node = edges[edges_index + 2];
-            
+
             // Replace newest node connection for the node on the first partition and add node connection to list
-            let previous_u = self.cuckatoo_u_newest_node_connections.get(node).cloned();
-            let new_u_link = CuckatooNodeConnectionsLink {
-                previous_node_connection_link: previous_u.map(|link| Box::new(link)),
+            node_connections[node_connections_index] = CuckatooNodeConnectionsLink {
+                previous_node_connection_link: self.cuckatoo_u_newest_node_connections.replace(node, node_connections_index),
                 node,
-                edge_index: *index,
+                edge_index: index,
             };
-            node_connections[node_connections_index] = new_u_link.clone();
-            self.cuckatoo_u_newest_node_connections.replace(node, &new_u_link);
-            
+
             // Replace newest node connection for the node on the second partition and add node connection to list
-            let previous_v = self.cuckatoo_v_newest_node_connections.get(self.cuckatoo_root_node).cloned();
-            let new_v_link = CuckatooNodeConnectionsLink {
-                previous_node_connection_link: previous_v.map(|link| Box::new(link)),
+            node_connections[node_connections_index + 1] = CuckatooNodeConnectionsLink {
+                previous_node_connection_link: self.cuckatoo_v_newest_node_connections.replace(self.cuckatoo_root_node, node_connections_index + 1),
                 node: self.cuckatoo_root_node,
-                edge_index: *index,
+                edge_index: index,
             };
-            node_connections[node_connections_index + 1] = new_v_link.clone();
-            self.cuckatoo_v_newest_node_connections.replace(self.cuckatoo_root_node, &new_v_link);
-            
+
             // Check if both nodes have a pair
-            if self.cuckatoo_u_newest_node_connections.contains(node ^ 1) && 
+            if self.cuckatoo_u_newest_node_connections.contains(node ^ 1) &&
                self.cuckatoo_v_newest_node_connections.contains(self.cuckatoo_root_node ^ 1) {
-                
+
                 // Reset visited nodes
                 self.cuckatoo_u_visited_node_pairs.clear();
                 self.cuckatoo_v_visited_node_pairs.clear();
-                
+
                 // Go through all nodes in the cycle (matching C++ complex loop exactly)
                 let mut cycle_size = 1u8;
                 let mut current_node = node;
-                let mut current_index = *index;
-                
+                let mut current_index = index;
+
                 loop {
                     // Set that node pair has been visited
                     self.cuckatoo_u_visited_node_pairs.set_unique((current_node >> 1) as u64, current_index);
-                    
+
+                    let Some(node_connection) = self.cuckatoo_u_newest_node_connections.get(current_node ^ 1) else {
+                        break;
+                    };
+
                     // Check if node's pair has more than one connection
-                    if let Some(node_connection) = self.cuckatoo_u_newest_node_connections.get(current_node ^ 1) {
-                        if node_connection.previous_node_connection_link.is_some() {
-                            // Go through all of the node's pair's connections
-                            let mut current_connection = node_connection;
-                            loop {
-                                // Check if the connected node's pair wasn't already visited
-                                if !self.cuckatoo_v_visited_node_pairs.contains(((current_connection.node + 1) >> 1) as u64) {
-                                    // Check if cycle is complete
-                                    if ((current_connection.node + 1) ^ 1) == self.cuckatoo_root_node {
-                                        // Check if cycle is a solution
-                                        if cycle_size == SOLUTION_SIZE as u8 - 1 {
+                    if node_connections[node_connection].previous_node_connection_link.is_some() {
+                        // Go through all of the node's pair's connections
+                        let mut current_connection = Some(node_connection);
+                        while let Some(connection) = current_connection {
+                            // The connection's opposite end (nodeConnection + 1)
+                            let opposite = node_connections[connection + 1];
+
+                            // Check if the connected node's pair wasn't already visited
+                            if !self.cuckatoo_v_visited_node_pairs.contains((opposite.node >> 1) as u64) {
+                                // Check if cycle is complete
+                                if (opposite.node ^ 1) == self.cuckatoo_root_node {
+                                    // Check if cycle is a solution
+                                    if cycle_size == last {
+                                        // Get solution from visited nodes
+                                        self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..half]);
+                                        self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[half..self.cycle_length - 1]);
+                                        solution[self.cycle_length - 1] = opposite.edge_index;
+
+                                        // Sort solution in ascending order
+                                        solution.sort();
+
+                                        return true;
+                                    }
+                                }
+                                // Otherwise check if cycle could be as solution
+                                else if cycle_size != last {
+                                    // Check if the connected node has a pair
+                                    if self.cuckatoo_v_newest_node_connections.contains(opposite.node ^ 1) {
+                                        // Check if solution was found at the connected node's pair
+                                        if self.search_node_connections_for_cuckatoo_solution_second_partition(
+                                            node_connections,
+                                            cycle_size + 1,
+                                            opposite.node ^ 1,
+                                            opposite.edge_index
+                                        ) {
                                             // Get solution from visited nodes
-                                            self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..SOLUTION_SIZE/2]);
-                                            self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[SOLUTION_SIZE/2..SOLUTION_SIZE-1]);
-                                            solution[SOLUTION_SIZE - 1] = current_connection.edge_index + 1;
-                                            
+                                            self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..half]);
+                                            self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[half..self.cycle_length]);
+
                                             // Sort solution in ascending order
                                             solution.sort();
-                                            
+
                                             return true;
                                         }
                                     }
-                                    // Otherwise check if cycle could be as solution
-                                    else if cycle_size != SOLUTION_SIZE as u8 - 1 {
-                                        // Check if the connected node has a pair
-                                        let has_pair = self.cuckatoo_v_newest_node_connections.contains((current_connection.node + 1) ^ 1);
-                                        if has_pair {
-                                            // Check if solution was found at the connected node's pair
-                                            let next_node = (current_connection.node + 1) ^ 1;
-                                            let next_index = current_connection.edge_index + 1;
-                                            if self.search_node_connections_for_cuckatoo_solution_second_partition(
-                                                cycle_size + 1,
-                                                next_node,
-                                                &next_index
-                                            ) {
-                                                // Get solution from visited nodes
-                                                self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..SOLUTION_SIZE/2]);
-                                                self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[SOLUTION_SIZE/2..SOLUTION_SIZE]);
-                                                
-                                                // Sort solution in ascending order
-                                                solution.sort();
-                                                
-                                                return true;
-                                            }
-                                        }
-                                    }
-                                }
-                                
-                                // Move to previous connection
-                                if let Some(ref prev) = current_connection.previous_node_connection_link {
-                                    current_connection = prev;
-                                } else {
-                                    break;
                                 }
                             }
-                            
-                            // Break
-                            break;
-                        }
-                        
-                        // Go to node's pair opposite end and get its edge index
-                        current_index = node_connection.edge_index + 1;
-                        current_node = node_connection.node + 1;
-                        
-                        // Check if node pair was already visited
-                        if self.cuckatoo_v_visited_node_pairs.contains((current_node >> 1) as u64) {
-                            break;
-                        }
-                        
-                        // Check if cycle is complete
-                        if (current_node ^ 1) == self.cuckatoo_root_node {
-                            // Check if cycle is a solution
-                            if cycle_size == SOLUTION_SIZE as u8 - 1 {
-                                // Get solution from visited nodes
-                                self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..SOLUTION_SIZE/2]);
-                                self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[SOLUTION_SIZE/2..SOLUTION_SIZE-1]);
-                                solution[SOLUTION_SIZE - 1] = current_index;
-                                
-                                // Sort solution in ascending order
-                                solution.sort();
-                                
-                                return true;
-                            }
-                            
-                            // Break
-                            break;
-                        }
-                        
-                        // Check if cycle isn't a solution
-                        if cycle_size == SOLUTION_SIZE as u8 - 1 {
-                            break;
+
+                            // Move to previous connection
+                            current_connection = node_connections[connection].previous_node_connection_link;
                         }
-                        
-                        // Check if node doesn't have a pair
-                        if !self.cuckatoo_v_newest_node_connections.contains(current_node ^ 1) {
-                            break;
+
+                        // Break
+                        break;
+                    }
+
+                    // Go to node's pair opposite end and get its edge index
+                    let opposite = node_connections[node_connection + 1];
+                    current_index = opposite.edge_index;
+                    current_node = opposite.node;
+
+                    // Check if node pair was already visited
+                    if self.cuckatoo_v_visited_node_pairs.contains((current_node >> 1) as u64) {
+                        break;
+                    }
+
+                    // Check if cycle is complete
+                    if (current_node ^ 1) == self.cuckatoo_root_node {
+                        // Check if cycle is a solution
+                        if cycle_size == last {
+                            // Get solution from visited nodes
+                            self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..half]);
+                            self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[half..self.cycle_length - 1]);
+                            solution[self.cycle_length - 1] = current_index;
+
+                            // Sort solution in ascending order
+                            solution.sort();
+
+                            return true;
                         }
-                        
-                        // Set that node pair has been visited
-                        self.cuckatoo_v_visited_node_pairs.set_unique((current_node >> 1) as u64, current_index);
-                        
-                        // Check if node's pair has more than one connection
-                        if let Some(node_connection) = self.cuckatoo_v_newest_node_connections.get(current_node ^ 1) {
-                            if node_connection.previous_node_connection_link.is_some() {
-                                // Go through all of the node's pair's connections
-                                let mut current_connection = node_connection;
-                                loop {
-                                    // Check if the connected node has a pair
-                                    let has_pair = self.cuckatoo_u_newest_node_connections.contains((current_connection.node - 1) ^ 1);
-                                    if has_pair {
-                                        // Check if the connected node's pair wasn't already visited
-                                        if !self.cuckatoo_u_visited_node_pairs.contains(((current_connection.node - 1) >> 1) as u64) {
-                                            // Check if solution was found at the connected node's pair
-                                            let next_node = (current_connection.node - 1) ^ 1;
-                                            let next_index = current_connection.edge_index - 1;
-                                            if self.search_node_connections_for_cuckatoo_solution_first_partition(
-                                                cycle_size + 2, 
-                                                next_node, 
-                                                &next_index
-                                            ) {
-                                                // Get solution from visited nodes
-                                                self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..SOLUTION_SIZE/2]);
-                                                self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[SOLUTION_SIZE/2..SOLUTION_SIZE]);
-                                                
-                                                // Sort solution in ascending order
-                                                solution.sort();
-                                                
-                                                return true;
-                                            }
-                                        }
-                                    }
-                                    
-                                    // Move to previous connection
-                                    if let Some(ref prev) = current_connection.previous_node_connection_link {
-                                        current_connection = prev;
-                                    } else {
-                                        break;
+
+                        // Break
+                        break;
+                    }
+
+                    // Check if cycle isn't a solution
+                    if cycle_size == last {
+                        break;
+                    }
+
+                    // Check if node doesn't have a pair
+                    let Some(node_connection) = self.cuckatoo_v_newest_node_connections.get(current_node ^ 1) else {
+                        break;
+                    };
+
+                    // Set that node pair has been visited
+                    self.cuckatoo_v_visited_node_pairs.set_unique((current_node >> 1) as u64, current_index);
+
+                    // Check if node's pair has more than one connection
+                    if node_connections[node_connection].previous_node_connection_link.is_some() {
+                        // Go through all of the node's pair's connections
+                        let mut current_connection = Some(node_connection);
+                        while let Some(connection) = current_connection {
+                            // The connection's opposite end (nodeConnection - 1)
+                            let opposite = node_connections[connection - 1];
+
+                            // Check if the connected node has a pair
+                            if self.cuckatoo_u_newest_node_connections.contains(opposite.node ^ 1) {
+                                // Check if the connected node's pair wasn't already visited
+                                if !self.cuckatoo_u_visited_node_pairs.contains((opposite.node >> 1) as u64) {
+                                    // Check if solution was found at the connected node's pair
+                                    if self.search_node_connections_for_cuckatoo_solution_first_partition(
+                                        node_connections,
+                                        cycle_size + 2,
+                                        opposite.node ^ 1,
+                                        opposite.edge_index
+                                    ) {
+                                        // Get solution from visited nodes
+                                        self.cuckatoo_u_visited_node_pairs.get_values(&mut solution[0..half]);
+                                        self.cuckatoo_v_visited_node_pairs.get_values(&mut solution[half..self.cycle_length]);
+
+                                        // Sort solution in ascending order
+                                        solution.sort();
+
+                                        return true;
                                     }
                                 }
-                                
-                                // Break
-                                break;
-                            }
-                            
-                        // Go to node's pair opposite end and get its edge index
-                        current_index = node_connection.edge_index - 1;
-                        current_node = node_connection.node - 1;
-                            
-                            // Check if node pair was already visited
-                            if self.cuckatoo_u_visited_node_pairs.contains((current_node >> 1) as u64) {
-                                break;
                             }
-                            
-                            // Check if node doesn't have a pair
-                            if !self.cuckatoo_u_newest_node_connections.contains(current_node ^ 1) {
-                                break;
-                            }
-                        } else {
-                            break;
+
+                            // Move to previous connection
+                            current_connection = node_connections[connection].previous_node_connection_link;
                         }
-                        
-                        cycle_size += 2;
-                    } else {
+
+                        // Break
+                        break;
+                    }
+
+                    // Go to node's pair opposite end and get its edge index
+                    let opposite = node_connections[node_connection - 1];
+                    current_index = opposite.edge_index;
+                    current_node = opposite.node;
+
+                    // Check if node pair was already visited
+                    if self.cuckatoo_u_visited_node_pairs.contains((current_node >> 1) as u64) {
                         break;
                     }
+
+                    // Check if node doesn't have a pair
+                    if !self.cuckatoo_u_newest_node_connections.contains(current_node ^ 1) {
+                        break;
+                    }
+
+                    cycle_size += 2;
                 }
             }
-            
+
             // Update indices (matching C++ exactly)
             node_connections_index += 2;
             edges_index += EDGE_NUMBER_OF_COMPONENTS;
         }
-        
+
         false
     }
-    
+
     /// Search node connections for cuckatoo solution first partition (matching C++ exactly)
-    fn search_node_connections_for_cuckatoo_solution_first_partition(&mut self, cycle_size: u8, node: u32, index: &u32) -> bool {
+    fn search_node_connections_for_cuckatoo_solution_first_partition(&mut self, node_connections: &[CuckatooNodeConnectionsLink], cycle_size: u8, node: u32, index: u32) -> bool {
         // Set that node pair has been visited
-        self.cuckatoo_u_visited_node_pairs.set_unique((node >> 1) as u64, *index);
-        
+        self.cuckatoo_u_visited_node_pairs.set_unique((node >> 1) as u64, index);
+
         // Go through all of the node's connections
-        if let Some(node_connection) = self.cuckatoo_u_newest_node_connections.get(node) {
-            let mut current_connection = node_connection;
-            loop {
-                // Check if the connected node's pair wasn't already visited
-                if !self.cuckatoo_v_visited_node_pairs.contains(((current_connection.node + 1) >> 1) as u64) {
-                    // Check if cycle is complete
-                    if ((current_connection.node + 1) ^ 1) == self.cuckatoo_root_node {
-                        // Check if cycle is a solution
-                        if cycle_size == SOLUTION_SIZE as u8 - 1 {
-                            // Set that the connected node's pair has been visited
-                            self.cuckatoo_v_visited_node_pairs.set_unique(((current_connection.node + 1) >> 1) as u64, current_connection.edge_index + 1);
-                            
-                            return true;
-                        }
+        let mut current_connection = self.cuckatoo_u_newest_node_connections.get(node);
+        while let Some(connection) = current_connection {
+            // The connection's opposite end (nodeConnection + 1)
+            let opposite = node_connections[connection + 1];
+
+            // Check if the connected node's pair wasn't already visited
+            if !self.cuckatoo_v_visited_node_pairs.contains((opposite.node >> 1) as u64) {
+                // Check if cycle is complete
+                if (opposite.node ^ 1) == self.cuckatoo_root_node {
+                    // Check if cycle is a solution
+                    if cycle_size == self.cycle_length as u8 - 1 {
+                        // Set that the connected node's pair has been visited
+                        self.cuckatoo_v_visited_node_pairs.set_unique((opposite.node >> 1) as u64, opposite.edge_index);
+
+                        return true;
                     }
-                    // Otherwise check if cycle could be as solution
-                    else if cycle_size != SOLUTION_SIZE as u8 - 1 {
-                        // Check if the connected node has a pair
-                        let has_pair = self.cuckatoo_v_newest_node_connections.contains((current_connection.node + 1) ^ 1);
-                        if has_pair {
-                            // Check if solution was found at the connected node's pair
-                            let next_node = (current_connection.node + 1) ^ 1;
-                            let next_index = current_connection.edge_index + 1;
-                            if self.search_node_connections_for_cuckatoo_solution_second_partition(
-                                cycle_size + 1,
-                                next_node,
-                                &next_index
-                            ) {
-                                return true;
-                            }
+                }
+                // Otherwise check if cycle could be as solution
+                else if cycle_size != self.cycle_length as u8 - 1 {
+                    // Check if the connected node has a pair
+                    if self.cuckatoo_v_newest_node_connections.contains(opposite.node ^ 1) {
+                        // Check if solution was found at the connected node's pair
+                        if self.search_node_connections_for_cuckatoo_solution_second_partition(
+                            node_connections,
+                            cycle_size + 1,
+                            opposite.node ^ 1,
+                            opposite.edge_index
+                        ) {
+                            return true;
                         }
                     }
                 }
-                
-                // Move to previous connection
-                if let Some(ref prev) = current_connection.previous_node_connection_link {
-                    current_connection = prev;
-                } else {
-                    break;
-                }
             }
+
+            // Move to previous connection
+            current_connection = node_connections[connection].previous_node_connection_link;
         }
-        
-        // Set that node pair hasn't been visited (remove from visited)
-        self.cuckatoo_u_visited_node_pairs.data.remove(&((node >> 1) as u64));
-        
+
+        // Set that node pair hasn't been visited
+        self.cuckatoo_u_visited_node_pairs.remove((node >> 1) as u64);
+
         false
     }
-    
+
     /// Search node connections for cuckatoo solution second partition (matching C++ exactly)
-    fn search_node_connections_for_cuckatoo_solution_second_partition(&mut self, cycle_size: u8, node: u32, index: &u32) -> bool {
+    fn search_node_connections_for_cuckatoo_solution_second_partition(&mut self, node_connections: &[CuckatooNodeConnectionsLink], cycle_size: u8, node: u32, index: u32) -> bool {
         // Set that node pair has been visited
-        self.cuckatoo_v_visited_node_pairs.set_unique((node >> 1) as u64, *index);
-        
+        self.cuckatoo_v_visited_node_pairs.set_unique((node >> 1) as u64, index);
+
         // Go through all of the node's connections
-        if let Some(node_connection) = self.cuckatoo_v_newest_node_connections.get(node) {
-            let mut current_connection = node_connection;
-            loop {
+        let mut current_connection = self.cuckatoo_v_newest_node_connections.get(node);
+        while let Some(connection) = current_connection {
+            // The connection's opposite end (nodeConnection - 1)
+            let opposite = node_connections[connection - 1];
+
+            // Check if the connected node has a pair
+            if self.cuckatoo_u_newest_node_connections.contains(opposite.node ^ 1) {
                 // Check if the connected node's pair wasn't already visited
-                if !self.cuckatoo_u_visited_node_pairs.contains(((current_connection.node - 1) >> 1) as u64) {
-                    // Check if cycle is complete
-                    if ((current_connection.node - 1) ^ 1) == self.cuckatoo_root_node {
-                        // Check if cycle is a solution
-                        if cycle_size == SOLUTION_SIZE as u8 - 1 {
-                            // Set that the connected node's pair has been visited
-                            self.cuckatoo_u_visited_node_pairs.set_unique(((current_connection.node - 1) >> 1) as u64, current_connection.edge_index - 1);
-                            
-                            return true;
-                        }
+                if !self.cuckatoo_u_visited_node_pairs.contains((opposite.node >> 1) as u64) {
+                    // Check if solution was found at the connected node's pair
+                    if self.search_node_connections_for_cuckatoo_solution_first_partition(
+                        node_connections,
+                        cycle_size + 1,
+                        opposite.node ^ 1,
+                        opposite.edge_index
+                    ) {
+                        return true;
                     }
-                    // Otherwise check if cycle could be as solution
-                    else if cycle_size != SOLUTION_SIZE as u8 - 1 {
-                        // Check if the connected node has a pair
-                        let has_pair = self.cuckatoo_u_newest_node_connections.contains((current_connection.node - 1) ^ 1);
-                        if has_pair {
-                            // Check if solution was found at the connected node's pair
-                            let next_node = (current_connection.node - 1) ^ 1;
-                            let next_index = current_connection.edge_index - 1;
-                            if self.search_node_connections_for_cuckatoo_solution_first_partition(
-                                cycle_size + 1, 
-                                next_node, 
-                                &next_index
-                            ) {
-                                return true;
-                            }
-                        }
-                    }
-                }
-                
-                // Move to previous connection
-                if let Some(ref prev) = current_connection.previous_node_connection_link {
-                    current_connection = prev;
-                } else {
-                    break;
                 }
             }
+
+            // Move to previous connection
+            current_connection = node_connections[connection].previous_node_connection_link;
         }
-        
-        // Set that node pair hasn't been visited (remove from visited)
-        self.cuckatoo_v_visited_node_pairs.data.remove(&((node >> 1) as u64));
-        
+
+        // Set that node pair hasn't been visited
+        self.cuckatoo_v_visited_node_pairs.remove((node >> 1) as u64);
+
         false
     }
+
+    /// Find cycle using the C++ algorithm (wrapper for getCuckatooSolution)
+    ///
+    /// Nodes and edge indices must fit in 32 bits, as in the C++ miner.
+    pub fn find_cycle(&mut self, edges: &[Edge]) -> Result<Option<Vec<usize>>> {
+        // Initialize thread-local global variables
+        self.initialize_cuckatoo_thread_local_global_variables();
+
+        // Convert edges to C++ format [edge_index, node_u, node_v]
+        let too_big = || CuckatooError::InternalError("Edges don't fit the C++ cycle finder's 32-bit nodes".to_string());
+        let mut cpp_edges = Vec::with_capacity(edges.len() * EDGE_NUMBER_OF_COMPONENTS);
+        for (i, edge) in edges.iter().enumerate() {
+            cpp_edges.push(u32::try_from(i).map_err(|_| too_big())?);
+            cpp_edges.push(u32::try_from(edge.u.value()).map_err(|_| too_big())?);
+            cpp_edges.push(u32::try_from(edge.v.value()).map_err(|_| too_big())?);
+        }
+
+        // Preallocate the links arena
+        let mut node_connections = vec![CuckatooNodeConnectionsLink::default(); edges.len() * 2];
+
+        let mut solution = Vec::with_capacity(self.cycle_length);
+        if self.get_cuckatoo_solution(&mut solution, &mut node_connections, &cpp_edges, edges.len() as u64) {
+            Ok(Some(solution.iter().map(|&index| index as usize).collect()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HashCycleFinder, Node, SipHash, SipKeys};
+
+    /// Cycle of `length` edges through U pairs and V pairs 0 to
+    /// `length / 2 - 1`, each pair reached at both of its nodes
+    fn node_pair_cycle(length: usize) -> Vec<Edge> {
+        let half = length as u64 / 2;
+        (0..half)
+            .flat_map(|pair| {
+                let next = (pair + 1) % half;
+                [
+                    Edge::new(Node::new(2 * pair), Node::new(2 * pair)),
+                    Edge::new(Node::new(2 * next + 1), Node::new(2 * pair + 1)),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cpp_cycle_finder_parity_with_hash_cycle_finder() {
+        // Random graphs, mostly without cycles
+        for seed in 0..4u64 {
+            let siphash = SipHash::with_key(SipKeys::from_header(b"cpp cycle finder", seed));
+            let edges = siphash.edges_for_indices(&(0..1 << 10).collect::<Vec<_>>(), 10);
+            for length in [4, SOLUTION_SIZE] {
+                let cpp = CppCycleFinder::new().with_cycle_length(length).find_cycle(&edges).unwrap();
+                let hash = HashCycleFinder::new().with_cycle_length(length).find_cycle(&edges).unwrap();
+                assert_eq!(cpp, hash, "seed {} length {}", seed, length);
+            }
+        }
+
+        // Planted node pair cycles, alone and among unrelated edges
+        for length in [4, 12, SOLUTION_SIZE] {
+            let mut edges: Vec<Edge> = (0..20).map(|i| Edge::new(Node::new(1000 + 4 * i), Node::new(1000 + 4 * i))).collect();
+            let first = edges.len();
+            edges.extend(node_pair_cycle(length));
+            let cpp = CppCycleFinder::new().with_cycle_length(length).find_cycle(&edges).unwrap();
+            assert_eq!(cpp, Some((first..edges.len()).collect::<Vec<_>>()), "length {}", length);
+            let hash = HashCycleFinder::new().with_cycle_length(length).find_cycle(&edges).unwrap();
+            assert_eq!(cpp, hash, "length {}", length);
+            assert_eq!(CppCycleFinder::new().with_cycle_length(length + 2).find_cycle(&edges).unwrap(), None);
+        }
+    }
 }
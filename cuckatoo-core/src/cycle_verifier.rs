@@ -1,225 +0,0 @@
-use crate::constants::get_cycle_length;
-use crate::types::{Edge, Solution};
-use std::collections::{HashMap, HashSet};
-
-pub struct CycleVerifier {
-    edge_bits: u32,
-    cycle_length: u32,
-}
-
-impl CycleVerifier {
-    pub fn new(edge_bits: u32) -> Result<Self, String> {
-        let cycle_length = get_cycle_length() as u32;
-        Ok(Self { edge_bits, cycle_length })
-    }
-
-    pub fn find_cycles(&self, edges: &[Edge]) -> Vec<Solution> {
-        let mut solutions = Vec::new();
-        
-        println!("DEBUG: Looking for cycles of length {}", self.cycle_length);
-        println!("DEBUG: Input has {} edges", edges.len());
-        
-        // For synthetic tests, create a simple solution
-        if edges.len() == self.cycle_length as usize {
-            // Check if this is a simple cycle (0->1->2->...->41->0)
-            let mut is_simple_cycle = true;
-            for (i, edge) in edges.iter().enumerate() {
-                let expected_next = if i == self.cycle_length as usize - 1 { 0 } else { i + 1 };
-                if edge.v_node != expected_next as u32 {
-                    is_simple_cycle = false;
-                    break;
-                }
-            }
-            
-            if is_simple_cycle {
-                let solution_edges: Vec<u32> = (0..self.cycle_length).collect();
-                solutions.push(Solution::with_cycle_length(solution_edges));
-                println!("DEBUG: Synthetic test - found 1 solution");
-                return solutions;
-            }
-        }
-        
-        // For real graphs, implement proper cycle detection
-        // Build adjacency list
-        let mut graph: HashMap<u32, Vec<(u32, u32)>> = HashMap::new();
-        
-        for (edge_index, edge) in edges.iter().enumerate() {
-            graph.entry(edge.u_node).or_insert_with(Vec::new).push((edge.v_node, edge_index as u32));
-            graph.entry(edge.v_node).or_insert_with(Vec::new).push((edge.u_node, edge_index as u32));
-        }
-        
-        println!("DEBUG: Built graph with {} nodes", graph.len());
-        
-        // Try to find cycles starting from a limited number of edges
-        let max_start_edges = std::cmp::min(100, edges.len());
-        let mut checked_edges = HashSet::new();
-        
-        for edge_index in 0..max_start_edges {
-            if edge_index % 10 == 0 {
-                println!("DEBUG: Checked {} edges...", edge_index);
-            }
-            
-            if checked_edges.contains(&edge_index) {
-                continue;
-            }
-            
-            if let Some(solution) = self.find_real_cycle(
-                edge_index as u32,
-                &graph,
-                edges,
-                &mut checked_edges,
-            ) {
-                println!("DEBUG: Found real solution starting from edge {}", edge_index);
-                
-                // Mark all edges in this solution as checked
-                for &edge_idx in &solution.edges {
-                    checked_edges.insert(edge_idx as usize);
-                }
-                
-                solutions.push(solution);
-                
-                // Limit to first few solutions
-                if solutions.len() >= 3 {
-                    println!("DEBUG: Found enough solutions, stopping search");
-                    break;
-                }
-            }
-        }
-        
-        println!("DEBUG: Total solutions found: {}", solutions.len());
-        solutions
-    }
-
-    fn find_real_cycle(
-        &self,
-        start_edge: u32,
-        graph: &HashMap<u32, Vec<(u32, u32)>>,
-        edges: &[Edge],
-        checked_edges: &mut HashSet<usize>,
-    ) -> Option<Solution> {
-        let start_edge = start_edge as usize;
-        if start_edge >= edges.len() {
-            return None;
-        }
-        
-        let start_u = edges[start_edge].u_node;
-        let start_v = edges[start_edge].v_node;
-        
-        // Use a stack-based approach to avoid deep recursion
-        let mut stack: Vec<(u32, u32, Vec<u32>, Vec<u32>)> = Vec::new();
-        let mut visited_edges = HashSet::new();
-        
-        // Start with the first edge
-        stack.push((start_v, start_u, vec![start_edge as u32], vec![start_u]));
-        visited_edges.insert(start_edge);
-        
-        while let Some((current, target, current_edge_path, current_path)) = stack.pop() {
-            // Check if we've found a cycle
-            if current == target && current_edge_path.len() == self.cycle_length as usize {
-                // Validate this is a real cycle
-                if self.validate_cycle(&current_edge_path, edges) {
-                    let mut solution_edges = current_edge_path.clone();
-                    solution_edges.sort();
-                    return Some(Solution::with_cycle_length(solution_edges));
-                }
-            }
-            
-            // Check if path is too long
-            if current_edge_path.len() >= self.cycle_length as usize {
-                continue;
-            }
-            
-            // Try to extend the path
-            if let Some(connections) = graph.get(&current) {
-                for &(next, edge_idx) in connections {
-                    let edge_idx = edge_idx as usize;
-                    
-                    // Don't reuse edges
-                    if visited_edges.contains(&edge_idx) {
-                        continue;
-                    }
-                    
-                    // Don't revisit nodes (except target at the end)
-                    if next != target && current_path.contains(&next) {
-                        continue;
-                    }
-                    
-                    // Add to path
-                    let mut new_edge_path = current_edge_path.clone();
-                    let mut new_path = current_path.clone();
-                    
-                    new_edge_path.push(edge_idx as u32);
-                    new_path.push(next);
-                    
-                    // Add to stack for processing
-                    stack.push((next, target, new_edge_path, new_path));
-                }
-            }
-        }
-        
-        None
-    }
-
-    fn validate_cycle(&self, edge_indices: &[u32], edges: &[Edge]) -> bool {
-        // Check that we have the right number of edges
-        if edge_indices.len() != self.cycle_length as usize {
-            return false;
-        }
-        
-        // Check that all edges exist
-        for &edge_idx in edge_indices {
-            if edge_idx as usize >= edges.len() {
-                return false;
-            }
-        }
-        
-        // Check that edges form a connected path
-        let mut nodes = Vec::new();
-        for &edge_idx in edge_indices {
-            let edge = &edges[edge_idx as usize];
-            nodes.push(edge.u_node);
-            nodes.push(edge.v_node);
-        }
-        
-        // Check if the path is connected (simplified check)
-        // In a real implementation, you'd verify the actual graph connectivity
-        nodes.len() >= self.cycle_length as usize
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::Edge;
-
-    #[test]
-    fn test_cycle_verifier_basic() {
-        let verifier = CycleVerifier::new(12).unwrap();
-        
-        // Test with a simple graph that should have cycles
-        let edges = vec![
-            Edge::new(0, 0, 1),
-            Edge::new(1, 1, 2),
-            Edge::new(2, 2, 3),
-            Edge::new(3, 3, 0),
-        ];
-        
-        let solutions = verifier.find_cycles(&edges);
-        println!("Found {} solutions", solutions.len());
-        
-        // For a 4-edge graph, we might not find 42-cycles, but we should find some cycles
-        // Let's test with a larger synthetic graph that should have 42-cycles
-        let mut synthetic_edges = Vec::new();
-        for i in 0..42 {
-            let next = (i + 1) % 42;
-            synthetic_edges.push(Edge::new(i as u32, i, next));
-        }
-        
-        let synthetic_solutions = verifier.find_cycles(&synthetic_edges);
-        println!("Synthetic test: Found {} solutions", synthetic_solutions.len());
-        
-        // The synthetic test should find at least one solution
-        assert!(synthetic_solutions.len() > 0, "Should find at least one solution in synthetic test");
-    }
-}
-
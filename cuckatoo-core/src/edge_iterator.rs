@@ -0,0 +1,152 @@
+//! Lazy edge generation
+//!
+//! `EdgeIterator` yields the edges of a graph in index order, hashing a
+//! small batch of nonces at a time, so a whole graph can be streamed
+//! without the 2^edge_bits `Vec<Edge>` that `SipHash::hash_header` builds
+//! (16 GiB at EDGE_BITS=31). `EdgeChunks` yields the same edges as
+//! fixed-size vectors for consumers that work a slice at a time.
+
+use crate::{Edge, Node, SipHasher};
+use std::iter::FusedIterator;
+use std::ops::Range;
+
+/// Edges hashed per batch
+const ITERATOR_BATCH_EDGES: usize = 1024;
+
+/// Iterator over the edges of a graph (or of a range of edge indices)
+pub struct EdgeIterator<H: SipHasher> {
+    hasher: H,
+    edge_bits: u32,
+    /// Index of the first edge not hashed yet
+    next_edge: u64,
+    end_edge: u64,
+    nonces: Vec<u64>,
+    /// U and V nodes of the current batch
+    nodes: Vec<u64>,
+    /// Position in the current batch, in edges
+    position: usize,
+    /// Edges in the current batch
+    batch_len: usize,
+}
+
+impl<H: SipHasher> EdgeIterator<H> {
+    /// Iterate over all 2^edge_bits edges of the hasher's graph
+    ///
+    /// Panics if `edge_bits` is 64 or more.
+    pub fn new(hasher: H, edge_bits: u32) -> Self {
+        assert!(edge_bits < u64::BITS, "EDGE_BITS={} can't be iterated", edge_bits);
+        Self::with_range(hasher, edge_bits, 0..1 << edge_bits)
+    }
+
+    /// Iterate over the edges with indices in `edges`, e.g. one worker's
+    /// share of a graph
+    pub fn with_range(hasher: H, edge_bits: u32, edges: Range<u64>) -> Self {
+        Self {
+            hasher,
+            edge_bits,
+            next_edge: edges.start,
+            end_edge: edges.end.max(edges.start),
+            nonces: Vec::with_capacity(2 * ITERATOR_BATCH_EDGES),
+            nodes: vec![0; 2 * ITERATOR_BATCH_EDGES],
+            position: 0,
+            batch_len: 0,
+        }
+    }
+
+    /// Index of the edge `next` returns
+    pub fn next_index(&self) -> u64 {
+        self.next_edge - (self.batch_len - self.position) as u64
+    }
+
+    /// Yield the edges as vectors of `chunk_edges` (the last one may be shorter)
+    ///
+    /// Panics if `chunk_edges` is zero.
+    pub fn chunks(self, chunk_edges: usize) -> EdgeChunks<H> {
+        assert!(chunk_edges > 0, "chunks need at least one edge");
+        EdgeChunks { edges: self, chunk_edges }
+    }
+
+    /// Hash the next batch of edges
+    fn refill(&mut self) {
+        let batch = (self.end_edge - self.next_edge).min(ITERATOR_BATCH_EDGES as u64);
+        self.nonces.clear();
+        self.nonces.extend((self.next_edge..self.next_edge + batch).flat_map(|edge| [edge * 2, edge * 2 + 1]));
+        self.hasher.hash_nonces(&self.nonces, self.edge_bits, &mut self.nodes);
+        self.next_edge += batch;
+        self.position = 0;
+        self.batch_len = batch as usize;
+    }
+}
+
+impl<H: SipHasher> Iterator for EdgeIterator<H> {
+    type Item = Edge;
+
+    fn next(&mut self) -> Option<Edge> {
+        if self.position == self.batch_len {
+            if self.next_edge == self.end_edge {
+                return None;
+            }
+            self.refill();
+        }
+        let pair = &self.nodes[self.position * 2..self.position * 2 + 2];
+        self.position += 1;
+        Some(Edge::new(Node::new(pair[0]), Node::new(pair[1])))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.end_edge - self.next_edge).saturating_add((self.batch_len - self.position) as u64);
+        match usize::try_from(remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl<H: SipHasher> FusedIterator for EdgeIterator<H> {}
+
+/// Edges of a graph as vectors of a fixed number of edges
+///
+/// Created by `EdgeIterator::chunks`.
+pub struct EdgeChunks<H: SipHasher> {
+    edges: EdgeIterator<H>,
+    chunk_edges: usize,
+}
+
+impl<H: SipHasher> Iterator for EdgeChunks<H> {
+    type Item = Vec<Edge>;
+
+    fn next(&mut self) -> Option<Vec<Edge>> {
+        let chunk: Vec<Edge> = self.edges.by_ref().take(self.chunk_edges).collect();
+        (!chunk.is_empty()).then_some(chunk)
+    }
+}
+
+impl<H: SipHasher> FusedIterator for EdgeChunks<H> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Header, SipHash};
+
+    #[test]
+    fn test_iterator_matches_hash_header() {
+        let siphash = SipHash::with_key(crate::self_test::SIPHASH_TEST_KEYS);
+        let expected = siphash.hash_header(&Header::new(b""), 12).unwrap();
+
+        let mut edges = siphash.edges(12);
+        assert_eq!(edges.size_hint(), (4096, Some(4096)));
+        assert_eq!(edges.next(), Some(expected[0]));
+        assert_eq!(edges.next_index(), 1);
+        assert_eq!(edges.size_hint(), (4095, Some(4095)));
+        assert!(edges.eq(expected[1..].iter().copied()));
+
+        // A range past a batch boundary
+        let range: Vec<Edge> = EdgeIterator::with_range(&siphash, 12, 1000..2100).collect();
+        assert_eq!(range, expected[1000..2100]);
+        assert_eq!(EdgeIterator::with_range(&siphash, 12, 5..5).next(), None);
+
+        let chunks: Vec<Vec<Edge>> = siphash.edges(12).chunks(1500).collect();
+        assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), [1500, 1500, 1096]);
+        assert_eq!(chunks.concat(), expected);
+    }
+}
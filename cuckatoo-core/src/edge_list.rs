@@ -0,0 +1,196 @@
+//! Edge lists dumped by other miners
+//!
+//! C miners can dump a trimmed graph or a candidate cycle as plain text,
+//! one `u v` pair per line (or `index u v` when they keep the edge
+//! indices), or as raw little-endian `u32` or `u64` pairs. Loading such a
+//! dump into an `EdgeList` lets this crate's cycle verifier check another
+//! miner's work.
+
+use crate::{CuckatooError, Edge, Node, Result};
+use std::fmt;
+use std::path::Path;
+
+/// Layout of an edge dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeListFormat {
+    /// `u v` or `index u v` per line, in decimal or `0x` hex; blank lines
+    /// and lines starting with `#` are skipped
+    #[default]
+    Text,
+    /// Little-endian `u32` pairs
+    Binary32,
+    /// Little-endian `u64` pairs
+    Binary64,
+}
+
+impl fmt::Display for EdgeListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdgeListFormat::Text => write!(f, "text"),
+            EdgeListFormat::Binary32 => write!(f, "u32"),
+            EdgeListFormat::Binary64 => write!(f, "u64"),
+        }
+    }
+}
+
+impl std::str::FromStr for EdgeListFormat {
+    type Err = CuckatooError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(EdgeListFormat::Text),
+            "u32" => Ok(EdgeListFormat::Binary32),
+            "u64" => Ok(EdgeListFormat::Binary64),
+            _ => Err(CuckatooError::InternalError(format!("Unknown edge list format: {} (expected text, u32 or u64)", s))),
+        }
+    }
+}
+
+/// Edges read from another miner's dump
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EdgeList {
+    /// Edges in dump order
+    pub edges: Vec<Edge>,
+    /// Edge index of each edge, if the dump had them (`index u v` lines)
+    pub indices: Option<Vec<u64>>,
+}
+
+impl EdgeList {
+    /// Parse a text dump
+    pub fn parse_text(text: &str) -> Result<Self> {
+        let mut list = EdgeList::default();
+        let mut indices = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: &str| {
+                CuckatooError::InternalError(format!("Invalid edge on line {}: {} ({:?})", number + 1, message, line))
+            };
+            let values = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|value| !value.is_empty())
+                .map(parse_number)
+                .collect::<Option<Vec<u64>>>()
+                .ok_or_else(|| invalid("bad number"))?;
+            let (index, u, v) = match values[..] {
+                [u, v] => (None, u, v),
+                [index, u, v] => (Some(index), u, v),
+                _ => return Err(invalid("expected `u v` or `index u v`")),
+            };
+            if !list.edges.is_empty() && index.is_some() == indices.is_empty() {
+                return Err(invalid("some lines have edge indices and some don't"));
+            }
+            indices.extend(index);
+            list.edges.push(Edge::new(Node::new(u), Node::new(v)));
+        }
+        if !indices.is_empty() {
+            list.indices = Some(indices);
+        }
+        Ok(list)
+    }
+
+    /// Parse a binary dump of little-endian `u32` or `u64` pairs
+    ///
+    /// Fails if the length isn't a whole number of pairs.
+    pub fn parse_binary(bytes: &[u8], format: EdgeListFormat) -> Result<Self> {
+        let width = match format {
+            EdgeListFormat::Binary32 => 4,
+            EdgeListFormat::Binary64 => 8,
+            EdgeListFormat::Text => return Err(CuckatooError::InternalError("Text edge lists aren't binary".to_string())),
+        };
+        if !bytes.len().is_multiple_of(2 * width) {
+            return Err(CuckatooError::InternalError(format!(
+                "Binary edge list of {} bytes isn't a whole number of {} pairs", bytes.len(), format
+            )));
+        }
+        let node = |chunk: &[u8]| {
+            let mut word = [0u8; 8];
+            word[..width].copy_from_slice(chunk);
+            Node::new(u64::from_le_bytes(word))
+        };
+        let edges = bytes
+            .chunks_exact(2 * width)
+            .map(|pair| Edge::new(node(&pair[..width]), node(&pair[width..])))
+            .collect();
+        Ok(Self { edges, indices: None })
+    }
+
+    /// Load a dump from a file
+    pub fn load(path: &Path, format: EdgeListFormat) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|error| {
+            CuckatooError::InternalError(format!("Cannot read edge list {}: {}", path.display(), error))
+        })?;
+        match format {
+            EdgeListFormat::Text => {
+                let text = String::from_utf8(bytes).map_err(|_| {
+                    CuckatooError::InternalError(format!("Edge list {} isn't text", path.display()))
+                })?;
+                Self::parse_text(&text)
+            },
+            _ => Self::parse_binary(&bytes, format),
+        }
+    }
+
+    /// Number of edges
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Check if the list has no edges
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// Fail with `InvalidEdgeBits` if a node doesn't fit in `edge_bits`
+    /// bits, i.e. the dump is of a different graph size
+    pub fn check_edge_bits(&self, edge_bits: u32) -> Result<()> {
+        let mask = crate::hashing::node_mask(edge_bits);
+        match self.edges.iter().any(|edge| (edge.u.value() | edge.v.value()) & !mask != 0) {
+            true => Err(CuckatooError::InvalidEdgeBits(edge_bits)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Parse a decimal or `0x` hex number
+fn parse_number(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(u: u64, v: u64) -> Edge {
+        Edge::new(Node::new(u), Node::new(v))
+    }
+
+    #[test]
+    fn test_parse_edge_dumps() {
+        let list = EdgeList::parse_text("# trimmed\n1 2\n\n0x10 3\n").unwrap();
+        assert_eq!(list.edges, [edge(1, 2), edge(16, 3)]);
+        assert_eq!(list.indices, None);
+
+        let indexed = EdgeList::parse_text("7 1 2\n9,4,5\n").unwrap();
+        assert_eq!(indexed.edges, [edge(1, 2), edge(4, 5)]);
+        assert_eq!(indexed.indices, Some(vec![7, 9]));
+        assert!(EdgeList::parse_text("1 2\n7 1 2\n").is_err());
+        assert!(EdgeList::parse_text("1 x\n").is_err());
+
+        let bytes: Vec<u8> = [1u32, 2, 3, 4].iter().flat_map(|n| n.to_le_bytes()).collect();
+        assert_eq!(EdgeList::parse_binary(&bytes, EdgeListFormat::Binary32).unwrap().edges, [edge(1, 2), edge(3, 4)]);
+        assert_eq!(EdgeList::parse_binary(&bytes, EdgeListFormat::Binary64).unwrap().edges, [edge(1 | 2 << 32, 3 | 4 << 32)]);
+        assert!(EdgeList::parse_binary(&bytes[..12], EdgeListFormat::Binary32).is_err());
+
+        assert!(list.check_edge_bits(5).is_ok());
+        assert!(matches!(list.check_edge_bits(4), Err(CuckatooError::InvalidEdgeBits(4))));
+        for format in [EdgeListFormat::Text, EdgeListFormat::Binary32, EdgeListFormat::Binary64] {
+            assert_eq!(format.to_string().parse::<EdgeListFormat>().unwrap(), format);
+        }
+    }
+}
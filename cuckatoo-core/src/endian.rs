@@ -0,0 +1,137 @@
+//! Explicit byte-order conversions
+//!
+//! Every conversion between bytes and integers (Blake2b output to SipHash
+//! keys, nonce injection into the header, proof packing) goes through these
+//! helpers so results are identical on little- and big-endian targets. All
+//! of them are little-endian except the nonce, which Grin serializes
+//! big-endian.
+
+use crate::{CuckatooError, Result};
+
+/// Number of bytes in the SipHash keys derived from the header
+pub const KEYS_SIZE: usize = 32;
+
+/// Read a little-endian u64 at `offset`
+///
+/// Returns None if fewer than 8 bytes are available.
+pub fn read_u64_le(bytes: &[u8], offset: usize) -> Option<u64> {
+    let end = offset.checked_add(8)?;
+    let chunk: [u8; 8] = bytes.get(offset..end)?.try_into().ok()?;
+    Some(u64::from_le_bytes(chunk))
+}
+
+/// Write a little-endian u64 at `offset`
+///
+/// Panics if fewer than 8 bytes are available, like slice indexing.
+pub fn write_u64_le(bytes: &mut [u8], offset: usize, value: u64) {
+    bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Convert a 32-byte hash output into the four SipHash keys
+pub fn keys_from_le_bytes(bytes: &[u8; KEYS_SIZE]) -> [u64; 4] {
+    let mut keys = [0u64; 4];
+    for (i, key) in keys.iter_mut().enumerate() {
+        *key = u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().expect("8-byte chunk"));
+    }
+    keys
+}
+
+/// Convert the four SipHash keys back into their 32-byte form
+pub fn keys_to_le_bytes(keys: &[u64; 4]) -> [u8; KEYS_SIZE] {
+    let mut bytes = [0u8; KEYS_SIZE];
+    for (i, &key) in keys.iter().enumerate() {
+        write_u64_le(&mut bytes, i * 8, key);
+    }
+    bytes
+}
+
+/// Append the nonce to the header as a big-endian u64
+///
+/// This is the byte string hashed to derive the SipHash keys, the header
+/// followed by the nonce the way Grin's `pre_pow` serializes it.
+pub fn header_with_nonce(header: &[u8], nonce: u64) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(header.len() + 8);
+    bytes.extend_from_slice(header);
+    bytes.extend_from_slice(&nonce.to_be_bytes());
+    bytes
+}
+
+/// Pack proof nonces into bytes, `edge_bits` bits per nonce, least
+/// significant bit first
+pub fn pack_proof(nonces: &[u64], edge_bits: u32) -> Vec<u8> {
+    let edge_bits = edge_bits as usize;
+    let mut bytes = vec![0u8; (nonces.len() * edge_bits).div_ceil(8)];
+
+    for (i, &nonce) in nonces.iter().enumerate() {
+        for bit in 0..edge_bits {
+            if (nonce >> bit) & 1 == 1 {
+                let position = i * edge_bits + bit;
+                bytes[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+
+    bytes
+}
+
+/// Unpack `count` proof nonces of `edge_bits` bits each from bytes packed
+/// by `pack_proof`
+pub fn unpack_proof(bytes: &[u8], edge_bits: u32, count: usize) -> Result<Vec<u64>> {
+    let edge_bits = edge_bits as usize;
+    let needed = (count * edge_bits).div_ceil(8);
+    if bytes.len() < needed {
+        return Err(CuckatooError::VerificationError(format!(
+            "Packed proof has {} bytes, expected {}", bytes.len(), needed
+        )));
+    }
+
+    let nonces = (0..count)
+        .map(|i| {
+            (0..edge_bits).fold(0u64, |nonce, bit| {
+                let position = i * edge_bits + bit;
+                let value = (bytes[position / 8] >> (position % 8)) & 1;
+                nonce | (value as u64) << bit
+            })
+        })
+        .collect();
+
+    Ok(nonces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_u64_le_round_trip() {
+        let mut bytes = [0u8; 10];
+        write_u64_le(&mut bytes, 1, 0x0102030405060708);
+        assert_eq!(bytes, [0, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(read_u64_le(&bytes, 1), Some(0x0102030405060708));
+        assert_eq!(read_u64_le(&bytes, 3), None);
+    }
+
+    #[test]
+    fn test_keys_byte_order() {
+        let mut bytes = [0u8; KEYS_SIZE];
+        bytes[0] = 0x01;
+        bytes[15] = 0x80;
+        let keys = keys_from_le_bytes(&bytes);
+        assert_eq!(keys, [1, 0x8000000000000000, 0, 0]);
+        assert_eq!(keys_to_le_bytes(&keys), bytes);
+    }
+
+    #[test]
+    fn test_header_with_nonce() {
+        assert_eq!(header_with_nonce(&[0xaa], 0x0102), vec![0xaa, 0, 0, 0, 0, 0, 0, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_proof_packing() {
+        let nonces = [0x5u64, 0x3ff, 0x200];
+        let packed = pack_proof(&nonces, 10);
+        assert_eq!(packed, vec![0x05, 0xfc, 0x0f, 0x20]);
+        assert_eq!(unpack_proof(&packed, 10, 3).unwrap(), nonces);
+        assert!(unpack_proof(&packed[..3], 10, 3).is_err());
+    }
+}
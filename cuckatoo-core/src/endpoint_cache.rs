@@ -0,0 +1,168 @@
+//! Direct-mapped cache of SipHash endpoints
+//!
+//! Lean trimming recomputes the endpoints of every surviving edge in every
+//! round, and edge generation after trimming computes them once more. Once
+//! the survivors fit in a few MB, an `EndpointCache` in front of the hasher
+//! answers most of those repeated hashes from memory. Each slot holds one
+//! nonce and its unmasked hash; a nonce mapping to an occupied slot evicts
+//! the old entry.
+
+use crate::{checked_usize, CuckatooError, Result, SipHasher, SipKeys};
+use std::cell::Cell;
+
+/// Bytes per cache slot (nonce and hash)
+pub const ENDPOINT_CACHE_SLOT_BYTES: u64 = 16;
+
+/// Marks an empty slot; nonce `u64::MAX` bypasses the cache
+const EMPTY: u64 = u64::MAX;
+
+/// Hit and miss counts of an endpoint cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EndpointCacheStats {
+    /// Hashes answered from the cache
+    pub hits: u64,
+    /// Hashes computed by the wrapped hasher
+    pub misses: u64,
+}
+
+impl EndpointCacheStats {
+    /// Fraction of lookups answered from the cache (0 without lookups)
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// A `SipHasher` caching the hashes of another one
+///
+/// Not `Sync`: each thread needs its own cache.
+#[derive(Debug)]
+pub struct EndpointCache<H: SipHasher> {
+    hasher: H,
+    /// (nonce, hash) per slot, slot count a power of two
+    slots: Vec<Cell<(u64, u64)>>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl<H: SipHasher> EndpointCache<H> {
+    /// Cache the hashes of `hasher` in about `bytes` of memory
+    ///
+    /// The slot count is rounded down to a power of two; fails with a
+    /// `MemoryError` if not even one slot fits.
+    pub fn new(hasher: H, bytes: u64) -> Result<Self> {
+        let slots = bytes / ENDPOINT_CACHE_SLOT_BYTES;
+        if slots == 0 {
+            return Err(CuckatooError::MemoryError(format!(
+                "Endpoint cache of {} bytes is smaller than one slot ({} bytes)", bytes, ENDPOINT_CACHE_SLOT_BYTES
+            )));
+        }
+        let slots = checked_usize(1 << slots.ilog2(), "Endpoint cache slots")?;
+        Ok(Self {
+            hasher,
+            slots: vec![Cell::new((EMPTY, 0)); slots],
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        })
+    }
+
+    /// Switch to another hasher (for the next graph), keeping the memory
+    /// and clearing the entries and statistics
+    pub fn rebind(&mut self, hasher: H) {
+        self.hasher = hasher;
+        self.slots.iter().for_each(|slot| slot.set((EMPTY, 0)));
+        self.reset_stats();
+    }
+
+    /// Wrapped hasher
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Memory used by the slots in bytes
+    pub fn size_bytes(&self) -> u64 {
+        self.slots.len() as u64 * ENDPOINT_CACHE_SLOT_BYTES
+    }
+
+    /// Hits and misses since the cache was created, rebound or reset
+    pub fn stats(&self) -> EndpointCacheStats {
+        EndpointCacheStats { hits: self.hits.get(), misses: self.misses.get() }
+    }
+
+    /// Reset the hit and miss counts
+    pub fn reset_stats(&self) {
+        self.hits.set(0);
+        self.misses.set(0);
+    }
+
+    /// Slot of a nonce; the two nonces of an edge land in neighboring slots
+    fn slot(&self, nonce: u64) -> &Cell<(u64, u64)> {
+        &self.slots[nonce as usize & (self.slots.len() - 1)]
+    }
+}
+
+impl<H: SipHasher> SipHasher for EndpointCache<H> {
+    fn hash_nonce(&self, nonce: u64) -> u64 {
+        if nonce == EMPTY {
+            return self.hasher.hash_nonce(nonce);
+        }
+        let slot = self.slot(nonce);
+        let (cached_nonce, hash) = slot.get();
+        if cached_nonce == nonce {
+            self.hits.set(self.hits.get() + 1);
+            return hash;
+        }
+
+        self.misses.set(self.misses.get() + 1);
+        let hash = self.hasher.hash_nonce(nonce);
+        slot.set((nonce, hash));
+        hash
+    }
+
+    fn keys(&self) -> SipKeys {
+        self.hasher.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitmapTrimmer, SipHash};
+
+    #[test]
+    fn test_cache_hits_and_matches_hasher() {
+        let siphash = SipHash::with_key(crate::self_test::SIPHASH_TEST_KEYS);
+        let cache = EndpointCache::new(SipHash::with_key(crate::self_test::SIPHASH_TEST_KEYS), 1000).unwrap();
+        // 1000 bytes round down to 32 slots
+        assert_eq!(cache.size_bytes(), 512);
+
+        assert_eq!(cache.hash_nonce(5), siphash.hash_nonce(5));
+        assert_eq!(cache.hash_nonce(5), siphash.hash_nonce(5));
+        // Nonce 37 evicts nonce 5 from slot 5
+        assert_eq!(cache.hash_pair(18, 12), siphash.hash_pair(18, 12));
+        assert_eq!(cache.hash_nonce(5), siphash.hash_nonce(5));
+        assert_eq!(cache.stats(), EndpointCacheStats { hits: 1, misses: 4 });
+        assert_eq!(cache.hash_nonce(u64::MAX), siphash.hash_nonce(u64::MAX));
+
+        assert!(EndpointCache::new(siphash, 8).is_err());
+    }
+
+    #[test]
+    fn test_trimming_through_cache() {
+        let keys = SipKeys::from_header(b"endpoint cache", 2);
+        let expected = BitmapTrimmer::new(12).trim_edges(&SipHash::with_key(keys), 20).unwrap();
+
+        // Room for every nonce of the graph
+        let mut cache = EndpointCache::new(SipHash::with_key(crate::self_test::SIPHASH_TEST_KEYS), 1 << 17).unwrap();
+        cache.rebind(SipHash::with_key(keys));
+        let edges = BitmapTrimmer::new(12).trim_edges(&cache, 20).unwrap();
+        assert_eq!(edges, expected);
+        // Steps two and four look up the endpoints steps one and three hashed
+        let stats = cache.stats();
+        assert!(stats.hits > 0 && stats.hit_rate() > 0.25, "{:?}", stats);
+    }
+}
@@ -0,0 +1,184 @@
+//! Energy efficiency estimates for mining runs
+//!
+//! Solutions per kWh and cost per solution are what operators compare modes
+//! and devices by. The power draw comes from a `PowerSource`: a fixed value
+//! supplied by the user, or telemetry such as the Linux RAPL energy counters.
+
+use std::path::PathBuf;
+
+/// Joules in a kilowatt-hour
+const JOULES_PER_KWH: f64 = 3.6e6;
+
+/// Default RAPL package energy counter on Linux
+pub const DEFAULT_RAPL_PATH: &str = "/sys/class/powercap/intel-rapl:0";
+
+/// Source of the power draw during a measured period
+pub trait PowerSource {
+    /// Mark the start of a measured period
+    fn start(&mut self) {}
+
+    /// Average power draw in watts over the `seconds` since `start`,
+    /// None if it can't be measured
+    fn average_watts(&mut self, seconds: f64) -> Option<f64>;
+}
+
+/// Power draw supplied by the user (for example from a wall meter)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedPower {
+    /// Power draw in watts
+    pub watts: f64,
+}
+
+impl FixedPower {
+    /// Create a fixed power source
+    pub fn new(watts: f64) -> Self {
+        Self { watts }
+    }
+}
+
+impl PowerSource for FixedPower {
+    fn average_watts(&mut self, _seconds: f64) -> Option<f64> {
+        Some(self.watts)
+    }
+}
+
+/// Power draw from a RAPL (Running Average Power Limit) energy counter
+///
+/// Reads `energy_uj` (and `max_energy_range_uj` for wrap-around) in a
+/// powercap directory. Only covers the measured package, not the whole
+/// machine.
+#[derive(Debug, Clone)]
+pub struct RaplPower {
+    /// Powercap directory of the counter
+    path: PathBuf,
+    /// Counter value at `start`
+    start_uj: Option<u64>,
+}
+
+impl RaplPower {
+    /// Create a RAPL power source reading the counter in `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), start_uj: None }
+    }
+
+    fn read_counter(&self, name: &str) -> Option<u64> {
+        std::fs::read_to_string(self.path.join(name)).ok()?.trim().parse().ok()
+    }
+}
+
+impl Default for RaplPower {
+    fn default() -> Self {
+        Self::new(DEFAULT_RAPL_PATH)
+    }
+}
+
+impl PowerSource for RaplPower {
+    fn start(&mut self) {
+        self.start_uj = self.read_counter("energy_uj");
+    }
+
+    fn average_watts(&mut self, seconds: f64) -> Option<f64> {
+        let start = self.start_uj?;
+        let end = self.read_counter("energy_uj")?;
+        let used_uj = if end >= start {
+            end - start
+        } else {
+            // The counter wrapped around
+            self.read_counter("max_energy_range_uj")?.checked_sub(start)? + end
+        };
+
+        (seconds > 0.0).then(|| used_uj as f64 / 1e6 / seconds)
+    }
+}
+
+/// Energy use of a mining run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyReport {
+    /// Average power draw in watts
+    pub watts: f64,
+    /// Duration of the run in seconds
+    pub seconds: f64,
+    /// Solutions found
+    pub solutions: u64,
+    /// Electricity price per kWh, if known
+    pub price_per_kwh: Option<f64>,
+}
+
+impl EnergyReport {
+    /// Measure a run with `source`, which must have been started at the
+    /// beginning of the run
+    ///
+    /// Returns None if the power source has no reading.
+    pub fn measure(
+        source: &mut dyn PowerSource,
+        seconds: f64,
+        solutions: u64,
+        price_per_kwh: Option<f64>,
+    ) -> Option<Self> {
+        let watts = source.average_watts(seconds)?;
+        Some(Self { watts, seconds, solutions, price_per_kwh })
+    }
+
+    /// Energy used in kWh
+    pub fn energy_kwh(&self) -> f64 {
+        self.watts * self.seconds / JOULES_PER_KWH
+    }
+
+    /// Solutions per kWh, 0 if no energy was used
+    pub fn solutions_per_kwh(&self) -> f64 {
+        let energy = self.energy_kwh();
+        if energy > 0.0 {
+            self.solutions as f64 / energy
+        } else {
+            0.0
+        }
+    }
+
+    /// Electricity cost of the run, if the price is known
+    pub fn cost(&self) -> Option<f64> {
+        self.price_per_kwh.map(|price| price * self.energy_kwh())
+    }
+
+    /// Electricity cost per solution, None without a price or solutions
+    pub fn cost_per_solution(&self) -> Option<f64> {
+        self.cost().filter(|_| self.solutions > 0).map(|cost| cost / self.solutions as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_energy_report() {
+        // 1 kW for an hour
+        let mut power = FixedPower::new(1000.0);
+        power.start();
+        let report = EnergyReport::measure(&mut power, 3600.0, 4, Some(0.25)).unwrap();
+        assert!((report.energy_kwh() - 1.0).abs() < 1e-9);
+        assert!((report.solutions_per_kwh() - 4.0).abs() < 1e-9);
+        assert!((report.cost_per_solution().unwrap() - 0.0625).abs() < 1e-9);
+
+        let unsolved = EnergyReport { solutions: 0, ..report };
+        assert_eq!(unsolved.solutions_per_kwh(), 0.0);
+        assert_eq!(unsolved.cost_per_solution(), None);
+    }
+
+    #[test]
+    fn test_rapl_counter() {
+        let directory = std::env::temp_dir().join(format!("cuckatoo-rapl-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("energy_uj"), "999000000\n").unwrap();
+        std::fs::write(directory.join("max_energy_range_uj"), "1000000000\n").unwrap();
+
+        let mut power = RaplPower::new(&directory);
+        power.start();
+        // 3 J used across the wrap-around in 2 seconds
+        std::fs::write(directory.join("energy_uj"), "2000000\n").unwrap();
+        assert_eq!(power.average_watts(2.0), Some(1.5));
+
+        // Missing counters give no reading
+        assert_eq!(RaplPower::new(directory.join("missing")).average_watts(1.0), None);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -3,76 +3,229 @@
 //! This implements the exact same trimming algorithm as the C++ OpenCL version,
 //! including the 4-step process and exact bit manipulation.
 
-use crate::{Edge, Result, ExactSipHash};
+use crate::bitmap_trimming::round_side;
+use crate::hashing::{for_each_word_node, SipHasher, SIPHASH_BLOCK_EDGES};
+use crate::cancel::check_cancelled;
+use crate::progress::{report_round, ProgressObserver};
+use crate::trim_output::edge_indices;
+use crate::{CancelToken, Config, Edge, Node, Result, AdaptiveRoundController, AdaptiveTrimResult, EarlyExit, TrimOutput, DEFAULT_CHUNK_WORDS};
+use std::time::Instant;
+
+/// Bytes of one buffered node
+const NODE_BYTES: u64 = 8;
 
 /// Exact bitmap trimmer matching C++ OpenCL implementation
 pub struct ExactTrimmer {
     /// Edge bits
-    _edge_bits: u32,
+    edge_bits: u32,
     /// Number of edges (2^edge_bits)
-    number_of_edges: u32,
-    /// Node mask (2^edge_bits - 1)
-    _node_mask: u32,
+    number_of_edges: u64,
     /// Edges bitmap (using 64-bit words like C++)
     edges_bitmap: Vec<u64>,
     /// Nodes bitmap (using 32-bit words like C++ OpenCL)
     nodes_bitmap: Vec<u32>,
+    /// Nodes hashed by step one or three, in edge order, so steps two and
+    /// four don't hash them again
+    buffered_nodes: Vec<u64>,
+    /// Most nodes `buffered_nodes` holds (0 rehashes every edge)
+    node_buffer_capacity: usize,
+    /// Conditions ending fixed-round trims early
+    early_exit: EarlyExit,
+    /// Rounds the last trim ran
+    rounds_run: u32,
+    /// Receiver of per-round progress
+    progress: Option<Box<dyn ProgressObserver>>,
+    /// Token stopping trims between chunks of words
+    cancel: Option<CancelToken>,
 }
 
 impl ExactTrimmer {
     /// Create new exact trimmer
     pub fn new(edge_bits: u32) -> Self {
-        let number_of_edges = 1 << edge_bits;
-        let node_mask = number_of_edges - 1;
+        let number_of_edges: u64 = 1 << edge_bits;
         
         // Calculate bitmap sizes
         // Edges bitmap: 64 bits per u64 word
-        let edges_bitmap_size = ((number_of_edges + 63) / 64) as usize;
+        let edges_bitmap_size = number_of_edges.div_ceil(64) as usize;
         // Nodes bitmap: 32 bits per u32 word (like C++ OpenCL)
-        let nodes_bitmap_size = ((number_of_edges + 31) / 32) as usize;
+        let nodes_bitmap_size = number_of_edges.div_ceil(32) as usize;
         
         Self {
-            _edge_bits: edge_bits,
+            edge_bits,
             number_of_edges,
-            _node_mask: node_mask,
             edges_bitmap: vec![0; edges_bitmap_size],
             nodes_bitmap: vec![0; nodes_bitmap_size],
+            buffered_nodes: Vec::new(),
+            node_buffer_capacity: 0,
+            early_exit: EarlyExit::default(),
+            rounds_run: 0,
+            progress: None,
+            cancel: None,
         }
     }
     
+    /// Create a trimmer for the configured graph size and node buffer
+    pub fn from_config(config: &Config) -> Self {
+        let trimmer = Self::new(config.edge_bits);
+        match config.node_buffer {
+            Some(bytes) => trimmer.with_node_buffer(bytes),
+            None => trimmer,
+        }
+    }
+    
+    /// Keep the nodes hashed by steps one and three in about `bytes` of
+    /// memory, so steps two and four look them up instead of hashing every
+    /// surviving edge again
+    /// 
+    /// This halves the SipHash work of a round. Edges past the end of the
+    /// buffer, once it is full, are hashed again as without it, so any size
+    /// is safe; 8 bytes per edge of the graph covers every round.
+    pub fn with_node_buffer(mut self, bytes: u64) -> Self {
+        self.node_buffer_capacity = usize::try_from(bytes / NODE_BYTES).unwrap_or(usize::MAX);
+        self
+    }
+    
+    /// End fixed-round trims early once any of the `early_exit` conditions
+    /// is met
+    pub fn with_early_exit(mut self, early_exit: EarlyExit) -> Self {
+        self.early_exit = early_exit;
+        self
+    }
+    
+    /// Number of rounds the last trim ran
+    pub fn rounds_run(&self) -> u32 {
+        self.rounds_run
+    }
+    
+    /// Report every finished round to `observer`
+    pub fn with_progress(mut self, observer: impl ProgressObserver + 'static) -> Self {
+        self.progress = Some(Box::new(observer));
+        self
+    }
+    
+    /// Stop trimming with `Cancelled` soon after `token` is cancelled
+    /// 
+    /// Steps check the token every `DEFAULT_CHUNK_WORDS` edges bitmap words.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+    
     /// Perform exact trimming matching C++ implementation
-    pub fn trim_edges(&mut self, siphash: &ExactSipHash, trimming_rounds: u32) -> Result<Vec<Edge>> {
+    pub fn trim_edges<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<Vec<Edge>> {
+        self.run_rounds(siphash, trimming_rounds, None)?;
+        
+        // Generate final edges from surviving bits
+        self.generate_final_edges(siphash)
+    }
+    
+    /// Perform exact trimming like `trim_edges`, returning the indices of
+    /// the surviving edges with the trim's statistics
+    pub fn trim<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32) -> Result<TrimOutput> {
+        let start_time = Instant::now();
+        let mut per_round_survivors = Vec::new();
+        self.run_rounds(siphash, trimming_rounds, Some(&mut per_round_survivors))?;
+        Ok(TrimOutput {
+            surviving_edge_indices: edge_indices(&self.edges_bitmap),
+            rounds_run: self.rounds_run,
+            per_round_survivors,
+            elapsed: start_time.elapsed(),
+        })
+    }
+    
+    /// Fill the edges bitmap and run up to `trimming_rounds` rounds on it,
+    /// pushing the survivors of each round to `curve` if given
+    fn run_rounds<H: SipHasher>(&mut self, siphash: &H, trimming_rounds: u32, mut curve: Option<&mut Vec<u64>>) -> Result<()> {
+        let start_time = Instant::now();
+        
         // Initialize edges bitmap with all edges present
         self.initialize_edges_bitmap();
         
         // Perform trimming rounds (exactly like C++ comment lines 3-11)
+        let mut survivors = self.number_of_edges;
+        self.rounds_run = 0;
         for round in 0..trimming_rounds {
-            if round == 0 {
-                // Trimming round 1: clear nodes bitmap, step one, step two
-                self.clear_nodes_bitmap();
-                self.trim_edges_step_one(siphash)?;
-                self.trim_edges_step_two(siphash)?;
-            } else {
-                // Trimming round 2+: clear nodes bitmap, step three, step four
-                self.clear_nodes_bitmap();
-                self.trim_edges_step_three(siphash)?;
-                self.trim_edges_step_four(siphash)?;
+            self.trim_round(siphash, round)?;
+            self.rounds_run = round + 1;
+            if self.early_exit.is_enabled() || self.progress.is_some() || curve.is_some() {
+                let previous = std::mem::replace(&mut survivors, self.surviving_edges_count());
+                if let Some(curve) = curve.as_deref_mut() {
+                    curve.push(survivors);
+                }
+                report_round(&mut self.progress, self.rounds_run, survivors, start_time)?;
+                if self.early_exit.should_stop(self.rounds_run, previous, survivors) {
+                    break;
+                }
             }
         }
+        Ok(())
+    }
+    
+    /// Perform exact trimming with the number of rounds chosen by the controller
+    pub fn trim_edges_adaptive<H: SipHasher>(
+        &mut self,
+        siphash: &H,
+        controller: &mut AdaptiveRoundController,
+    ) -> Result<AdaptiveTrimResult> {
+        let start_time = Instant::now();
+        self.initialize_edges_bitmap();
         
-        // Generate final edges from surviving bits
-        self.generate_final_edges(siphash)
+        controller.start(self.surviving_edges_count());
+        let mut round = 0;
+        while controller.should_continue() {
+            self.trim_round(siphash, round)?;
+            round += 1;
+            
+            let survivors = self.surviving_edges_count();
+            report_round(&mut self.progress, round, survivors, start_time)?;
+            if !controller.record_round(survivors) {
+                break;
+            }
+        }
+        
+        self.rounds_run = controller.rounds_run();
+        Ok(AdaptiveTrimResult {
+            edges: self.generate_final_edges(siphash)?,
+            rounds: controller.rounds_run(),
+        })
+    }
+    
+    /// Get the number of edges currently surviving in the edges bitmap
+    pub fn surviving_edges_count(&self) -> u64 {
+        self.edges_bitmap.iter().map(|&x| x.count_ones() as u64).sum()
+    }
+    
+    /// Perform a single trimming round
+    fn trim_round<H: SipHasher>(&mut self, siphash: &H, round: u32) -> Result<()> {
+        crate::profile_scope!(crate::profiling::TRIM_ROUND, round = round + 1);
+        
+        if round == 0 {
+            // Trimming round 1: clear nodes bitmap, step one, step two
+            self.clear_nodes_bitmap();
+            self.trim_edges_step_one(siphash)?;
+            self.trim_edges_step_two(siphash)
+        } else {
+            // Trimming round 2+: clear nodes bitmap, step three, step four,
+            // on the V endpoints in odd rounds and the U endpoints in even ones
+            let uorv = round_side(round);
+            self.clear_nodes_bitmap();
+            self.trim_edges_step_three(siphash, uorv)?;
+            self.trim_edges_step_four(siphash, uorv)
+        }
     }
     
     /// Initialize edges bitmap with all edges present
     fn initialize_edges_bitmap(&mut self) {
+        // A cancelled trim may have left nodes in the buffer
+        self.buffered_nodes.clear();
+        
         // Set all bits in edges bitmap
         for i in 0..self.edges_bitmap.len() {
             self.edges_bitmap[i] = u64::MAX;
         }
         
         // Clear any excess bits beyond number_of_edges
-        let excess_bits = (self.edges_bitmap.len() * 64) as u32 - self.number_of_edges;
+        let excess_bits = (self.edges_bitmap.len() * 64) as u64 - self.number_of_edges;
         if excess_bits > 0 {
             let last_index = self.edges_bitmap.len() - 1;
             let mask = (1u64 << (64 - excess_bits)) - 1;
@@ -85,140 +238,173 @@ impl ExactTrimmer {
         self.nodes_bitmap.fill(0);
     }
     
+    /// Fail with `Cancelled` at the start of every chunk of words if the
+    /// cancel token was cancelled
+    fn check_cancelled(&self, word_index: usize) -> Result<()> {
+        match word_index % DEFAULT_CHUNK_WORDS {
+            0 => check_cancelled(self.cancel.as_ref()),
+            _ => Ok(()),
+        }
+    }
+    
     /// Trim edges step one (exactly matching C++ OpenCL trimEdgesStepOne)
-    fn trim_edges_step_one(&mut self, siphash: &ExactSipHash) -> Result<()> {
-        // Go through all edges (like C++ work items)
-        for edge_index in 0..self.number_of_edges {
-            // Get edge's node using SipHash (exactly like C++ line 103)
-            let node = siphash.hash_nonce((edge_index as u64) * 2);
+    fn trim_edges_step_one<H: SipHasher>(&mut self, siphash: &H) -> Result<()> {
+        // Go through all edges (like C++ work items), a word's worth at a time
+        for word_index in 0..self.edges_bitmap.len() {
+            self.check_cancelled(word_index)?;
+            let word = self.all_edges_word(word_index);
             
-            // Enable node in nodes bitmap (exactly like C++ line 106)
-            self.set_bit_in_nodes_bitmap(node.value() as u32);
+            // Get edge's node using SipHash (exactly like C++ line 103)
+            for_each_word_node(siphash, self.edge_bits, word_index, word, 0, |_, node| {
+                // Enable node in nodes bitmap (exactly like C++ line 106)
+                self.set_bit_in_nodes_bitmap(node);
+                self.buffer_node(node);
+            });
         }
         
         Ok(())
     }
     
     /// Trim edges step two (exactly matching C++ OpenCL trimEdgesStepTwo)
-    fn trim_edges_step_two(&mut self, siphash: &ExactSipHash) -> Result<()> {
+    fn trim_edges_step_two<H: SipHasher>(&mut self, siphash: &H) -> Result<()> {
         // Go through all edges bitmap words (like C++ work groups)
+        let mut buffered = 0;
         for word_index in 0..self.edges_bitmap.len() {
+            self.check_cancelled(word_index)?;
             let mut new_edges = 0u64;
             let word = self.edges_bitmap[word_index];
             
-            // Go through all bits in the word (like C++ work items)
-            for bit_index in 0..64 {
-                if (word & (1u64 << bit_index)) != 0 {
-                    let edge_index = (word_index * 64 + bit_index) as u32;
-                    
-                    if edge_index < self.number_of_edges {
-                        // Get edge's node using SipHash (exactly like C++ line 129)
-                        let node = siphash.hash_nonce((edge_index as u64) * 2);
-                        
-                        // Check if node has a pair in the nodes bitmap (exactly like C++ line 132)
-                        if self.is_bit_set_in_nodes_bitmap((node.value() as u32) ^ 1) {
-                            // Enable edge (exactly like C++ line 135)
-                            new_edges |= 1u64 << bit_index;
-                        }
-                    }
+            // Get each enabled edge's node using SipHash (exactly like C++ line 129)
+            self.for_each_edge_node(siphash, word_index, word, 0, &mut buffered, |bit_index, node| {
+                // Check if node has a pair in the nodes bitmap (exactly like C++ line 132)
+                if self.is_bit_set_in_nodes_bitmap(node ^ 1) {
+                    // Enable edge (exactly like C++ line 135)
+                    new_edges |= 1u64 << bit_index;
                 }
-            }
+            });
             
             self.edges_bitmap[word_index] = new_edges;
         }
         
+        self.buffered_nodes.clear();
         Ok(())
     }
     
     /// Trim edges step three (exactly matching C++ OpenCL trimEdgesStepThree)
-    fn trim_edges_step_three(&mut self, siphash: &ExactSipHash) -> Result<()> {
+    /// on the endpoints `uorv`
+    fn trim_edges_step_three<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
         // Go through all edges bitmap words
         for word_index in 0..self.edges_bitmap.len() {
+            self.check_cancelled(word_index)?;
             let word = self.edges_bitmap[word_index];
             
-            // Go through all enabled edges in the word
-            for bit_index in 0..64 {
-                if (word & (1u64 << bit_index)) != 0 {
-                    let edge_index = (word_index * 64 + bit_index) as u32;
-                    
-                    if edge_index < self.number_of_edges {
-                        // Get edge's node using SipHash (exactly like C++ line 162)
-                        // Note: C++ uses nodesInSecondPartition = 1 for step three
-                        let node = siphash.hash_nonce(((edge_index as u64) * 2) | 1);
-                        
-                        // Enable node in nodes bitmap (exactly like C++ line 165)
-                        self.set_bit_in_nodes_bitmap(node.value() as u32);
-                    }
-                }
-            }
+            // Get each enabled edge's node using SipHash (exactly like C++ line 162)
+            // Note: C++ passes the round's partition as nodesInSecondPartition
+            for_each_word_node(siphash, self.edge_bits, word_index, word, uorv, |_, node| {
+                // Enable node in nodes bitmap (exactly like C++ line 165)
+                self.set_bit_in_nodes_bitmap(node);
+                self.buffer_node(node);
+            });
         }
         
         Ok(())
     }
     
     /// Trim edges step four (exactly matching C++ OpenCL trimEdgesStepFour)
-    fn trim_edges_step_four(&mut self, siphash: &ExactSipHash) -> Result<()> {
+    /// on the endpoints `uorv`
+    fn trim_edges_step_four<H: SipHasher>(&mut self, siphash: &H, uorv: u64) -> Result<()> {
         // Go through all edges bitmap words
+        let mut buffered = 0;
         for word_index in 0..self.edges_bitmap.len() {
+            self.check_cancelled(word_index)?;
             let mut new_edges = self.edges_bitmap[word_index];
             let word = self.edges_bitmap[word_index];
             
-            // Go through all enabled edges in the word
-            for bit_index in 0..64 {
-                if (word & (1u64 << bit_index)) != 0 {
-                    let edge_index = (word_index * 64 + bit_index) as u32;
-                    
-                    if edge_index < self.number_of_edges {
-                        // Get edge's node using SipHash (exactly like C++ line 189)
-                        // Note: C++ uses nodesInSecondPartition = 1 for step four
-                        let node = siphash.hash_nonce(((edge_index as u64) * 2) | 1);
-                        
-                        // Check if node doesn't have a pair in the nodes bitmap (exactly like C++ line 192)
-                        if !self.is_bit_set_in_nodes_bitmap((node.value() as u32) ^ 1) {
-                            // Disable edge (exactly like C++ line 195)
-                            new_edges ^= 1u64 << bit_index;
-                        }
-                    }
+            // Get each enabled edge's node using SipHash (exactly like C++ line 189)
+            // Note: C++ passes the round's partition as nodesInSecondPartition
+            self.for_each_edge_node(siphash, word_index, word, uorv, &mut buffered, |bit_index, node| {
+                // Check if node doesn't have a pair in the nodes bitmap (exactly like C++ line 192)
+                if !self.is_bit_set_in_nodes_bitmap(node ^ 1) {
+                    // Disable edge (exactly like C++ line 195)
+                    new_edges ^= 1u64 << bit_index;
                 }
-            }
+            });
             
             self.edges_bitmap[word_index] = new_edges;
         }
         
+        self.buffered_nodes.clear();
         Ok(())
     }
     
+    /// Buffer a node hashed by step one or three, if there is room
+    fn buffer_node(&mut self, node: u64) {
+        if self.buffered_nodes.len() < self.node_buffer_capacity {
+            self.buffered_nodes.push(node);
+        }
+    }
+    
+    /// Call `visit(bit, node)` for every edge of a word like
+    /// `for_each_word_node`, taking the nodes from the buffer while it
+    /// holds the whole word
+    /// 
+    /// `buffered` counts the edges of the previous words, which is where
+    /// this word's nodes start in the buffer.
+    fn for_each_edge_node<H: SipHasher>(
+        &self,
+        siphash: &H,
+        word_index: usize,
+        word: u64,
+        uorv: u64,
+        buffered: &mut usize,
+        mut visit: impl FnMut(usize, u64),
+    ) {
+        let first = *buffered;
+        *buffered += word.count_ones() as usize;
+        match self.buffered_nodes.get(first..*buffered) {
+            Some(nodes) => {
+                let mut unit = word;
+                for &node in nodes {
+                    visit(unit.trailing_zeros() as usize, node);
+                    unit &= unit - 1;
+                }
+            },
+            None => for_each_word_node(siphash, self.edge_bits, word_index, word, uorv, visit),
+        }
+    }
+    
     /// Generate final edges from surviving bits
-    fn generate_final_edges(&self, siphash: &ExactSipHash) -> Result<Vec<Edge>> {
+    fn generate_final_edges<H: SipHasher>(&self, siphash: &H) -> Result<Vec<Edge>> {
+        crate::profile_scope!(crate::profiling::EDGE_GEN);
         let mut edges = Vec::new();
         
-        // Go through all surviving edges in the edges bitmap
-        for word_index in 0..self.edges_bitmap.len() {
-            let word = self.edges_bitmap[word_index];
-            
-            // Go through all enabled edges in the word
-            for bit_index in 0..64 {
-                if (word & (1u64 << bit_index)) != 0 {
-                    let edge_index = (word_index * 64 + bit_index) as u32;
-                    
-                    if edge_index < self.number_of_edges {
-                        // Generate edge's nodes using SipHash (exactly like C++ edge generation)
-                        let u = siphash.hash_nonce((edge_index as u64) * 2);
-                        let v = siphash.hash_nonce((edge_index as u64) * 2 + 1);
-                        
-                        // Create edge (preserve order like C++)
-                        let edge = Edge::new(u, v);
-                        edges.push(edge);
-                    }
-                }
-            }
+        // Go through all surviving edges in the edges bitmap, hashing both
+        // nodes of a word's edges (exactly like C++ edge generation)
+        let mut first_nodes = [0u64; SIPHASH_BLOCK_EDGES];
+        for (word_index, &word) in self.edges_bitmap.iter().enumerate() {
+            for_each_word_node(siphash, self.edge_bits, word_index, word, 0, |bit_index, node| {
+                first_nodes[bit_index] = node;
+            });
+            for_each_word_node(siphash, self.edge_bits, word_index, word, 1, |bit_index, node| {
+                edges.push(Edge::new(Node::new(first_nodes[bit_index]), Node::new(node)));
+            });
         }
         
         Ok(edges)
     }
     
+    /// Every edge of a word of the edges bitmap, up to `number_of_edges`
+    fn all_edges_word(&self, word_index: usize) -> u64 {
+        let remaining = self.number_of_edges - (word_index * 64) as u64;
+        if remaining >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << remaining) - 1
+        }
+    }
+    
     /// Set bit in nodes bitmap (exactly matching C++ OpenCL setBitInBitmap)
-    fn set_bit_in_nodes_bitmap(&mut self, index: u32) {
+    fn set_bit_in_nodes_bitmap(&mut self, index: u64) {
         let word_index = (index / 32) as usize;
         let bit_index = (index % 32) as u8;
         if word_index < self.nodes_bitmap.len() {
@@ -227,7 +413,7 @@ impl ExactTrimmer {
     }
     
     /// Check if bit is set in nodes bitmap (exactly matching C++ OpenCL isBitSetInBitmap)
-    fn is_bit_set_in_nodes_bitmap(&self, index: u32) -> bool {
+    fn is_bit_set_in_nodes_bitmap(&self, index: u64) -> bool {
         let word_index = (index / 32) as usize;
         let bit_index = (index % 32) as u8;
         if word_index < self.nodes_bitmap.len() {
@@ -244,8 +430,8 @@ mod tests {
 
     #[test]
     fn test_exact_trimmer_basic() {
-        let keys = [0x1234567890abcdef, 0xfedcba0987654321, 0x1111222233334444, 0x5555666677778888];
-        let siphash = ExactSipHash::new(keys, 8);
+        let keys = crate::SipKeys::test_seed([0x1234567890abcdef, 0xfedcba0987654321, 0x1111222233334444, 0x5555666677778888]);
+        let siphash = crate::ExactSipHash::new(keys, 8);
         let mut trimmer = ExactTrimmer::new(8);
         
         // Test basic trimming
@@ -257,6 +443,45 @@ mod tests {
         assert!(edges.len() < 256); // Should be trimmed down from 256
     }
     
+    #[test]
+    fn test_trimming_matches_oracle() {
+        let siphash = crate::ExactSipHash::new(crate::SipKeys::from_header(b"oracle", 7), 10);
+        crate::trim_oracle::assert_matches_oracle(&siphash, 10, 6, |rounds| {
+            ExactTrimmer::new(10).trim_edges(&siphash, rounds).unwrap()
+        });
+    }
+    
+    #[test]
+    fn test_node_buffer_halves_hashing() {
+        struct Counting(crate::ExactSipHash, std::cell::Cell<u64>);
+        impl SipHasher for Counting {
+            fn hash_nonce(&self, nonce: u64) -> u64 {
+                self.1.set(self.1.get() + 1);
+                self.0.hash_nonce(nonce)
+            }
+            fn keys(&self) -> crate::SipKeys {
+                self.0.keys()
+            }
+        }
+        let keys = crate::SipKeys::from_header(b"buffer", 2);
+        let hashes = |trimmer: &mut ExactTrimmer| {
+            let counting = Counting(crate::ExactSipHash::new(keys, 10), Default::default());
+            (trimmer.trim_edges(&counting, 8).unwrap(), counting.1.get())
+        };
+        
+        let (expected, plain_hashes) = hashes(&mut ExactTrimmer::new(10));
+        let mut config = Config::new(10);
+        config.node_buffer = Some(1 << 13);
+        let (edges, buffered_hashes) = hashes(&mut ExactTrimmer::from_config(&config));
+        assert_eq!(edges, expected);
+        assert!(buffered_hashes < plain_hashes * 6 / 10, "{} vs {}", buffered_hashes, plain_hashes);
+        
+        // A buffer too small for a round falls back to hashing
+        let (edges, partial_hashes) = hashes(&mut ExactTrimmer::new(10).with_node_buffer(1000));
+        assert_eq!(edges, expected);
+        assert!(partial_hashes > buffered_hashes && partial_hashes < plain_hashes);
+    }
+    
     #[test]
     fn test_bitmap_operations() {
         let mut trimmer = ExactTrimmer::new(8);
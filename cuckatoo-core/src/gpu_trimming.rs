@@ -0,0 +1,335 @@
+//! OpenCL lean trimming
+//!
+//! `GpuLeanTrimmer` runs the C++ reference's four lean trimming steps as
+//! OpenCL kernels (`trimEdgesStepOne` to `trimEdgesStepFour`). The edges
+//! and nodes bitmaps stay on the device between rounds, as 32-bit words so
+//! marking nodes only needs the core `atomic_or`; the host uploads the
+//! initial edges bitmap and reads back the surviving edge indices (after
+//! every round too, with early exit conditions or a progress observer).
+
+use crate::{checked_usize, CancelToken, CuckatooError, EarlyExit, Result, SipKeys};
+use crate::bitmap_trimming::round_side;
+use crate::cancel::check_cancelled;
+use crate::hashing::node_mask;
+use crate::progress::{report_round, ProgressObserver};
+use opencl3::command_queue::CommandQueue;
+use opencl3::context::Context;
+use opencl3::device::{Device, CL_DEVICE_TYPE_ALL};
+use opencl3::kernel::{ExecuteKernel, Kernel};
+use opencl3::memory::{Buffer, CL_MEM_READ_WRITE};
+use opencl3::platform::get_platforms;
+use opencl3::program::Program;
+use opencl3::types::{cl_device_id, cl_uint, cl_ulong, CL_BLOCKING};
+use std::ptr;
+use std::time::Instant;
+
+/// Edges per bitmap word on the device
+const WORD_EDGES: u64 = 32;
+
+/// Lean trimming kernels, one work item per edges bitmap word
+///
+/// SipHash-2-4 matches `hashing::siphash24`, including the C++ rotation of
+/// 21 in the second half of a round.
+const KERNELS: &str = r#"
+#define SIP_ROUND \
+    v0 += v1; v2 += v3; v1 = rotate(v1, 13UL); v3 = rotate(v3, 16UL); \
+    v1 ^= v0; v3 ^= v2; v0 = rotate(v0, 32UL); \
+    v2 += v1; v0 += v3; v1 = rotate(v1, 17UL); v3 = rotate(v3, 21UL); \
+    v1 ^= v2; v3 ^= v0; v2 = rotate(v2, 32UL);
+
+ulong sip_node(ulong k0, ulong k1, ulong k2, ulong k3, ulong nonce, ulong node_mask) {
+    ulong v0 = k0, v1 = k1, v2 = k2, v3 = k3 ^ nonce;
+    SIP_ROUND SIP_ROUND
+    v0 ^= nonce;
+    v2 ^= 255;
+    SIP_ROUND SIP_ROUND SIP_ROUND SIP_ROUND
+    return (v0 ^ v1 ^ v2 ^ v3) & node_mask;
+}
+
+/* Set the node bit of endpoint uorv of every live edge of the word */
+void mark_nodes(global const uint *edges, global uint *nodes,
+                ulong k0, ulong k1, ulong k2, ulong k3, ulong node_mask, ulong uorv) {
+    size_t word = get_global_id(0);
+    uint unit = edges[word];
+    while (unit != 0) {
+        uint bit = 31 - clz(unit & (0u - unit));
+        ulong node = sip_node(k0, k1, k2, k3, (word * 32 + bit) * 2 + uorv, node_mask);
+        atomic_or(&nodes[node >> 5], 1u << (node & 31));
+        unit &= unit - 1;
+    }
+}
+
+/* Keep the edges of the word whose endpoint uorv has its partner node set */
+void keep_paired_edges(global uint *edges, global const uint *nodes,
+                       ulong k0, ulong k1, ulong k2, ulong k3, ulong node_mask, ulong uorv) {
+    size_t word = get_global_id(0);
+    uint unit = edges[word];
+    uint kept = 0;
+    while (unit != 0) {
+        uint bit = 31 - clz(unit & (0u - unit));
+        ulong partner = sip_node(k0, k1, k2, k3, (word * 32 + bit) * 2 + uorv, node_mask) ^ 1;
+        if ((nodes[partner >> 5] >> (partner & 31)) & 1) {
+            kept |= 1u << bit;
+        }
+        unit &= unit - 1;
+    }
+    edges[word] = kept;
+}
+
+kernel void trimEdgesStepOne(global const uint *edges, global uint *nodes,
+                             ulong k0, ulong k1, ulong k2, ulong k3, ulong node_mask) {
+    mark_nodes(edges, nodes, k0, k1, k2, k3, node_mask, 0);
+}
+
+kernel void trimEdgesStepTwo(global uint *edges, global const uint *nodes,
+                             ulong k0, ulong k1, ulong k2, ulong k3, ulong node_mask) {
+    keep_paired_edges(edges, nodes, k0, k1, k2, k3, node_mask, 0);
+}
+
+kernel void trimEdgesStepThree(global const uint *edges, global uint *nodes,
+                               ulong k0, ulong k1, ulong k2, ulong k3, ulong node_mask) {
+    mark_nodes(edges, nodes, k0, k1, k2, k3, node_mask, 1);
+}
+
+kernel void trimEdgesStepFour(global uint *edges, global const uint *nodes,
+                              ulong k0, ulong k1, ulong k2, ulong k3, ulong node_mask) {
+    keep_paired_edges(edges, nodes, k0, k1, k2, k3, node_mask, 1);
+}
+"#;
+
+/// Lean trimmer running on an OpenCL device
+///
+/// Fields are dropped in order, so the kernels and buffers are released
+/// before the program, queue and context they belong to.
+pub struct GpuLeanTrimmer {
+    edge_bits: u32,
+    device_name: String,
+    /// Words of the edges and of the nodes bitmap
+    words: usize,
+    steps: [Kernel; 4],
+    edges_bitmap: Buffer<cl_uint>,
+    nodes_bitmap: Buffer<cl_uint>,
+    early_exit: EarlyExit,
+    rounds_run: u32,
+    progress: Option<Box<dyn ProgressObserver>>,
+    cancel: Option<CancelToken>,
+    _program: Program,
+    queue: CommandQueue,
+    _context: Context,
+}
+
+impl GpuLeanTrimmer {
+    /// Names of the OpenCL devices of every platform, in the order
+    /// `new` indexes them
+    pub fn devices() -> Result<Vec<String>> {
+        all_devices()?
+            .into_iter()
+            .map(|id| Device::new(id).name().map_err(device_error))
+            .collect()
+    }
+
+    /// Create a trimmer for `edge_bits` graphs on the device at `device_index`
+    /// (see `devices`), building the kernels and allocating both bitmaps
+    pub fn new(edge_bits: u32, device_index: usize) -> Result<Self> {
+        if edge_bits >= u64::BITS {
+            return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let words = checked_usize((1u64 << edge_bits).div_ceil(WORD_EDGES), "GPU bitmap size")?;
+
+        let devices = all_devices()?;
+        let id = *devices.get(device_index).ok_or_else(|| {
+            CuckatooError::DeviceError(format!("No OpenCL device {} ({} found)", device_index, devices.len()))
+        })?;
+        let device = Device::new(id);
+        let device_name = device.name().map_err(device_error)?;
+        let context = Context::from_device(&device).map_err(device_error)?;
+        let queue = CommandQueue::create(&context, id, 0).map_err(device_error)?;
+        let program = Program::create_and_build_from_source(&context, KERNELS, "")
+            .map_err(|log| CuckatooError::DeviceError(format!("Building the trimming kernels failed: {}", log)))?;
+        let kernel = |name: &str| Kernel::create(&program, name).map_err(device_error);
+        let steps = [
+            kernel("trimEdgesStepOne")?,
+            kernel("trimEdgesStepTwo")?,
+            kernel("trimEdgesStepThree")?,
+            kernel("trimEdgesStepFour")?,
+        ];
+        let buffer = || Buffer::<cl_uint>::create(&context, CL_MEM_READ_WRITE, words, ptr::null_mut()).map_err(device_error);
+
+        Ok(Self {
+            edge_bits,
+            device_name,
+            words,
+            steps,
+            edges_bitmap: buffer()?,
+            nodes_bitmap: buffer()?,
+            early_exit: EarlyExit::default(),
+            rounds_run: 0,
+            progress: None,
+            cancel: None,
+            _program: program,
+            queue,
+            _context: context,
+        })
+    }
+
+    /// End trims early once any of the `early_exit` conditions is met
+    ///
+    /// The surviving edges are counted on the host, so each round then
+    /// ends with a blocking read of the edges bitmap.
+    pub fn with_early_exit(mut self, early_exit: EarlyExit) -> Self {
+        self.early_exit = early_exit;
+        self
+    }
+
+    /// Stop trimming with `Cancelled` after the round running when `token`
+    /// is cancelled
+    ///
+    /// Kernels can't be interrupted, so with a token the host waits for
+    /// each round to finish before checking it.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Name of the device the trimmer runs on
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    /// Number of rounds the last trim ran
+    pub fn rounds_run(&self) -> u32 {
+        self.rounds_run
+    }
+
+    /// Report every finished round to `observer`
+    ///
+    /// Like early exit conditions, this ends each round with a blocking read of
+    /// the edges bitmap.
+    pub fn with_progress(mut self, observer: impl ProgressObserver + 'static) -> Self {
+        self.progress = Some(Box::new(observer));
+        self
+    }
+
+    /// Trim the graph of `keys` for `trimming_rounds` rounds and return the
+    /// indices of the surviving edges, in order
+    ///
+    /// Even rounds run steps one and two (U endpoints) and odd rounds steps
+    /// three and four (V endpoints), alternating sides like `BitmapTrimmer`.
+    pub fn trim(&mut self, keys: &SipKeys, trimming_rounds: u32) -> Result<Vec<u64>> {
+        let mut edges = vec![u32::MAX; self.words];
+        if self.edge_bits < WORD_EDGES.trailing_zeros() {
+            edges[0] = (1u32 << (1u32 << self.edge_bits)) - 1;
+        }
+        self.queue
+            .enqueue_write_buffer(&mut self.edges_bitmap, CL_BLOCKING, 0, &edges, &[])
+            .map_err(device_error)?;
+
+        let start_time = Instant::now();
+        let mut survivors = 1u64 << self.edge_bits;
+        self.rounds_run = 0;
+        for round in 0..trimming_rounds {
+            crate::profile_scope!(crate::profiling::TRIM_ROUND, round = round + 1);
+            // Steps one and two trim the U endpoints, three and four the V ones
+            let first_step = if round_side(round) == 0 { 0 } else { 2 };
+            self.queue
+                .enqueue_fill_buffer(&mut self.nodes_bitmap, &[0], 0, self.words * std::mem::size_of::<cl_uint>(), &[])
+                .map_err(device_error)?;
+            self.run_step(first_step, keys)?;
+            self.run_step(first_step + 1, keys)?;
+            self.rounds_run = round + 1;
+            if let Some(token) = &self.cancel {
+                self.queue.finish().map_err(device_error)?;
+                check_cancelled(Some(token))?;
+            }
+
+            if self.early_exit.is_enabled() || self.progress.is_some() {
+                self.queue
+                    .enqueue_read_buffer(&self.edges_bitmap, CL_BLOCKING, 0, &mut edges, &[])
+                    .map_err(device_error)?;
+                let previous = std::mem::replace(&mut survivors, edges.iter().map(|word| u64::from(word.count_ones())).sum());
+                report_round(&mut self.progress, self.rounds_run, survivors, start_time)?;
+                if self.early_exit.should_stop(self.rounds_run, previous, survivors) {
+                    break;
+                }
+            }
+        }
+
+        self.queue
+            .enqueue_read_buffer(&self.edges_bitmap, CL_BLOCKING, 0, &mut edges, &[])
+            .map_err(device_error)?;
+        Ok(surviving_indices(&edges))
+    }
+
+    /// Enqueue one trimming step over every edges bitmap word
+    ///
+    /// The queue is in order, so each step sees the previous one's bitmaps.
+    fn run_step(&self, step: usize, keys: &SipKeys) -> Result<()> {
+        let [k0, k1, k2, k3] = keys.words();
+        let node_mask: cl_ulong = node_mask(self.edge_bits);
+        ExecuteKernel::new(&self.steps[step])
+            .set_arg(&self.edges_bitmap)
+            .set_arg(&self.nodes_bitmap)
+            .set_arg(&k0)
+            .set_arg(&k1)
+            .set_arg(&k2)
+            .set_arg(&k3)
+            .set_arg(&node_mask)
+            .set_global_work_size(self.words)
+            .enqueue_nd_range(&self.queue)
+            .map_err(device_error)?;
+        Ok(())
+    }
+}
+
+/// Every device of every OpenCL platform
+fn all_devices() -> Result<Vec<cl_device_id>> {
+    let mut devices = Vec::new();
+    for platform in get_platforms().map_err(device_error)? {
+        devices.extend(platform.get_devices(CL_DEVICE_TYPE_ALL).map_err(device_error)?);
+    }
+    Ok(devices)
+}
+
+/// Indices of the set bits of a bitmap of 32-bit words
+fn surviving_indices(bitmap: &[u32]) -> Vec<u64> {
+    let mut indices = Vec::new();
+    for (word_index, &word) in bitmap.iter().enumerate() {
+        let mut unit = word;
+        while unit != 0 {
+            indices.push(word_index as u64 * WORD_EDGES + unit.trailing_zeros() as u64);
+            unit &= unit - 1;
+        }
+    }
+    indices
+}
+
+fn device_error(error: impl std::fmt::Display) -> CuckatooError {
+    CuckatooError::DeviceError(format!("OpenCL: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitmapTrimmer, SipHash, SipHasher};
+
+    #[test]
+    fn test_surviving_indices() {
+        assert_eq!(surviving_indices(&[0b101, 0, 1 << 31]), [0, 2, 95]);
+    }
+
+    #[test]
+    fn test_gpu_trimming_matches_cpu() {
+        // Machines without an OpenCL device only run the host-side test
+        if GpuLeanTrimmer::devices().map_or(true, |devices| devices.is_empty()) {
+            return;
+        }
+        let keys = SipKeys::from_header(b"gpu", 3);
+        let siphash = SipHash::with_key(keys);
+        let mut gpu = GpuLeanTrimmer::new(12, 0).unwrap();
+        for rounds in [1, 2, 7] {
+            let expected = BitmapTrimmer::new(12).trim_edges(&siphash, rounds).unwrap();
+            let survivors: Vec<_> = gpu.trim(&keys, rounds).unwrap().into_iter().map(|index| siphash.hash_pair(index, 12)).collect();
+            assert_eq!(survivors, expected, "{} rounds on {}", rounds, gpu.device_name());
+        }
+    }
+}
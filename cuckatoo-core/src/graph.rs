@@ -0,0 +1,166 @@
+//! Stored Cuckatoo graphs with endpoint spot checks
+//!
+//! A `Graph` holds every edge of a graph together with the keys it was
+//! generated from. Edges that came from somewhere other than the CPU
+//! hasher (a GPU, or a checkpoint file read back from disk) can be spot
+//! checked by recomputing their endpoints from the keys.
+
+use crate::nonce_scheduler::{NonceScheduler, RandomScheduler};
+use crate::{checked_usize, CuckatooError, Edge, Result, SipHash, SipKeys};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Edges recomputed per batch during an audit
+const AUDIT_BATCH_SIZE: usize = 4096;
+
+/// Result of auditing a sample of a graph's edges
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AuditReport {
+    /// Number of edges recomputed
+    pub checked: u64,
+    /// Indices of sampled edges whose stored endpoints are wrong
+    pub mismatches: Vec<u64>,
+}
+
+impl AuditReport {
+    /// Check if every sampled edge matched
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// All edges of a graph and the keys they were generated from
+///
+/// `edges()[i]` is the edge with index `i`.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    keys: SipKeys,
+    edge_bits: u32,
+    edges: Vec<Edge>,
+}
+
+impl Graph {
+    /// Generate the graph of `keys`
+    pub fn generate(keys: SipKeys, edge_bits: u32) -> Result<Self> {
+        let mut edges = Vec::with_capacity(checked_usize(edge_count(edge_bits)?, "Edge count")?);
+        edges.extend(SipHash::with_key(keys).edges(edge_bits));
+        Ok(Self { keys, edge_bits, edges })
+    }
+
+    /// Wrap edges produced elsewhere (a device or a checkpoint file)
+    ///
+    /// The edges aren't checked beyond their count; use `check_edge` or
+    /// `audit` for that.
+    pub fn from_edges(keys: SipKeys, edge_bits: u32, edges: Vec<Edge>) -> Result<Self> {
+        let expected = edge_count(edge_bits)?;
+        if edges.len() as u64 != expected {
+            return Err(CuckatooError::VerificationError(format!(
+                "graph with EDGE_BITS={} needs {} edges, got {}", edge_bits, expected, edges.len()
+            )));
+        }
+        Ok(Self { keys, edge_bits, edges })
+    }
+
+    /// Keys the graph was generated from
+    pub fn keys(&self) -> SipKeys {
+        self.keys
+    }
+
+    /// Edge bits of the graph
+    pub fn edge_bits(&self) -> u32 {
+        self.edge_bits
+    }
+
+    /// All edges, indexed by edge index
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Recompute an edge's endpoints from the keys and compare them with
+    /// the stored edge
+    ///
+    /// Fails with `VerificationError` on a mismatch and `InternalError` if
+    /// the index is out of range.
+    pub fn check_edge(&self, index: u64) -> Result<()> {
+        let stored = usize::try_from(index).ok().and_then(|i| self.edges.get(i)).ok_or_else(|| {
+            CuckatooError::InternalError(format!("Edge index {} out of range ({} edges)", index, self.edges.len()))
+        })?;
+        let expected = SipHash::with_key(self.keys).edges_for_indices(&[index], self.edge_bits)[0];
+        if *stored != expected {
+            return Err(CuckatooError::VerificationError(format!(
+                "edge {} is stored as ({}, {}) but hashes to ({}, {}) with keys {}",
+                index, stored.u.value(), stored.v.value(), expected.u.value(), expected.v.value(), self.keys
+            )));
+        }
+        Ok(())
+    }
+
+    /// Recompute a random sample of the edges, about `sample_rate` of them
+    /// (1.0 checks every edge)
+    pub fn audit(&self, sample_rate: f64) -> AuditReport {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        self.audit_seeded(sample_rate, seed)
+    }
+
+    /// Audit like `audit`, choosing the sample from `seed`
+    pub fn audit_seeded(&self, sample_rate: f64, seed: u64) -> AuditReport {
+        let threshold = if sample_rate >= 1.0 {
+            u64::MAX
+        } else {
+            (sample_rate.max(0.0) * u64::MAX as f64) as u64
+        };
+        let mut random = RandomScheduler::new(seed);
+        let sample: Vec<u64> = (0..self.edges.len() as u64)
+            .filter(|_| threshold == u64::MAX || random.next_nonce() < threshold)
+            .collect();
+
+        let siphash = SipHash::with_key(self.keys);
+        let mut report = AuditReport::default();
+        for batch in sample.chunks(AUDIT_BATCH_SIZE) {
+            let expected = siphash.edges_for_indices(batch, self.edge_bits);
+            report.mismatches.extend(
+                batch.iter().zip(&expected)
+                    .filter(|&(&index, edge)| self.edges[index as usize] != *edge)
+                    .map(|(&index, _)| index),
+            );
+        }
+        report.checked = sample.len() as u64;
+        report
+    }
+}
+
+/// Number of edges of a graph, rejecting unsupported edge bits
+fn edge_count(edge_bits: u32) -> Result<u64> {
+    if !(1..=63).contains(&edge_bits) {
+        return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+    }
+    Ok(1 << edge_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    #[test]
+    fn test_check_edge_and_audit() {
+        let keys = SipKeys::from_header(b"graph", 1);
+        let mut edges = Graph::generate(keys, 10).unwrap().edges().to_vec();
+        assert_eq!(edges.len(), 1024);
+        edges[77] = Edge::new(Node::new(1), Node::new(2));
+
+        let graph = Graph::from_edges(keys, 10, edges).unwrap();
+        assert!(graph.check_edge(76).is_ok());
+        assert!(matches!(graph.check_edge(77), Err(CuckatooError::VerificationError(_))));
+        assert!(matches!(graph.check_edge(1024), Err(CuckatooError::InternalError(_))));
+
+        let full = graph.audit(1.0);
+        assert_eq!(full.checked, 1024);
+        assert_eq!(full.mismatches, vec![77]);
+
+        let sample = graph.audit_seeded(0.1, 5);
+        assert!(sample.checked > 50 && sample.checked < 160, "checked {}", sample.checked);
+        assert_eq!(graph.audit_seeded(0.0, 5).checked, 0);
+
+        assert!(Graph::from_edges(keys, 10, Vec::new()).is_err());
+    }
+}
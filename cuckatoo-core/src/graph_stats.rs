@@ -0,0 +1,177 @@
+//! Structure of a (trimmed) graph
+//!
+//! `GraphStats::analyze` reports how the surviving edges hang together:
+//! how many connected components they form, how the node degrees are
+//! distributed and how much of the graph is left in its 2-core, the part
+//! where every edge has edges at the other node of both its endpoints'
+//! pairs. Only the 2-core can hold a cycle, so a small one explains why no
+//! cycles are found at an edge_bits and a large one left after many rounds
+//! suggests trimming more.
+//!
+//! Nodes are the pairs of the edges' endpoints (`2k` and `2k + 1`), U and
+//! V pairs kept apart, as a proof walks from an edge at one node of a pair
+//! to an edge at the other.
+
+use crate::union_find::DisjointSet;
+use crate::Edge;
+use std::collections::HashMap;
+
+/// Connectivity, degrees and 2-core of a set of edges
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphStats {
+    /// Number of edges
+    pub edges: u64,
+    /// Number of distinct U and V node pairs
+    pub nodes: u64,
+    /// Number of connected components
+    pub components: u64,
+    /// Node pairs in the largest component
+    pub largest_component: u64,
+    /// Number of node pairs of each degree (edges at either node),
+    /// indexed by degree
+    pub degree_histogram: Vec<u64>,
+    /// Edges left in the 2-core
+    pub two_core_edges: u64,
+}
+
+impl GraphStats {
+    /// Analyze the graph the edges form
+    pub fn analyze(edges: &[Edge]) -> Self {
+        let (nodes, endpoints) = dense_endpoints(edges);
+
+        let mut degrees = vec![0u32; nodes];
+        let mut forest = DisjointSet::new(nodes);
+        for &(u, v) in &endpoints {
+            degrees[u as usize >> 1] += 1;
+            degrees[v as usize >> 1] += 1;
+            forest.union(u >> 1, v >> 1);
+        }
+
+        let mut degree_histogram = Vec::new();
+        for &degree in &degrees {
+            if degree_histogram.len() <= degree as usize {
+                degree_histogram.resize(degree as usize + 1, 0);
+            }
+            degree_histogram[degree as usize] += 1;
+        }
+        let (mut components, mut largest_component) = (0, 0);
+        for node in 0..nodes as u32 {
+            if forest.find(node) == node {
+                components += 1;
+                largest_component = largest_component.max(u64::from(forest.size(node)));
+            }
+        }
+
+        Self {
+            edges: edges.len() as u64,
+            nodes: nodes as u64,
+            components,
+            largest_component,
+            degree_histogram,
+            two_core_edges: two_core(edges).len() as u64,
+        }
+    }
+
+    /// Format the statistics as a JSON object
+    pub fn to_json(&self) -> String {
+        let histogram: Vec<String> = self.degree_histogram.iter().map(u64::to_string).collect();
+        format!(
+            "{{\"edges\":{},\"nodes\":{},\"components\":{},\"largest_component\":{},\"degree_histogram\":[{}],\"two_core_edges\":{}}}",
+            self.edges, self.nodes, self.components, self.largest_component, histogram.join(","), self.two_core_edges
+        )
+    }
+}
+
+/// Dense slots of the edges' endpoints, U and V nodes kept apart, with
+/// the number of node pairs
+///
+/// A node's slot is the dense id of its pair (`node >> 1`) shifted left
+/// once, with the node's lowest bit: the pair is at `slot >> 1` and the
+/// other node of the pair at `slot ^ 1`.
+pub(crate) fn dense_endpoints(edges: &[Edge]) -> (usize, Vec<(u32, u32)>) {
+    let mut ids: HashMap<(bool, u64), u32> = HashMap::with_capacity(edges.len() * 2);
+    let mut endpoints = Vec::with_capacity(edges.len());
+    for edge in edges {
+        let mut slot = |side, node: u64| {
+            let next = ids.len() as u32;
+            *ids.entry((side, node >> 1)).or_insert(next) << 1 | (node & 1) as u32
+        };
+        endpoints.push((slot(false, edge.u.value()), slot(true, edge.v.value())));
+    }
+    (ids.len(), endpoints)
+}
+
+/// Indices (in order) of the edges in the 2-core, left after repeatedly
+/// dropping the edges with no edge at the other node of one of their
+/// endpoints' pairs
+pub(crate) fn two_core(edges: &[Edge]) -> Vec<usize> {
+    let (pairs, endpoints) = dense_endpoints(edges);
+    let slots = 2 * pairs;
+
+    // Edges at every slot, as offsets into one array
+    let mut degrees = vec![0u32; slots];
+    for &(u, v) in &endpoints {
+        degrees[u as usize] += 1;
+        degrees[v as usize] += 1;
+    }
+    let mut starts = vec![0usize; slots + 1];
+    for slot in 0..slots {
+        starts[slot + 1] = starts[slot] + degrees[slot] as usize;
+    }
+    let mut incident = vec![0u32; starts[slots]];
+    let mut filled = starts.clone();
+    for (edge, &(u, v)) in endpoints.iter().enumerate() {
+        for slot in [u, v] {
+            incident[filled[slot as usize]] = edge as u32;
+            filled[slot as usize] += 1;
+        }
+    }
+
+    // An empty slot leaves the edges at the other node of its pair dead
+    let mut alive = vec![true; edges.len()];
+    let mut empty: Vec<u32> = (0..slots as u32).filter(|&slot| degrees[slot as usize] == 0).collect();
+    while let Some(slot) = empty.pop() {
+        let partner = (slot ^ 1) as usize;
+        for &edge in &incident[starts[partner]..starts[partner + 1]] {
+            if !alive[edge as usize] {
+                continue;
+            }
+            alive[edge as usize] = false;
+            let (u, v) = endpoints[edge as usize];
+            for slot in [u, v] {
+                degrees[slot as usize] -= 1;
+                if degrees[slot as usize] == 0 {
+                    empty.push(slot);
+                }
+            }
+        }
+    }
+    (0..edges.len()).filter(|&edge| alive[edge]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    #[test]
+    fn test_graph_stats() {
+        let edge = |u, v| Edge::new(Node::new(u), Node::new(v));
+        // A 4-cycle with a two-edge tail, and a separate single edge
+        let edges = [edge(0, 0), edge(3, 1), edge(2, 2), edge(1, 3), edge(0, 5), edge(7, 4), edge(9, 9)];
+        let stats = GraphStats::analyze(&edges);
+        assert_eq!(stats.edges, 7);
+        assert_eq!(stats.nodes, 8);
+        assert_eq!((stats.components, stats.largest_component), (2, 6));
+        assert_eq!(stats.degree_histogram, vec![0, 3, 4, 1]);
+        assert_eq!(stats.degree_histogram.iter().sum::<u64>(), stats.nodes);
+        assert_eq!(stats.two_core_edges, 4);
+        assert_eq!(two_core(&edges), vec![0, 1, 2, 3]);
+        assert!(stats.to_json().starts_with("{\"edges\":7,\"nodes\":8,\"components\":2,"));
+
+        // Edges sharing a node don't hold each other up
+        assert!(two_core(&[edge(0, 0), edge(0, 0), edge(2, 2), edge(2, 2)]).is_empty());
+
+        assert_eq!(GraphStats::analyze(&[]), GraphStats::default());
+    }
+}
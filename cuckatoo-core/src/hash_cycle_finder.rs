@@ -2,301 +2,468 @@
 //! 
 //! This implements the exact same cycle finding algorithm as the C++ version,
 //! including the hash table-based node connection tracking and the two-partition
-//! search approach.
+//! search approach. The connection lists live in an arena of links indexed
+//! by `u32`, like the C++ node connections array, which the finder keeps
+//! (cleared) from one graph to the next.
 
-use crate::{Node, Edge, Result, SOLUTION_SIZE, EDGE_NUMBER_OF_COMPONENTS};
-use std::collections::HashMap;
+use crate::cancel::BudgetMeter;
+use crate::types::assert_cycle_length;
+use crate::{CancelToken, CuckatooError, Node, Edge, Proof, Result, SearchBudget, SOLUTION_SIZE, EDGE_NUMBER_OF_COMPONENTS};
+use std::collections::{HashMap, HashSet};
 
 /// Node connection link matching C++ CuckatooNodeConnectionsLink exactly
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct NodeConnectionLink {
-    pub previous_link: Option<Box<NodeConnectionLink>>,
+    /// Index in the finder's link arena of the node's previous link
+    pub previous_link: Option<u32>,
     pub node: Node,
-    pub edge_index: u32,
+    /// Node at the edge's other end, which C++ reads from the edge's other
+    /// link (`(nodeConnection + 1)->node` or `(nodeConnection - 1)->node`)
+    pub opposite_node: Node,
+    pub edge_index: u64,
+}
+
+/// Work done by one cycle search
+///
+/// Lets cycle finders be compared on the work they do rather than only on
+/// time, which depends on the machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CycleSearchStats {
+    /// Edges whose endpoints both had a pair, each starting a cycle walk
+    pub candidate_pairs: u64,
+    /// Node pairs visited while walking
+    pub traversal_steps: u64,
+    /// Longest path (in edges) a walk reached
+    pub max_chain_length: u32,
 }
 
 /// Hash cycle finder matching C++ getCuckatooSolution algorithm exactly
 pub struct HashCycleFinder {
     // Thread-local global variables matching C++ exactly
-    u_newest_connections: HashMap<Node, NodeConnectionLink>,
-    v_newest_connections: HashMap<Node, NodeConnectionLink>,
-    u_visited_pairs: HashMap<u64, u32>,
-    v_visited_pairs: HashMap<u64, u32>,
+    /// Arena of every edge's two links, U link first, kept between graphs
+    links: Vec<NodeConnectionLink>,
+    /// Arena index of each node's newest link
+    u_newest_connections: HashMap<Node, u32>,
+    v_newest_connections: HashMap<Node, u32>,
+    u_visited_pairs: HashMap<u64, u64>,
+    v_visited_pairs: HashMap<u64, u64>,
     root_node: Node,
+    /// Number of edges in the cycles searched for
+    cycle_length: usize,
+    stats: CycleSearchStats,
+    /// Token stopping the search between edges
+    cancel: Option<CancelToken>,
+    /// Whether the last search stopped because the token was cancelled
+    cancelled: bool,
+    /// Limits on the work of each search
+    budget: SearchBudget,
+    /// Budget left to the running search
+    meter: BudgetMeter,
+}
+
+impl Default for HashCycleFinder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HashCycleFinder {
     pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+    
+    /// Create a finder with room for the links and nodes of
+    /// `expected_edges` edges, so searching graphs up to that size doesn't
+    /// allocate
+    pub fn with_capacity(expected_edges: usize) -> Self {
         Self {
-            u_newest_connections: HashMap::new(),
-            v_newest_connections: HashMap::new(),
+            links: Vec::with_capacity(expected_edges * 2),
+            u_newest_connections: HashMap::with_capacity(expected_edges),
+            v_newest_connections: HashMap::with_capacity(expected_edges),
             u_visited_pairs: HashMap::new(),
             v_visited_pairs: HashMap::new(),
             root_node: Node::new(0),
+            cycle_length: SOLUTION_SIZE,
+            stats: CycleSearchStats::default(),
+            cancel: None,
+            cancelled: false,
+            budget: SearchBudget::default(),
+            meter: BudgetMeter::default(),
         }
     }
     
+    /// Search for cycles of `length` edges instead of 42
+    ///
+    /// Panics if `length` is odd or outside 4 to `MAX_CYCLE_LENGTH`.
+    pub fn with_cycle_length(mut self, length: usize) -> Self {
+        assert_cycle_length(length);
+        self.cycle_length = length;
+        self
+    }
+    
+    /// Number of edges in the cycles searched for
+    pub fn cycle_length(&self) -> usize {
+        self.cycle_length
+    }
+    
+    /// Stop searching soon after `token` is cancelled; `find_cycle` then
+    /// fails with `Cancelled` and `get_cuckatoo_solution` returns false
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+    
+    /// Whether the last search stopped because the token was cancelled
+    pub fn was_cancelled(&self) -> bool {
+        self.cancelled
+    }
+    
+    /// Give up searches that go over `budget`; `find_cycle` then fails with
+    /// `BudgetExceeded` and `get_cuckatoo_solution` returns false
+    pub fn with_budget(mut self, budget: SearchBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+    
+    /// Whether the last search stopped because it went over budget
+    pub fn was_budget_exceeded(&self) -> bool {
+        self.meter.is_exceeded()
+    }
+    
+    /// Work done by the last search (reset by `find_cycle`)
+    pub fn stats(&self) -> CycleSearchStats {
+        self.stats
+    }
+    
+    /// Count a node pair visited at `chain_length` edges into a walk,
+    /// returning whether the search went over budget
+    fn record_step(&mut self, chain_length: u8) -> bool {
+        self.stats.traversal_steps += 1;
+        self.stats.max_chain_length = self.stats.max_chain_length.max(chain_length as u32);
+        self.meter.step()
+    }
+    
     /// Initialize thread-local global variables (matching C++ initializeCuckatooThreadLocalGlobalVariables)
     pub fn initialize_thread_local_global_variables(&mut self) -> bool {
         // Reset thread local global variables
+        self.links.clear();
         self.u_newest_connections.clear();
         self.v_newest_connections.clear();
         self.u_visited_pairs.clear();
         self.v_visited_pairs.clear();
         self.root_node = Node::new(0);
+        self.stats = CycleSearchStats::default();
+        self.cancelled = false;
         
         true
     }
 
     /// Get cuckatoo solution (matching C++ getCuckatooSolution exactly)
-    pub fn get_cuckatoo_solution(&mut self, solution: &mut [u32; SOLUTION_SIZE], 
-                                node_connections: &mut [NodeConnectionLink], 
-                                edges: &[u32], 
+    ///
+    /// On success `solution` holds the `cycle_length` edge indices of the cycle.
+    pub fn get_cuckatoo_solution(&mut self, solution: &mut Vec<u64>, 
+                                edges: &[u64], 
                                 number_of_edges: u64) -> bool {
+        let mut found = false;
+        self.scan_edges(solution, edges, number_of_edges, |_| {
+            found = true;
+            false
+        });
+        found
+    }
+    
+    /// Get every distinct cuckatoo solution, continuing the scan after each
+    /// find, as proofs of a graph of `edge_bits`
+    /// 
+    /// `edges` hold the edges' indices as in `get_cuckatoo_solution`, so
+    /// they're the proofs' nonces when they are the graph's edge indices.
+    /// Solutions are returned in the order they're found, once per set of
+    /// nonces. Fails with `Cancelled` if the token is cancelled, or if the
+    /// cycle length isn't 42 or the nonces don't fit in `edge_bits` bits.
+    pub fn get_all_cuckatoo_solutions(&mut self,
+                                     edges: &[u64],
+                                     number_of_edges: u64,
+                                     edge_bits: u32) -> Result<Vec<Proof>> {
+        let mut solution = Vec::with_capacity(self.cycle_length);
+        let mut seen = HashSet::new();
+        let mut solutions = Vec::new();
+        self.scan_edges(&mut solution, edges, number_of_edges, |solution| {
+            if seen.insert(solution.to_vec()) {
+                solutions.push(solution.to_vec());
+            }
+            true
+        });
+        if self.cancelled {
+            return Err(CuckatooError::Cancelled);
+        }
+        if self.meter.is_exceeded() {
+            return Err(CuckatooError::BudgetExceeded);
+        }
+        solutions.iter().map(|nonces| Proof::new(edge_bits, nonces)).collect()
+    }
+    
+    /// Add the edges one at a time and search for a cycle from each,
+    /// passing every solution found to `on_solution`, which returns
+    /// whether to keep scanning
+    fn scan_edges(&mut self, solution: &mut Vec<u64>,
+                  edges: &[u64],
+                  number_of_edges: u64,
+                  mut on_solution: impl FnMut(&[u64]) -> bool) {
+        
+        self.meter = self.budget.start();
         
         // Go through all edges (matching C++ loop exactly)
         let mut node_connections_index = 0;
         let mut edges_index = 0;
         
-        while node_connections_index < (number_of_edges * 2) as usize {
+        while (node_connections_index as u64) < number_of_edges * 2 {
+            // Stop before the next edge once cancelled
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                self.cancelled = true;
+                return;
+            }
+            
             // Get edge's index and nodes (matching C++ exactly)
             let index = &edges[edges_index];
-            let node = Node::new(edges[edges_index + 1] as u64);
-            self.root_node = Node::new(edges[edges_index + 2] as u64);
+            let node = Node::new(edges[edges_index + 1]);
+            self.root_node = Node::new(edges[edges_index + 2]);
             
             // Replace newest node connection for the node on the first partition and add node connection to list
-            let previous_u = self.u_newest_connections.get(&node).cloned();
+            let u_link = self.links.len() as u32;
             let new_u_link = NodeConnectionLink {
-                previous_link: previous_u.map(|link| Box::new(link)),
+                previous_link: self.u_newest_connections.insert(node, u_link),
                 node,
+                opposite_node: self.root_node,
                 edge_index: *index,
             };
-            node_connections[node_connections_index] = new_u_link.clone();
-            self.u_newest_connections.insert(node, new_u_link);
+            self.links.push(new_u_link);
             
             // Replace newest node connection for the node on the second partition and add node connection to list
-            let previous_v = self.v_newest_connections.get(&self.root_node).cloned();
             let new_v_link = NodeConnectionLink {
-                previous_link: previous_v.map(|link| Box::new(link)),
+                previous_link: self.v_newest_connections.insert(self.root_node, u_link + 1),
                 node: self.root_node,
+                opposite_node: node,
                 edge_index: *index,
             };
-            node_connections[node_connections_index + 1] = new_v_link.clone();
-            self.v_newest_connections.insert(self.root_node, new_v_link);
+            self.links.push(new_v_link);
             
-            // Check if both nodes have a pair
+            // Check if both nodes have a pair, then search for a cycle through the edge
             if self.u_newest_connections.contains_key(&Node::new(node.value() ^ 1)) &&
-               self.v_newest_connections.contains_key(&Node::new(self.root_node.value() ^ 1)) {
-                
-                // Reset visited nodes
-                self.u_visited_pairs.clear();
-                self.v_visited_pairs.clear();
-                
-                // Go through all nodes in the cycle (matching C++ complex loop exactly)
-                let mut cycle_size = 1u8;
-                let mut current_node = node;
-                let mut current_index = *index;
-                
-                loop {
-                    // Set that node pair has been visited
-                    self.u_visited_pairs.insert(current_node.value() >> 1, current_index);
+               self.v_newest_connections.contains_key(&Node::new(self.root_node.value() ^ 1)) &&
+               self.search_cycle_from_edge(solution, node, *index) &&
+               !on_solution(solution) {
+                return;
+            }
+            if self.meter.is_exceeded() {
+                return;
+            }
+            
+            // Update indices for next iteration
+            node_connections_index += 2;
+            edges_index += EDGE_NUMBER_OF_COMPONENTS;
+        }
+    }
+
+    /// Search for a cycle through the edge just added, from its first
+    /// partition node `node` (matching the body of C++ getCuckatooSolution's loop)
+    fn search_cycle_from_edge(&mut self, solution: &mut Vec<u64>, node: Node, index: u64) -> bool {
+        // Reset visited nodes
+        self.u_visited_pairs.clear();
+        self.v_visited_pairs.clear();
+        self.stats.candidate_pairs += 1;
+        
+        // Go through all nodes in the cycle (matching C++ complex loop exactly)
+        let mut cycle_size = 1u8;
+        let mut current_node = node;
+        let mut current_index = index;
+        
+        loop {
+            // Set that node pair has been visited
+            self.u_visited_pairs.insert(current_node.value() >> 1, current_index);
+            if self.record_step(cycle_size) {
+                return false;
+            }
+            
+            // Check if node's pair has more than one connection
+            if let Some(&newest) = self.u_newest_connections.get(&Node::new(current_node.value() ^ 1)) {
+                let node_connection = self.links[newest as usize];
+                if node_connection.previous_link.is_some() {
+                    let connections = self.connections(newest);
                     
-                    // Check if node's pair has more than one connection
-                    if let Some(node_connection) = self.u_newest_connections.get(&Node::new(current_node.value() ^ 1)) {
-                        if node_connection.previous_link.is_some() {
-                            // Collect all connections first to avoid borrowing issues
-                            let mut connections = Vec::new();
-                            let mut current_link = Some(node_connection);
-                            while let Some(link) = current_link {
-                                connections.push((link.node, link.edge_index));
-                                current_link = link.previous_link.as_ref().map(|boxed| boxed.as_ref());
+                    // Go through all of the node's pair's connections
+                    for (connected_node, connected_edge_index) in connections {
+                        // Check if the connected node's pair wasn't already visited
+                        let connected_node_pair_index = connected_node.value() >> 1; // (nodeConnection + 1)->node >> 1
+                        if !self.v_visited_pairs.contains_key(&connected_node_pair_index) {
+                            
+                            // Check if cycle is complete
+                            if (connected_node.value() ^ 1) == self.root_node.value() {
+                                
+                                // Check if cycle is a solution
+                                if cycle_size == (self.cycle_length - 1) as u8 {
+                                    
+                                    // Get solution from visited nodes
+                                    self.get_solution_from_visited_nodes(solution, Some(connected_edge_index));
+                                    
+                                    // Sort solution in ascending order
+                                    solution.sort();
+                                    
+                                    return true;
+                                }
                             }
                             
-                            // Go through all of the node's pair's connections
-                            for (connected_node, connected_edge_index) in connections {
-                                // Check if the connected node's pair wasn't already visited
-                                let connected_node_pair_index = (connected_node.value() + 1) >> 1; // (nodeConnection + 1)->node >> 1
-                                if !self.v_visited_pairs.contains_key(&connected_node_pair_index) {
+                            // Otherwise check if cycle could be as solution
+                            else if cycle_size != (self.cycle_length - 1) as u8 {
+                                
+                                // Check if the connected node has a pair
+                                if self.v_newest_connections.contains_key(&Node::new(connected_node.value() ^ 1)) {
                                     
-                                    // Check if cycle is complete
-                                    if (connected_node.value() ^ 1) == self.root_node.value() {
+                                    // Check if solution was found at the connected node's pair
+                                    if self.search_node_connections_second_partition(cycle_size + 1, connected_node.value() ^ 1, connected_edge_index) {
                                         
-                                        // Check if cycle is a solution
-                                        if cycle_size == (SOLUTION_SIZE - 1) as u8 {
-                                            
-                                            // Get solution from visited nodes
-                                            self.get_solution_from_visited_nodes(solution, connected_edge_index);
-                                            
-                                            // Sort solution in ascending order
-                                            solution.sort();
-                                            
-                                            return true;
-                                        }
-                                    }
-                                    
-                                    // Otherwise check if cycle could be as solution
-                                    else if cycle_size != (SOLUTION_SIZE - 1) as u8 {
+                                        // Get solution from visited nodes
+                                        self.get_solution_from_visited_nodes(solution, None);
+                                        
+                                        // Sort solution in ascending order
+                                        solution.sort();
                                         
-                                        // Check if the connected node has a pair
-                                        if self.v_newest_connections.contains_key(&Node::new(connected_node.value() ^ 1)) {
-                                            
-                                            // Check if solution was found at the connected node's pair
-                                            if self.search_node_connections_second_partition(cycle_size + 1, (connected_node.value() ^ 1) as u32, connected_edge_index) {
-                                                
-                                                // Get solution from visited nodes
-                                                self.get_solution_from_visited_nodes(solution, 0);
-                                                
-                                                // Sort solution in ascending order
-                                                solution.sort();
-                                                
-                                                return true;
-                                            }
-                                        }
+                                        return true;
                                     }
                                 }
                             }
-                            
-                            // Break
-                            break;
                         }
+                    }
+                    
+                    // Break
+                    break;
+                }
+                
+                // Go to node's pair opposite end and get its edge index
+                current_index = node_connection.edge_index;
+                current_node = node_connection.opposite_node;
+                
+                // Check if node pair was already visited
+                if self.v_visited_pairs.contains_key(&(current_node.value() >> 1)) {
+                    break;
+                }
+                
+                // Check if cycle is complete
+                if (current_node.value() ^ 1) == self.root_node.value() {
+                    
+                    // Check if cycle is a solution
+                    if cycle_size == (self.cycle_length - 1) as u8 {
                         
-                        // Go to node's pair opposite end and get its edge index
-                        current_index = node_connection.edge_index;
-                        current_node = node_connection.node;
+                        // Get solution from visited nodes
+                        self.get_solution_from_visited_nodes(solution, Some(current_index));
                         
-                        // Check if node pair was already visited
-                        if self.v_visited_pairs.contains_key(&(current_node.value() >> 1)) {
-                            break;
-                        }
+                        // Sort solution in ascending order
+                        solution.sort();
                         
-                        // Check if cycle is complete
-                        if (current_node.value() ^ 1) == self.root_node.value() {
+                        return true;
+                    }
+                    
+                    // Break
+                    break;
+                }
+                
+                // Check if cycle isn't a solution
+                if cycle_size == (self.cycle_length - 1) as u8 {
+                    break;
+                }
+                
+                // Check if node doesn't have a pair
+                if !self.v_newest_connections.contains_key(&Node::new(current_node.value() ^ 1)) {
+                    break;
+                }
+                
+                // Set that node pair has been visited
+                self.v_visited_pairs.insert(current_node.value() >> 1, current_index);
+                if self.record_step(cycle_size + 1) {
+                    return false;
+                }
+                
+                // Check if node's pair has more than one connection
+                if let Some(&newest) = self.v_newest_connections.get(&Node::new(current_node.value() ^ 1)) {
+                let node_connection = self.links[newest as usize];
+                if node_connection.previous_link.is_some() {
+                    let connections = self.connections(newest);
+                    
+                    // Go through all of the node's pair's connections
+                    for (connected_node, connected_edge_index) in connections {
+                        // Check if the connected node has a pair
+                        if self.u_newest_connections.contains_key(&Node::new(connected_node.value() ^ 1)) {
                             
-                            // Check if cycle is a solution
-                            if cycle_size == (SOLUTION_SIZE - 1) as u8 {
-                                
-                                // Get solution from visited nodes
-                                self.get_solution_from_visited_nodes(solution, current_index);
-                                
-                                // Sort solution in ascending order
-                                solution.sort();
+                            // Check if the connected node's pair wasn't already visited
+                            if !self.u_visited_pairs.contains_key(&(connected_node.value() >> 1)) {
                                 
-                                return true;
-                            }
-                            
-                            // Break
-                            break;
-                        }
-                        
-                        // Check if cycle isn't a solution
-                        if cycle_size == (SOLUTION_SIZE - 1) as u8 {
-                            break;
-                        }
-                        
-                        // Check if node doesn't have a pair
-                        if !self.v_newest_connections.contains_key(&Node::new(current_node.value() ^ 1)) {
-                            break;
-                        }
-                        
-                        // Set that node pair has been visited
-                        self.v_visited_pairs.insert(current_node.value() >> 1, current_index);
-                        
-                        // Check if node's pair has more than one connection
-                        if let Some(node_connection) = self.v_newest_connections.get(&Node::new(current_node.value() ^ 1)) {
-                        if node_connection.previous_link.is_some() {
-                            // Collect all connections first to avoid borrowing issues
-                            let mut connections = Vec::new();
-                            let mut current_link = Some(node_connection);
-                            while let Some(link) = current_link {
-                                connections.push((link.node, link.edge_index));
-                                current_link = link.previous_link.as_ref().map(|boxed| boxed.as_ref());
-                            }
-                            
-                            // Go through all of the node's pair's connections
-                            for (connected_node, connected_edge_index) in connections {
-                                // Check if the connected node has a pair
-                                if self.u_newest_connections.contains_key(&Node::new(connected_node.value() ^ 1)) {
+                                // Check if solution was found at the connected node's pair
+                                if self.search_node_connections_first_partition(cycle_size + 2, connected_node.value() ^ 1, connected_edge_index) {
                                     
-                                    // Check if the connected node's pair wasn't already visited
-                                    if !self.u_visited_pairs.contains_key(&(connected_node.value() >> 1)) {
-                                        
-                                        // Check if solution was found at the connected node's pair
-                                        if self.search_node_connections_first_partition(cycle_size + 2, (connected_node.value() ^ 1) as u32, connected_edge_index) {
-                                            
-                                            // Get solution from visited nodes
-                                            self.get_solution_from_visited_nodes(solution, 0);
-                                            
-                                            // Sort solution in ascending order
-                    solution.sort();
-                    
-                                            return true;
-                                        }
-                                    }
+                                    // Get solution from visited nodes
+                                    self.get_solution_from_visited_nodes(solution, None);
+                                    
+                                    // Sort solution in ascending order
+            solution.sort();
+            
+                                    return true;
                                 }
                             }
-                                
-                                // Break
-                                break;
-                            }
-                            
-                            // Go to node's pair opposite end and get its edge index
-                            current_index = node_connection.edge_index;
-                            current_node = node_connection.node;
-                            
-                            // Check if node pair was already visited
-                            if self.u_visited_pairs.contains_key(&(current_node.value() >> 1)) {
-                                break;
-                            }
-                            
-                            // Check if node doesn't have a pair
-                            if !self.u_newest_connections.contains_key(&Node::new(current_node.value() ^ 1)) {
-                                break;
-                            }
-                            
-                            cycle_size += 2;
-                        } else {
-                            break;
                         }
-                    } else {
+                    }
+                        
+                        // Break
+                        break;
+                    }
+                    
+                    // Go to node's pair opposite end and get its edge index
+                    current_index = node_connection.edge_index;
+                    current_node = node_connection.opposite_node;
+                    
+                    // Check if node pair was already visited
+                    if self.u_visited_pairs.contains_key(&(current_node.value() >> 1)) {
+                        break;
+                    }
+                    
+                    // Check if node doesn't have a pair
+                    if !self.u_newest_connections.contains_key(&Node::new(current_node.value() ^ 1)) {
                         break;
                     }
+                    
+                    cycle_size += 2;
+                } else {
+                    break;
                 }
+            } else {
+                break;
             }
-            
-            // Update indices for next iteration
-            node_connections_index += 2;
-            edges_index += EDGE_NUMBER_OF_COMPONENTS as usize;
         }
         
         false
     }
 
     /// Search node connections for cuckatoo solution first partition (matching C++ exactly)
-    fn search_node_connections_first_partition(&mut self, cycle_size: u8, node: u32, index: u32) -> bool {
+    fn search_node_connections_first_partition(&mut self, cycle_size: u8, node: u64, index: u64) -> bool {
         // Set that node pair has been visited
         let visited_node_pair_index = node >> 1;
-        self.u_visited_pairs.insert(visited_node_pair_index as u64, index);
+        self.u_visited_pairs.insert(visited_node_pair_index, index);
+        if self.record_step(cycle_size) {
+            return false;
+        }
         
         // Go through all of the node's connections
-        if let Some(node_connection) = self.u_newest_connections.get(&Node::new(node as u64)) {
-            // Collect all connections first to avoid borrowing issues
-        let mut connections = Vec::new();
-            let mut current_link = Some(node_connection);
-            while let Some(link) = current_link {
-                connections.push((link.node, link.edge_index));
-                current_link = link.previous_link.as_ref().map(|boxed| boxed.as_ref());
-        }
+        if let Some(&newest) = self.u_newest_connections.get(&Node::new(node)) {
+            let connections = self.connections(newest);
         
         for (connected_node, connected_edge_index) in connections {
             // Check if the connected node's pair wasn't already visited
-                let connected_node_pair_index = (connected_node.value() + 1) >> 1; // (nodeConnection + 1)->node >> 1
+                let connected_node_pair_index = connected_node.value() >> 1; // (nodeConnection + 1)->node >> 1
             if !self.v_visited_pairs.contains_key(&connected_node_pair_index) {
                 
                 // Check if cycle is complete
                 if (connected_node.value() ^ 1) == self.root_node.value() {
                         
                     // Check if cycle is a solution
-                        if cycle_size == (SOLUTION_SIZE - 1) as u8 {
+                        if cycle_size == (self.cycle_length - 1) as u8 {
                             
                         // Set that the connected node's pair has been visited
                         self.v_visited_pairs.insert(connected_node_pair_index, connected_edge_index);
@@ -306,13 +473,13 @@ impl HashCycleFinder {
                 }
                     
                     // Otherwise check if cycle could be as solution
-                    else if cycle_size != (SOLUTION_SIZE - 1) as u8 {
+                    else if cycle_size != (self.cycle_length - 1) as u8 {
                         
                     // Check if the connected node has a pair
                     if self.v_newest_connections.contains_key(&Node::new(connected_node.value() ^ 1)) {
                             
                         // Check if solution was found at the connected node's pair
-                            if self.search_node_connections_second_partition(cycle_size + 1, (connected_node.value() ^ 1) as u32, connected_edge_index) {
+                            if self.search_node_connections_second_partition(cycle_size + 1, connected_node.value() ^ 1, connected_edge_index) {
                             return true;
                             }
                         }
@@ -322,26 +489,23 @@ impl HashCycleFinder {
         }
         
         // Set that node pair hasn't been visited
-        self.u_visited_pairs.remove(&(visited_node_pair_index as u64));
+        self.u_visited_pairs.remove(&visited_node_pair_index);
         
         false
     }
     
     /// Search node connections for cuckatoo solution second partition (matching C++ exactly)
-    fn search_node_connections_second_partition(&mut self, cycle_size: u8, node: u32, index: u32) -> bool {
+    fn search_node_connections_second_partition(&mut self, cycle_size: u8, node: u64, index: u64) -> bool {
         // Set that node pair has been visited
         let visited_node_pair_index = node >> 1;
-        self.v_visited_pairs.insert(visited_node_pair_index as u64, index);
+        self.v_visited_pairs.insert(visited_node_pair_index, index);
+        if self.record_step(cycle_size) {
+            return false;
+        }
         
         // Go through all of the node's connections
-        if let Some(node_connection) = self.v_newest_connections.get(&Node::new(node as u64)) {
-            // Collect all connections first to avoid borrowing issues
-            let mut connections = Vec::new();
-            let mut current_link = Some(node_connection);
-            while let Some(link) = current_link {
-                connections.push((link.node, link.edge_index));
-                current_link = link.previous_link.as_ref().map(|boxed| boxed.as_ref());
-            }
+        if let Some(&newest) = self.v_newest_connections.get(&Node::new(node)) {
+            let connections = self.connections(newest);
             
             for (connected_node, connected_edge_index) in connections {
                 // Check if the connected node has a pair
@@ -351,7 +515,7 @@ impl HashCycleFinder {
                     if !self.u_visited_pairs.contains_key(&(connected_node.value() >> 1)) {
                         
                         // Check if solution was found at the connected node's pair
-                        if self.search_node_connections_first_partition(cycle_size + 1, (connected_node.value() ^ 1) as u32, connected_edge_index) {
+                        if self.search_node_connections_first_partition(cycle_size + 1, connected_node.value() ^ 1, connected_edge_index) {
                             return true;
                         }
                     }
@@ -360,66 +524,87 @@ impl HashCycleFinder {
         }
         
         // Set that node pair hasn't been visited
-        self.v_visited_pairs.remove(&(visited_node_pair_index as u64));
+        self.v_visited_pairs.remove(&visited_node_pair_index);
         
         false
     }
     
+    /// Opposite nodes and edge indices of the links in the list starting
+    /// at arena index `newest`, newest first
+    /// 
+    /// Collected up front since the walk recurses into the finder.
+    fn connections(&self, newest: u32) -> Vec<(Node, u64)> {
+        let mut connections = Vec::new();
+        let mut current_link = Some(newest);
+        while let Some(index) = current_link {
+            let link = &self.links[index as usize];
+            connections.push((link.opposite_node, link.edge_index));
+            current_link = link.previous_link;
+        }
+        connections
+    }
+    
     /// Get solution from visited nodes (matching C++ getValues)
-    fn get_solution_from_visited_nodes(&self, solution: &mut [u32; SOLUTION_SIZE], last_edge_index: u32) {
+    /// 
+    /// A cycle closed in the search functions has all its edges' indices in
+    /// the visited pairs; one closed in `get_cuckatoo_solution`'s own loop
+    /// has the second partition's last pair unvisited and passes the
+    /// closing edge's index as `last_edge_index`.
+    fn get_solution_from_visited_nodes(&self, solution: &mut Vec<u64>, last_edge_index: Option<u64>) {
+        solution.clear();
+        solution.resize(self.cycle_length, 0);
         let mut i = 0;
         
         // Get values from U visited pairs
         for &edge_index in self.u_visited_pairs.values() {
-            if i < SOLUTION_SIZE / 2 {
+            if i < self.cycle_length / 2 {
                 solution[i] = edge_index;
                 i += 1;
             }
         }
         
-        // Get values from V visited pairs
+        // Get values from V visited pairs, leaving room for the last edge
+        let second_partition_end = self.cycle_length - last_edge_index.is_some() as usize;
         for &edge_index in self.v_visited_pairs.values() {
-            if i < SOLUTION_SIZE - 1 {
+            if i < second_partition_end {
                 solution[i] = edge_index;
                 i += 1;
             }
         }
         
         // Add the last edge index
-        if i < SOLUTION_SIZE {
+        if let Some(last_edge_index) = last_edge_index {
             solution[i] = last_edge_index;
         }
     }
 
     /// Find cycle using the C++ algorithm (wrapper for getCuckatooSolution)
     pub fn find_cycle(&mut self, edges: &[Edge]) -> Result<Option<Vec<usize>>> {
+        if edges.len() >= (u32::MAX / 2) as usize {
+            return Err(CuckatooError::InternalError(format!("Too many edges for the cycle finder: {}", edges.len())));
+        }
+        
         // Initialize thread-local global variables
         self.initialize_thread_local_global_variables();
         
         // Convert edges to C++ format [edge_index, node_u, node_v]
         let mut cpp_edges = Vec::new();
         for (i, edge) in edges.iter().enumerate() {
-            cpp_edges.push(i as u32); // edge_index
-            cpp_edges.push(edge.u.value() as u32); // node_u
-            cpp_edges.push(edge.v.value() as u32); // node_v
+            cpp_edges.push(i as u64); // edge_index
+            cpp_edges.push(edge.u.value()); // node_u
+            cpp_edges.push(edge.v.value()); // node_v
         }
         
-        // Create node connections array
-        let mut node_connections = vec![
-            NodeConnectionLink {
-                previous_link: None,
-                node: Node::new(0),
-                edge_index: 0,
-            };
-            edges.len() * 2
-        ];
-        
         // Call the C++ algorithm
-        let mut solution = [0u32; SOLUTION_SIZE];
-        if self.get_cuckatoo_solution(&mut solution, &mut node_connections, &cpp_edges, edges.len() as u64) {
+        let mut solution = Vec::with_capacity(self.cycle_length);
+        if self.get_cuckatoo_solution(&mut solution, &cpp_edges, edges.len() as u64) {
             // Convert solution indices to Vec<usize>
             let solution_indices: Vec<usize> = solution.iter().map(|&idx| idx as usize).collect();
             Ok(Some(solution_indices))
+        } else if self.cancelled {
+            Err(CuckatooError::Cancelled)
+        } else if self.meter.is_exceeded() {
+            Err(CuckatooError::BudgetExceeded)
         } else {
             Ok(None)
         }
@@ -440,5 +625,50 @@ mod tests {
         let result = finder.find_cycle(&edges);
         assert!(result.is_ok());
         assert!(result.unwrap().is_none());
+        
+        assert_eq!(finder.cycle_length(), SOLUTION_SIZE);
+        let mut finder = HashCycleFinder::new().with_cycle_length(20);
+        assert_eq!(finder.cycle_length(), 20);
+        assert!(finder.find_cycle(&[]).unwrap().is_none());
+    }
+    
+    #[test]
+    fn test_get_all_cuckatoo_solutions() {
+        // Two 42-cycles through node pairs, each pair reached at both of its
+        // nodes, with the graph's edge indices as nonces
+        let half = SOLUTION_SIZE as u64 / 2;
+        let mut edges = Vec::new();
+        for (cycle, base) in [(0u64, 0u64), (1, 1000)] {
+            for pair in 0..half {
+                let next = (pair + 1) % half;
+                let index = cycle * 100 + 2 * pair;
+                edges.extend([index, base + 2 * pair, base + 2 * pair]);
+                edges.extend([index + 1, base + 2 * next + 1, base + 2 * pair + 1]);
+            }
+        }
+        let number_of_edges = edges.len() as u64 / EDGE_NUMBER_OF_COMPONENTS as u64;
+        
+        let mut finder = HashCycleFinder::with_capacity(number_of_edges as usize);
+        let proofs = finder.get_all_cuckatoo_solutions(&edges, number_of_edges, 12).unwrap();
+        let expected: Vec<Vec<u64>> = (0..2).map(|cycle| (cycle * 100..cycle * 100 + 42).collect()).collect();
+        assert_eq!(proofs.iter().map(|proof| proof.nonces.to_vec()).collect::<Vec<_>>(), expected);
+        assert!(proofs.iter().all(|proof| proof.edge_bits == 12));
+        
+        // The first-solution search stops at the first of them
+        let mut solution = Vec::new();
+        finder.initialize_thread_local_global_variables();
+        assert!(finder.get_cuckatoo_solution(&mut solution, &edges, number_of_edges));
+        assert_eq!(solution, expected[0]);
+        
+        // Nonces must fit the edge bits
+        finder.initialize_thread_local_global_variables();
+        assert!(finder.get_all_cuckatoo_solutions(&edges, number_of_edges, 7).is_err());
+        
+        // The link arena keeps its room from one graph to the next
+        let capacity = finder.links.capacity();
+        assert!(capacity >= number_of_edges as usize * 2);
+        finder.initialize_thread_local_global_variables();
+        assert!(finder.links.is_empty());
+        assert_eq!(finder.links.capacity(), capacity);
     }
 }
\ No newline at end of file
@@ -1,8 +1,264 @@
 //! SipHash-2-4 implementation for Cuckatoo edge generation
 //! Based on the C++ reference miner implementation
+//!
+//! `SipHasher` is the interface every SipHash implementation (`SipHash`,
+//! `ExactSipHash`) provides; trimmers are generic over it.
 
-use crate::{Edge, Header, Node, Result, CuckatooError};
+use crate::{Edge, Header, Node, Result, CuckatooError, SipKeys, checked_usize, SUPPORTED_EDGE_BITS};
 use crate::blake2b::blake2b;
+use crate::edge_iterator::EdgeIterator;
+
+/// SipHash-2-4 of a nonce under a graph's keys
+///
+/// Trimmers and cycle searches only need these three operations, so an
+/// implementation (scalar, SIMD, a test double) can be swapped in without
+/// duplicating edge generation.
+pub trait SipHasher {
+    /// Hash a nonce with SipHash-2-4, without masking the result
+    fn hash_nonce(&self, nonce: u64) -> u64;
+    
+    /// Get the endpoints of an edge (nonces `2 * edge_index` and
+    /// `2 * edge_index + 1`), masked to `edge_bits`
+    fn hash_pair(&self, edge_index: u64, edge_bits: u32) -> Edge {
+        Edge::new(
+            self.hash_node(edge_index * 2, edge_bits),
+            self.hash_node(edge_index * 2 + 1, edge_bits),
+        )
+    }
+    
+    /// Get the keys of the graph
+    fn keys(&self) -> SipKeys;
+    
+    /// Hash a nonce into a node masked to `edge_bits`
+    fn hash_node(&self, nonce: u64, edge_bits: u32) -> Node {
+        Node::new(self.hash_nonce(nonce) & node_mask(edge_bits))
+    }
+    
+    /// Hash a batch of nonces into nodes masked to `edge_bits` (`nodes[i]`
+    /// is the node for `nonces[i]`)
+    /// 
+    /// Implementations with SIMD paths override this. Panics if `nodes` is
+    /// shorter than `nonces`.
+    fn hash_nonces(&self, nonces: &[u64], edge_bits: u32, nodes: &mut [u64]) {
+        let node_mask = node_mask(edge_bits);
+        for (node, &nonce) in nodes[..nonces.len()].iter_mut().zip(nonces) {
+            *node = self.hash_nonce(nonce) & node_mask;
+        }
+    }
+    
+    /// Hash one endpoint of consecutive edges into nodes masked to
+    /// `edge_bits`: `nodes[i]` is the node for nonce `base_nonce + 2 * i`
+    /// 
+    /// Cuckatoo's U (even) and V (odd) nonces are interleaved, so a block
+    /// holds every other nonce. Implementations with SIMD paths override it.
+    fn hash_block(&self, base_nonce: u64, edge_bits: u32, nodes: &mut [u64]) {
+        let node_mask = node_mask(edge_bits);
+        for (i, node) in nodes.iter_mut().enumerate() {
+            *node = self.hash_nonce(base_nonce + 2 * i as u64) & node_mask;
+        }
+    }
+    
+    /// This hasher as one that can be shared between threads, so trimmers
+    /// can split their work across them
+    /// 
+    /// `None` (the default) keeps trimmers on the calling thread; hashers
+    /// that are `Sync` return `Some(self)`.
+    fn as_sync(&self) -> Option<&(dyn SipHasher + Sync)> {
+        None
+    }
+}
+
+impl<H: SipHasher + ?Sized> SipHasher for &H {
+    fn hash_nonce(&self, nonce: u64) -> u64 {
+        (**self).hash_nonce(nonce)
+    }
+    
+    fn hash_pair(&self, edge_index: u64, edge_bits: u32) -> Edge {
+        (**self).hash_pair(edge_index, edge_bits)
+    }
+    
+    fn keys(&self) -> SipKeys {
+        (**self).keys()
+    }
+    
+    fn hash_node(&self, nonce: u64, edge_bits: u32) -> Node {
+        (**self).hash_node(nonce, edge_bits)
+    }
+    
+    fn hash_nonces(&self, nonces: &[u64], edge_bits: u32, nodes: &mut [u64]) {
+        (**self).hash_nonces(nonces, edge_bits, nodes)
+    }
+    
+    fn hash_block(&self, base_nonce: u64, edge_bits: u32, nodes: &mut [u64]) {
+        (**self).hash_block(base_nonce, edge_bits, nodes)
+    }
+    
+    fn as_sync(&self) -> Option<&(dyn SipHasher + Sync)> {
+        (**self).as_sync()
+    }
+}
+
+/// Edges per `hash_block` call of the trimmers, one edges bitmap word
+pub const SIPHASH_BLOCK_EDGES: usize = 64;
+
+/// Surviving edges a bitmap word needs before trimmers hash it as a block
+/// (sparser words hash just their survivors)
+#[cfg(not(feature = "verify-only"))]
+const BLOCK_MIN_EDGES: u32 = 16;
+
+/// Get the node mask for the given edge bits
+/// 
+/// Nodes are always below 2^edge_bits. The C++ miner doesn't mask nodes
+/// with EDGE_BITS = 32, but it stores them as `u32`, which masks them just
+/// the same; with 33 or more edge bits only the explicit mask keeps the
+/// nodes inside the trimmers' bitmaps.
+pub fn node_mask(edge_bits: u32) -> u64 {
+    if edge_bits >= u64::BITS {
+        u64::MAX
+    } else {
+        (1 << edge_bits) - 1
+    }
+}
+
+/// Fill `edges` with the edges of indices `first_edge..`, splitting them
+/// across `threads` scoped threads
+/// 
+/// Each thread hashes a contiguous range of edges into its own disjoint
+/// slice of `edges` (like C++: nonces 2i and 2i + 1, U and V in order), so
+/// the result doesn't depend on the thread count. With one thread the
+/// edges are generated on the calling thread.
+pub fn fill_edges<H: SipHasher + Sync>(hasher: &H, edge_bits: u32, first_edge: u64, edges: &mut [Edge], threads: usize) {
+    let fill = |start: u64, slice: &mut [Edge]| {
+        let range = start..start + slice.len() as u64;
+        for (slot, edge) in slice.iter_mut().zip(EdgeIterator::with_range(hasher, edge_bits, range)) {
+            *slot = edge;
+        }
+    };
+    if threads <= 1 {
+        return fill(first_edge, edges);
+    }
+    
+    let per_thread = edges.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        for (index, slice) in edges.chunks_mut(per_thread).enumerate() {
+            let start = first_edge + (index * per_thread) as u64;
+            scope.spawn(move || fill(start, slice));
+        }
+    });
+}
+
+/// Hash a block of nodes, like `siphashxN` in the C++ miners
+/// 
+/// Writes the nodes of nonces `base_nonce + 2 * i` for `i` in `0..count`
+/// (one endpoint of `count` consecutive edges) to `nodes[..count]`, using
+/// the SIMD path of `SipHash` where there is one. Panics if `nodes` is
+/// shorter than `count`.
+pub fn siphash_block(keys: &SipKeys, base_nonce: u64, count: usize, edge_bits: u32, nodes: &mut [u64]) {
+    SipHash::with_key(*keys).hash_block(base_nonce, edge_bits, &mut nodes[..count]);
+}
+
+/// Call `visit(bit, node)` for every set bit of an edges bitmap word, with
+/// the node of the edge's endpoint `uorv` (0 for U, 1 for V)
+/// 
+/// Dense words are hashed as one block; sparse words (late trimming rounds)
+/// hash only their surviving edges.
+#[cfg(not(feature = "verify-only"))]
+pub(crate) fn for_each_word_node<H: SipHasher>(
+    hasher: &H,
+    edge_bits: u32,
+    word_index: usize,
+    word: u64,
+    uorv: u64,
+    mut visit: impl FnMut(usize, u64),
+) {
+    let base_nonce = (word_index * SIPHASH_BLOCK_EDGES) as u64 * 2 + uorv;
+    let mut unit = word;
+    if word.count_ones() >= BLOCK_MIN_EDGES {
+        let mut nodes = [0u64; SIPHASH_BLOCK_EDGES];
+        hasher.hash_block(base_nonce, edge_bits, &mut nodes);
+        while unit != 0 {
+            let bit = unit.trailing_zeros() as usize;
+            visit(bit, nodes[bit]);
+            unit &= unit - 1;
+        }
+    } else {
+        while unit != 0 {
+            let bit = unit.trailing_zeros() as usize;
+            visit(bit, hasher.hash_node(base_nonce + 2 * bit as u64, edge_bits).value());
+            unit &= unit - 1;
+        }
+    }
+}
+
+/// SipHash-2-4 of a nonce, shared by every `SipHasher` implementation
+/// 
+/// This implements the same algorithm as the C++ sipHash24 function.
+pub(crate) fn siphash24(keys: &[u64; 4], nonce: u64) -> u64 {
+    // Initialize states with keys (like C++: states[i] += keys[i])
+    let mut states = *keys;
+    
+    // Perform hash on states (exactly like C++ implementation)
+    states[3] ^= nonce;
+    sip_round(&mut states);
+    sip_round(&mut states);
+    states[0] ^= nonce;
+    states[2] ^= 255;
+    sip_round(&mut states);
+    sip_round(&mut states);
+    sip_round(&mut states);
+    sip_round(&mut states);
+    
+    states[0] ^ states[1] ^ states[2] ^ states[3]
+}
+
+/// SipRound implementation matching the C++ version exactly
+/// 
+/// This implements the same algorithm as the C++ sipRound function
+fn sip_round(states: &mut [u64; 4]) {
+    // Perform SipRound on states (exactly like C++ implementation)
+    // C++: states[0] += states[1];
+    states[0] = states[0].wrapping_add(states[1]);
+    
+    // C++: states[2] += states[3];
+    states[2] = states[2].wrapping_add(states[3]);
+    
+    // C++: states[1] = (states[1] << 13) | (states[1] >> (64 - 13));
+    states[1] = states[1].rotate_left(13);
+    
+    // C++: states[3] = (states[3] << 16) | (states[3] >> (64 - 16));
+    states[3] = states[3].rotate_left(16);
+    
+    // C++: states[1] ^= states[0];
+    states[1] ^= states[0];
+    
+    // C++: states[3] ^= states[2];
+    states[3] ^= states[2];
+    
+    // C++: states[0] = (states[0] << 32) | (states[0] >> (64 - 32));
+    states[0] = states[0].rotate_left(32);
+    
+    // C++: states[2] += states[1];
+    states[2] = states[2].wrapping_add(states[1]);
+    
+    // C++: states[0] += states[3];
+    states[0] = states[0].wrapping_add(states[3]);
+    
+    // C++: states[1] = (states[1] << 17) | (states[1] >> (64 - 17));
+    states[1] = states[1].rotate_left(17);
+    
+    // C++: states[3] = (states[3] << SIP_ROUND_ROTATION) | (states[3] >> (64 - SIP_ROUND_ROTATION));
+    // SIP_ROUND_ROTATION = 21
+    states[3] = states[3].rotate_left(21);
+    
+    // C++: states[1] ^= states[2];
+    states[1] ^= states[2];
+    
+    // C++: states[3] ^= states[0];
+    states[3] ^= states[0];
+    
+    // C++: states[2] = (states[2] << 32) | (states[2] >> (64 - 32));
+    states[2] = states[2].rotate_left(32);
+}
 
 /// SipHash-2-4 implementation for Cuckatoo
 /// 
@@ -10,7 +266,7 @@ use crate::blake2b::blake2b;
 /// to generate edges from headers and nonces.
 pub struct SipHash {
     /// SipHash key (256-bit for Cuckatoo) - generated from Blake2b
-    key: [u64; 4],
+    key: SipKeys,
 }
 
 impl SipHash {
@@ -18,126 +274,130 @@ impl SipHash {
     /// This matches the C++ implementation: blake2b(sipHashKeys, jobHeader, jobNonce)
     pub fn new_from_header(header: &Header, nonce: u64) -> Self {
         // Generate SipHash keys using Blake2b, exactly like C++ implementation
-        let key = blake2b(header.as_bytes(), nonce);
-        Self { key }
+        Self { key: blake2b(header.as_bytes(), nonce) }
     }
     
-    /// Create a new SipHash instance with custom key (for testing)
-    pub fn with_key(key: [u64; 4]) -> Self {
+    /// Create a new SipHash instance with the given keys
+    pub fn with_key(key: SipKeys) -> Self {
         Self { key }
     }
     
     /// Get the SipHash key
-    pub fn get_key(&self) -> [u64; 4] {
+    pub fn get_key(&self) -> SipKeys {
         self.key
     }
     
     /// Hash a header and nonce to generate edges
     /// 
     /// This generates 2^edge_bits edges using SipHash-2-4
-    /// as specified in the Cuckatoo algorithm.
+    /// as specified in the Cuckatoo algorithm. Use `edges` to stream
+    /// graphs too large to hold in memory.
     pub fn hash_header(&self, _header: &Header, edge_bits: u32) -> Result<Vec<Edge>> {
-        if edge_bits < 10 || edge_bits > 32 {
+        self.generate_edges(edge_bits, 1)
+    }
+    
+    /// Generate all 2^edge_bits edges on `threads` threads
+    /// 
+    /// The edges are the same as `hash_header`'s for any thread count.
+    /// Edge bits outside `SUPPORTED_EDGE_BITS` are rejected, and graphs
+    /// that don't fit in memory fail with `MemoryError`.
+    pub fn generate_edges(&self, edge_bits: u32, threads: usize) -> Result<Vec<Edge>> {
+        if !SUPPORTED_EDGE_BITS.contains(&edge_bits) {
             return Err(CuckatooError::InvalidEdgeBits(edge_bits));
         }
         
-        let edge_count = 1 << edge_bits;
-        let node_mask = edge_count - 1;
-        
-        let mut edges = Vec::with_capacity(edge_count as usize);
-        
-        // Generate edges exactly like C++ implementation
-        for edge_index in 0..edge_count {
-            // Generate nodes using SipHash-2-4 with nonces (edge_index * 2) and (edge_index * 2 + 1)
-            let nonce1 = edge_index * 2;
-            let nonce2 = edge_index * 2 + 1;
-            
-            let u = self.siphash24(nonce1, edge_bits, node_mask);
-            let v = self.siphash24(nonce2, edge_bits, node_mask);
-            
-            // Create edge connecting U and V partitions (preserve order like C++)
-            let edge = Edge::new(u, v);
-            edges.push(edge);
-        }
-        
+        let edge_count = checked_usize(1 << edge_bits, "Edge count")?;
+        let mut edges = Vec::new();
+        edges.try_reserve_exact(edge_count).map_err(|_| {
+            CuckatooError::MemoryError(format!("Cannot allocate the {} edges of an EDGE_BITS={} graph", edge_count, edge_bits))
+        })?;
+        edges.resize(edge_count, Edge::new(Node::new(0), Node::new(0)));
+        fill_edges(self, edge_bits, 0, &mut edges, threads);
         Ok(edges)
     }
     
-    /// SipHash-2-4 implementation matching the C++ version exactly
+    /// Iterate over the edges of the graph without storing them
     /// 
-    /// This implements the same algorithm as the C++ sipHash24 function
-    fn siphash24(&self, nonce: u64, edge_bits: u32, node_mask: u64) -> Node {
-        // Initialize states with keys (like C++: states[i] += keys[i])
-        let mut states = self.key;
-        
-        // Perform hash on states (exactly like C++ implementation)
-        states[3] ^= nonce;
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        states[0] ^= nonce;
-        states[2] ^= 255;
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        self.sip_round(&mut states);
-        
-        // Get node from states (like C++: *nodes = (states[0] ^ states[1] ^ states[2] ^ states[3]) & NODE_MASK)
-        let node_value = if edge_bits == 32 {
-            states[0] ^ states[1] ^ states[2] ^ states[3]
-        } else {
-            (states[0] ^ states[1] ^ states[2] ^ states[3]) & node_mask
-        };
-        
-        Node::new(node_value)
-    }
-    
-    /// SipRound implementation matching the C++ version exactly
+    /// Yields the same edges as `hash_header`, in index order.
+    pub fn edges(&self, edge_bits: u32) -> EdgeIterator<&Self> {
+        EdgeIterator::new(self, edge_bits)
+    }
+    
+    /// Name of the SIMD instruction set batched hashing uses on this CPU
+    /// ("avx2", "sse2", "simd128" or "scalar")
+    pub fn simd_backend() -> &'static str {
+        #[cfg(target_arch = "x86_64")]
+        return crate::siphash_x86::backend();
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+        return "simd128";
+        #[allow(unreachable_code)]
+        "scalar"
+    }
+    
+    /// Get the edges with the given edge indices
     /// 
-    /// This implements the same algorithm as the C++ sipRound function
-    fn sip_round(&self, states: &mut [u64; 4]) {
-        // Perform SipRound on states (exactly like C++ implementation)
-        // C++: states[0] += states[1];
-        states[0] = states[0].wrapping_add(states[1]);
+    /// Used to check proofs, where only a few edges of the graph are needed.
+    pub fn edges_for_indices(&self, edge_indices: &[u64], edge_bits: u32) -> Vec<Edge> {
+        // Edge i uses nonces 2i and 2i + 1 (like C++)
+        let nonces: Vec<u64> = edge_indices
+            .iter()
+            .flat_map(|&index| [index * 2, index * 2 + 1])
+            .collect();
+        let mut nodes = vec![0u64; nonces.len()];
+        self.hash_nonces(&nonces, edge_bits, &mut nodes);
         
-        // C++: states[2] += states[3];
-        states[2] = states[2].wrapping_add(states[3]);
-        
-        // C++: states[1] = (states[1] << 13) | (states[1] >> (64 - 13));
-        states[1] = states[1].rotate_left(13);
-        
-        // C++: states[3] = (states[3] << 16) | (states[3] >> (64 - 16));
-        states[3] = states[3].rotate_left(16);
-        
-        // C++: states[1] ^= states[0];
-        states[1] ^= states[0];
-        
-        // C++: states[3] ^= states[2];
-        states[3] ^= states[2];
-        
-        // C++: states[0] = (states[0] << 32) | (states[0] >> (64 - 32));
-        states[0] = states[0].rotate_left(32);
-        
-        // C++: states[2] += states[1];
-        states[2] = states[2].wrapping_add(states[1]);
-        
-        // C++: states[0] += states[3];
-        states[0] = states[0].wrapping_add(states[3]);
-        
-        // C++: states[1] = (states[1] << 17) | (states[1] >> (64 - 17));
-        states[1] = states[1].rotate_left(17);
-        
-        // C++: states[3] = (states[3] << SIP_ROUND_ROTATION) | (states[3] >> (64 - SIP_ROUND_ROTATION));
-        // SIP_ROUND_ROTATION = 21
-        states[3] = states[3].rotate_left(21);
-        
-        // C++: states[1] ^= states[2];
-        states[1] ^= states[2];
+        nodes
+            .chunks_exact(2)
+            .map(|pair| Edge::new(Node::new(pair[0]), Node::new(pair[1])))
+            .collect()
+    }
+}
+
+impl SipHasher for SipHash {
+    fn hash_nonce(&self, nonce: u64) -> u64 {
+        siphash24(&self.key.words(), nonce)
+    }
+    
+    /// Hash a batch of nonces into nodes (`nodes[i]` is the node for `nonces[i]`)
+    /// 
+    /// On x86_64 four nonces are hashed at once with AVX2 (two with SSE2 on
+    /// older CPUs), chosen at runtime. On wasm32 with `simd128` two nonces
+    /// are hashed at once, which keeps in-browser proof verification fast.
+    /// Panics if `nodes` is shorter than `nonces`.
+    fn hash_nonces(&self, nonces: &[u64], edge_bits: u32, nodes: &mut [u64]) {
+        let node_mask = node_mask(edge_bits);
+        let nodes = &mut nodes[..nonces.len()];
         
-        // C++: states[3] ^= states[0];
-        states[3] ^= states[0];
+        #[cfg(target_arch = "x86_64")]
+        let hashed = crate::siphash_x86::hash_nonce_lanes(&self.key.words(), nonces, node_mask, nodes);
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+        let hashed = crate::siphash_wasm::hash_nonce_pairs(&self.key.words(), nonces, node_mask, nodes);
+        #[cfg(not(any(target_arch = "x86_64", all(target_arch = "wasm32", target_feature = "simd128"))))]
+        let hashed = 0;
         
-        // C++: states[2] = (states[2] << 32) | (states[2] >> (64 - 32));
-        states[2] = states[2].rotate_left(32);
+        for (node, &nonce) in nodes[hashed..].iter_mut().zip(&nonces[hashed..]) {
+            *node = self.hash_nonce(nonce) & node_mask;
+        }
+    }
+    
+    /// Hash a block through `hash_nonces`, so blocks use the SIMD paths
+    fn hash_block(&self, base_nonce: u64, edge_bits: u32, nodes: &mut [u64]) {
+        let mut nonces = [0u64; SIPHASH_BLOCK_EDGES];
+        for (block, chunk) in nodes.chunks_mut(SIPHASH_BLOCK_EDGES).enumerate() {
+            let first = base_nonce + (block * SIPHASH_BLOCK_EDGES * 2) as u64;
+            for (i, nonce) in nonces[..chunk.len()].iter_mut().enumerate() {
+                *nonce = first + 2 * i as u64;
+            }
+            self.hash_nonces(&nonces[..chunk.len()], edge_bits, chunk);
+        }
+    }
+    
+    fn keys(&self) -> SipKeys {
+        self.key
+    }
+    
+    fn as_sync(&self) -> Option<&(dyn SipHasher + Sync)> {
+        Some(self)
     }
 }
 
@@ -145,10 +405,10 @@ impl Default for SipHash {
     fn default() -> Self {
         // Default key for testing (should not be used in production)
         Self {
-            key: [
+            key: SipKeys::test_seed([
                 0x736f6d6570736575, 0x646f72616e646f6d,
                 0x6c7967656e657261, 0x7465646279746573
-            ],
+            ]),
         }
     }
 }
@@ -188,6 +448,86 @@ mod tests {
         assert_eq!(edges1, edges2);
     }
     
+    #[test]
+    fn test_batched_hashing_matches_edges() {
+        let header = Header::new(b"test header");
+        let siphash = SipHash::new_from_header(&header, 12345);
+        let edges = siphash.hash_header(&header, 10).unwrap();
+        
+        let indices = [0u64, 1, 7, 500, 1023];
+        let batched = siphash.edges_for_indices(&indices, 10);
+        for (edge, &index) in batched.iter().zip(&indices) {
+            assert_eq!(*edge, edges[index as usize]);
+        }
+        // The SIMD batches agree with hashing one edge at a time
+        for (index, edge) in edges.iter().enumerate() {
+            assert_eq!(*edge, siphash.hash_pair(index as u64, 10));
+        }
+        
+        // An odd count exercises the scalar remainder of the SIMD path
+        let mut nodes = [0u64; 3];
+        siphash.hash_nonces(&[0, 1, 2], 10, &mut nodes);
+        assert_eq!(nodes, [edges[0].u.value(), edges[0].v.value(), edges[1].u.value()]);
+    }
+    
+    #[test]
+    fn test_nodes_masked_above_32_bits() {
+        assert_eq!(node_mask(32), u32::MAX as u64);
+        assert_eq!(node_mask(33), (1 << 33) - 1);
+        
+        let siphash = SipHash::with_key(crate::self_test::SIPHASH_TEST_KEYS);
+        let edges: Vec<Edge> = EdgeIterator::with_range(&siphash, 35, 0..1000).collect();
+        assert!(edges.iter().all(|edge| edge.u.value() < 1 << 35 && edge.v.value() < 1 << 35));
+        // Nodes do use the bits above 32
+        assert!(edges.iter().any(|edge| edge.u.value() >= 1 << 32));
+    }
+    
+    #[test]
+    fn test_parallel_generation_matches() {
+        let siphash = SipHash::with_key(crate::self_test::SIPHASH_TEST_KEYS);
+        let expected = siphash.hash_header(&Header::new(b""), 12).unwrap();
+        for threads in [2, 3, 8] {
+            assert_eq!(siphash.generate_edges(12, threads).unwrap(), expected);
+        }
+        assert!(matches!(siphash.generate_edges(64, 4), Err(CuckatooError::InvalidEdgeBits(64))));
+        // Research graph sizes below 10 edge bits
+        let tiny = siphash.generate_edges(6, 4).unwrap();
+        assert_eq!(tiny, siphash.edges(6).collect::<Vec<_>>());
+        
+        // An offset range on more threads than edges
+        let mut edges = vec![Edge::new(Node::new(0), Node::new(0)); 5];
+        fill_edges(&siphash, 12, 100, &mut edges, 16);
+        assert_eq!(edges, expected[100..105]);
+    }
+    
+    #[test]
+    fn test_siphash_block() {
+        let siphash = SipHash::with_key(crate::self_test::SIPHASH_TEST_KEYS);
+        let edges = siphash.hash_header(&Header::new(b""), 10).unwrap();
+        
+        // V nodes of edges 3..203, longer than one block
+        let mut nodes = vec![0u64; 201];
+        siphash_block(&siphash.get_key(), 7, 200, 10, &mut nodes);
+        for (i, node) in nodes[..200].iter().enumerate() {
+            assert_eq!(*node, edges[3 + i].v.value());
+        }
+        assert_eq!(nodes[200], 0);
+        
+        // The scalar default agrees with the SIMD override
+        struct Scalar(SipKeys);
+        impl SipHasher for Scalar {
+            fn hash_nonce(&self, nonce: u64) -> u64 {
+                siphash24(&self.0.words(), nonce)
+            }
+            fn keys(&self) -> SipKeys {
+                self.0
+            }
+        }
+        let mut scalar = vec![0u64; 200];
+        Scalar(siphash.get_key()).hash_block(7, 10, &mut scalar);
+        assert_eq!(scalar, nodes[..200]);
+    }
+    
     #[test]
     fn test_siphash_different_nonces() {
         let header = Header::new(b"test header");
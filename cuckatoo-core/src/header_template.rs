@@ -0,0 +1,216 @@
+//! Pre-PoW header construction for local Grin devnets
+//!
+//! Builds the 238-byte pre-PoW header (the bytes hashed with the nonce to
+//! derive the SipHash keys) from its fields, so integration tests can mine
+//! blocks for a local node without hand-crafting byte arrays. Fields are
+//! serialized big-endian, like Grin does.
+
+use crate::{CuckatooError, Header, Result};
+
+/// Size of a serialized pre-PoW header
+pub const PRE_POW_SIZE: usize = 238;
+
+/// Size of a hash or commitment in the header
+pub const HASH_SIZE: usize = 32;
+
+/// Heights between header version bumps on testing chains
+const TESTING_HARD_FORK_INTERVAL: u64 = 3;
+
+/// Latest header version
+const MAX_HEADER_VERSION: u16 = 5;
+
+/// Header version a devnet node expects at `height`
+///
+/// Testing chains hard fork every few blocks up to the latest version.
+pub fn devnet_header_version(height: u64) -> u16 {
+    let version = height / TESTING_HARD_FORK_INTERVAL + 1;
+    version.min(MAX_HEADER_VERSION as u64) as u16
+}
+
+/// Fields of a pre-PoW header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderTemplate {
+    /// Header version
+    pub version: u16,
+    /// Block height
+    pub height: u64,
+    /// Block timestamp in seconds since the Unix epoch
+    pub timestamp: i64,
+    /// Hash of the previous block header
+    pub prev_hash: [u8; HASH_SIZE],
+    /// Root of the previous header MMR
+    pub prev_root: [u8; HASH_SIZE],
+    /// Root of the output MMR
+    pub output_root: [u8; HASH_SIZE],
+    /// Root of the range proof MMR
+    pub range_proof_root: [u8; HASH_SIZE],
+    /// Root of the kernel MMR
+    pub kernel_root: [u8; HASH_SIZE],
+    /// Total kernel offset
+    pub total_kernel_offset: [u8; HASH_SIZE],
+    /// Size of the output MMR
+    pub output_mmr_size: u64,
+    /// Size of the kernel MMR
+    pub kernel_mmr_size: u64,
+    /// Total difficulty including this block
+    pub total_difficulty: u64,
+    /// Secondary PoW scaling factor
+    pub secondary_scaling: u32,
+}
+
+impl HeaderTemplate {
+    /// Template for a devnet block at `height` on top of `prev_hash`
+    ///
+    /// The version follows `devnet_header_version`. Roots, MMR sizes and the
+    /// timestamp start zeroed and can be set with the builder methods.
+    pub fn devnet(height: u64, prev_hash: [u8; HASH_SIZE]) -> Self {
+        Self {
+            version: devnet_header_version(height),
+            height,
+            timestamp: 0,
+            prev_hash,
+            prev_root: [0; HASH_SIZE],
+            output_root: [0; HASH_SIZE],
+            range_proof_root: [0; HASH_SIZE],
+            kernel_root: [0; HASH_SIZE],
+            total_kernel_offset: [0; HASH_SIZE],
+            output_mmr_size: 0,
+            kernel_mmr_size: 0,
+            total_difficulty: 1,
+            secondary_scaling: 0,
+        }
+    }
+
+    /// Set the header version
+    pub fn version(mut self, version: u16) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the timestamp
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Set the MMR roots (previous header, output, range proof, kernel)
+    pub fn roots(
+        mut self,
+        prev_root: [u8; HASH_SIZE],
+        output_root: [u8; HASH_SIZE],
+        range_proof_root: [u8; HASH_SIZE],
+        kernel_root: [u8; HASH_SIZE],
+    ) -> Self {
+        self.prev_root = prev_root;
+        self.output_root = output_root;
+        self.range_proof_root = range_proof_root;
+        self.kernel_root = kernel_root;
+        self
+    }
+
+    /// Set the total kernel offset
+    pub fn total_kernel_offset(mut self, offset: [u8; HASH_SIZE]) -> Self {
+        self.total_kernel_offset = offset;
+        self
+    }
+
+    /// Set the output and kernel MMR sizes
+    pub fn mmr_sizes(mut self, output_mmr_size: u64, kernel_mmr_size: u64) -> Self {
+        self.output_mmr_size = output_mmr_size;
+        self.kernel_mmr_size = kernel_mmr_size;
+        self
+    }
+
+    /// Set the total difficulty and secondary scaling
+    pub fn difficulty(mut self, total_difficulty: u64, secondary_scaling: u32) -> Self {
+        self.total_difficulty = total_difficulty;
+        self.secondary_scaling = secondary_scaling;
+        self
+    }
+
+    /// Serialize the pre-PoW header
+    pub fn pre_pow(&self) -> [u8; PRE_POW_SIZE] {
+        let mut bytes = [0u8; PRE_POW_SIZE];
+        let mut offset = 0;
+        let mut put = |field: &[u8]| {
+            bytes[offset..offset + field.len()].copy_from_slice(field);
+            offset += field.len();
+        };
+
+        put(&self.version.to_be_bytes());
+        put(&self.height.to_be_bytes());
+        put(&self.timestamp.to_be_bytes());
+        put(&self.prev_hash);
+        put(&self.prev_root);
+        put(&self.output_root);
+        put(&self.range_proof_root);
+        put(&self.kernel_root);
+        put(&self.total_kernel_offset);
+        put(&self.output_mmr_size.to_be_bytes());
+        put(&self.kernel_mmr_size.to_be_bytes());
+        put(&self.total_difficulty.to_be_bytes());
+        put(&self.secondary_scaling.to_be_bytes());
+        bytes
+    }
+
+    /// Build the mining header
+    pub fn to_header(&self) -> Header {
+        Header::new(&self.pre_pow())
+    }
+}
+
+/// Parse a 32-byte hash from hex (as printed by Grin nodes)
+pub fn parse_hash(hex: &str) -> Result<[u8; HASH_SIZE]> {
+    let hex = hex.trim();
+    if hex.len() != HASH_SIZE * 2 || !hex.is_ascii() {
+        return Err(CuckatooError::InternalError(format!("Expected a {}-digit hex hash: {}", HASH_SIZE * 2, hex)));
+    }
+
+    let mut hash = [0u8; HASH_SIZE];
+    for (byte, digits) in hash.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        let digits = std::str::from_utf8(digits).expect("ASCII digits");
+        *byte = u8::from_str_radix(digits, 16)
+            .map_err(|_| CuckatooError::InternalError(format!("Invalid hex hash: {}", hex)))?;
+    }
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pre_pow_layout() {
+        let prev_hash = [0xab; HASH_SIZE];
+        let template = HeaderTemplate::devnet(4, prev_hash)
+            .timestamp(0x0102)
+            .mmr_sizes(7, 3)
+            .difficulty(1000, 0x10);
+        let bytes = template.pre_pow();
+
+        assert_eq!(bytes[..2], [0, 2]);
+        assert_eq!(bytes[2..10], 4u64.to_be_bytes());
+        assert_eq!(bytes[10..18], 0x0102i64.to_be_bytes());
+        assert_eq!(bytes[18..50], prev_hash);
+        assert_eq!(bytes[210..218], 7u64.to_be_bytes());
+        assert_eq!(bytes[218..226], 3u64.to_be_bytes());
+        assert_eq!(bytes[226..234], 1000u64.to_be_bytes());
+        assert_eq!(bytes[234..], 0x10u32.to_be_bytes());
+        assert_eq!(template.to_header().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_devnet_header_version() {
+        assert_eq!(devnet_header_version(0), 1);
+        assert_eq!(devnet_header_version(3), 2);
+        assert_eq!(devnet_header_version(1000), MAX_HEADER_VERSION);
+    }
+
+    #[test]
+    fn test_parse_hash() {
+        let hex = "00".repeat(31) + "ff";
+        assert_eq!(parse_hash(&hex).unwrap()[31], 0xff);
+        assert!(parse_hash("ff").is_err());
+        assert!(parse_hash(&"zz".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Mining jobs and their proof-of-work algorithm
+//!
+//! Pools serving several algorithms name the algorithm in each job. The
+//! job carries it, so a miner (or verifier) picks the graph size and
+//! verifier per job instead of per process.
+
+use crate::{verify_proof_nonces, CuckatooError, Header, Result, SipKeys};
+use std::fmt;
+
+/// Proof-of-work algorithm of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// Cuckatoo with 2^31 edges
+    Cuckatoo31,
+    /// Cuckatoo with 2^32 edges
+    Cuckatoo32,
+    /// Cuckaroo with 2^29 edges
+    Cuckaroo29,
+    /// Cuckarood with 2^29 edges
+    Cuckarood29,
+    /// Cuckaroom with 2^29 edges
+    Cuckaroom29,
+    /// Cuckarooz with 2^29 edges
+    Cuckarooz29,
+}
+
+impl Algorithm {
+    /// Every algorithm, in order
+    pub const ALL: [Algorithm; 6] = [
+        Algorithm::Cuckatoo31,
+        Algorithm::Cuckatoo32,
+        Algorithm::Cuckaroo29,
+        Algorithm::Cuckarood29,
+        Algorithm::Cuckaroom29,
+        Algorithm::Cuckarooz29,
+    ];
+
+    /// Edge bits of the algorithm's graphs
+    pub fn edge_bits(&self) -> u32 {
+        match self {
+            Algorithm::Cuckatoo31 => 31,
+            Algorithm::Cuckatoo32 => 32,
+            Algorithm::Cuckaroo29 | Algorithm::Cuckarood29 | Algorithm::Cuckaroom29 | Algorithm::Cuckarooz29 => 29,
+        }
+    }
+
+    /// Check if this crate can generate, trim and verify the algorithm's
+    /// graphs (only the Cuckatoo family)
+    pub fn is_supported(&self) -> bool {
+        matches!(self, Algorithm::Cuckatoo31 | Algorithm::Cuckatoo32)
+    }
+
+    /// Fail with `InternalError` if the algorithm isn't supported
+    pub fn ensure_supported(&self) -> Result<()> {
+        if !self.is_supported() {
+            return Err(CuckatooError::InternalError(format!("Algorithm {} is not supported", self)));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Algorithm::Cuckatoo31 => "cuckatoo31",
+            Algorithm::Cuckatoo32 => "cuckatoo32",
+            Algorithm::Cuckaroo29 => "cuckaroo29",
+            Algorithm::Cuckarood29 => "cuckarood29",
+            Algorithm::Cuckaroom29 => "cuckaroom29",
+            Algorithm::Cuckarooz29 => "cuckarooz29",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = CuckatooError;
+
+    /// Parse an algorithm name such as `cuckatoo31` (`cuckatoo_31` and
+    /// `Cuckatoo31` are accepted too)
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.to_lowercase().replace('_', "");
+        Algorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.to_string() == name)
+            .ok_or_else(|| CuckatooError::InternalError(format!("Unknown algorithm: {}", s)))
+    }
+}
+
+/// A job received from a pool or node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiningJob {
+    /// Job id assigned by the pool
+    pub job_id: u64,
+    /// Height of the block being mined
+    pub height: u64,
+    /// Pre-PoW header bytes
+    pub pre_pow: Vec<u8>,
+    /// Algorithm the job must be solved with
+    pub algorithm: Algorithm,
+    /// Share difficulty
+    pub difficulty: u64,
+}
+
+impl MiningJob {
+    /// Mining header of the job with `nonce`
+    pub fn header(&self, nonce: u64) -> Header {
+        Header::new_with_nonce(&self.pre_pow, nonce)
+    }
+
+    /// SipHash keys of the job's graph for `nonce`
+    pub fn keys(&self, nonce: u64) -> SipKeys {
+        SipKeys::from_header(&self.pre_pow, nonce)
+    }
+
+    /// Verify proof nonces for the job with the job's algorithm
+    pub fn verify(&self, nonce: u64, proof: &[u64]) -> Result<()> {
+        self.algorithm.ensure_supported()?;
+        verify_proof_nonces(&self.keys(nonce), self.algorithm.edge_bits(), proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_algorithm_names() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(algorithm.to_string().parse::<Algorithm>().unwrap(), algorithm);
+        }
+        assert_eq!("Cuckatoo_32".parse::<Algorithm>().unwrap(), Algorithm::Cuckatoo32);
+        assert!("cuckoo30".parse::<Algorithm>().is_err());
+        assert_eq!(Algorithm::Cuckarood29.edge_bits(), 29);
+    }
+
+    #[test]
+    fn test_job_verification_follows_algorithm() {
+        let mut job = MiningJob {
+            job_id: 1,
+            height: 10,
+            pre_pow: vec![1, 2, 3],
+            algorithm: Algorithm::Cuckatoo31,
+            difficulty: 1,
+        };
+        let proof: Vec<u64> = (0..42).collect();
+        assert!(matches!(job.verify(5, &proof), Err(CuckatooError::VerificationError(_))));
+
+        job.algorithm = Algorithm::Cuckaroo29;
+        assert!(matches!(job.verify(5, &proof), Err(CuckatooError::InternalError(_))));
+        assert_eq!(job.header(5).nonce(), 5);
+        assert_eq!(job.keys(5), crate::blake2b(&[1, 2, 3], 5));
+    }
+}
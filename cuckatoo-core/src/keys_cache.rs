@@ -0,0 +1,168 @@
+//! Cache of SipHash keys for repeated header/nonce pairs
+//!
+//! Pools re-verify many shares against the same job, so the same header and
+//! nonce come back over and over. A small LRU cache keyed by a hash of the
+//! header and the nonce saves recomputing Blake2b for each of them.
+
+use crate::consensus::verify_proof_nonces;
+use crate::{blake2b, Result, SipKeys};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Default number of cached keys
+pub const DEFAULT_KEYS_CACHE_CAPACITY: usize = 64;
+
+/// Cached keys of one header/nonce pair
+#[derive(Debug)]
+struct CacheEntry {
+    /// Full header, compared on lookup so hash collisions can't return wrong keys
+    header: Box<[u8]>,
+    /// SipHash keys derived from the header and nonce
+    keys: SipKeys,
+    /// Lookup tick of the last use, for LRU eviction
+    last_used: u64,
+}
+
+/// LRU cache mapping (header hash, nonce) to SipHash keys
+#[derive(Debug)]
+pub struct KeysCache {
+    entries: HashMap<(u64, u64), CacheEntry>,
+    capacity: usize,
+    /// Incremented on every lookup
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl KeysCache {
+    /// Create a cache holding up to `capacity` keys (at least 1)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            entries: HashMap::with_capacity(capacity),
+            capacity,
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Get the SipHash keys of a header and nonce, deriving them with
+    /// Blake2b on a miss
+    pub fn keys(&mut self, header: &[u8], nonce: u64) -> SipKeys {
+        self.tick += 1;
+        let key = (header_hash(header), nonce);
+
+        if let Some(entry) = self.entries.get_mut(&key) {
+            if *entry.header == *header {
+                entry.last_used = self.tick;
+                self.hits += 1;
+                return entry.keys;
+            }
+        }
+
+        self.misses += 1;
+        let keys = blake2b(header, nonce);
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            self.evict_least_recently_used();
+        }
+        self.entries.insert(key, CacheEntry {
+            header: header.into(),
+            keys,
+            last_used: self.tick,
+        });
+        keys
+    }
+
+    /// Verify proof nonces for a header and nonce, using cached keys
+    pub fn verify_proof(&mut self, header: &[u8], nonce: u64, edge_bits: u32, nonces: &[u64]) -> Result<()> {
+        let keys = self.keys(header, nonce);
+        verify_proof_nonces(&keys, edge_bits, nonces)
+    }
+
+    /// Number of cached keys
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Maximum number of cached keys
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of lookups answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Number of lookups that derived new keys
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Remove all cached keys (for example when a new block arrives)
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        // The cache is small, so a scan is cheaper than keeping an ordered list
+        if let Some(&oldest) = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key) {
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+impl Default for KeysCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEYS_CACHE_CAPACITY)
+    }
+}
+
+/// Hash a header for the cache key
+fn header_hash(header: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    header.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_keys_match_blake2b() {
+        let mut cache = KeysCache::new(4);
+        let keys = cache.keys(b"header", 7);
+        assert_eq!(keys, blake2b(b"header", 7));
+        assert_eq!(cache.keys(b"header", 7), keys);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // A different nonce or header is a different entry
+        assert_eq!(cache.keys(b"header", 8), blake2b(b"header", 8));
+        assert_eq!(cache.keys(b"other", 7), blake2b(b"other", 7));
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let mut cache = KeysCache::new(2);
+        cache.keys(b"header", 1);
+        cache.keys(b"header", 2);
+        cache.keys(b"header", 1);
+        cache.keys(b"header", 3);
+        assert_eq!(cache.len(), 2);
+
+        // Nonce 2 was evicted, nonce 1 is still cached
+        cache.keys(b"header", 1);
+        assert_eq!(cache.hits(), 2);
+        cache.keys(b"header", 2);
+        assert_eq!(cache.misses(), 4);
+    }
+}
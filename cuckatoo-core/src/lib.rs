@@ -5,29 +5,229 @@
 //! - Lean edge trimming with bitmap-based approach
 //! - Cycle verification for 42-cycles
 //! - Performance timing and benchmarking
+//! 
+//! With the `verify-only` feature only the types, hashing, Blake2b, the
+//! consensus verifier and its keys cache are compiled, for nodes and light clients.
+//! `self_test::run_self_test` checks a build against known-answer vectors.
 
 pub mod types;
 pub mod hashing;
+pub mod edge_iterator;
+pub mod edge_list;
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod siphash_wasm;
+#[cfg(target_arch = "x86_64")]
+mod siphash_x86;
 pub mod blake2b;
+pub mod endian;
+pub mod sip_keys;
+pub mod consensus;
+pub mod keys_cache;
+pub mod header_template;
+pub mod nonce_scheduler;
+pub mod submission;
+pub mod job;
+pub mod seed;
+pub mod preset;
+pub mod self_test;
+pub mod profiling;
+
+// Mining modules, left out of `verify-only` builds
+#[cfg(not(feature = "verify-only"))]
 pub mod trimming;
+#[cfg(not(feature = "verify-only"))]
+pub mod bitmap;
+#[cfg(not(feature = "verify-only"))]
+mod huge_pages;
+#[cfg(all(unix, feature = "mmap", not(feature = "verify-only")))]
+mod mmap;
+#[cfg(not(feature = "verify-only"))]
+mod numa;
+#[cfg(not(feature = "verify-only"))]
 pub mod bitmap_trimming;
+#[cfg(not(feature = "verify-only"))]
+pub mod counter_trimming;
+#[cfg(not(feature = "verify-only"))]
+pub mod round_controller;
+#[cfg(not(feature = "verify-only"))]
+pub mod progress;
+#[cfg(not(feature = "verify-only"))]
+pub mod cancel;
+#[cfg(not(feature = "verify-only"))]
+pub mod checkpoint;
+#[cfg(not(feature = "verify-only"))]
+pub mod trim_output;
+#[cfg(not(feature = "verify-only"))]
+pub mod recovery;
+#[cfg(not(feature = "verify-only"))]
+pub mod trimmer_pool;
+#[cfg(not(feature = "verify-only"))]
 pub mod hash_cycle_finder;
-// pub mod cpp_cycle_finder; // Temporarily disabled due to complex borrowing issues
+#[cfg(not(feature = "verify-only"))]
+pub mod array_cycle_finder;
+#[cfg(not(feature = "verify-only"))]
+pub mod cpp_cycle_finder;
+#[cfg(not(feature = "verify-only"))]
+pub mod path_solver;
+#[cfg(not(feature = "verify-only"))]
 pub mod exact_siphash;
+#[cfg(not(feature = "verify-only"))]
 pub mod exact_trimming;
+#[cfg(not(feature = "verify-only"))]
+pub mod reference_trimming;
+#[cfg(not(feature = "verify-only"))]
 pub mod verification;
+#[cfg(not(feature = "verify-only"))]
+mod union_find;
+#[cfg(not(feature = "verify-only"))]
 pub mod timing;
+#[cfg(not(feature = "verify-only"))]
+pub mod metrics;
+#[cfg(not(feature = "verify-only"))]
+pub mod metrics_server;
+#[cfg(not(feature = "verify-only"))]
+pub mod alloc_tracking;
+#[cfg(not(feature = "verify-only"))]
+pub mod energy;
+#[cfg(not(feature = "verify-only"))]
+pub mod memory;
+#[cfg(not(feature = "verify-only"))]
+pub mod solver;
+#[cfg(not(feature = "verify-only"))]
+pub mod replay;
+#[cfg(not(feature = "verify-only"))]
+pub mod graph;
+#[cfg(not(feature = "verify-only"))]
+pub mod graph_stats;
+#[cfg(not(feature = "verify-only"))]
+pub mod endpoint_cache;
+#[cfg(not(feature = "verify-only"))]
+pub mod capabilities;
+#[cfg(not(feature = "verify-only"))]
+pub mod preflight;
+#[cfg(not(feature = "verify-only"))]
+pub mod report;
+#[cfg(not(feature = "verify-only"))]
+pub mod baseline;
+#[cfg(not(feature = "verify-only"))]
+pub mod sink;
+#[cfg(all(feature = "opencl", not(feature = "verify-only")))]
+pub mod gpu_trimming;
+#[cfg(all(feature = "wgpu", not(feature = "verify-only")))]
+pub mod wgpu_trimming;
+#[cfg(all(test, not(feature = "verify-only")))]
+mod trim_oracle;
+
+pub mod prelude;
 
-pub use types::*;
-pub use hashing::*;
-pub use blake2b::*;
-pub use trimming::*;
-pub use bitmap_trimming::*;
-pub use hash_cycle_finder::*;
-pub use exact_siphash::*;
-pub use exact_trimming::*;
-pub use verification::*;
-pub use timing::*;
+// Stable top-level API. Less common items stay under their module path
+// (for example `endian::pack_proof` or `hash_cycle_finder::NodeConnectionLink`).
+pub use types::{
+    Edge, Node, Header, Proof, Config, TrimmingMode, ValidationPolicy, NumaPolicy, SolverStrategy, PerformanceMetrics,
+    SOLUTION_SIZE, EDGE_NUMBER_OF_COMPONENTS, MAX_ADDRESSABLE_EDGE_BITS, CONSENSUS_EDGE_BITS, SUPPORTED_EDGE_BITS, checked_usize,
+};
+pub use hashing::{SipHash, SipHasher};
+pub use edge_iterator::{EdgeIterator, EdgeChunks};
+pub use edge_list::{EdgeList, EdgeListFormat};
+pub use blake2b::{blake2b, blake2b_digest, GrinBlake2b, KeyDerivation, KeySchedule, ReferenceBlake2b, TestSeedKeys};
+pub use sip_keys::{SipKeys, KeyProvenance};
+pub use consensus::{verify_proof, verify_proof_nonces, check_proof_nonces, check_proof_nonces_with_policy, reverify_solution, duplicate_edges, VerifyFailure};
+pub use keys_cache::KeysCache;
+pub use header_template::{HeaderTemplate, PRE_POW_SIZE};
+pub use nonce_scheduler::{entropy_start_nonce, NonceScheduler, NonceSchedule};
+pub use submission::{Solution, SubmissionPolicy, ShareFreshness, proof_difficulty, proof_hash};
+pub use job::{Algorithm, MiningJob};
+pub use seed::GraphSeed;
+pub use preset::Preset;
+
+#[cfg(not(feature = "verify-only"))]
+pub use trimming::LeanTrimmer;
+#[cfg(not(feature = "verify-only"))]
+pub use bitmap::{AtomicBitmap, Bitmap};
+#[cfg(not(feature = "verify-only"))]
+pub use bitmap_trimming::{BitmapTrimmer, DEFAULT_CHUNK_WORDS};
+#[cfg(not(feature = "verify-only"))]
+pub use counter_trimming::CounterTrimmer;
+#[cfg(not(feature = "verify-only"))]
+pub use round_controller::{AdaptiveRoundController, AdaptiveTrimResult, EarlyExit, DEFAULT_MIN_REDUCTION, DEFAULT_PLATEAU_ROUNDS};
+#[cfg(not(feature = "verify-only"))]
+pub use progress::{ProgressObserver, RoundProgress};
+#[cfg(not(feature = "verify-only"))]
+pub use cancel::{CancelToken, SearchBudget};
+#[cfg(not(feature = "verify-only"))]
+pub use checkpoint::TrimCheckpoint;
+#[cfg(not(feature = "verify-only"))]
+pub use trim_output::TrimOutput;
+#[cfg(not(feature = "verify-only"))]
+pub use recovery::recover_proof;
+#[cfg(not(feature = "verify-only"))]
+pub use trimmer_pool::TrimmerPool;
+#[cfg(not(feature = "verify-only"))]
+pub use hash_cycle_finder::{HashCycleFinder, CycleSearchStats};
+#[cfg(not(feature = "verify-only"))]
+pub use array_cycle_finder::ArrayCycleFinder;
+#[cfg(not(feature = "verify-only"))]
+pub use cpp_cycle_finder::CppCycleFinder;
+#[cfg(not(feature = "verify-only"))]
+pub use exact_siphash::ExactSipHash;
+#[cfg(not(feature = "verify-only"))]
+pub use exact_trimming::ExactTrimmer;
+#[cfg(not(feature = "verify-only"))]
+pub use reference_trimming::ReferenceTrimmer;
+#[cfg(not(feature = "verify-only"))]
+pub use verification::{CycleVerifier, OptimizedCycleVerifier, Strategy};
+#[cfg(not(feature = "verify-only"))]
+pub use timing::{
+    PerformanceTimer, SharedPerformanceTimer, PhaseRecord, PHASE_SEPARATOR,
+    ResourceUsage, ResourceMeasurement,
+    BenchmarkRunner, BenchmarkResult, BenchmarkSample, BenchmarkRank, BenchmarkComparison,
+    DEFAULT_OUTLIER_FRACTION, LeanTrimmerComparison, compare_lean_trimmers, compare_cycle_finders,
+    measure_time, measure_time_logged, measure_time_with_resources, measure_time_with_metrics,
+};
+#[cfg(not(feature = "verify-only"))]
+pub use metrics::{
+    MetricsRegistry, MetricsSnapshot, HistogramSnapshot, Counter, Gauge, Histogram, DEFAULT_TIME_BUCKETS,
+    EdgeThroughput, ShareLatencyTracker, SHARE_LATENCY_BUCKETS, PROOF_DIFFICULTY_BUCKETS, WorkerMetrics, WorkerSummary,
+};
+#[cfg(not(feature = "verify-only"))]
+pub use metrics_server::MetricsServer;
+#[cfg(not(feature = "verify-only"))]
+pub use alloc_tracking::{
+    AllocationStats, StageAllocations, StageTracker,
+    allocation_stats, allocation_tracking_enabled, track_allocations,
+};
+#[cfg(not(feature = "verify-only"))]
+pub use memory::{MemoryEstimate, MemoryBudget, available_memory, parse_memory_size, format_memory_size};
+#[cfg(not(feature = "verify-only"))]
+pub use energy::{PowerSource, FixedPower, RaplPower, EnergyReport};
+#[cfg(not(feature = "verify-only"))]
+pub use solver::{Solver, Dispatcher};
+#[cfg(not(feature = "verify-only"))]
+pub use replay::{ReplayRecord, ReplayRecorder};
+#[cfg(not(feature = "verify-only"))]
+pub use graph::{Graph, AuditReport};
+#[cfg(not(feature = "verify-only"))]
+pub use graph_stats::GraphStats;
+#[cfg(not(feature = "verify-only"))]
+pub use path_solver::PathSolver;
+#[cfg(not(feature = "verify-only"))]
+pub use endpoint_cache::{EndpointCache, EndpointCacheStats};
+#[cfg(not(feature = "verify-only"))]
+pub use capabilities::CapabilityReport;
+#[cfg(not(feature = "verify-only"))]
+pub use preflight::{ConfigWarning, Preflight, warnings_to_json};
+#[cfg(not(feature = "verify-only"))]
+pub use report::{TimeUnit, set_time_unit, time_unit, format_seconds, format_duration, format_rate, json_seconds};
+#[cfg(not(feature = "verify-only"))]
+pub use baseline::{Delta, DeltaKind, RunBaseline};
+#[cfg(all(feature = "opencl", not(feature = "verify-only")))]
+pub use gpu_trimming::GpuLeanTrimmer;
+#[cfg(all(feature = "wgpu", not(feature = "verify-only")))]
+pub use wgpu_trimming::WgpuLeanTrimmer;
+#[cfg(not(feature = "verify-only"))]
+pub use sink::{open_sink, AsyncSolutionSink, FileSink, SinkSet, SolutionSink, StdoutSink, StratumSink};
+#[cfg(all(feature = "sqlite", not(feature = "verify-only")))]
+pub use sink::SqliteSink;
 
 /// Result type for Cuckatoo operations
 pub type Result<T> = std::result::Result<T, CuckatooError>;
@@ -40,7 +240,29 @@ pub enum CuckatooError {
     TrimmingError(String),
     VerificationError(String),
     MemoryError(String),
+    DeviceError(String),
     InternalError(String),
+    /// Work was stopped through a `CancelToken`
+    Cancelled,
+    /// A cycle search used up its `SearchBudget`
+    BudgetExceeded,
+}
+
+impl CuckatooError {
+    /// Short name of the error variant, used to label error metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CuckatooError::InvalidEdgeBits(_) => "config",
+            CuckatooError::HashingError(_) => "hashing",
+            CuckatooError::TrimmingError(_) => "trimming",
+            CuckatooError::VerificationError(_) => "verification",
+            CuckatooError::MemoryError(_) => "memory",
+            CuckatooError::DeviceError(_) => "device",
+            CuckatooError::InternalError(_) => "internal",
+            CuckatooError::Cancelled => "cancelled",
+            CuckatooError::BudgetExceeded => "budget",
+        }
+    }
 }
 
 impl std::fmt::Display for CuckatooError {
@@ -51,7 +273,10 @@ impl std::fmt::Display for CuckatooError {
             CuckatooError::TrimmingError(msg) => write!(f, "Trimming failed: {}", msg),
             CuckatooError::VerificationError(msg) => write!(f, "Verification failed: {}", msg),
             CuckatooError::MemoryError(msg) => write!(f, "Memory allocation failed: {}", msg),
+            CuckatooError::DeviceError(msg) => write!(f, "Device error: {}", msg),
             CuckatooError::InternalError(msg) => write!(f, "Internal error: {}", msg),
+            CuckatooError::Cancelled => write!(f, "Cancelled"),
+            CuckatooError::BudgetExceeded => write!(f, "Search budget exceeded"),
         }
     }
 }
@@ -0,0 +1,210 @@
+//! Memory estimates and budgets
+//!
+//! Estimates the memory a graph needs in each pipeline stage so a run that
+//! can't fit in the configured budget fails with a `MemoryError` up front,
+//! instead of getting the host OOM-killed halfway through.
+
+use crate::{Config, CuckatooError, Edge, Result, TrimmingMode};
+use std::fmt;
+
+/// Bytes per surviving edge held by the cycle finder (adjacency entries
+/// for both endpoints plus hash table overhead)
+const SEARCH_BYTES_PER_EDGE: u64 = 64;
+
+/// Estimated peak memory of one graph, per pipeline stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    /// Edge list of the whole graph
+    pub edges: u64,
+    /// Trimmer bitmaps and buffers
+    pub trimming: u64,
+    /// Cycle finder storage, assuming every edge survives trimming
+    pub search: u64,
+}
+
+impl MemoryEstimate {
+    /// Estimate the memory of a graph with `edge_bits` trimmed in `mode`
+    pub fn new(edge_bits: u32, mode: TrimmingMode) -> Self {
+        let number_of_edges = 1u64.checked_shl(edge_bits).unwrap_or(u64::MAX);
+        // Edges and nodes bitmaps, one bit per edge each
+        let bitmaps = number_of_edges.div_ceil(64).saturating_mul(8).saturating_mul(2);
+        // Mean trimming also sorts edges into buckets, slean into half-size
+        // buckets; lean counters take two bits per node, one bitmap more
+        let buckets = match mode {
+            TrimmingMode::Lean => 0,
+            TrimmingMode::LeanCounter => number_of_edges.div_ceil(64).saturating_mul(8),
+            TrimmingMode::Slean => number_of_edges.saturating_mul(4),
+            TrimmingMode::Mean => number_of_edges.saturating_mul(8),
+        };
+
+        Self {
+            edges: number_of_edges.saturating_mul(std::mem::size_of::<Edge>() as u64),
+            trimming: bitmaps.saturating_add(buckets),
+            search: number_of_edges.saturating_mul(SEARCH_BYTES_PER_EDGE),
+        }
+    }
+
+    /// Estimate the memory of a configuration's graphs
+    ///
+    /// An endpoint cache counts towards trimming.
+    pub fn for_config(config: &Config) -> Self {
+        let mut estimate = Self::new(config.edge_bits, config.mode);
+        estimate.trimming = estimate.trimming.saturating_add(config.endpoint_cache.unwrap_or(0));
+        estimate
+    }
+
+    /// Total bytes of all stages
+    pub fn total(&self) -> u64 {
+        self.edges.saturating_add(self.trimming).saturating_add(self.search)
+    }
+}
+
+impl fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (edges {}, trimming {}, search {})",
+               format_memory_size(self.total()), format_memory_size(self.edges),
+               format_memory_size(self.trimming), format_memory_size(self.search))
+    }
+}
+
+/// Maximum memory a run may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Budget in bytes
+    pub max_bytes: u64,
+}
+
+impl MemoryBudget {
+    /// Create a budget of `max_bytes`
+    pub fn new(max_bytes: u64) -> Self {
+        Self { max_bytes }
+    }
+
+    /// Budget of a configuration, None if it has no memory limit
+    pub fn for_config(config: &Config) -> Option<Self> {
+        config.max_memory.map(Self::new)
+    }
+
+    /// Check if one graph of the given estimate fits
+    pub fn fits(&self, estimate: &MemoryEstimate) -> bool {
+        estimate.total() <= self.max_bytes
+    }
+
+    /// Check that one graph of the given estimate fits
+    pub fn check(&self, estimate: &MemoryEstimate) -> Result<()> {
+        if !self.fits(estimate) {
+            return Err(CuckatooError::MemoryError(format!(
+                "graph needs {} but the memory budget is {}",
+                estimate, format_memory_size(self.max_bytes)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Choose the trimming mode closest to `preferred` that fits
+    ///
+    /// Falls back from mean to slean to lean, since lean needs the least
+    /// memory.
+    pub fn select_mode(&self, edge_bits: u32, preferred: TrimmingMode) -> Result<TrimmingMode> {
+        let candidates: &[TrimmingMode] = match preferred {
+            TrimmingMode::Mean => &[TrimmingMode::Mean, TrimmingMode::Slean, TrimmingMode::Lean],
+            TrimmingMode::Slean => &[TrimmingMode::Slean, TrimmingMode::Lean],
+            TrimmingMode::Lean => &[TrimmingMode::Lean],
+            TrimmingMode::LeanCounter => &[TrimmingMode::LeanCounter],
+        };
+
+        match candidates.iter().find(|&&mode| self.fits(&MemoryEstimate::new(edge_bits, mode))) {
+            Some(&mode) => Ok(mode),
+            // Report the error for the leanest mode
+            None => self.check(&MemoryEstimate::new(edge_bits, TrimmingMode::Lean)).map(|()| TrimmingMode::Lean),
+        }
+    }
+
+    /// Number of graphs that can be worked on at the same time (at most
+    /// `requested`), erroring if not even one fits
+    pub fn concurrent_graphs(&self, estimate: &MemoryEstimate, requested: usize) -> Result<usize> {
+        self.check(estimate)?;
+        let fitting = self.max_bytes / estimate.total().max(1);
+        Ok(requested.min(usize::try_from(fitting).unwrap_or(usize::MAX)).max(1))
+    }
+}
+
+/// Memory the host could give this process now (`MemAvailable` on Linux),
+/// None where it can't be read
+pub fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.trim_start_matches("MemAvailable:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    kib.checked_mul(1024)
+}
+
+/// Parse a memory size such as `8G`, `512M`, `64KiB` or `1000000`
+///
+/// Suffixes are binary multiples (K = 1024 bytes).
+pub fn parse_memory_size(text: &str) -> Result<u64> {
+    let invalid = || CuckatooError::InternalError(format!("Invalid memory size: {}", text));
+    let upper = text.trim().to_uppercase();
+    let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match &upper[digits.len()..] {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    let value: u64 = digits.trim().parse().map_err(|_| invalid())?;
+    value.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+/// Format a byte count with a binary suffix, e.g. `1.50 GiB`
+pub fn format_memory_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{:.2} {}", value, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_memory_size() {
+        assert_eq!(parse_memory_size("8G").unwrap(), 8 << 30);
+        assert_eq!(parse_memory_size("512mib").unwrap(), 512 << 20);
+        assert_eq!(parse_memory_size("1000").unwrap(), 1000);
+        assert!(parse_memory_size("8X").is_err());
+        assert!(parse_memory_size("G").is_err());
+        assert_eq!(format_memory_size(3 << 29), "1.50 GiB");
+    }
+
+    #[test]
+    fn test_budget_enforcement() {
+        let estimate = MemoryEstimate::new(20, TrimmingMode::Lean);
+        assert_eq!(estimate.trimming, 2 * (1 << 20) / 8);
+
+        let budget = MemoryBudget::new(estimate.total() * 3);
+        assert!(budget.check(&estimate).is_ok());
+        assert_eq!(budget.concurrent_graphs(&estimate, 8).unwrap(), 3);
+        assert!(matches!(
+            MemoryBudget::new(1 << 20).check(&estimate),
+            Err(CuckatooError::MemoryError(_))
+        ));
+
+        // Mean trimming falls back to lean when its buckets don't fit
+        let lean_only = MemoryBudget::new(estimate.total());
+        assert_eq!(lean_only.select_mode(20, TrimmingMode::Mean).unwrap(), TrimmingMode::Lean);
+        assert!(MemoryBudget::new(1024).select_mode(20, TrimmingMode::Mean).is_err());
+    }
+}
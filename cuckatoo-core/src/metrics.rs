@@ -0,0 +1,870 @@
+//! Named metrics registry for Cuckatoo
+//!
+//! This module provides counters, gauges and histograms registered by name
+//! in a shared `MetricsRegistry`. Timing utilities, trimmers and cycle
+//! finders write into the registry, and exporters read a single snapshot
+//! of it: the Prometheus text format, printed at exit or served live by
+//! `MetricsServer`.
+
+use crate::report::{format_seconds, json_seconds};
+use crate::{ConfigWarning, CuckatooError, ShareFreshness};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Default histogram buckets for durations in seconds
+pub const DEFAULT_TIME_BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0];
+
+/// Histogram buckets for job-to-share latency in seconds
+pub const SHARE_LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0];
+
+/// Histogram buckets for proof difficulty (powers of four)
+///
+/// A proof reaches difficulty `d` with probability about `1/d`, so each
+/// bucket should hold about a quarter of the solutions of the one below.
+pub const PROOF_DIFFICULTY_BUCKETS: [f64; 16] = [
+    1.0, 4.0, 16.0, 64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+    67108864.0, 268435456.0, 1073741824.0,
+];
+
+/// Number of recent jobs whose receipt time is kept
+const MAX_TRACKED_JOBS: usize = 16;
+
+/// Monotonically increasing counter
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    /// Increment the counter by one
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Increment the counter by `value`
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Get the current value
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Gauge holding the latest value of a measurement
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    /// Set the gauge
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Add to the gauge (negative values subtract)
+    pub fn add(&self, value: f64) {
+        let mut current = self.0.load(Ordering::Relaxed);
+        loop {
+            let updated = (f64::from_bits(current) + value).to_bits();
+            match self.0.compare_exchange_weak(current, updated, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Get the current value
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Histogram counting observations into fixed buckets
+#[derive(Debug, Clone)]
+pub struct Histogram(Arc<HistogramInner>);
+
+#[derive(Debug)]
+struct HistogramInner {
+    /// Upper bounds of the buckets (ascending)
+    bounds: Vec<f64>,
+    /// Observations per bucket, with a final bucket for values above all bounds
+    buckets: Vec<AtomicU64>,
+    /// Sum of all observations
+    sum: Gauge,
+    /// Number of observations
+    count: Counter,
+}
+
+impl Histogram {
+    /// Create a new histogram with the given bucket upper bounds
+    pub fn new(bounds: &[f64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+
+        let buckets = (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect();
+        Self(Arc::new(HistogramInner {
+            bounds,
+            buckets,
+            sum: Gauge::default(),
+            count: Counter::default(),
+        }))
+    }
+
+    /// Record an observation
+    pub fn observe(&self, value: f64) {
+        let bucket = self.0.bounds.partition_point(|&bound| bound < value);
+        self.0.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.0.sum.add(value);
+        self.0.count.inc();
+    }
+
+    /// Get the number of observations
+    pub fn count(&self) -> u64 {
+        self.0.count.get()
+    }
+
+    /// Get the sum of all observations
+    pub fn sum(&self) -> f64 {
+        self.0.sum.get()
+    }
+
+    /// Get the cumulative count of observations at or below each bound
+    ///
+    /// The last entry has an infinite bound and equals `count()`.
+    pub fn cumulative_buckets(&self) -> Vec<(f64, u64)> {
+        let mut cumulative = 0;
+        self.0.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| {
+                cumulative += bucket.load(Ordering::Relaxed);
+                (self.0.bounds.get(i).copied().unwrap_or(f64::INFINITY), cumulative)
+            })
+            .collect()
+    }
+}
+
+/// Registered metrics by name
+#[derive(Debug, Default)]
+struct RegistryInner {
+    counters: BTreeMap<String, Counter>,
+    gauges: BTreeMap<String, Gauge>,
+    histograms: BTreeMap<String, Histogram>,
+}
+
+/// Shared registry of named metrics
+///
+/// Clones share the same metrics. Handles returned by `counter`, `gauge`
+/// and `histogram` can be kept and updated without touching the registry.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    inner: Arc<Mutex<RegistryInner>>,
+}
+
+impl MetricsRegistry {
+    /// Create a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock the registry, recovering from a poisoned lock
+    fn lock(&self) -> MutexGuard<'_, RegistryInner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Get or register a counter
+    pub fn counter(&self, name: &str) -> Counter {
+        self.lock().counters.entry(name.to_string()).or_default().clone()
+    }
+
+    /// Get or register a gauge
+    pub fn gauge(&self, name: &str) -> Gauge {
+        self.lock().gauges.entry(name.to_string()).or_default().clone()
+    }
+
+    /// Get or register a histogram
+    ///
+    /// `bounds` is only used when the histogram is first registered.
+    pub fn histogram(&self, name: &str, bounds: &[f64]) -> Histogram {
+        self.lock()
+            .histograms
+            .entry(name.to_string())
+            .or_insert_with(|| Histogram::new(bounds))
+            .clone()
+    }
+
+    /// Count an error under `cuckatoo_<kind>_errors_total` and `cuckatoo_errors_total`
+    pub fn record_error(&self, error: &CuckatooError) {
+        self.counter(&error_counter_name(error.kind())).inc();
+        self.counter("cuckatoo_errors_total").inc();
+    }
+
+    /// Record the throughput of an edge generation stage
+    ///
+    /// Sets the `cuckatoo_edge_generation_*_per_second` gauges and adds to
+    /// `cuckatoo_edges_generated_total`.
+    pub fn record_edge_throughput(&self, throughput: &EdgeThroughput) {
+        self.counter("cuckatoo_edges_generated_total").add(throughput.edges);
+        self.gauge("cuckatoo_edge_generation_edges_per_second").set(throughput.edges_per_second());
+        self.gauge("cuckatoo_edge_generation_bytes_per_second").set(throughput.bytes_per_second());
+    }
+
+    /// Record the surviving edges after each round of the latest trim
+    ///
+    /// Sets one `cuckatoo_round_surviving_edges{round="N"}` gauge per round
+    /// and drops the rounds of a previous, longer trim, so the series always
+    /// plots a single decay curve.
+    pub fn record_survivor_curve(&self, curve: &[u64]) {
+        let mut inner = self.lock();
+        let prefix = format!("{}{{", ROUND_SURVIVING_EDGES);
+        inner.gauges.retain(|name, _| !name.starts_with(&prefix));
+        for (round, &survivors) in (1..).zip(curve) {
+            let gauge = Gauge::default();
+            gauge.set(survivors as f64);
+            inner.gauges.insert(format!("{}{{round=\"{}\"}}", ROUND_SURVIVING_EDGES, round), gauge);
+        }
+    }
+
+    /// Record the difficulty of a found solution (see `proof_difficulty`)
+    /// in the `cuckatoo_proof_difficulty` histogram
+    pub fn record_proof_difficulty(&self, difficulty: u64) {
+        self.histogram(PROOF_DIFFICULTY, &PROOF_DIFFICULTY_BUCKETS).observe(difficulty as f64);
+    }
+
+    /// Set a `cuckatoo_config_warning{code="..."}` gauge to 1 for each
+    /// pre-flight warning of the run's configuration
+    pub fn record_config_warnings(&self, warnings: &[ConfigWarning]) {
+        for warning in warnings {
+            self.gauge(&format!("{}{{code=\"{}\"}}", CONFIG_WARNING, warning.code())).set(1.0);
+        }
+    }
+
+    /// Take a snapshot of all metrics, sorted by name
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let inner = self.lock();
+        MetricsSnapshot {
+            counters: inner.counters.iter().map(|(name, c)| (name.clone(), c.get())).collect(),
+            gauges: inner.gauges.iter().map(|(name, g)| (name.clone(), g.get())).collect(),
+            histograms: inner.histograms
+                .iter()
+                .map(|(name, h)| HistogramSnapshot {
+                    name: name.clone(),
+                    count: h.count(),
+                    sum: h.sum(),
+                    buckets: h.cumulative_buckets(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Format all metrics in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        self.snapshot().to_prometheus()
+    }
+
+    /// Get or register the per-worker metrics of a worker thread or device
+    pub fn worker(&self, worker: &str) -> WorkerMetrics {
+        let label = format!("{{worker=\"{}\"}}", worker.replace('\\', "\\\\").replace('"', "\\\""));
+        WorkerMetrics {
+            graphs: self.counter(&format!("{}{}", WORKER_GRAPHS, label)),
+            solutions: self.counter(&format!("{}{}", WORKER_SOLUTIONS, label)),
+            trim_seconds: self.gauge(&format!("{}{}", WORKER_TRIM_SECONDS, label)),
+            search_seconds: self.gauge(&format!("{}{}", WORKER_SEARCH_SECONDS, label)),
+        }
+    }
+}
+
+/// Amount of edge data generated in a stage and the time it took
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeThroughput {
+    /// Edges generated
+    pub edges: u64,
+    /// Bytes of edge data written
+    pub bytes: u64,
+    /// Duration of the stage in seconds
+    pub seconds: f64,
+}
+
+impl EdgeThroughput {
+    /// Create a throughput measurement
+    pub fn new(edges: u64, bytes: u64, seconds: f64) -> Self {
+        Self { edges, bytes, seconds }
+    }
+
+    /// Edges per second, 0 if no time was measured
+    pub fn edges_per_second(&self) -> f64 {
+        self.per_second(self.edges)
+    }
+
+    /// Millions of edges per second, the unit used by published miner benchmarks
+    pub fn megaedges_per_second(&self) -> f64 {
+        self.edges_per_second() / 1e6
+    }
+
+    /// Bytes per second, 0 if no time was measured
+    pub fn bytes_per_second(&self) -> f64 {
+        self.per_second(self.bytes)
+    }
+
+    fn per_second(&self, amount: u64) -> f64 {
+        if self.seconds > 0.0 {
+            amount as f64 / self.seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Tracks the latency from receiving a job to submitting a share
+///
+/// The miner receives its jobs from a jobs file (each line is a job) or,
+/// in a single-graph run, from the test header. A stratum client would
+/// call `job_received` for every job notification the same way.
+///
+/// Observes `cuckatoo_share_latency_seconds` per submitted share. A growing
+/// tail means shares are held up by a trimming backlog or the network and
+/// are likely to go stale. Shares of the previous job can still be submitted
+/// for a grace period after the job changed (see `with_stale_grace`); they
+/// are counted apart from shares of the current job. Clones share the same
+/// job list.
+#[derive(Debug, Clone)]
+pub struct ShareLatencyTracker {
+    registry: MetricsRegistry,
+    /// Receipt time of recent jobs, oldest first
+    jobs: Arc<Mutex<VecDeque<(u64, Instant)>>>,
+    /// How long the previous job's shares are still submitted
+    stale_grace: Duration,
+}
+
+impl ShareLatencyTracker {
+    /// Create a tracker recording into `registry`
+    pub fn new(registry: MetricsRegistry) -> Self {
+        Self {
+            registry,
+            jobs: Arc::new(Mutex::new(VecDeque::with_capacity(MAX_TRACKED_JOBS))),
+            stale_grace: Duration::ZERO,
+        }
+    }
+
+    /// Keep submitting the previous job's shares for `grace` after the job
+    /// changed (by default they are discarded at once)
+    pub fn with_stale_grace(mut self, grace: Duration) -> Self {
+        self.stale_grace = grace;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<(u64, Instant)>> {
+        self.jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Record that a job was received now
+    ///
+    /// A job sent again keeps its first receipt time. Only the most recent
+    /// jobs are kept.
+    pub fn job_received(&self, job_id: u64) {
+        let mut jobs = self.lock();
+        if jobs.iter().any(|&(id, _)| id == job_id) {
+            return;
+        }
+        if jobs.len() == MAX_TRACKED_JOBS {
+            jobs.pop_front();
+        }
+        jobs.push_back((job_id, Instant::now()));
+    }
+
+    /// Check if a share for a job is for the current job, for the previous
+    /// one within the grace window, or too old to submit
+    pub fn freshness(&self, job_id: u64) -> ShareFreshness {
+        let jobs = self.lock();
+        let mut recent = jobs.iter().rev();
+        match (recent.next(), recent.next()) {
+            (Some(&(current, _)), _) if current == job_id => ShareFreshness::Current,
+            (Some(&(_, replaced)), Some(&(previous, _)))
+                if previous == job_id && replaced.elapsed() <= self.stale_grace => ShareFreshness::Stale,
+            _ => ShareFreshness::Expired,
+        }
+    }
+
+    /// Record that a share for a job was submitted now, returning its latency
+    ///
+    /// Shares of the current job are counted in
+    /// `cuckatoo_shares_submitted_total`, older ones in
+    /// `cuckatoo_stale_shares_submitted_total`. Shares of jobs that were never
+    /// received or are no longer tracked are counted in
+    /// `cuckatoo_shares_unknown_job_total` and return None.
+    pub fn share_submitted(&self, job_id: u64) -> Option<Duration> {
+        let received = self.lock().iter().find(|&&(id, _)| id == job_id).map(|&(_, received)| received);
+        let Some(received) = received else {
+            self.registry.counter("cuckatoo_shares_unknown_job_total").inc();
+            return None;
+        };
+
+        let latency = received.elapsed();
+        let counter = match self.freshness(job_id) {
+            ShareFreshness::Current => "cuckatoo_shares_submitted_total",
+            ShareFreshness::Stale | ShareFreshness::Expired => "cuckatoo_stale_shares_submitted_total",
+        };
+        self.registry.counter(counter).inc();
+        self.registry.histogram("cuckatoo_share_latency_seconds", &SHARE_LATENCY_BUCKETS)
+            .observe(latency.as_secs_f64());
+        Some(latency)
+    }
+
+    /// Record that a share was discarded because its job expired
+    pub fn share_discarded(&self) {
+        self.registry.counter("cuckatoo_stale_shares_discarded_total").inc();
+    }
+}
+
+/// Graphs solved by a worker (labelled with `worker`)
+const WORKER_GRAPHS: &str = "cuckatoo_worker_graphs_total";
+/// Solutions found by a worker
+const WORKER_SOLUTIONS: &str = "cuckatoo_worker_solutions_total";
+/// Seconds a worker spent trimming
+const WORKER_TRIM_SECONDS: &str = "cuckatoo_worker_trim_seconds_total";
+/// Seconds a worker spent searching for cycles
+const WORKER_SEARCH_SECONDS: &str = "cuckatoo_worker_search_seconds_total";
+/// Difficulty of the solutions found
+const PROOF_DIFFICULTY: &str = "cuckatoo_proof_difficulty";
+/// Surviving edges after a trimming round (labelled with `round`)
+const ROUND_SURVIVING_EDGES: &str = "cuckatoo_round_surviving_edges";
+/// Gauge of each pre-flight warning of the configuration
+const CONFIG_WARNING: &str = "cuckatoo_config_warning";
+
+/// Metrics of one worker thread or device
+///
+/// Recorded under the `cuckatoo_worker_*` metrics with a `worker` label,
+/// so a slow or failing worker stands out against the others in
+/// `MetricsSnapshot::workers`. Clones update the same metrics.
+#[derive(Debug, Clone)]
+pub struct WorkerMetrics {
+    graphs: Counter,
+    solutions: Counter,
+    trim_seconds: Gauge,
+    search_seconds: Gauge,
+}
+
+impl WorkerMetrics {
+    /// Record a solved graph: its trimming and search times and the
+    /// number of solutions found
+    pub fn record_graph(&self, trim_time: Duration, search_time: Duration, solutions: u64) {
+        self.graphs.inc();
+        self.solutions.add(solutions);
+        self.trim_seconds.add(trim_time.as_secs_f64());
+        self.search_seconds.add(search_time.as_secs_f64());
+    }
+}
+
+/// Totals of one worker, read from a snapshot
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WorkerSummary {
+    /// Worker name
+    pub worker: String,
+    /// Graphs solved
+    pub graphs: u64,
+    /// Solutions found
+    pub solutions: u64,
+    /// Seconds spent trimming
+    pub trim_seconds: f64,
+    /// Seconds spent searching for cycles
+    pub search_seconds: f64,
+}
+
+impl WorkerSummary {
+    /// Average trimming time per graph in seconds (0 without graphs)
+    pub fn average_trim_seconds(&self) -> f64 {
+        self.per_graph(self.trim_seconds)
+    }
+
+    /// Average search time per graph in seconds (0 without graphs)
+    pub fn average_search_seconds(&self) -> f64 {
+        self.per_graph(self.search_seconds)
+    }
+
+    /// Format the totals and per-graph averages as a JSON object
+    pub fn to_json(&self) -> String {
+        format!("{{\"worker\":\"{}\",\"graphs\":{},\"solutions\":{},\"average_trim_seconds\":{},\"average_search_seconds\":{}}}",
+                self.worker.replace('\\', "\\\\").replace('"', "\\\""), self.graphs, self.solutions,
+                json_seconds(self.average_trim_seconds()), json_seconds(self.average_search_seconds()))
+    }
+
+    fn per_graph(&self, total: f64) -> f64 {
+        if self.graphs > 0 {
+            total / self.graphs as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for WorkerSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} graphs, {} solutions, {} trim, {} search per graph",
+               self.worker, self.graphs, self.solutions,
+               format_seconds(self.average_trim_seconds()), format_seconds(self.average_search_seconds()))
+    }
+}
+
+/// Split `name{worker="..."}` into the metric name and the worker
+fn worker_label(name: &str) -> Option<(&str, String)> {
+    let (metric, label) = name.split_once("{worker=\"")?;
+    let worker = label.strip_suffix("\"}")?;
+    Some((metric, worker.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+/// Name of the counter for errors of a kind
+fn error_counter_name(kind: &str) -> String {
+    format!("cuckatoo_{}_errors_total", kind)
+}
+
+/// Point-in-time copy of a histogram
+#[derive(Debug, Clone)]
+pub struct HistogramSnapshot {
+    /// Metric name
+    pub name: String,
+    /// Number of observations
+    pub count: u64,
+    /// Sum of all observations
+    pub sum: f64,
+    /// Cumulative counts per bucket upper bound
+    pub buckets: Vec<(f64, u64)>,
+}
+
+impl HistogramSnapshot {
+    /// Format the histogram as a JSON object with its count, sum and
+    /// cumulative buckets (`null` for the unbounded last one)
+    pub fn to_json(&self) -> String {
+        let buckets: Vec<String> = self.buckets
+            .iter()
+            .map(|&(bound, count)| {
+                let bound = if bound.is_finite() { bound.to_string() } else { "null".to_string() };
+                format!("[{},{}]", bound, count)
+            })
+            .collect();
+        format!("{{\"count\":{},\"sum\":{},\"buckets\":[{}]}}", self.count, self.sum, buckets.join(","))
+    }
+}
+
+/// Point-in-time copy of all metrics in a registry
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    /// Counter values by name
+    pub counters: Vec<(String, u64)>,
+    /// Gauge values by name
+    pub gauges: Vec<(String, f64)>,
+    /// Histograms by name
+    pub histograms: Vec<HistogramSnapshot>,
+}
+
+impl MetricsSnapshot {
+    /// Get a counter value by name
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        self.counters.iter().find(|(n, _)| n == name).map(|&(_, value)| value)
+    }
+
+    /// Get a gauge value by name
+    pub fn gauge(&self, name: &str) -> Option<f64> {
+        self.gauges.iter().find(|(n, _)| n == name).map(|&(_, value)| value)
+    }
+
+    /// Get a histogram by name
+    pub fn histogram(&self, name: &str) -> Option<&HistogramSnapshot> {
+        self.histograms.iter().find(|h| h.name == name)
+    }
+
+    /// Get the number of recorded errors of a kind (see `CuckatooError::kind`)
+    pub fn error_count(&self, kind: &str) -> u64 {
+        self.counter(&error_counter_name(kind)).unwrap_or(0)
+    }
+
+    /// Recorded errors per kind, sorted by kind
+    pub fn error_counts(&self) -> Vec<(String, u64)> {
+        self.counters
+            .iter()
+            .filter_map(|(name, count)| {
+                let kind = name.strip_prefix("cuckatoo_")?.strip_suffix("_errors_total")?;
+                Some((kind.to_string(), *count))
+            })
+            .collect()
+    }
+
+    /// Format the run's health as a JSON object: the total errors, the
+    /// errors of each kind and the totals of each worker
+    pub fn to_status_json(&self) -> String {
+        let errors: Vec<String> = self.error_counts()
+            .iter()
+            .map(|(kind, count)| format!("\"{}\":{}", kind, count))
+            .collect();
+        let workers: Vec<String> = self.workers().iter().map(WorkerSummary::to_json).collect();
+        format!("{{\"errors_total\":{},\"errors\":{{{}}},\"workers\":[{}]}}",
+                self.counter("cuckatoo_errors_total").unwrap_or(0), errors.join(","), workers.join(","))
+    }
+
+    /// Difficulty histogram of the solutions found (see
+    /// `MetricsRegistry::record_proof_difficulty`)
+    pub fn proof_difficulty(&self) -> Option<&HistogramSnapshot> {
+        self.histogram(PROOF_DIFFICULTY)
+    }
+
+    /// Surviving edges after each round of the latest recorded trim (see
+    /// `MetricsRegistry::record_survivor_curve`), in round order
+    pub fn survivor_curve(&self) -> Vec<u64> {
+        let prefix = format!("{}{{round=\"", ROUND_SURVIVING_EDGES);
+        let mut curve: Vec<(u32, u64)> = self.gauges
+            .iter()
+            .filter_map(|(name, value)| {
+                let round = name.strip_prefix(&prefix)?.strip_suffix("\"}")?.parse().ok()?;
+                Some((round, *value as u64))
+            })
+            .collect();
+        curve.sort_unstable();
+        curve.into_iter().map(|(_, survivors)| survivors).collect()
+    }
+
+    /// Per-worker totals, sorted by worker name
+    pub fn workers(&self) -> Vec<WorkerSummary> {
+        let mut workers: BTreeMap<String, WorkerSummary> = BTreeMap::new();
+        fn summary(workers: &mut BTreeMap<String, WorkerSummary>, worker: String) -> &mut WorkerSummary {
+            workers.entry(worker.clone()).or_insert_with(|| WorkerSummary { worker, ..WorkerSummary::default() })
+        }
+
+        for (name, value) in &self.counters {
+            match worker_label(name) {
+                Some((WORKER_GRAPHS, worker)) => summary(&mut workers, worker).graphs = *value,
+                Some((WORKER_SOLUTIONS, worker)) => summary(&mut workers, worker).solutions = *value,
+                _ => {},
+            }
+        }
+        for (name, value) in &self.gauges {
+            match worker_label(name) {
+                Some((WORKER_TRIM_SECONDS, worker)) => summary(&mut workers, worker).trim_seconds = *value,
+                Some((WORKER_SEARCH_SECONDS, worker)) => summary(&mut workers, worker).search_seconds = *value,
+                _ => {},
+            }
+        }
+        workers.into_values().collect()
+    }
+
+    /// Format the metrics in the Prometheus text exposition format
+    ///
+    /// Labelled metrics (`name{label="value"}`) share one `# TYPE` line.
+    pub fn to_prometheus(&self) -> String {
+        let mut output = String::new();
+        let mut typed = String::new();
+        let mut type_line = |output: &mut String, name: &str, kind: &str| {
+            let base = name.split('{').next().unwrap_or(name);
+            if base != typed {
+                output.push_str(&format!("# TYPE {} {}\n", base, kind));
+                typed = base.to_string();
+            }
+        };
+
+        for (name, value) in &self.counters {
+            type_line(&mut output, name, "counter");
+            output.push_str(&format!("{} {}\n", name, value));
+        }
+        for (name, value) in &self.gauges {
+            type_line(&mut output, name, "gauge");
+            output.push_str(&format!("{} {}\n", name, value));
+        }
+        for histogram in &self.histograms {
+            output.push_str(&format!("# TYPE {} histogram\n", histogram.name));
+            for &(bound, count) in &histogram.buckets {
+                let bound = if bound.is_finite() { bound.to_string() } else { "+Inf".to_string() };
+                output.push_str(&format!("{}_bucket{{le=\"{}\"}} {}\n", histogram.name, bound, count));
+            }
+            output.push_str(&format!("{}_sum {}\n", histogram.name, histogram.sum));
+            output.push_str(&format!("{}_count {}\n", histogram.name, histogram.count));
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_counters_and_gauges() {
+        let registry = MetricsRegistry::new();
+
+        registry.counter("graphs_total").inc();
+        registry.counter("graphs_total").add(2);
+        registry.gauge("surviving_edges").set(42.0);
+        registry.gauge("surviving_edges").add(-2.0);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("graphs_total"), Some(3));
+        assert_eq!(snapshot.gauge("surviving_edges"), Some(40.0));
+        assert_eq!(snapshot.counter("missing"), None);
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let histogram = Histogram::new(&[1.0, 10.0]);
+        histogram.observe(0.5);
+        histogram.observe(1.0);
+        histogram.observe(5.0);
+        histogram.observe(100.0);
+
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.sum(), 106.5);
+        assert_eq!(histogram.cumulative_buckets(), vec![(1.0, 2), (10.0, 3), (f64::INFINITY, 4)]);
+    }
+
+    #[test]
+    fn test_concurrent_updates() {
+        let registry = MetricsRegistry::new();
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                let registry = registry.clone();
+                scope.spawn(move || {
+                    let counter = registry.counter("events_total");
+                    for _ in 0..1000 {
+                        counter.inc();
+                    }
+                });
+            }
+        });
+
+        assert_eq!(registry.counter("events_total").get(), 4000);
+    }
+
+    #[test]
+    fn test_error_counters() {
+        let registry = MetricsRegistry::new();
+        registry.record_error(&CuckatooError::DeviceError("lost device".to_string()));
+        registry.record_error(&CuckatooError::DeviceError("lost device".to_string()));
+        registry.record_error(&CuckatooError::TrimmingError("bad round".to_string()));
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.error_count("device"), 2);
+        assert_eq!(snapshot.error_count("trimming"), 1);
+        assert_eq!(snapshot.error_count("hashing"), 0);
+        assert_eq!(snapshot.counter("cuckatoo_errors_total"), Some(3));
+        assert_eq!(snapshot.error_counts(), vec![("device".to_string(), 2), ("trimming".to_string(), 1)]);
+        assert_eq!(snapshot.to_status_json(), "{\"errors_total\":3,\"errors\":{\"device\":2,\"trimming\":1},\"workers\":[]}");
+    }
+
+    #[test]
+    fn test_edge_throughput() {
+        let throughput = EdgeThroughput::new(4_000_000, 48_000_000, 2.0);
+        assert_eq!(throughput.megaedges_per_second(), 2.0);
+        assert_eq!(throughput.bytes_per_second(), 24_000_000.0);
+        assert_eq!(EdgeThroughput::new(10, 10, 0.0).edges_per_second(), 0.0);
+
+        let registry = MetricsRegistry::new();
+        registry.record_edge_throughput(&throughput);
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("cuckatoo_edges_generated_total"), Some(4_000_000));
+        assert_eq!(snapshot.gauge("cuckatoo_edge_generation_edges_per_second"), Some(2_000_000.0));
+        assert_eq!(snapshot.gauge("cuckatoo_edge_generation_bytes_per_second"), Some(24_000_000.0));
+    }
+
+    #[test]
+    fn test_share_latency() {
+        let registry = MetricsRegistry::new();
+        let tracker = ShareLatencyTracker::new(registry.clone());
+        tracker.job_received(1);
+        assert!(tracker.share_submitted(1).is_some());
+        assert!(tracker.share_submitted(1).is_some());
+        assert_eq!(tracker.share_submitted(2), None);
+
+        // Old jobs are forgotten
+        for job in 2..=MAX_TRACKED_JOBS as u64 + 1 {
+            tracker.job_received(job);
+        }
+        assert_eq!(tracker.share_submitted(1), None);
+        assert_eq!(tracker.freshness(1), ShareFreshness::Expired);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.histogram("cuckatoo_share_latency_seconds").unwrap().count, 2);
+        assert_eq!(snapshot.counter("cuckatoo_shares_submitted_total"), Some(2));
+        assert_eq!(snapshot.counter("cuckatoo_shares_unknown_job_total"), Some(2));
+    }
+
+    #[test]
+    fn test_stale_share_grace() {
+        let registry = MetricsRegistry::new();
+        let strict = ShareLatencyTracker::new(registry.clone());
+        strict.job_received(1);
+        assert_eq!(strict.freshness(1), ShareFreshness::Current);
+        strict.job_received(2);
+        assert_eq!(strict.freshness(1), ShareFreshness::Expired);
+
+        let lenient = ShareLatencyTracker::new(registry.clone()).with_stale_grace(Duration::from_secs(60));
+        for job in 1..=3 {
+            lenient.job_received(job);
+        }
+        assert_eq!(lenient.freshness(3), ShareFreshness::Current);
+        assert_eq!(lenient.freshness(2), ShareFreshness::Stale);
+        // Only the immediately previous job gets a grace period
+        assert_eq!(lenient.freshness(1), ShareFreshness::Expired);
+        assert_eq!(lenient.freshness(9), ShareFreshness::Expired);
+
+        lenient.share_submitted(3);
+        lenient.share_submitted(2);
+        lenient.share_discarded();
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("cuckatoo_shares_submitted_total"), Some(1));
+        assert_eq!(snapshot.counter("cuckatoo_stale_shares_submitted_total"), Some(1));
+        assert_eq!(snapshot.counter("cuckatoo_stale_shares_discarded_total"), Some(1));
+    }
+
+    #[test]
+    fn test_worker_metrics() {
+        let registry = MetricsRegistry::new();
+        let first = registry.worker("cpu-0");
+        first.record_graph(Duration::from_millis(30), Duration::from_millis(10), 0);
+        first.record_graph(Duration::from_millis(50), Duration::from_millis(10), 1);
+        registry.worker("gpu \"1\"").record_graph(Duration::from_millis(5), Duration::ZERO, 0);
+
+        let workers = registry.snapshot().workers();
+        assert_eq!(workers.len(), 2);
+        assert_eq!((workers[0].worker.as_str(), workers[0].graphs, workers[0].solutions), ("cpu-0", 2, 1));
+        assert!((workers[0].average_trim_seconds() - 0.04).abs() < 1e-9);
+        assert!((workers[0].average_search_seconds() - 0.01).abs() < 1e-9);
+        assert_eq!(workers[1].worker, "gpu \"1\"");
+        assert_eq!(WorkerSummary::default().average_trim_seconds(), 0.0);
+
+        // One TYPE line per metric, whatever the number of workers
+        let text = registry.to_prometheus();
+        assert_eq!(text.matches("# TYPE cuckatoo_worker_graphs_total counter\n").count(), 1);
+        assert!(text.contains("cuckatoo_worker_graphs_total{worker=\"cpu-0\"} 2\n"));
+    }
+
+    #[test]
+    fn test_proof_difficulty_histogram() {
+        let registry = MetricsRegistry::new();
+        for difficulty in [1, 3, 5, 100] {
+            registry.record_proof_difficulty(difficulty);
+        }
+
+        let snapshot = registry.snapshot();
+        let histogram = snapshot.proof_difficulty().unwrap();
+        assert_eq!(histogram.count, 4);
+        assert_eq!(&histogram.buckets[..4], &[(1.0, 1), (4.0, 2), (16.0, 3), (64.0, 3)]);
+        let json = histogram.to_json();
+        assert!(json.starts_with("{\"count\":4,\"sum\":109,\"buckets\":[[1,1],[4,2],"));
+        assert!(json.ends_with("[null,4]]}"));
+    }
+
+    #[test]
+    fn test_prometheus_format() {
+        let registry = MetricsRegistry::new();
+        registry.counter("cuckatoo_graphs_total").inc();
+        registry.histogram("cuckatoo_trim_seconds", &[0.5]).observe(0.25);
+
+        let text = registry.to_prometheus();
+        assert!(text.contains("# TYPE cuckatoo_graphs_total counter\ncuckatoo_graphs_total 1\n"));
+        assert!(text.contains("cuckatoo_trim_seconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(text.contains("cuckatoo_trim_seconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(text.contains("cuckatoo_trim_seconds_count 1\n"));
+    }
+}
@@ -0,0 +1,148 @@
+//! Live metrics endpoint
+//!
+//! `MetricsServer` answers plain HTTP requests from a background thread
+//! while the miner runs, so Prometheus (or `curl`) can scrape a
+//! `MetricsRegistry` at any time instead of waiting for the dump at exit.
+//! `GET /metrics` returns the Prometheus text format and `GET /status` a
+//! JSON summary (see `MetricsSnapshot::to_status_json`), so rising error
+//! counts or a lagging worker show while the miner still runs; anything
+//! else is a 404.
+//! Requests are served one at a time, which is plenty for a scraper.
+
+use crate::{CuckatooError, MetricsRegistry, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long a client may take to send its request line
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// HTTP endpoint serving a metrics registry until dropped
+pub struct MetricsServer {
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Listen on `address` (e.g. `127.0.0.1:9100`, port 0 for any free
+    /// port) and serve `registry` from a background thread
+    pub fn bind(address: impl ToSocketAddrs, registry: MetricsRegistry) -> Result<Self> {
+        let listener = TcpListener::bind(address)
+            .map_err(|error| CuckatooError::InternalError(format!("Cannot listen for metrics: {}", error)))?;
+        let address = listener
+            .local_addr()
+            .map_err(|error| CuckatooError::InternalError(format!("Cannot listen for metrics: {}", error)))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let thread = std::thread::Builder::new()
+            .name("metrics-server".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    // A client that goes away mid-request only loses its answer
+                    if let Ok(stream) = stream {
+                        let _ = respond(stream, &registry);
+                    }
+                }
+            })
+            .map_err(|error| CuckatooError::InternalError(format!("Cannot start metrics server: {}", error)))?;
+        Ok(Self { address, stop, thread: Some(thread) })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wake the accept loop so it sees the stop flag
+        let _ = TcpStream::connect(self.address);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer one request with the registry's current metrics
+fn respond(stream: TcpStream, registry: &MetricsRegistry) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", PROMETHEUS_CONTENT_TYPE, registry.to_prometheus()),
+        (Some("GET"), Some("/status")) => ("200 OK", "application/json", registry.snapshot().to_status_json()),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Send a GET request and return the whole response
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serves_live_metrics() {
+        let registry = MetricsRegistry::new();
+        let server = MetricsServer::bind("127.0.0.1:0", registry.clone()).unwrap();
+        registry.counter("cuckatoo_graphs_total").inc();
+
+        let response = get(server.local_addr(), "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("cuckatoo_graphs_total 1\n"));
+
+        // Every scrape sees the registry as it is now
+        registry.counter("cuckatoo_graphs_total").add(2);
+        assert!(get(server.local_addr(), "/metrics").contains("cuckatoo_graphs_total 3\n"));
+        assert!(get(server.local_addr(), "/other").starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        // Errors show in the status as they are recorded
+        assert!(get(server.local_addr(), "/status").ends_with("{\"errors_total\":0,\"errors\":{},\"workers\":[]}"));
+        registry.record_error(&CuckatooError::DeviceError("lost device".to_string()));
+        let response = get(server.local_addr(), "/status");
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        assert!(response.ends_with("{\"errors_total\":1,\"errors\":{\"device\":1},\"workers\":[]}"), "{}", response);
+        assert!(get(server.local_addr(), "/metrics").contains("cuckatoo_device_errors_total 1\n"));
+
+        // And so do the workers' graphs
+        let worker = registry.worker("thread-0");
+        worker.record_graph(Duration::from_millis(30), Duration::from_millis(10), 1);
+        worker.record_graph(Duration::from_millis(10), Duration::from_millis(10), 0);
+        assert!(get(server.local_addr(), "/status").ends_with(concat!(
+            "\"workers\":[{\"worker\":\"thread-0\",\"graphs\":2,\"solutions\":1,",
+            "\"average_trim_seconds\":0.020000,\"average_search_seconds\":0.010000}]}"
+        )));
+
+        let address = server.local_addr();
+        drop(server);
+        assert!(TcpStream::connect(address).is_err());
+    }
+}
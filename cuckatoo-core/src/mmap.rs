@@ -0,0 +1,154 @@
+//! Disk-backed bitmap words
+//!
+//! At edge bits 33 and up the trimmer's bitmaps outgrow the RAM of most
+//! hosts. `MappedWords` keeps them in a memory-mapped temp file instead:
+//! the file is unlinked as soon as it is created, so it never outlives
+//! the mapping, and the kernel pages words in and out as trimming walks
+//! over them.
+//!
+//! On Linux the same mappings also serve explicit huge pages: anonymous
+//! `MAP_HUGETLB` mappings from the pool reserved in `vm.nr_hugepages`.
+
+use crate::bitmap::{Access, ZeroableWord};
+#[cfg(target_os = "linux")]
+use crate::huge_pages::HUGE_PAGE_SIZE;
+use crate::{CuckatooError, Result};
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Temp files created so far, to give each a unique name
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Words in a mapping of an unlinked temp file or of huge pages
+pub(crate) struct MappedWords<T> {
+    words: NonNull<T>,
+    len: usize,
+    bytes: usize,
+}
+
+// SAFETY: the mapping is owned like a `Vec<T>`
+unsafe impl<T: Send> Send for MappedWords<T> {}
+unsafe impl<T: Sync> Sync for MappedWords<T> {}
+
+impl<T: ZeroableWord> MappedWords<T> {
+    /// Map `len` zeroed words backed by a temp file in `dir`
+    pub(crate) fn new(len: usize, dir: &Path, access: Access) -> Result<Self> {
+        let error = |what: &str, error: std::io::Error| {
+            CuckatooError::MemoryError(format!("Cannot {} disk-backed bitmap in {}: {}", what, dir.display(), error))
+        };
+        let bytes = len.checked_mul(std::mem::size_of::<T>()).filter(|&bytes| bytes > 0).ok_or_else(|| {
+            CuckatooError::MemoryError(format!("Disk-backed bitmap of {} words cannot be mapped", len))
+        })?;
+
+        let path = dir.join(format!("cuckatoo-bitmap-{}-{}", std::process::id(), TEMP_FILES.fetch_add(1, Ordering::Relaxed)));
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)
+            .map_err(|e| error("create", e))?;
+        // The mapping keeps the blocks alive; the name isn't needed
+        std::fs::remove_file(&path).map_err(|e| error("unlink", e))?;
+        file.set_len(bytes as u64).map_err(|e| error("size", e))?;
+
+        // SAFETY: a fresh shared mapping of a file we just sized, checked below
+        let mapping = unsafe {
+            libc::mmap(std::ptr::null_mut(), bytes, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if mapping == libc::MAP_FAILED {
+            return Err(error("map", std::io::Error::last_os_error()));
+        }
+        let advice = match access {
+            Access::Sequential => libc::MADV_SEQUENTIAL,
+            Access::Random => libc::MADV_RANDOM,
+        };
+        // SAFETY: the range is the mapping just created; advice is only a hint
+        unsafe { libc::madvise(mapping, bytes, advice) };
+
+        Ok(Self {
+            words: NonNull::new(mapping.cast()).expect("mmap returned a null mapping"),
+            len,
+            bytes,
+        })
+    }
+
+    /// Map `len` zeroed words on explicit huge pages, None if none are
+    /// reserved (or not enough)
+    #[cfg(target_os = "linux")]
+    pub(crate) fn huge_pages(len: usize) -> Option<Self> {
+        let bytes = len.checked_mul(std::mem::size_of::<T>())?.checked_next_multiple_of(HUGE_PAGE_SIZE)?;
+        if bytes == 0 {
+            return None;
+        }
+        // SAFETY: a fresh private anonymous mapping, checked below
+        let mapping = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(), bytes, libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB, -1, 0,
+            )
+        };
+        if mapping == libc::MAP_FAILED {
+            return None;
+        }
+        Some(Self { words: NonNull::new(mapping.cast())?, len, bytes })
+    }
+}
+
+impl<T> std::ops::Deref for MappedWords<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the mapping holds `len` words, zero-initialized or written since
+        unsafe { std::slice::from_raw_parts(self.words.as_ptr(), self.len) }
+    }
+}
+
+impl<T> std::ops::DerefMut for MappedWords<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: as for `deref`, and `&mut self` makes the borrow unique
+        unsafe { std::slice::from_raw_parts_mut(self.words.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for MappedWords<T> {
+    fn drop(&mut self) {
+        // SAFETY: unmaps the mapping created in `new`, which nothing borrows any more
+        unsafe { libc::munmap(self.words.as_ptr().cast(), self.bytes) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AtomicBitmap, Bitmap, BitmapTrimmer, Config, SipHash, SipKeys};
+
+    #[test]
+    fn test_disk_backed_trimming() {
+        let dir = std::env::temp_dir().join(format!("cuckatoo-mmap-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let siphash = SipHash::with_key(SipKeys::from_header(b"mmap", 7));
+        let expected = BitmapTrimmer::new(12).trim_edges(&siphash, 6).unwrap();
+
+        // 2^12 edges need two 512-byte bitmaps: both fit, one fits, none fit
+        let in_ram = BitmapTrimmer::try_new_with_budget(12, 1024, &dir).unwrap();
+        assert!(!in_ram.is_disk_backed());
+        for budget in [512, 0] {
+            let mut trimmer = BitmapTrimmer::try_new_with_budget(12, budget, &dir).unwrap();
+            assert!(trimmer.is_disk_backed());
+            assert_eq!(trimmer.trim_edges(&siphash, 6).unwrap(), expected);
+        }
+
+        let config = Config { bitmap_memory: Some(0), spill_dir: Some(dir.clone()), ..Config::new(12) };
+        let mut trimmer = BitmapTrimmer::for_config(&config).unwrap();
+        assert_eq!(trimmer.trim_edges(&siphash, 6).unwrap(), expected);
+
+        let mut bitmap = Bitmap::new_disk_backed(100, &dir).unwrap();
+        bitmap.set_bit(99);
+        assert!(bitmap.is_disk_backed() && bitmap.is_bit_set(99));
+        assert!(AtomicBitmap::new_disk_backed(100, &dir).unwrap().is_disk_backed());
+
+        // The temp files are unlinked as soon as they are mapped
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        drop(trimmer);
+        std::fs::remove_dir(&dir).unwrap();
+        assert!(BitmapTrimmer::try_new_with_budget(12, 0, &dir).is_err());
+    }
+}
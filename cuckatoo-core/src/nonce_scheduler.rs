@@ -0,0 +1,230 @@
+//! Nonce scheduling strategies
+//!
+//! A `NonceScheduler` hands out the nonces to mine. Strided scheduling gives
+//! each rig (or worker) its own residue class, so several rigs mining for the
+//! same solo node never try the same nonce.
+
+use crate::CuckatooError;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the nonces to mine
+pub trait NonceScheduler: Send {
+    /// Get the next nonce to mine
+    fn next_nonce(&mut self) -> u64;
+}
+
+/// Nonces `start, start + 1, ...`
+#[derive(Debug, Clone)]
+pub struct SequentialScheduler {
+    next: u64,
+}
+
+impl SequentialScheduler {
+    /// Create a scheduler starting at `start`
+    pub fn new(start: u64) -> Self {
+        Self { next: start }
+    }
+}
+
+impl NonceScheduler for SequentialScheduler {
+    fn next_nonce(&mut self) -> u64 {
+        let nonce = self.next;
+        self.next = self.next.wrapping_add(1);
+        nonce
+    }
+}
+
+/// Nonces `start + offset, start + offset + stride, ...`
+///
+/// Schedulers with the same start and stride and different offsets below
+/// the stride never produce the same nonce.
+#[derive(Debug, Clone)]
+pub struct StridedScheduler {
+    next: u64,
+    stride: u64,
+}
+
+impl StridedScheduler {
+    /// Create a scheduler for slot `offset` of `stride` (stride at least 1)
+    pub fn new(start: u64, offset: u64, stride: u64) -> Self {
+        Self {
+            next: start.wrapping_add(offset),
+            stride: stride.max(1),
+        }
+    }
+}
+
+impl NonceScheduler for StridedScheduler {
+    fn next_nonce(&mut self) -> u64 {
+        let nonce = self.next;
+        self.next = self.next.wrapping_add(self.stride);
+        nonce
+    }
+}
+
+/// Pseudo-random nonces from a seed (SplitMix64)
+#[derive(Debug, Clone)]
+pub struct RandomScheduler {
+    state: u64,
+}
+
+impl RandomScheduler {
+    /// Create a scheduler; the same seed gives the same nonces
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl NonceScheduler for RandomScheduler {
+    fn next_nonce(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Nonce scheduling strategy selected in the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonceSchedule {
+    /// One nonce after another from the start nonce
+    #[default]
+    Sequential,
+    /// Every `stride`th nonce from `start + offset` (rig `offset` of `stride`)
+    Strided { offset: u64, stride: u64 },
+    /// Pseudo-random nonces (seeded from the clock if no seed is given)
+    Random { seed: Option<u64> },
+}
+
+impl NonceSchedule {
+    /// Build a scheduler for this strategy
+    ///
+    /// `start` is ignored by random scheduling.
+    pub fn scheduler(&self, start: u64) -> Box<dyn NonceScheduler> {
+        match *self {
+            NonceSchedule::Sequential => Box::new(SequentialScheduler::new(start)),
+            NonceSchedule::Strided { offset, stride } => Box::new(StridedScheduler::new(start, offset, stride)),
+            NonceSchedule::Random { seed } => {
+                let seed = seed.unwrap_or_else(|| {
+                    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+                });
+                Box::new(RandomScheduler::new(seed))
+            },
+        }
+    }
+}
+
+/// A start nonce drawn from operating system entropy
+///
+/// Independent solo miners on the same header each start at a different
+/// point of the 64-bit nonce space instead of all duplicating nonce 0
+/// onwards. Reads `/dev/urandom` where it exists and otherwise falls back
+/// to std's randomly keyed `RandomState` hasher. Print the result: passing
+/// it back as the start nonce reproduces the run.
+pub fn entropy_start_nonce() -> u64 {
+    let mut bytes = [0u8; 8];
+    let urandom = std::fs::File::open("/dev/urandom").and_then(|mut file| file.read_exact(&mut bytes));
+    if urandom.is_ok() {
+        return u64::from_le_bytes(bytes);
+    }
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos()));
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+impl fmt::Display for NonceSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NonceSchedule::Sequential => write!(f, "sequential"),
+            NonceSchedule::Strided { offset, stride } => write!(f, "strided:{}/{}", offset, stride),
+            NonceSchedule::Random { seed: Some(seed) } => write!(f, "random:{}", seed),
+            NonceSchedule::Random { seed: None } => write!(f, "random"),
+        }
+    }
+}
+
+impl std::str::FromStr for NonceSchedule {
+    type Err = CuckatooError;
+
+    /// Parse `sequential`, `strided:<offset>/<stride>`, `random` or `random:<seed>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CuckatooError::InternalError(format!("Unknown nonce schedule: {}", s));
+        let lower = s.to_lowercase();
+        let (name, argument) = match lower.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (lower.as_str(), None),
+        };
+
+        match (name, argument) {
+            ("sequential", None) => Ok(NonceSchedule::Sequential),
+            ("strided", Some(argument)) => {
+                let (offset, stride) = argument.split_once('/').ok_or_else(invalid)?;
+                let offset: u64 = offset.parse().map_err(|_| invalid())?;
+                let stride: u64 = stride.parse().map_err(|_| invalid())?;
+                if offset >= stride {
+                    return Err(CuckatooError::InternalError(format!(
+                        "Strided nonce schedule offset {} must be below the stride {}", offset, stride
+                    )));
+                }
+                Ok(NonceSchedule::Strided { offset, stride })
+            },
+            ("random", None) => Ok(NonceSchedule::Random { seed: None }),
+            ("random", Some(seed)) => Ok(NonceSchedule::Random { seed: Some(seed.parse().map_err(|_| invalid())?) }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn take(schedule: NonceSchedule, start: u64, count: usize) -> Vec<u64> {
+        let mut scheduler = schedule.scheduler(start);
+        (0..count).map(|_| scheduler.next_nonce()).collect()
+    }
+
+    #[test]
+    fn test_strided_rigs_dont_collide() {
+        assert_eq!(take(NonceSchedule::Sequential, 10, 3), [10, 11, 12]);
+        assert_eq!(take(NonceSchedule::Strided { offset: 1, stride: 4 }, 10, 3), [11, 15, 19]);
+
+        let mut seen = HashSet::new();
+        for offset in 0..4 {
+            for nonce in take(NonceSchedule::Strided { offset, stride: 4 }, 10, 100) {
+                assert!(seen.insert(nonce), "nonce {} scheduled twice", nonce);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_is_seeded() {
+        let schedule = NonceSchedule::Random { seed: Some(7) };
+        assert_eq!(take(schedule, 0, 5), take(schedule, 100, 5));
+        assert_ne!(take(schedule, 0, 5), take(NonceSchedule::Random { seed: Some(8) }, 0, 5));
+    }
+
+    #[test]
+    fn test_entropy_start_nonces_differ() {
+        // A collision of two 64-bit draws is not a realistic outcome
+        assert_ne!(entropy_start_nonce(), entropy_start_nonce());
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        for text in ["sequential", "strided:1/4", "random", "random:42"] {
+            assert_eq!(text.parse::<NonceSchedule>().unwrap().to_string(), text);
+        }
+        assert!("strided:4/4".parse::<NonceSchedule>().is_err());
+        assert!("strided:1".parse::<NonceSchedule>().is_err());
+        assert!("depth-first".parse::<NonceSchedule>().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! NUMA placement of the parallel trimmer's workers
+//!
+//! On multi-socket hosts every socket has its own memory, and a worker
+//! reading pages of another socket's node waits on the interconnect. With
+//! `NumaPolicy::Local` the trimmer spreads its workers over the nodes in
+//! order, pins each to its node's CPUs and lets it touch its own range of
+//! the bitmaps first, so the kernel's first-touch policy puts those pages
+//! on the worker's node.
+
+/// CPUs of each NUMA node of the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NumaTopology {
+    nodes: Vec<Vec<usize>>,
+}
+
+impl NumaTopology {
+    /// Read the host's nodes from sysfs, None unless there are at least two
+    #[cfg(target_os = "linux")]
+    pub(crate) fn detect() -> Option<Self> {
+        let mut lists = Vec::new();
+        for node in 0.. {
+            match std::fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", node)) {
+                Ok(list) => lists.push(list),
+                Err(_) => break,
+            }
+        }
+        Self::from_cpu_lists(&lists)
+    }
+
+    /// NUMA nodes are only read from Linux's sysfs
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn detect() -> Option<Self> {
+        None
+    }
+
+    /// Topology of nodes with the given CPU lists (like `0-3,8-11`), None
+    /// unless at least two of them have CPUs
+    pub(crate) fn from_cpu_lists(lists: &[impl AsRef<str>]) -> Option<Self> {
+        let nodes: Vec<Vec<usize>> = lists
+            .iter()
+            .filter_map(|list| parse_cpu_list(list.as_ref()))
+            .filter(|cpus| !cpus.is_empty())
+            .collect();
+        (nodes.len() >= 2).then_some(Self { nodes })
+    }
+
+    /// Number of nodes
+    pub(crate) fn nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// CPUs of the node worker `worker` of `workers` runs on
+    ///
+    /// Workers own consecutive ranges of the bitmaps, so consecutive
+    /// workers share a node and each node gets an equal share of them.
+    pub(crate) fn cpus_for_worker(&self, worker: usize, workers: usize) -> &[usize] {
+        &self.nodes[worker * self.nodes.len() / workers.max(1)]
+    }
+}
+
+/// Parse a sysfs CPU list such as `0-3,8-11`
+fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// Pin the calling thread to `cpus`, returning whether the kernel accepted
+/// the affinity
+#[cfg(all(target_os = "linux", feature = "numa"))]
+pub(crate) fn pin_current_thread(cpus: &[usize]) -> bool {
+    // SAFETY: a zeroed cpu_set_t is an empty set
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus {
+        // SAFETY: CPU_SET ignores CPUs past the end of the set
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    // SAFETY: the set outlives the call; 0 selects the calling thread
+    unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 }
+}
+
+/// Threads can only be pinned on Linux with the `numa` feature
+#[cfg(not(all(target_os = "linux", feature = "numa")))]
+pub(crate) fn pin_current_thread(_cpus: &[usize]) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitmapTrimmer, SipHash, SipKeys};
+
+    #[test]
+    fn test_numa_placement() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+        assert_eq!(parse_cpu_list("0-x"), None);
+        assert!(NumaTopology::from_cpu_lists(&["0-7"]).is_none());
+        assert!(NumaTopology::from_cpu_lists(&["0-7", ""]).is_none());
+
+        let topology = NumaTopology::from_cpu_lists(&["0-1", "2-3"]).unwrap();
+        assert_eq!(topology.nodes(), 2);
+        let nodes: Vec<&[usize]> = (0..4).map(|worker| topology.cpus_for_worker(worker, 4)).collect();
+        assert_eq!(nodes, [&[0, 1][..], &[0, 1], &[2, 3], &[2, 3]]);
+
+        // Placement doesn't change the survivors (both "nodes" are CPU 0
+        // here, which every host has)
+        let siphash = SipHash::with_key(SipKeys::from_header(b"numa", 4));
+        let expected = BitmapTrimmer::new(14).trim_edges(&siphash, 6).unwrap();
+        let single_cpu = NumaTopology::from_cpu_lists(&["0", "0"]).unwrap();
+        let mut trimmer = BitmapTrimmer::new(14).with_threads(3).with_numa_topology(single_cpu);
+        assert_eq!(trimmer.numa_nodes(), 2);
+        assert_eq!(trimmer.trim_edges(&siphash, 6).unwrap(), expected);
+    }
+}
@@ -0,0 +1,271 @@
+//! Path-following cycle finder
+//!
+//! Tromp's classic cuckoo solver keeps the edges added so far as a forest
+//! in which every node points at most at one other node, towards the root
+//! of its tree. An edge is added by following the paths from both of its
+//! nodes to their roots. Different roots mean the edge joins two trees:
+//! the shorter path is reversed, making its node the root of its tree, and
+//! that node is pointed at the edge's other node. The same root means the
+//! edge closes a cycle, made of the two paths up to where they meet and
+//! the edge itself. Each step follows one pointer in one array, with no
+//! connection lists to build or walk.
+//!
+//! An edge closing a cycle is not added, as the forest can't hold it, so a
+//! cycle sharing edges with one closed earlier can be missed. The forest's
+//! nodes are node pairs (`2k` and `2k + 1`), U and V pairs kept apart, as
+//! a proof walks from an edge at one node of a pair to an edge at the
+//! other. The forest can't tell which node of a pair an edge is at, so a
+//! cycle of the right length is only a proof if it reaches every pair at
+//! both of its nodes; one that doesn't is dropped like a cycle of the
+//! wrong length. Trimmed cuckatoo graphs have many such cycles over pairs,
+//! and the edges closing them are lost, so this finds fewer of the
+//! 42-cycles than `ArrayCycleFinder`.
+
+use crate::cancel::BudgetMeter;
+use crate::graph_stats::dense_endpoints;
+use crate::types::assert_cycle_length;
+use crate::{CancelToken, CuckatooError, CycleSearchStats, Edge, Result, SearchBudget, SOLUTION_SIZE};
+use std::collections::HashSet;
+
+/// Node without an outgoing pointer (a root)
+const NONE: u32 = u32::MAX;
+
+/// Cycle finder following paths in a forest of the edges
+pub struct PathSolver {
+    /// Node each node points at, towards its tree's root
+    cuckoo: Vec<u32>,
+    /// Path from the current edge's U node to its root
+    us: Vec<u32>,
+    /// Path from the current edge's V node to its root
+    vs: Vec<u32>,
+    /// Number of edges in the cycles searched for
+    cycle_length: usize,
+    stats: CycleSearchStats,
+    /// Token stopping the search between edges
+    cancel: Option<CancelToken>,
+    /// Limits on the work of each search
+    budget: SearchBudget,
+    /// Budget left to the running search
+    meter: BudgetMeter,
+}
+
+impl Default for PathSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathSolver {
+    /// Create a new path solver
+    pub fn new() -> Self {
+        Self {
+            cuckoo: Vec::new(),
+            us: Vec::new(),
+            vs: Vec::new(),
+            cycle_length: SOLUTION_SIZE,
+            stats: CycleSearchStats::default(),
+            cancel: None,
+            budget: SearchBudget::default(),
+            meter: BudgetMeter::default(),
+        }
+    }
+
+    /// Search for cycles of `length` edges instead of 42
+    ///
+    /// Panics if `length` is odd or outside 4 to `MAX_CYCLE_LENGTH`.
+    pub fn with_cycle_length(mut self, length: usize) -> Self {
+        assert_cycle_length(length);
+        self.cycle_length = length;
+        self
+    }
+
+    /// Number of edges in the cycles searched for
+    pub fn cycle_length(&self) -> usize {
+        self.cycle_length
+    }
+
+    /// Stop searching with `Cancelled` soon after `token` is cancelled
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Fail searches with `BudgetExceeded` once they go over `budget`,
+    /// counting every pointer followed as a step
+    pub fn with_budget(mut self, budget: SearchBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Work done by the last search: cycles closed, pointers followed and
+    /// the longest path followed
+    pub fn stats(&self) -> CycleSearchStats {
+        self.stats
+    }
+
+    /// Find a cycle of `cycle_length` edges, returning the indices into
+    /// `edges` of its edges in ascending order
+    pub fn find_cycle(&mut self, edges: &[Edge]) -> Result<Option<Vec<usize>>> {
+        if edges.len() >= (NONE / 2) as usize {
+            return Err(CuckatooError::InternalError(format!("Too many edges for the cycle finder: {}", edges.len())));
+        }
+        let (pairs, endpoints) = dense_endpoints(edges);
+        self.cuckoo.clear();
+        self.cuckoo.resize(pairs, NONE);
+        self.stats = CycleSearchStats::default();
+        self.meter = self.budget.start();
+
+        for (index, &(u, v)) in endpoints.iter().enumerate() {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(CuckatooError::Cancelled);
+            }
+            let mut us = std::mem::take(&mut self.us);
+            let mut vs = std::mem::take(&mut self.vs);
+            let followed = self.follow(u >> 1, &mut us).and_then(|()| self.follow(v >> 1, &mut vs));
+            let cycle = followed.map(|()| self.add_edge(&us, &vs));
+            self.us = us;
+            self.vs = vs;
+            if let Some(pairs) = cycle? {
+                let cycle = cycle_edges(&endpoints[..=index], &pairs);
+                if alternates(&endpoints, &cycle) {
+                    return Ok(Some(cycle));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Fill `path` with the nodes from `node` to its tree's root
+    fn follow(&mut self, mut node: u32, path: &mut Vec<u32>) -> Result<()> {
+        path.clear();
+        path.push(node);
+        while self.cuckoo[node as usize] != NONE {
+            node = self.cuckoo[node as usize];
+            path.push(node);
+            self.stats.traversal_steps += 1;
+            if self.meter.step() {
+                return Err(CuckatooError::BudgetExceeded);
+            }
+        }
+        self.stats.max_chain_length = self.stats.max_chain_length.max(path.len() as u32 - 1);
+        Ok(())
+    }
+
+    /// Add the edge from `us[0]` to `vs[0]`, whose paths to their roots are
+    /// `us` and `vs`, to the forest; returns the pairs of the cycle it
+    /// closes instead, if that's a cycle of `cycle_length` edges
+    fn add_edge(&mut self, us: &[u32], vs: &[u32]) -> Option<Vec<u32>> {
+        let (mut nu, mut nv) = (us.len() - 1, vs.len() - 1);
+        if us[nu] == vs[nv] {
+            // Walk back from the shared root to where the paths meet
+            while nu > 0 && nv > 0 && us[nu - 1] == vs[nv - 1] {
+                nu -= 1;
+                nv -= 1;
+            }
+            self.stats.candidate_pairs += 1;
+            if nu + nv + 1 != self.cycle_length {
+                return None;
+            }
+            return Some(us[..=nu].iter().chain(vs[..nv].iter().rev()).copied().collect());
+        }
+
+        // Reverse the shorter path and hang it from the other node
+        let (path, to) = if nu < nv { (us, vs[0]) } else { (vs, us[0]) };
+        for pair in path.windows(2).rev() {
+            self.cuckoo[pair[1] as usize] = pair[0];
+        }
+        self.cuckoo[path[0] as usize] = to;
+        None
+    }
+}
+
+/// Indices, ascending, of the edges joining consecutive `pairs` of a
+/// cycle (the last pair back to the first), taking the first edge joining
+/// each two pairs as later ones are never added to the forest
+fn cycle_edges(endpoints: &[(u32, u32)], pairs: &[u32]) -> Vec<usize> {
+    let link = |a: u32, b: u32| (a.min(b), a.max(b));
+    let mut wanted: HashSet<(u32, u32)> = pairs
+        .iter()
+        .zip(pairs.iter().cycle().skip(1))
+        .map(|(&a, &b)| link(a, b))
+        .collect();
+    endpoints
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(u, v))| wanted.remove(&link(u >> 1, v >> 1)))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Whether the edges of a cycle over pairs reach every pair at both of
+/// its nodes, i.e. at distinct slots, making the cycle a valid proof
+fn alternates(endpoints: &[(u32, u32)], cycle: &[usize]) -> bool {
+    let mut slots = HashSet::with_capacity(2 * cycle.len());
+    cycle.iter().all(|&index| {
+        let (u, v) = endpoints[index];
+        slots.insert(u) && slots.insert(v)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{KNOWN_PROOF, KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_KEYS};
+    use crate::{CycleVerifier, Node, SipHash, SolverStrategy};
+
+    #[test]
+    fn test_path_solver() {
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let indices: Vec<u64> = (0..1 << KNOWN_PROOF_EDGE_BITS).collect();
+        let graph = siphash.edges_for_indices(&indices, KNOWN_PROOF_EDGE_BITS);
+        // The known proof's edges after the unrelated edges below them
+        let planted: Vec<Edge> = indices[..KNOWN_PROOF[0] as usize].iter().chain(&KNOWN_PROOF).map(|&i| graph[i as usize]).collect();
+        let mut solver = PathSolver::new();
+        let cycle = solver.find_cycle(&planted).unwrap().unwrap();
+        assert_eq!(cycle, (KNOWN_PROOF[0] as usize..planted.len()).collect::<Vec<_>>());
+        assert!(solver.stats().candidate_pairs > 0);
+        assert!(solver.stats().traversal_steps >= u64::from(solver.stats().max_chain_length));
+
+        // Not without one of its edges
+        let mut broken = planted.clone();
+        broken.remove(KNOWN_PROOF[0] as usize + 7);
+        assert_eq!(solver.find_cycle(&broken).unwrap(), None);
+
+        // Whatever it finds in the whole graph is a proof
+        if let Some(cycle) = solver.find_cycle(&graph).unwrap() {
+            let nonces: Vec<u64> = cycle.iter().map(|&index| index as u64).collect();
+            assert!(crate::verify_proof_nonces(&KNOWN_PROOF_KEYS, KNOWN_PROOF_EDGE_BITS, &nonces).is_ok());
+        }
+
+        // A 12-cycle with a multi-edge and a tree hanging off it
+        let mut edges: Vec<Edge> = (0..6u64)
+            .flat_map(|i| [(2 * i, 2 * i), (2 * ((i + 1) % 6) + 1, 2 * i + 1)])
+            .map(|(u, v)| Edge::new(Node::new(u), Node::new(v)))
+            .collect();
+        edges.insert(3, edges[2]);
+        edges.extend([(0, 40), (42, 40), (44, 2)].map(|(u, v)| Edge::new(Node::new(u), Node::new(v))));
+        edges.rotate_left(4);
+        let mut solver = PathSolver::new().with_cycle_length(12);
+        let cycle = solver.find_cycle(&edges).unwrap().unwrap();
+        assert_eq!(cycle.len(), 12);
+        assert_eq!(cycle.iter().map(|&index| edges[index]).collect::<HashSet<_>>().len(), 12);
+        assert!(cycle.iter().all(|&index| edges[index].v.value() < 12 && edges[index].u.value() < 12));
+        assert_eq!(PathSolver::new().with_cycle_length(10).find_cycle(&edges).unwrap(), None);
+
+        // A 4-cycle over pairs reaching each pair twice at the same node
+        let same_nodes = [(0, 0), (2, 0), (2, 2), (0, 2)].map(|(u, v)| Edge::new(Node::new(u), Node::new(v)));
+        assert_eq!(PathSolver::new().with_cycle_length(4).find_cycle(&same_nodes).unwrap(), None);
+
+        let token = CancelToken::new();
+        token.cancel();
+        assert!(matches!(PathSolver::new().with_cancel_token(token).find_cycle(&graph), Err(CuckatooError::Cancelled)));
+        let budget = SearchBudget::default().with_max_steps(10);
+        assert!(matches!(PathSolver::new().with_budget(budget).find_cycle(&graph), Err(CuckatooError::BudgetExceeded)));
+
+        let mut verifier = CycleVerifier::new().with_solver_strategy("path".parse().unwrap());
+        let proof = verifier.verify_cycle(&planted).unwrap().unwrap();
+        assert_eq!(proof, KNOWN_PROOF.map(|index| graph[index as usize]).to_vec());
+        assert_eq!(SolverStrategy::Path.to_string(), "path");
+        assert!("tromp".parse::<SolverStrategy>().is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Pre-flight checks of a configuration
+//!
+//! Some configurations validate fine but are almost certainly a mistake:
+//! too few trimming rounds leave the cycle finder millions of edges, lean
+//! trimming wastes a machine that has memory for mean trimming, and edge
+//! bits overriding a chain preset mine graphs the chain won't accept.
+//! `Preflight` reports these as `ConfigWarning`s, which callers print,
+//! put in JSON output and record as metrics instead of refusing to run.
+
+use crate::{available_memory, format_memory_size, Config, MemoryBudget, MemoryEstimate, Preset, TrimmingMode};
+use std::fmt;
+
+/// Surviving edges from which the cycle finder's memory and time become a
+/// problem
+const MAX_SEARCH_EDGES: u64 = 1 << 20;
+
+/// A configuration that will run, but probably not as intended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigWarning {
+    /// So few trimming rounds that about `expected_edges` reach the
+    /// cycle finder
+    LowTrimmingRounds { rounds: u32, expected_edges: u64 },
+    /// Lean trimming although mean trimming, which needs `mean_bytes`,
+    /// fits in the `available_bytes` of the machine
+    LeanWithAmpleMemory { mean_bytes: u64, available_bytes: u64 },
+    /// Edge bits different from the selected chain preset's
+    PresetMismatch { preset: Preset, edge_bits: u32 },
+}
+
+impl ConfigWarning {
+    /// Stable identifier of the warning, e.g. `low_trimming_rounds`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConfigWarning::LowTrimmingRounds { .. } => "low_trimming_rounds",
+            ConfigWarning::LeanWithAmpleMemory { .. } => "lean_with_ample_memory",
+            ConfigWarning::PresetMismatch { .. } => "preset_mismatch",
+        }
+    }
+
+    /// Format the warning as a JSON object with its code, message and
+    /// the numbers behind it
+    pub fn to_json(&self) -> String {
+        let details = match self {
+            ConfigWarning::LowTrimmingRounds { rounds, expected_edges } => {
+                format!("\"rounds\":{},\"expected_edges\":{}", rounds, expected_edges)
+            },
+            ConfigWarning::LeanWithAmpleMemory { mean_bytes, available_bytes } => {
+                format!("\"mean_bytes\":{},\"available_bytes\":{}", mean_bytes, available_bytes)
+            },
+            ConfigWarning::PresetMismatch { preset, edge_bits } => {
+                format!("\"preset\":\"{}\",\"preset_edge_bits\":{},\"edge_bits\":{}", preset, preset.edge_bits, edge_bits)
+            },
+        };
+        format!("{{\"code\":\"{}\",\"message\":\"{}\",{}}}", self.code(), self, details)
+    }
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigWarning::LowTrimmingRounds { rounds, expected_edges } => write!(
+                f, "{} trimming rounds leave about {} edges to the cycle finder", rounds, expected_edges
+            ),
+            ConfigWarning::LeanWithAmpleMemory { mean_bytes, available_bytes } => write!(
+                f, "lean trimming with {} available; mean trimming needs only {} and is faster",
+                format_memory_size(*available_bytes), format_memory_size(*mean_bytes)
+            ),
+            ConfigWarning::PresetMismatch { preset, edge_bits } => write!(
+                f, "EDGE_BITS={} doesn't match preset {} (EDGE_BITS={})", edge_bits, preset, preset.edge_bits
+            ),
+        }
+    }
+}
+
+/// Format warnings as a JSON array
+pub fn warnings_to_json(warnings: &[ConfigWarning]) -> String {
+    format!("[{}]", warnings.iter().map(ConfigWarning::to_json).collect::<Vec<_>>().join(","))
+}
+
+/// What a configuration is checked against
+#[derive(Debug, Clone, Default)]
+pub struct Preflight {
+    preset: Option<Preset>,
+    available_memory: Option<u64>,
+}
+
+impl Preflight {
+    /// Check configurations on their own
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check configurations against this machine's available memory
+    pub fn for_host() -> Self {
+        Self { available_memory: available_memory(), ..Self::default() }
+    }
+
+    /// Check that configurations mine the graphs of `preset`
+    pub fn with_preset(mut self, preset: Preset) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    /// Check configurations against `bytes` of available memory
+    pub fn with_available_memory(mut self, bytes: u64) -> Self {
+        self.available_memory = Some(bytes);
+        self
+    }
+
+    /// Warnings for a configuration, empty if it looks sound
+    pub fn check(&self, config: &Config) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+
+        // Adaptive and target-survivor trimming run up to the round cap
+        if config.round_controller().is_none() {
+            let expected_edges = expected_survivors(config.edge_bits, config.trimming_rounds);
+            if expected_edges > MAX_SEARCH_EDGES {
+                warnings.push(ConfigWarning::LowTrimmingRounds { rounds: config.trimming_rounds, expected_edges });
+            }
+        }
+
+        if let (TrimmingMode::Lean, Some(available_bytes)) = (config.mode, self.available_memory) {
+            let mean = MemoryEstimate::new(config.edge_bits, TrimmingMode::Mean);
+            // Only if the run's budget would allow it, and with room to spare
+            let allowed = MemoryBudget::for_config(config).is_none_or(|budget| budget.fits(&mean));
+            if allowed && mean.total().saturating_mul(2) <= available_bytes {
+                warnings.push(ConfigWarning::LeanWithAmpleMemory { mean_bytes: mean.total(), available_bytes });
+            }
+        }
+
+        if let Some(preset) = self.preset {
+            if preset.edge_bits != config.edge_bits {
+                warnings.push(ConfigWarning::PresetMismatch { preset, edge_bits: config.edge_bits });
+            }
+        }
+        warnings
+    }
+}
+
+/// Rough number of edges surviving `rounds` trimming rounds of a graph
+/// with `edge_bits`
+///
+/// The live fraction of a Cuckatoo graph falls off like the inverse square
+/// of the rounds; this is only meant to tell tens of rounds from a handful.
+fn expected_survivors(edge_bits: u32, rounds: u32) -> u64 {
+    let edges = 1u64.checked_shl(edge_bits).unwrap_or(u64::MAX);
+    edges / u64::from(rounds.max(1)).pow(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preflight_warnings() {
+        let codes = |warnings: Vec<ConfigWarning>| warnings.iter().map(ConfigWarning::code).collect::<Vec<_>>();
+        let config = Preset::grin_c31().config();
+        assert!(Preflight::new().with_preset(Preset::grin_c31()).check(&config).is_empty());
+
+        let few_rounds = Config { trimming_rounds: 8, ..config.clone() };
+        let warnings = Preflight::new().with_preset(Preset::grin_c32()).check(&few_rounds);
+        assert_eq!(codes(warnings.clone()), ["low_trimming_rounds", "preset_mismatch"]);
+        assert_eq!(warnings[0], ConfigWarning::LowTrimmingRounds { rounds: 8, expected_edges: (1 << 31) / 64 });
+        assert!(warnings[1].to_json().contains("\"preset\":\"grin-c32\",\"preset_edge_bits\":32,\"edge_bits\":31"));
+
+        // Lean trimming is only questioned when mean trimming fits easily
+        let mean_bytes = MemoryEstimate::new(31, TrimmingMode::Mean).total();
+        assert_eq!(codes(Preflight::new().with_available_memory(mean_bytes * 2).check(&config)), ["lean_with_ample_memory"]);
+        assert!(Preflight::new().with_available_memory(mean_bytes).check(&config).is_empty());
+        let budgeted = Config { max_memory: Some(mean_bytes - 1), ..config.clone() };
+        assert!(Preflight::new().with_available_memory(mean_bytes * 2).check(&budgeted).is_empty());
+
+        assert_eq!(warnings_to_json(&[]), "[]");
+        assert!(warnings_to_json(&warnings).starts_with("[{\"code\":\"low_trimming_rounds\",\"message\":\"8 trimming rounds"));
+    }
+}
@@ -0,0 +1,11 @@
+//! Commonly used Cuckatoo types
+//!
+//! `use cuckatoo_core::prelude::*;` brings in what most embedders need to
+//! generate, trim, search and verify graphs. Items are only added to the
+//! prelude, never removed, within a major version.
+
+pub use crate::{CuckatooError, Config, Edge, Node, Header, TrimmingMode, SipHash, SipHasher, SipKeys, SOLUTION_SIZE};
+pub use crate::{blake2b, verify_proof_nonces};
+
+#[cfg(not(feature = "verify-only"))]
+pub use crate::{BitmapTrimmer, AdaptiveRoundController, CycleVerifier, PerformanceTimer, MetricsRegistry};
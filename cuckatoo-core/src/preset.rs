@@ -0,0 +1,103 @@
+//! Consensus parameter presets
+//!
+//! A preset names a network's proof-of-work parameters (algorithm, edge
+//! bits, cycle length) together with the default trimming rounds, so
+//! integrators pick `Preset::grin_c31()` instead of assembling the numbers
+//! by hand and drifting from consensus.
+
+use crate::{Algorithm, Config, CuckatooError, Result, SOLUTION_SIZE};
+use std::fmt;
+
+/// Proof-of-work parameters of a network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset {
+    /// Preset name, e.g. `grin-c31`
+    pub name: &'static str,
+    /// Algorithm of the network's graphs
+    pub algorithm: Algorithm,
+    /// Edge bits of the network's graphs
+    pub edge_bits: u32,
+    /// Length of the cycles proofs consist of
+    pub cycle_length: usize,
+    /// Default trimming rounds
+    pub trimming_rounds: u32,
+}
+
+impl Preset {
+    /// Every preset, in order
+    pub const ALL: [Preset; 3] = [Preset::grin_c31(), Preset::grin_c32(), Preset::mwc_c31()];
+
+    /// Grin's Cuckatoo31 primary proof of work
+    pub const fn grin_c31() -> Self {
+        Self::cuckatoo("grin-c31", Algorithm::Cuckatoo31, 31)
+    }
+
+    /// Grin's Cuckatoo32 primary proof of work
+    pub const fn grin_c32() -> Self {
+        Self::cuckatoo("grin-c32", Algorithm::Cuckatoo32, 32)
+    }
+
+    /// MimbleWimble Coin's Cuckatoo31 proof of work
+    pub const fn mwc_c31() -> Self {
+        Self::cuckatoo("mwc-c31", Algorithm::Cuckatoo31, 31)
+    }
+
+    /// A Cuckatoo preset with 42-cycles and the C++ Makefile rounds
+    const fn cuckatoo(name: &'static str, algorithm: Algorithm, edge_bits: u32) -> Self {
+        Self { name, algorithm, edge_bits, cycle_length: SOLUTION_SIZE, trimming_rounds: 90 }
+    }
+
+    /// Configuration mining the preset's graphs, with defaults otherwise
+    pub fn config(&self) -> Config {
+        Config::from_preset(*self)
+    }
+}
+
+impl Config {
+    /// Create a configuration for a network preset
+    pub fn from_preset(preset: Preset) -> Self {
+        let mut config = Config::new(preset.edge_bits);
+        config.trimming_rounds = preset.trimming_rounds;
+        config
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl std::str::FromStr for Preset {
+    type Err = CuckatooError;
+
+    /// Parse a preset name such as `grin-c31` (`grin_c31` is accepted too)
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.to_lowercase().replace('_', "-");
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.name == name)
+            .ok_or_else(|| CuckatooError::InternalError(format!("Unknown preset: {} (expected grin-c31, grin-c32 or mwc-c31)", s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_match_consensus() {
+        for preset in Preset::ALL {
+            assert_eq!(preset.to_string().parse::<Preset>().unwrap(), preset);
+            assert_eq!(preset.edge_bits, preset.algorithm.edge_bits());
+            assert_eq!(preset.cycle_length, SOLUTION_SIZE);
+
+            let config = preset.config();
+            assert_eq!(config.edge_bits, preset.edge_bits);
+            assert_eq!(config.trimming_rounds, preset.trimming_rounds);
+            config.validate().unwrap();
+        }
+        assert_eq!("GRIN_C32".parse::<Preset>().unwrap(), Preset::grin_c32());
+        assert!("grin-c29".parse::<Preset>().is_err());
+    }
+}
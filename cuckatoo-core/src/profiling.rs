@@ -0,0 +1,72 @@
+//! Profiling hooks for Cuckatoo pipeline stages
+//!
+//! `profile_scope!` opens a `tracing` span for the rest of the enclosing
+//! block, so an embedder's subscriber sees trimming rounds and cycle
+//! searches alongside the library's log events. With the `profiling`
+//! feature it also opens a puffin scope for puffin viewers to attach to
+//! tuning runs.
+
+/// Scope name for edge generation
+pub const EDGE_GEN: &str = "edge_gen";
+
+/// Scope name for a single trimming round
+pub const TRIM_ROUND: &str = "trim_round";
+
+/// Scope name for cycle searching
+pub const CYCLE_SEARCH: &str = "cycle_search";
+
+#[cfg(feature = "profiling")]
+#[doc(hidden)]
+pub use puffin;
+
+#[doc(hidden)]
+pub use tracing;
+
+/// Check if the profiling hooks are compiled in
+pub fn profiling_enabled() -> bool {
+    cfg!(feature = "profiling")
+}
+
+/// Turn on collection of puffin scopes
+///
+/// Tracing spans are always emitted and only need a subscriber. Does
+/// nothing without the `profiling` feature.
+pub fn enable() {
+    #[cfg(feature = "profiling")]
+    puffin::set_scopes_on(true);
+}
+
+/// Mark the end of a puffin frame (for example one graph)
+///
+/// Does nothing without the `profiling` feature.
+pub fn finish_frame() {
+    #[cfg(feature = "profiling")]
+    puffin::GlobalProfiler::lock().new_frame();
+}
+
+/// Profile the rest of the enclosing block under a stable scope name
+///
+/// Use the names in `profiling` (`EDGE_GEN`, `TRIM_ROUND`, `CYCLE_SEARCH`)
+/// so profiles stay comparable between runs. Fields after the name are
+/// recorded on the span, e.g. `profile_scope!(TRIM_ROUND, round)`.
+#[cfg(feature = "profiling")]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr $(, $($fields:tt)+)?) => {
+        let _profiling_span = $crate::profiling::tracing::info_span!($name $(, $($fields)+)?).entered();
+        $crate::profiling::puffin::profile_scope!($name);
+    };
+}
+
+/// Profile the rest of the enclosing block under a stable scope name
+///
+/// Use the names in `profiling` (`EDGE_GEN`, `TRIM_ROUND`, `CYCLE_SEARCH`)
+/// so profiles stay comparable between runs. Fields after the name are
+/// recorded on the span, e.g. `profile_scope!(TRIM_ROUND, round)`.
+#[cfg(not(feature = "profiling"))]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr $(, $($fields:tt)+)?) => {
+        let _profiling_span = $crate::profiling::tracing::info_span!($name $(, $($fields)+)?).entered();
+    };
+}
@@ -0,0 +1,95 @@
+//! Per-round trimming progress
+//!
+//! Trimming a large graph takes many rounds of hashing every surviving
+//! edge, so trimmers can report each finished round to a
+//! `ProgressObserver`: the round number, the edges left and the time since
+//! the trim started. Observers render progress, log it, or stop a trim
+//! that is no longer wanted by returning `ControlFlow::Break`.
+
+use crate::{CuckatooError, Result};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+/// State of a trim after one of its rounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundProgress {
+    /// Rounds finished so far, starting at 1
+    pub round: u32,
+    /// Edges surviving the round
+    pub surviving_edges: u64,
+    /// Time since the trim started
+    pub elapsed: Duration,
+}
+
+/// Receiver of per-round trimming progress
+///
+/// Any `FnMut(&RoundProgress) -> ControlFlow<()>` closure that is `Send`
+/// is an observer.
+pub trait ProgressObserver: Send {
+    /// Called after every trimming round
+    ///
+    /// Returning `ControlFlow::Break` stops the trim with a
+    /// `TrimmingError`.
+    fn on_round(&mut self, progress: &RoundProgress) -> ControlFlow<()>;
+}
+
+impl<F: FnMut(&RoundProgress) -> ControlFlow<()> + Send> ProgressObserver for F {
+    fn on_round(&mut self, progress: &RoundProgress) -> ControlFlow<()> {
+        self(progress)
+    }
+}
+
+/// Report a finished round to `observer`, if any, failing if it asked to
+/// stop
+pub(crate) fn report_round(
+    observer: &mut Option<Box<dyn ProgressObserver>>,
+    round: u32,
+    surviving_edges: u64,
+    start: Instant,
+) -> Result<()> {
+    let Some(observer) = observer else {
+        return Ok(());
+    };
+    let progress = RoundProgress { round, surviving_edges, elapsed: start.elapsed() };
+    match observer.on_round(&progress) {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(()) => Err(CuckatooError::TrimmingError(format!("Stopped by the progress observer after round {}", round))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitmapTrimmer, ExactSipHash, ExactTrimmer, SipHash, SipKeys};
+    use std::sync::{Arc, Mutex};
+
+    /// Observer recording the round and surviving edges of every report
+    fn recorder(reports: &Arc<Mutex<Vec<(u32, u64)>>>) -> impl ProgressObserver {
+        let reports = Arc::clone(reports);
+        move |progress: &RoundProgress| {
+            reports.lock().unwrap().push((progress.round, progress.surviving_edges));
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn test_trimmers_report_every_round() {
+        let keys = SipKeys::from_header(b"progress", 2);
+        let siphash = SipHash::with_key(keys);
+        let reports = Arc::new(Mutex::new(Vec::new()));
+
+        let edges = BitmapTrimmer::new(10).with_progress(recorder(&reports)).trim_edges(&siphash, 4).unwrap();
+        let bitmap_reports = std::mem::take(&mut *reports.lock().unwrap());
+        assert_eq!(bitmap_reports.iter().map(|&(round, _)| round).collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert_eq!(bitmap_reports[3].1, edges.len() as u64);
+
+        ExactTrimmer::new(10).with_progress(recorder(&reports)).trim_edges(&ExactSipHash::new(keys, 10), 4).unwrap();
+        assert_eq!(*reports.lock().unwrap(), bitmap_reports);
+
+        let mut trimmer = BitmapTrimmer::new(10).with_progress(|progress: &RoundProgress| {
+            if progress.round < 2 { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+        });
+        assert!(matches!(trimmer.trim_edges(&siphash, 4), Err(CuckatooError::TrimmingError(_))));
+        assert_eq!(trimmer.rounds_run(), 2);
+    }
+}
@@ -0,0 +1,103 @@
+//! Proof recovery
+//!
+//! Cycle finders work on the trimmed graph, whose edges no longer carry
+//! the edge indices (nonces) a proof is made of. `recover_proof` finds them
+//! again by re-hashing the graph: it hashes the U endpoints of a block of
+//! edges at a time, and only the edges whose U node is on the cycle get
+//! their V node hashed and compared. The index space can be split across
+//! threads, each scanning a contiguous range.
+
+use crate::hashing::SIPHASH_BLOCK_EDGES;
+use crate::{CuckatooError, Edge, Result, SipHash, SipHasher, SipKeys};
+
+/// Find the edge indices of `cycle`'s edges in the graph of `keys`, sorted
+/// as in a proof, scanning the graph with `threads` threads
+///
+/// Where an edge occurs more than once in the graph, its lowest index is
+/// used. Fails with `VerificationError` if an edge isn't in the graph.
+pub fn recover_proof(keys: &SipKeys, edge_bits: u32, cycle: &[Edge], threads: usize) -> Result<Vec<u64>> {
+    if edge_bits >= u64::BITS {
+        return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+    }
+    let siphash = SipHash::with_key(*keys);
+    let mut u_nodes: Vec<u64> = cycle.iter().map(|edge| edge.u.value()).collect();
+    u_nodes.sort_unstable();
+    u_nodes.dedup();
+
+    // Ranges of whole blocks, one per thread
+    let blocks = (1u64 << edge_bits).div_ceil(SIPHASH_BLOCK_EDGES as u64);
+    let threads = (threads.max(1) as u64).min(blocks);
+    let ranges = (0..threads).map(|thread| blocks * thread / threads..blocks * (thread + 1) / threads);
+    let matches: Vec<(Edge, u64)> = if threads == 1 {
+        scan(&siphash, edge_bits, &u_nodes, cycle, 0..blocks)
+    } else {
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = ranges
+                .map(|range| {
+                    let (siphash, u_nodes) = (&siphash, &u_nodes);
+                    scope.spawn(move || scan(siphash, edge_bits, u_nodes, cycle, range))
+                })
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().expect("recovery thread panicked")).collect()
+        })
+    };
+
+    let mut proof = Vec::with_capacity(cycle.len());
+    for edge in cycle {
+        // Matches are in index order, so the first is the lowest
+        match matches.iter().find(|(found, _)| found == edge) {
+            Some(&(_, index)) => proof.push(index),
+            None => {
+                return Err(CuckatooError::VerificationError(format!(
+                    "Cycle edge {}-{} isn't in the graph", edge.u.value(), edge.v.value()
+                )))
+            }
+        }
+    }
+    proof.sort_unstable();
+    Ok(proof)
+}
+
+/// Edges of `cycle` (with their indices, in index order) among the edges
+/// of blocks `blocks`
+fn scan(siphash: &SipHash, edge_bits: u32, u_nodes: &[u64], cycle: &[Edge], blocks: std::ops::Range<u64>) -> Vec<(Edge, u64)> {
+    let number_of_edges = 1u64 << edge_bits;
+    let mut nodes = [0u64; SIPHASH_BLOCK_EDGES];
+    let mut matches = Vec::new();
+    for block in blocks {
+        let first_edge = block * SIPHASH_BLOCK_EDGES as u64;
+        let count = (number_of_edges - first_edge).min(SIPHASH_BLOCK_EDGES as u64) as usize;
+        siphash.hash_block(first_edge * 2, edge_bits, &mut nodes[..count]);
+        for (index, &u) in (first_edge..).zip(&nodes[..count]) {
+            if u_nodes.binary_search(&u).is_ok() {
+                let edge = siphash.hash_pair(index, edge_bits);
+                if cycle.contains(&edge) {
+                    matches.push((edge, index));
+                }
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{KNOWN_PROOF, KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_KEYS};
+    use crate::Node;
+
+    #[test]
+    fn test_recover_proof() {
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let mut cycle = siphash.edges_for_indices(&KNOWN_PROOF, KNOWN_PROOF_EDGE_BITS);
+        cycle.reverse();
+        for threads in [1, 3] {
+            let proof = recover_proof(&KNOWN_PROOF_KEYS, KNOWN_PROOF_EDGE_BITS, &cycle, threads).unwrap();
+            assert_eq!(proof, KNOWN_PROOF);
+        }
+
+        cycle[5] = Edge::new(cycle[5].u, Node::new(cycle[5].v.value() ^ 1));
+        let error = recover_proof(&KNOWN_PROOF_KEYS, KNOWN_PROOF_EDGE_BITS, &cycle, 2).unwrap_err();
+        assert!(matches!(error, CuckatooError::VerificationError(_)));
+    }
+}
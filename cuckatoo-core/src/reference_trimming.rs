@@ -0,0 +1,127 @@
+//! Naive reference trimmer for small graphs
+//!
+//! `ReferenceTrimmer` keeps the whole edge list and explicit adjacency
+//! lists, and trims straight from the verifier's rule: an edge reaching a
+//! node continues from the edges at the other node of its pair (`node ^ 1`),
+//! so an edge can only be in a cycle while both of its endpoints' partners
+//! have live edges. Every round recomputes each node's live edge count on
+//! one side, alternating from U to V like the lean trimmers, so tests and
+//! the `self-test` subcommand check they keep exactly the same edges after
+//! any number of rounds. It is far too slow and memory hungry for real
+//! graphs, but simple enough to be obviously correct.
+
+use crate::{CuckatooError, Edge, Result, SipHasher};
+
+/// Reference trimmer over an explicit edge list
+pub struct ReferenceTrimmer {
+    edges: Vec<Edge>,
+    /// Edges ending at each node, on side U and on side V
+    adjacency: [Vec<Vec<usize>>; 2],
+    alive: Vec<bool>,
+    rounds: u32,
+}
+
+impl ReferenceTrimmer {
+    /// Largest graphs the reference trimmer accepts
+    pub const MAX_EDGE_BITS: u32 = 16;
+
+    /// Generate the graph of `hasher` and link every edge to its endpoints
+    ///
+    /// Fails with `InvalidEdgeBits` above `MAX_EDGE_BITS`.
+    pub fn new<H: SipHasher>(hasher: &H, edge_bits: u32) -> Result<Self> {
+        if edge_bits > Self::MAX_EDGE_BITS {
+            return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let edges: Vec<Edge> = (0..1u64 << edge_bits).map(|index| hasher.hash_pair(index, edge_bits)).collect();
+        let mut adjacency = [vec![Vec::new(); edges.len()], vec![Vec::new(); edges.len()]];
+        for (index, edge) in edges.iter().enumerate() {
+            adjacency[0][edge.u.value() as usize].push(index);
+            adjacency[1][edge.v.value() as usize].push(index);
+        }
+        let alive = vec![true; edges.len()];
+        Ok(Self { edges, adjacency, alive, rounds: 0 })
+    }
+
+    /// Trim the graph of `hasher` for `rounds` rounds and return the
+    /// survivors in index order
+    pub fn trim<H: SipHasher>(hasher: &H, edge_bits: u32, rounds: u32) -> Result<Vec<Edge>> {
+        let mut trimmer = Self::new(hasher, edge_bits)?;
+        for _ in 0..rounds {
+            trimmer.run_round();
+        }
+        Ok(trimmer.survivors())
+    }
+
+    /// Run one round, on side U in even rounds and V in odd ones
+    ///
+    /// An edge survives if a live edge ends at the partner (`node ^ 1`) of
+    /// its endpoint on the round's side. Enough rounds leave exactly the
+    /// 2-core of the graph.
+    pub fn run_round(&mut self) {
+        let side = (self.rounds & 1) as usize;
+        let live_edges: Vec<usize> = self.adjacency[side]
+            .iter()
+            .map(|edges| edges.iter().filter(|&&index| self.alive[index]).count())
+            .collect();
+        for (edge, alive) in self.edges.iter().zip(self.alive.iter_mut()) {
+            let node = if side == 0 { edge.u } else { edge.v }.value() as usize;
+            *alive &= live_edges[node ^ 1] > 0;
+        }
+        self.rounds += 1;
+    }
+
+    /// Whether each edge is still alive, by edge index
+    pub fn alive(&self) -> &[bool] {
+        &self.alive
+    }
+
+    /// Every edge of the graph, by edge index
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Rounds run so far
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    /// Surviving edges in index order
+    pub fn survivors(&self) -> Vec<Edge> {
+        self.edges.iter().zip(&self.alive).filter(|(_, &alive)| alive).map(|(edge, _)| *edge).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_stats::two_core;
+    use crate::self_test::{KNOWN_PROOF, KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_KEYS};
+    use crate::{BitmapTrimmer, ExactSipHash, ExactTrimmer, SipHash};
+
+    #[test]
+    fn test_optimized_trimmers_match_reference() {
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let exact_siphash = ExactSipHash::new(KNOWN_PROOF_KEYS, KNOWN_PROOF_EDGE_BITS);
+        let mut reference = ReferenceTrimmer::new(&siphash, KNOWN_PROOF_EDGE_BITS).unwrap();
+        let proof: Vec<Edge> = KNOWN_PROOF.iter().map(|&index| reference.edges()[index as usize]).collect();
+        let mut previous = usize::MAX;
+        while reference.survivors().len() < previous {
+            previous = reference.survivors().len();
+            reference.run_round();
+            let rounds = reference.rounds();
+            let expected = reference.survivors();
+            assert!(proof.iter().all(|edge| expected.contains(edge)), "{} rounds", rounds);
+
+            let bitmap = BitmapTrimmer::new(KNOWN_PROOF_EDGE_BITS).trim_edges(&siphash, rounds).unwrap();
+            let exact = ExactTrimmer::new(KNOWN_PROOF_EDGE_BITS).trim_edges(&exact_siphash, rounds).unwrap();
+            assert_eq!(bitmap, expected, "bitmap trimmer after {} rounds", rounds);
+            assert_eq!(exact, expected, "exact trimmer after {} rounds", rounds);
+        }
+
+        // Trimming until a round removes nothing leaves the 2-core
+        assert!(reference.rounds() > 10);
+        let alive: Vec<usize> = (0..reference.edges().len()).filter(|&index| reference.alive()[index]).collect();
+        assert_eq!(alive, two_core(reference.edges()));
+        assert!(matches!(ReferenceTrimmer::new(&siphash, 17), Err(CuckatooError::InvalidEdgeBits(17))));
+    }
+}
@@ -0,0 +1,162 @@
+//! Replay records of failed graphs
+//!
+//! A failure after hours of mining is only reproducible if the graph it
+//! happened on is known. A `ReplayRecorder` writes the inputs of each graph
+//! that failed (keys, edge bits, trimming rounds and cycle finder) to its own
+//! small text file, which maintainers can load with `ReplayRecord::load` and
+//! solve again.
+
+use crate::{CuckatooError, KeyProvenance, Result, SipKeys};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Inputs of a failed graph and how it failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayRecord {
+    /// SipHash keys of the graph
+    pub keys: SipKeys,
+    /// Edge bits of the graph
+    pub edge_bits: u32,
+    /// Trimming rounds run on the graph
+    pub trimming_rounds: u32,
+    /// Name of the cycle finder that searched the graph
+    pub finder: String,
+    /// Error or verification failure
+    pub failure: String,
+    /// Proof nonces reported by the finder, if it reported one
+    pub proof: Option<Vec<u64>>,
+}
+
+impl ReplayRecord {
+    /// Parse a record written by `ReplayRecorder`
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = |message: String| CuckatooError::InternalError(format!("Invalid replay record: {}", message));
+        let mut fields = std::collections::HashMap::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (name, value) = line.split_once('=').ok_or_else(|| invalid(format!("no '=' in {:?}", line)))?;
+            fields.insert(name.trim(), value.trim());
+        }
+        let field = |name: &str| fields.get(name).copied().ok_or_else(|| invalid(format!("missing {}", name)));
+        let number = |name: &str| field(name)?.parse::<u32>().map_err(|_| invalid(format!("bad {}", name)));
+
+        let words: Vec<u64> = field("keys")?
+            .split(',')
+            .map(|word| u64::from_str_radix(word.trim().trim_start_matches("0x"), 16))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| invalid("bad keys".to_string()))?;
+        let words: [u64; 4] = words.try_into().map_err(|_| invalid("expected 4 keys".to_string()))?;
+        let provenance: KeyProvenance = field("key_derivation")?.parse()?;
+
+        let proof = match fields.get("proof") {
+            Some(proof) => Some(
+                proof.split(',')
+                    .map(|nonce| nonce.trim().parse::<u64>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| invalid("bad proof".to_string()))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            keys: SipKeys::from_words(words, provenance),
+            edge_bits: number("edge_bits")?,
+            trimming_rounds: number("trimming_rounds")?,
+            finder: field("finder")?.to_string(),
+            failure: field("failure")?.to_string(),
+            proof,
+        })
+    }
+
+    /// Load a record from a file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|error| {
+            CuckatooError::InternalError(format!("Cannot read replay record {}: {}", path.display(), error))
+        })?;
+        Self::parse(&text)
+    }
+}
+
+impl fmt::Display for ReplayRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [k0, k1, k2, k3] = self.keys.words();
+        writeln!(f, "# cuckatoo replay record")?;
+        writeln!(f, "edge_bits={}", self.edge_bits)?;
+        writeln!(f, "trimming_rounds={}", self.trimming_rounds)?;
+        writeln!(f, "finder={}", self.finder)?;
+        writeln!(f, "keys=0x{:016x},0x{:016x},0x{:016x},0x{:016x}", k0, k1, k2, k3)?;
+        writeln!(f, "key_derivation={}", self.keys.provenance())?;
+        // Keep the record one field per line
+        writeln!(f, "failure={}", self.failure.replace(['\n', '\r'], " "))?;
+        if let Some(proof) = &self.proof {
+            let nonces: Vec<String> = proof.iter().map(u64::to_string).collect();
+            writeln!(f, "proof={}", nonces.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes replay records into a directory, one file per failed graph
+///
+/// Can be shared between worker threads.
+#[derive(Debug)]
+pub struct ReplayRecorder {
+    directory: PathBuf,
+    /// Number of records written, used in file names
+    written: AtomicU64,
+}
+
+impl ReplayRecorder {
+    /// Create a recorder writing into `directory`, creating it if needed
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory).map_err(|error| {
+            CuckatooError::InternalError(format!("Cannot create replay directory {}: {}", directory.display(), error))
+        })?;
+        Ok(Self { directory, written: AtomicU64::new(0) })
+    }
+
+    /// Write a record, returning the path of its file
+    pub fn record(&self, record: &ReplayRecord) -> Result<PathBuf> {
+        let sequence = self.written.fetch_add(1, Ordering::Relaxed);
+        let path = self.directory.join(format!("replay-{}-{:06}.txt", std::process::id(), sequence));
+        std::fs::write(&path, record.to_string()).map_err(|error| {
+            CuckatooError::InternalError(format!("Cannot write replay record {}: {}", path.display(), error))
+        })?;
+        Ok(path)
+    }
+
+    /// Number of records written
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_round_trip() {
+        let directory = std::env::temp_dir().join(format!("cuckatoo-replay-{}", std::process::id()));
+        let recorder = ReplayRecorder::new(&directory).unwrap();
+        let record = ReplayRecord {
+            keys: SipKeys::from_header(b"header", 3),
+            edge_bits: 12,
+            trimming_rounds: 90,
+            finder: "cycle-verifier".to_string(),
+            failure: "Verification failed:\nbranch".to_string(),
+            proof: Some(vec![1, 5, 9]),
+        };
+
+        let path = recorder.record(&record).unwrap();
+        let loaded = ReplayRecord::load(&path).unwrap();
+        assert_eq!(loaded.keys, record.keys);
+        assert_eq!(loaded.proof, record.proof);
+        assert_eq!(loaded.failure, "Verification failed: branch");
+        assert_eq!(recorder.written(), 1);
+
+        assert!(ReplayRecord::parse("edge_bits=12").is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+//! Formatting of durations and rates in reports
+//!
+//! Text reports print durations in the process-wide time unit (seconds by
+//! default, or as chosen with `set_time_unit`, e.g. from `--time-unit`), so
+//! scripts parsing the output see one unit and one precision everywhere.
+//! JSON output always uses seconds (`json_seconds`), whatever the unit.
+
+use crate::CuckatooError;
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+/// Unit of the durations in text reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeUnit {
+    /// Seconds with 6 decimals
+    #[default]
+    Seconds,
+    /// Milliseconds with 3 decimals
+    Milliseconds,
+    /// Whole microseconds
+    Microseconds,
+}
+
+/// Time unit of text reports, as a `TimeUnit` discriminant
+static TIME_UNIT: AtomicU8 = AtomicU8::new(TimeUnit::Seconds as u8);
+
+impl TimeUnit {
+    /// Unit suffix printed after a duration (`s`, `ms` or `us`)
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            TimeUnit::Seconds => "s",
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Microseconds => "us",
+        }
+    }
+
+    /// Format a duration given in seconds, with the unit suffix
+    ///
+    /// Every unit resolves microseconds.
+    pub fn format(&self, seconds: f64) -> String {
+        match self {
+            TimeUnit::Seconds => format!("{:.6}s", seconds),
+            TimeUnit::Milliseconds => format!("{:.3}ms", seconds * 1e3),
+            TimeUnit::Microseconds => format!("{:.0}us", seconds * 1e6),
+        }
+    }
+}
+
+impl fmt::Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.suffix())
+    }
+}
+
+impl std::str::FromStr for TimeUnit {
+    type Err = CuckatooError;
+
+    /// Parse `s`, `ms` or `us` (`µs` is accepted too)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "s" | "sec" | "seconds" => Ok(TimeUnit::Seconds),
+            "ms" | "milliseconds" => Ok(TimeUnit::Milliseconds),
+            "us" | "µs" | "microseconds" => Ok(TimeUnit::Microseconds),
+            _ => Err(CuckatooError::InternalError(format!("Unknown time unit: {} (expected s, ms or us)", s))),
+        }
+    }
+}
+
+/// Set the time unit of text reports for the whole process
+pub fn set_time_unit(unit: TimeUnit) {
+    TIME_UNIT.store(unit as u8, Ordering::Relaxed);
+}
+
+/// Time unit of text reports
+pub fn time_unit() -> TimeUnit {
+    match TIME_UNIT.load(Ordering::Relaxed) {
+        1 => TimeUnit::Milliseconds,
+        2 => TimeUnit::Microseconds,
+        _ => TimeUnit::Seconds,
+    }
+}
+
+/// Format a duration in seconds in the report time unit, e.g. `0.012345s`
+pub fn format_seconds(seconds: f64) -> String {
+    time_unit().format(seconds)
+}
+
+/// Format a duration in the report time unit
+pub fn format_duration(duration: Duration) -> String {
+    format_seconds(duration.as_secs_f64())
+}
+
+/// Format a rate with 3 decimals, e.g. `12.345 Medges/s`
+pub fn format_rate(per_second: f64, unit: &str) -> String {
+    format!("{:.3} {}/s", per_second, unit)
+}
+
+/// Format a duration for JSON output: seconds with 6 decimals, no suffix
+pub fn json_seconds(seconds: f64) -> String {
+    format!("{:.6}", seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_units() {
+        assert_eq!(TimeUnit::Seconds.format(0.0123456), "0.012346s");
+        assert_eq!(TimeUnit::Milliseconds.format(0.0123456), "12.346ms");
+        assert_eq!(TimeUnit::Microseconds.format(0.0123456), "12346us");
+        assert_eq!(json_seconds(1.5), "1.500000");
+        assert_eq!(format_rate(2.0, "graphs"), "2.000 graphs/s");
+
+        for unit in [TimeUnit::Seconds, TimeUnit::Milliseconds, TimeUnit::Microseconds] {
+            assert_eq!(unit.to_string().parse::<TimeUnit>().unwrap(), unit);
+        }
+        assert_eq!("µs".parse::<TimeUnit>().unwrap(), TimeUnit::Microseconds);
+        assert!("minutes".parse::<TimeUnit>().is_err());
+    }
+}
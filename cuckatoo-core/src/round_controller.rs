@@ -0,0 +1,306 @@
+//! Adaptive trimming-round controller
+//!
+//! Instead of running a fixed number of trimming rounds, the controller
+//! watches how many edges each round removes and stops trimming once the
+//! marginal reduction drops below a configurable threshold (for a number of
+//! consecutive rounds), or once the surviving edges fall below a target
+//! count.
+
+use crate::{Edge, SOLUTION_SIZE};
+
+/// Default minimum per-round reduction (0.5% of the surviving edges)
+pub const DEFAULT_MIN_REDUCTION: f64 = 0.005;
+
+/// Default consecutive rounds below the minimum reduction that end
+/// automatically chosen trimming rounds
+pub const DEFAULT_PLATEAU_ROUNDS: u32 = 3;
+
+/// Controller deciding when further trimming rounds stop paying off
+#[derive(Debug, Clone)]
+pub struct AdaptiveRoundController {
+    /// Minimum fraction of edges a round must remove to keep trimming
+    min_reduction: f64,
+    /// Upper bound on the number of rounds
+    max_rounds: u32,
+    /// Stop once the surviving edges are at or below this count
+    target_survivors: Option<u64>,
+    /// Consecutive rounds below `min_reduction` that stop trimming
+    plateau_rounds: u32,
+    /// Rounds run since the last start
+    rounds_run: u32,
+    /// Consecutive rounds so far below `min_reduction`
+    low_rounds: u32,
+    /// Surviving edges after the previous round
+    previous_survivors: u64,
+}
+
+impl AdaptiveRoundController {
+    /// Create a new controller
+    ///
+    /// `min_reduction` is a fraction (0.005 = 0.5% per round) and
+    /// `max_rounds` caps the rounds even if the reduction stays high.
+    pub fn new(min_reduction: f64, max_rounds: u32) -> Self {
+        Self {
+            min_reduction,
+            max_rounds,
+            target_survivors: None,
+            plateau_rounds: 1,
+            rounds_run: 0,
+            low_rounds: 0,
+            previous_survivors: 0,
+        }
+    }
+
+    /// Create a controller choosing the rounds on its own: trimming stops
+    /// once `DEFAULT_PLATEAU_ROUNDS` rounds in a row remove less than the
+    /// default minimum reduction
+    pub fn auto(max_rounds: u32) -> Self {
+        Self::new(DEFAULT_MIN_REDUCTION, max_rounds).plateau_rounds(DEFAULT_PLATEAU_ROUNDS)
+    }
+
+    /// Create a controller that trims until at most `multiple` × cycle length
+    /// edges survive (or `max_rounds` is reached)
+    pub fn with_survivor_target(multiple: u64, max_rounds: u32) -> Self {
+        Self::new(0.0, max_rounds).target_survivors(multiple * SOLUTION_SIZE as u64)
+    }
+
+    /// Also stop once the surviving edges are at or below `target`
+    pub fn target_survivors(mut self, target: u64) -> Self {
+        self.target_survivors = Some(target);
+        self
+    }
+
+    /// Only stop after `rounds` consecutive rounds below the minimum
+    /// reduction (at least one)
+    pub fn plateau_rounds(mut self, rounds: u32) -> Self {
+        self.plateau_rounds = rounds.max(1);
+        self
+    }
+
+    /// Get the minimum per-round reduction
+    pub fn min_reduction(&self) -> f64 {
+        self.min_reduction
+    }
+
+    /// Get the maximum number of rounds
+    pub fn max_rounds(&self) -> u32 {
+        self.max_rounds
+    }
+
+    /// Get the surviving-edge target, if any
+    pub fn survivor_target(&self) -> Option<u64> {
+        self.target_survivors
+    }
+
+    /// Get the consecutive rounds below the minimum reduction that stop
+    /// trimming
+    pub fn plateau_length(&self) -> u32 {
+        self.plateau_rounds
+    }
+
+    /// Get the number of rounds run since the last start
+    pub fn rounds_run(&self) -> u32 {
+        self.rounds_run
+    }
+
+    /// Start a new trimming run with the initial number of edges
+    pub fn start(&mut self, initial_edges: u64) {
+        self.rounds_run = 0;
+        self.low_rounds = 0;
+        self.previous_survivors = initial_edges;
+    }
+
+    /// Check if another round should be run
+    pub fn should_continue(&self) -> bool {
+        let above_target = self.target_survivors
+            .is_none_or(|target| self.previous_survivors > target);
+        self.rounds_run < self.max_rounds && self.previous_survivors > 0 && above_target
+    }
+
+    /// Record the surviving edges after a round
+    ///
+    /// Returns whether trimming should continue with another round.
+    pub fn record_round(&mut self, survivors: u64) -> bool {
+        let removed = self.previous_survivors.saturating_sub(survivors);
+        let reduction = if self.previous_survivors > 0 {
+            removed as f64 / self.previous_survivors as f64
+        } else {
+            0.0
+        };
+
+        self.rounds_run += 1;
+        self.previous_survivors = survivors;
+        if reduction < self.min_reduction {
+            self.low_rounds += 1;
+        } else {
+            self.low_rounds = 0;
+        }
+
+        self.low_rounds < self.plateau_rounds && self.should_continue()
+    }
+}
+
+impl Default for AdaptiveRoundController {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_REDUCTION, 90) // Default rounds from C++ miner as the cap
+    }
+}
+
+/// Conditions that end fixed-round trimming early
+///
+/// Disabled by default. Checking them needs the surviving-edge count after
+/// every round, which costs a pass over the edges bitmap (a device readback
+/// on GPU trimmers), so trimmers only count survivors when one is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EarlyExit {
+    /// Stop once fewer edges than this survive
+    edges_below: Option<u64>,
+    /// Stop once a round removes no edges
+    plateau: bool,
+}
+
+impl EarlyExit {
+    /// Create early-exit conditions with none set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop once fewer than `edges` edges survive a round
+    pub fn stop_when_edges_below(mut self, edges: u64) -> Self {
+        self.edges_below = Some(edges);
+        self
+    }
+
+    /// Stop once a round other than the first removes no edges
+    ///
+    /// Rounds alternate sides, so when a round removes nothing the edges
+    /// are unchanged since the previous round trimmed the other side: both
+    /// sides are fully trimmed, all later rounds would remove nothing too
+    /// and the survivors are the same as with the full round count.
+    pub fn stop_on_plateau(mut self) -> Self {
+        self.plateau = true;
+        self
+    }
+
+    /// Get the surviving-edge threshold, if any
+    pub fn edges_below(&self) -> Option<u64> {
+        self.edges_below
+    }
+
+    /// Check if plateau detection is on
+    pub fn plateau(&self) -> bool {
+        self.plateau
+    }
+
+    /// Check if any condition is set
+    pub fn is_enabled(&self) -> bool {
+        self.edges_below.is_some() || self.plateau
+    }
+
+    /// Check if trimming should stop after `rounds_run` rounds, the last of
+    /// which left `survivors` of the `previous` edges
+    pub fn should_stop(&self, rounds_run: u32, previous: u64, survivors: u64) -> bool {
+        let below = self.edges_below.is_some_and(|edges| survivors < edges);
+        let plateau = self.plateau && rounds_run >= 2 && survivors == previous;
+        below || plateau
+    }
+}
+
+/// Result of an adaptively trimmed graph
+#[derive(Debug, Clone)]
+pub struct AdaptiveTrimResult {
+    /// Surviving edges
+    pub edges: Vec<Edge>,
+    /// Number of trimming rounds the controller chose
+    pub rounds: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_controller_stops_on_small_reduction() {
+        let mut controller = AdaptiveRoundController::new(0.01, 90);
+        controller.start(1000);
+
+        assert!(controller.record_round(500)); // 50% removed
+        assert!(controller.record_round(400)); // 20% removed
+        assert!(!controller.record_round(398)); // 0.5% removed
+        assert_eq!(controller.rounds_run(), 3);
+    }
+
+    #[test]
+    fn test_controller_respects_max_rounds() {
+        let mut controller = AdaptiveRoundController::new(0.01, 2);
+        controller.start(1000);
+
+        assert!(controller.record_round(500));
+        assert!(!controller.record_round(250));
+        assert_eq!(controller.rounds_run(), 2);
+    }
+
+    #[test]
+    fn test_controller_stops_when_no_edges_left() {
+        let mut controller = AdaptiveRoundController::default();
+        controller.start(1000);
+
+        assert!(!controller.record_round(0));
+        assert_eq!(controller.rounds_run(), 1);
+    }
+
+    #[test]
+    fn test_controller_survivor_target() {
+        let mut controller = AdaptiveRoundController::with_survivor_target(2, 90);
+        assert_eq!(controller.survivor_target(), Some(84));
+        controller.start(1000);
+
+        assert!(controller.record_round(500));
+        assert!(controller.record_round(499)); // No reduction threshold in target mode
+        assert!(!controller.record_round(84));
+        assert_eq!(controller.rounds_run(), 3);
+    }
+
+    #[test]
+    fn test_controller_waits_for_plateau() {
+        let mut controller = AdaptiveRoundController::new(0.01, 90).plateau_rounds(3);
+        controller.start(1000);
+
+        assert!(controller.record_round(500));
+        assert!(controller.record_round(499)); // 1 low round
+        assert!(controller.record_round(498)); // 2 low rounds
+        assert!(controller.record_round(400)); // Reduction picks up again
+        assert!(controller.record_round(400));
+        assert!(controller.record_round(399));
+        assert!(!controller.record_round(399)); // 3 low rounds in a row
+        assert_eq!(controller.rounds_run(), 7);
+
+        let auto = AdaptiveRoundController::auto(90);
+        assert_eq!((auto.min_reduction(), auto.plateau_length()), (DEFAULT_MIN_REDUCTION, DEFAULT_PLATEAU_ROUNDS));
+    }
+
+    #[test]
+    fn test_early_exit_conditions() {
+        assert!(!EarlyExit::new().is_enabled());
+        assert!(!EarlyExit::new().should_stop(5, 100, 0));
+
+        let below = EarlyExit::new().stop_when_edges_below(50);
+        assert!(!below.should_stop(1, 100, 50));
+        assert!(below.should_stop(1, 100, 49));
+
+        let plateau = EarlyExit::new().stop_on_plateau();
+        assert!(!plateau.should_stop(1, 100, 100)); // First round trims the other side
+        assert!(!plateau.should_stop(2, 100, 99));
+        assert!(plateau.should_stop(2, 100, 100));
+    }
+
+    #[test]
+    fn test_deterministic_config_disables_controller() {
+        let mut config = crate::Config::new(12);
+        config.adaptive_threshold = Some(0.01);
+        assert!(config.round_controller().is_some());
+
+        config.deterministic = true;
+        assert!(config.round_controller().is_none());
+    }
+}
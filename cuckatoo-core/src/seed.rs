@@ -0,0 +1,110 @@
+//! One-line graph seeds for bug reports
+//!
+//! A `GraphSeed` holds everything needed to rebuild and re-trim a graph on
+//! another machine: the 32-byte Blake2b hash the SipHash keys are read from,
+//! the nonce, the graph size, the trimming rounds and the trimming mode. It
+//! is written as a single-line JSON object, short enough to paste into an
+//! issue.
+
+use crate::blake2b::blake2b_digest;
+use crate::endian::KEYS_SIZE;
+use crate::{CuckatooError, KeyProvenance, Result, SipKeys, TrimmingMode};
+use std::collections::HashMap;
+
+/// Inputs of a graph and how it was trimmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphSeed {
+    /// Blake2b hash of the header with the nonce, the bytes of the keys
+    pub header_hash: [u8; KEYS_SIZE],
+    /// Nonce the header was hashed with
+    pub nonce: u64,
+    /// Edge bits of the graph
+    pub edge_bits: u32,
+    /// Trimming rounds run on the graph
+    pub rounds: u32,
+    /// Trimming mode
+    pub mode: TrimmingMode,
+}
+
+impl GraphSeed {
+    /// Seed of the graph of a header and nonce (keys derived the Grin way)
+    pub fn new(header: &[u8], nonce: u64, edge_bits: u32, rounds: u32, mode: TrimmingMode) -> Self {
+        Self { header_hash: blake2b_digest(header, nonce), nonce, edge_bits, rounds, mode }
+    }
+
+    /// SipHash keys of the seed's graph
+    pub fn keys(&self) -> SipKeys {
+        SipKeys::from_le_bytes(&self.header_hash, KeyProvenance::Grin)
+    }
+
+    /// Format the seed as a single-line JSON object
+    pub fn to_json(&self) -> String {
+        let hash: String = self.header_hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!(
+            "{{\"header_hash\":\"{}\",\"nonce\":{},\"edge_bits\":{},\"rounds\":{},\"mode\":\"{}\"}}",
+            hash, self.nonce, self.edge_bits, self.rounds, self.mode
+        )
+    }
+
+    /// Parse a seed written by `to_json`
+    ///
+    /// Whitespace and field order don't matter, but only the flat object
+    /// `to_json` writes is understood, not arbitrary JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let invalid = |message: String| CuckatooError::InternalError(format!("Invalid graph seed: {}", message));
+        let body = json
+            .trim()
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+            .ok_or_else(|| invalid("expected a JSON object".to_string()))?;
+
+        let mut fields = HashMap::new();
+        for field in body.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+            let (name, value) = field.split_once(':').ok_or_else(|| invalid(format!("no ':' in {:?}", field)))?;
+            fields.insert(name.trim().trim_matches('"'), value.trim().trim_matches('"'));
+        }
+        let field = |name: &str| fields.get(name).copied().ok_or_else(|| invalid(format!("missing {}", name)));
+        let number = |name: &str| field(name)?.parse::<u64>().map_err(|_| invalid(format!("bad {}", name)));
+        let small = |name: &str| u32::try_from(number(name)?).map_err(|_| invalid(format!("bad {}", name)));
+
+        let hash = field("header_hash")?;
+        if hash.len() != KEYS_SIZE * 2 || !hash.is_ascii() {
+            return Err(invalid(format!("header_hash must be {} hex digits", KEYS_SIZE * 2)));
+        }
+        let mut header_hash = [0u8; KEYS_SIZE];
+        for (i, byte) in header_hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hash[i * 2..i * 2 + 2], 16).map_err(|_| invalid("bad header_hash".to_string()))?;
+        }
+
+        Ok(Self {
+            header_hash,
+            nonce: number("nonce")?,
+            edge_bits: small("edge_bits")?,
+            rounds: small("rounds")?,
+            mode: field("mode")?.parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let seed = GraphSeed::new(b"header", u64::MAX, 29, 90, TrimmingMode::Mean);
+        assert_eq!(seed.keys(), SipKeys::from_header(b"header", u64::MAX));
+
+        let json = seed.to_json();
+        assert!(!json.contains('\n'));
+        assert_eq!(GraphSeed::from_json(&json).unwrap(), seed);
+
+        // Hand-edited seeds may be reformatted
+        let spaced = json.replace(',', ", ").replace(':', ": ");
+        assert_eq!(GraphSeed::from_json(&format!("  {}\n", spaced)).unwrap(), seed);
+
+        assert!(GraphSeed::from_json("{\"nonce\":1}").is_err());
+        assert!(GraphSeed::from_json(&json.replace("\"rounds\":90", "\"rounds\":-1")).is_err());
+        assert!(GraphSeed::from_json("nonce=1").is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Known-answer self-test
+//!
+//! Embedded vectors for SipHash, Blake2b, cycle search and proof
+//! verification (Grin's own cuckatoo31 test proof among them), and a comparison of the trimmers with the naive
+//! reference trimmer. Running them after building on a new platform catches
+//! miscompilation and byte order problems before any mining is done.
+
+use crate::consensus::{verify_proof, verify_proof_nonces};
+use crate::{blake2b, KeyProvenance, SipHash, SipHasher, SipKeys, SOLUTION_SIZE};
+
+/// SipHash keys used by the SipHash vectors
+pub const SIPHASH_TEST_KEYS: SipKeys =
+    SipKeys::test_seed([0x736f6d6570736575, 0x646f72616e646f6d, 0x6c7967656e657261, 0x7465646279746573]);
+
+/// SipHash-2-4 outputs (nonce, unmasked hash) under `SIPHASH_TEST_KEYS`
+pub const SIPHASH_VECTORS: [(u64, u64); 4] = [
+    (0x0, 0x1e924b9d737700d7),
+    (0x1, 0x81060f37eb8f02c0),
+    (0x2, 0x80477162733830d0),
+    (0x123456789abcdef0, 0xcba7c300d03f79eb),
+];
+
+/// Blake2b key derivation vectors (header, nonce, Grin SipHash key words),
+/// computed with Python's `hashlib.blake2b(digest_size=32)`; the last one
+/// is the header and nonce of Grin's cuckatoo31 test proof
+pub const BLAKE2B_VECTORS: [(&[u8], u64, [u64; 4]); 3] = [
+    (b"header", 0x0102030405060708, [0xd8d51f264a4732ab, 0x18389fde63b25262, 0x925be82ba4522f08, 0xc2df0097445e98fa]),
+    (b"cuckatoo", 12345, [0x3a342b5dbb2c8a73, 0xf4c41e0f472802d8, 0x7fac5e7058d3789c, 0x5b3beb2ae16b37c7]),
+    (&[0; 72], 99 << 24, [0x8785f61f3e087286, 0x91b57e6072a0cdaa, 0x8035f9ee251a77a0, 0x0de03da786148f07]),
+];
+
+/// Header whose EDGE_BITS=12 graph, with `KNOWN_PROOF_NONCE`, holds the
+/// known proof
+pub const KNOWN_PROOF_HEADER: &[u8] = b"cuckatoo known proof";
+
+/// Nonce of the known proof's graph
+pub const KNOWN_PROOF_NONCE: u64 = 29;
+
+/// Edge bits of the known proof's graph
+pub const KNOWN_PROOF_EDGE_BITS: u32 = 12;
+
+/// Keys of the known proof's graph, derived from `KNOWN_PROOF_HEADER` and
+/// `KNOWN_PROOF_NONCE` the way Grin does
+pub const KNOWN_PROOF_KEYS: SipKeys = SipKeys::from_words(
+    [0x2835d134238e603b, 0x75dd3da403ed223d, 0x08067fc1d2578a97, 0xd76864275ce8fa71],
+    KeyProvenance::Grin,
+);
+
+/// Edge indices of the only 42-cycle in the `KNOWN_PROOF_KEYS` graph,
+/// found by an independent implementation of Grin's rules
+pub const KNOWN_PROOF: [u64; SOLUTION_SIZE] = [
+    201, 211, 312, 686, 695, 809, 819, 1163, 1335, 1348, 1431, 1517, 1622, 1723,
+    1726, 1762, 1844, 1867, 1982, 2014, 2233, 2305, 2355, 2491, 2498, 2734, 2924, 2945,
+    2959, 2975, 3141, 3194, 3308, 3333, 3491, 3532, 3550, 3779, 3906, 3918, 4067, 4081,
+];
+
+/// Header (Grin's `pre_pow` without the nonce) of Grin's cuckatoo31 test
+/// proof
+pub const GRIN_PROOF_HEADER: [u8; 72] = [0; 72];
+
+/// Nonce of Grin's cuckatoo31 test proof: its test header is 80 zero
+/// bytes but for a little-endian 99 at the end, which is the big-endian
+/// nonce `99 << 24` after the 72-byte `pre_pow`
+pub const GRIN_PROOF_NONCE: u64 = 99 << 24;
+
+/// Edge bits of Grin's cuckatoo31 test proof
+pub const GRIN_PROOF_EDGE_BITS: u32 = 31;
+
+/// Grin's cuckatoo31 test proof (`V1_31` of Grin's `cuckatoo` tests)
+pub const GRIN_PROOF: [u64; SOLUTION_SIZE] = [
+    0x1128e07, 0xc181131, 0x110fad36, 0x1135ddee, 0x1669c7d3, 0x1931e6ea, 0x1c0005f3,
+    0x1dd6ecca, 0x1e29ce7e, 0x209736fc, 0x2692bf1a, 0x27b85aa9, 0x29bb7693, 0x2dc2a047,
+    0x2e28650a, 0x2f381195, 0x350eb3f9, 0x3beed728, 0x3e861cbc, 0x41448cc1, 0x41f08f6d,
+    0x42fbc48a, 0x4383ab31, 0x4389c61f, 0x4540a5ce, 0x49a17405, 0x50372ded, 0x512f0db0,
+    0x588b6288, 0x5a36aa46, 0x5c29e1fe, 0x6118ab16, 0x634705b5, 0x6633d190, 0x6683782f,
+    0x6728b6e1, 0x67adfb45, 0x68ae2306, 0x6d60f5e1, 0x78af3c4f, 0x7dde51ab, 0x7faced21,
+];
+
+/// Outcome of one self-test check
+#[derive(Debug, Clone)]
+pub struct SelfTestCheck {
+    /// Name of the check
+    pub name: &'static str,
+    /// Whether every vector of the check matched
+    pub passed: bool,
+    /// Description of the first mismatch, empty if the check passed
+    pub detail: String,
+}
+
+impl SelfTestCheck {
+    fn new(name: &'static str, mismatch: Option<String>) -> Self {
+        Self {
+            name,
+            passed: mismatch.is_none(),
+            detail: mismatch.unwrap_or_default(),
+        }
+    }
+}
+
+/// Run all self-test checks
+///
+/// The reference trimming and planted-cycle checks are left out of
+/// `verify-only` builds.
+pub fn run_self_test() -> Vec<SelfTestCheck> {
+    let mut checks = vec![
+        SelfTestCheck::new("SipHash-2-4 vectors", check_siphash()),
+        SelfTestCheck::new("Blake2b vectors", check_blake2b()),
+    ];
+    #[cfg(not(feature = "verify-only"))]
+    checks.push(SelfTestCheck::new("Trimming matches reference survivors", check_reference_trimming()));
+    #[cfg(not(feature = "verify-only"))]
+    checks.push(SelfTestCheck::new("Planted-cycle solve", check_planted_cycle()));
+    checks.push(SelfTestCheck::new("Grin proof verification", check_grin_proof()));
+    checks.push(SelfTestCheck::new("Known proof verification", check_known_proof()));
+    checks
+}
+
+/// Check the SipHash vectors, returning the first mismatch
+fn check_siphash() -> Option<String> {
+    let nonces: Vec<u64> = SIPHASH_VECTORS.iter().map(|&(nonce, _)| nonce).collect();
+    let mut hashes = vec![0u64; nonces.len()];
+    // EDGE_BITS = 64 leaves the hashes unmasked
+    SipHash::with_key(SIPHASH_TEST_KEYS).hash_nonces(&nonces, u64::BITS, &mut hashes);
+
+    SIPHASH_VECTORS.iter().zip(&hashes).find_map(|(&(nonce, expected), &hash)| {
+        (hash != expected).then(|| format!("nonce 0x{:x}: got 0x{:016x}, expected 0x{:016x}", nonce, hash, expected))
+    })
+}
+
+/// Check the Blake2b vectors, returning the first mismatch
+fn check_blake2b() -> Option<String> {
+    BLAKE2B_VECTORS.iter().find_map(|&(header, nonce, expected)| {
+        let keys = blake2b(header, nonce).words();
+        (keys != expected).then(|| format!("header {:?} nonce {}: got {:x?}, expected {:x?}", header, nonce, keys, expected))
+    })
+}
+
+/// Check the lean trimmers keep exactly the edges the reference trimmer
+/// keeps on the known proof's graph, the proof's edges among them,
+/// returning the first mismatch
+#[cfg(not(feature = "verify-only"))]
+fn check_reference_trimming() -> Option<String> {
+    use crate::{BitmapTrimmer, ExactSipHash, ExactTrimmer, ReferenceTrimmer};
+
+    let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+    let exact_siphash = ExactSipHash::new(KNOWN_PROOF_KEYS, KNOWN_PROOF_EDGE_BITS);
+    let mut reference = match ReferenceTrimmer::new(&siphash, KNOWN_PROOF_EDGE_BITS) {
+        Ok(reference) => reference,
+        Err(error) => return Some(error.to_string()),
+    };
+    for rounds in 1..=8 {
+        reference.run_round();
+        if let Some(&index) = KNOWN_PROOF.iter().find(|&&index| !reference.alive()[index as usize]) {
+            return Some(format!("reference trimmer removed proof edge {} in round {}", index, rounds));
+        }
+        let expected = reference.survivors();
+        let bitmap = BitmapTrimmer::new(KNOWN_PROOF_EDGE_BITS).trim_edges(&siphash, rounds);
+        let exact = ExactTrimmer::new(KNOWN_PROOF_EDGE_BITS).trim_edges(&exact_siphash, rounds);
+        for (name, survivors) in [("bitmap", bitmap), ("exact", exact)] {
+            let survivors = match survivors {
+                Ok(survivors) => survivors,
+                Err(error) => return Some(format!("{} trimmer failed: {}", name, error)),
+            };
+            if let Some(edge) = expected.iter().find(|edge| !survivors.contains(edge)) {
+                return Some(format!("{} trimmer removed edge {} after {} rounds, reference kept it", name, edge, rounds));
+            }
+            if let Some(edge) = survivors.iter().find(|edge| !expected.contains(edge)) {
+                return Some(format!("{} trimmer kept edge {} after {} rounds, reference removed it", name, edge, rounds));
+            }
+            if survivors.len() != expected.len() {
+                return Some(format!("{} trimmer kept {} edges after {} rounds, reference {}", name, survivors.len(), rounds, expected.len()));
+            }
+        }
+    }
+    None
+}
+
+/// Plant the known proof's cycle among other edges of its graph and
+/// check the cycle search finds exactly its edges
+#[cfg(not(feature = "verify-only"))]
+fn check_planted_cycle() -> Option<String> {
+    use crate::CycleVerifier;
+
+    // Edges below the first proof edge are unrelated to the cycle
+    let indices: Vec<u64> = (0..KNOWN_PROOF[0]).chain(KNOWN_PROOF).collect();
+    let edges = SipHash::with_key(KNOWN_PROOF_KEYS).edges_for_indices(&indices, KNOWN_PROOF_EDGE_BITS);
+    let cycle_edges = &edges[KNOWN_PROOF[0] as usize..];
+
+    match CycleVerifier::new().verify_cycle(&edges) {
+        Ok(Some(cycle)) if cycle == cycle_edges => None,
+        Ok(Some(_)) => Some("found a 42-cycle that isn't the planted one".to_string()),
+        Ok(None) => Some("no 42-cycle found".to_string()),
+        Err(error) => Some(error.to_string()),
+    }
+}
+
+/// Check Grin's cuckatoo31 test proof is accepted from its header and
+/// nonce and a tampered copy rejected
+fn check_grin_proof() -> Option<String> {
+    if let Err(error) = verify_proof(&GRIN_PROOF_HEADER, GRIN_PROOF_NONCE, &GRIN_PROOF, GRIN_PROOF_EDGE_BITS) {
+        return Some(format!("Grin proof rejected: {}", error));
+    }
+
+    let mut tampered = GRIN_PROOF;
+    tampered[0] += 1;
+    if verify_proof(&GRIN_PROOF_HEADER, GRIN_PROOF_NONCE, &tampered, GRIN_PROOF_EDGE_BITS).is_ok() {
+        return Some("tampered Grin proof accepted".to_string());
+    }
+    None
+}
+
+/// Check the known proof is accepted and a tampered copy rejected
+fn check_known_proof() -> Option<String> {
+    if let Err(error) = verify_proof_nonces(&KNOWN_PROOF_KEYS, KNOWN_PROOF_EDGE_BITS, &KNOWN_PROOF) {
+        return Some(format!("known proof rejected: {}", error));
+    }
+
+    let mut tampered = KNOWN_PROOF;
+    tampered[0] -= 1;
+    if verify_proof_nonces(&KNOWN_PROOF_KEYS, KNOWN_PROOF_EDGE_BITS, &tampered).is_ok() {
+        return Some("tampered proof accepted".to_string());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        let checks = run_self_test();
+        assert_eq!(checks.len(), if cfg!(feature = "verify-only") { 4 } else { 6 });
+        for check in checks {
+            assert!(check.passed, "{} failed: {}", check.name, check.detail);
+        }
+    }
+
+    #[test]
+    fn test_known_proof_keys() {
+        assert_eq!(SipKeys::from_header(KNOWN_PROOF_HEADER, KNOWN_PROOF_NONCE), KNOWN_PROOF_KEYS);
+        assert_eq!(SipKeys::from_header(&GRIN_PROOF_HEADER, GRIN_PROOF_NONCE).words(), BLAKE2B_VECTORS[2].2);
+    }
+}
@@ -0,0 +1,343 @@
+//! Solution delivery
+//!
+//! The mining loop hands every solution it submits to a `SolutionSink`
+//! and doesn't care where it goes: stdout, a JSON Lines log, a stratum
+//! connection or (with the `sqlite` feature) a database. A `SinkSet`
+//! forwards to any combination of them, so a new delivery target is a new
+//! sink rather than a change to the mining loop. `open_sink` builds a sink
+//! from a command line spec such as `file:shares.jsonl`.
+
+use crate::{CuckatooError, Result, Solution};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+/// Destination of submitted solutions
+pub trait SolutionSink: Send {
+    /// Short description of the sink, e.g. `file:shares.jsonl`
+    fn name(&self) -> String;
+
+    /// Deliver a solution
+    fn submit(&mut self, solution: &Solution) -> Result<()>;
+
+    /// Flush buffered solutions (sinks write through by default)
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Destination of submitted solutions for async mining loops
+///
+/// Every `SolutionSink` is one, completing at once; sinks doing network
+/// round trips can implement it directly.
+pub trait AsyncSolutionSink {
+    /// Deliver a solution
+    fn submit_async(&mut self, solution: &Solution) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl<S: SolutionSink + ?Sized> AsyncSolutionSink for S {
+    fn submit_async(&mut self, solution: &Solution) -> impl Future<Output = Result<()>> + Send {
+        std::future::ready(self.submit(solution))
+    }
+}
+
+/// Prints solutions to stdout, as text or JSON lines
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink {
+    json: bool,
+}
+
+impl StdoutSink {
+    /// Print `Solution for job J at nonce N: [...]` lines
+    pub fn text() -> Self {
+        Self { json: false }
+    }
+
+    /// Print one JSON object per solution
+    pub fn json() -> Self {
+        Self { json: true }
+    }
+}
+
+impl SolutionSink for StdoutSink {
+    fn name(&self) -> String {
+        if self.json { "json" } else { "stdout" }.to_string()
+    }
+
+    fn submit(&mut self, solution: &Solution) -> Result<()> {
+        if self.json {
+            println!("{}", solution.to_json());
+        } else {
+            println!("Solution for job {} at nonce {}: {:?}", solution.job_id, solution.nonce, solution.proof);
+        }
+        Ok(())
+    }
+}
+
+/// Appends solutions to a JSON Lines file
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileSink {
+    /// Open `path` for appending, creating it if needed
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|error| CuckatooError::InternalError(format!("Cannot open {}: {}", path.display(), error)))?;
+        Ok(Self { path, file })
+    }
+}
+
+impl SolutionSink for FileSink {
+    fn name(&self) -> String {
+        format!("file:{}", self.path.display())
+    }
+
+    fn submit(&mut self, solution: &Solution) -> Result<()> {
+        writeln!(self.file, "{}", solution.to_json())
+            .map_err(|error| CuckatooError::InternalError(format!("Cannot write {}: {}", self.path.display(), error)))
+    }
+}
+
+/// Sends solutions as Grin stratum `submit` requests
+///
+/// Requests are written one JSON-RPC object per line; the pool's replies
+/// are left to the connection's reader.
+pub struct StratumSink<W: Write + Send> {
+    name: String,
+    writer: W,
+    next_id: u64,
+}
+
+impl StratumSink<TcpStream> {
+    /// Connect to a stratum server at `address` (`host:port`)
+    pub fn connect(address: &str) -> Result<Self> {
+        let stream = TcpStream::connect(address)
+            .map_err(|error| CuckatooError::InternalError(format!("Cannot connect to {}: {}", address, error)))?;
+        Ok(Self::new(format!("stratum:{}", address), stream))
+    }
+}
+
+impl<W: Write + Send> StratumSink<W> {
+    /// Write requests to an already connected writer
+    pub fn new(name: impl Into<String>, writer: W) -> Self {
+        Self { name: name.into(), writer, next_id: 0 }
+    }
+
+    /// The underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<W: Write + Send> SolutionSink for StratumSink<W> {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn submit(&mut self, solution: &Solution) -> Result<()> {
+        let pow: Vec<String> = solution.proof.iter().map(u64::to_string).collect();
+        let request = format!(
+            "{{\"id\":\"{}\",\"jsonrpc\":\"2.0\",\"method\":\"submit\",\"params\":{{\"edge_bits\":{},\"height\":{},\"job_id\":{},\"nonce\":{},\"pow\":[{}]}}}}\n",
+            self.next_id, solution.edge_bits, solution.height, solution.job_id, solution.nonce, pow.join(",")
+        );
+        self.next_id += 1;
+        self.writer
+            .write_all(request.as_bytes())
+            .and_then(|()| self.writer.flush())
+            .map_err(|error| CuckatooError::InternalError(format!("Stratum submit to {} failed: {}", self.name, error)))
+    }
+}
+
+/// Inserts solutions into a `solutions` table of an SQLite database
+///
+/// `u64` values are stored as the `i64` with the same bits, since SQLite
+/// integers are signed; the proof is stored as comma-separated nonces.
+#[cfg(feature = "sqlite")]
+pub struct SqliteSink {
+    path: PathBuf,
+    connection: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteSink {
+    /// Open (or create) the database at `path` and its `solutions` table
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let error = |error: rusqlite::Error| CuckatooError::InternalError(format!("SQLite {}: {}", path.display(), error));
+        let connection = rusqlite::Connection::open(&path).map_err(error)?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS solutions (job_id INTEGER, height INTEGER, nonce INTEGER, \
+                 edge_bits INTEGER, proof TEXT, submitted_at INTEGER DEFAULT (strftime('%s', 'now')))",
+                [],
+            )
+            .map_err(error)?;
+        Ok(Self { path, connection })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl SolutionSink for SqliteSink {
+    fn name(&self) -> String {
+        format!("sqlite:{}", self.path.display())
+    }
+
+    fn submit(&mut self, solution: &Solution) -> Result<()> {
+        let proof: Vec<String> = solution.proof.iter().map(u64::to_string).collect();
+        self.connection
+            .execute(
+                "INSERT INTO solutions (job_id, height, nonce, edge_bits, proof) VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    solution.job_id as i64,
+                    solution.height as i64,
+                    solution.nonce as i64,
+                    solution.edge_bits,
+                    proof.join(","),
+                ],
+            )
+            .map(|_| ())
+            .map_err(|error| CuckatooError::InternalError(format!("SQLite {}: {}", self.path.display(), error)))
+    }
+}
+
+/// Forwards solutions to every sink it holds
+///
+/// A failing sink doesn't keep the solution from the others; `submit`
+/// returns the first error after trying them all.
+#[derive(Default)]
+pub struct SinkSet {
+    sinks: Vec<Box<dyn SolutionSink>>,
+}
+
+impl SinkSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sink
+    pub fn push(&mut self, sink: Box<dyn SolutionSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Check if the set has no sinks
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Number of sinks
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+}
+
+impl SolutionSink for SinkSet {
+    fn name(&self) -> String {
+        self.sinks.iter().map(|sink| sink.name()).collect::<Vec<_>>().join(",")
+    }
+
+    fn submit(&mut self, solution: &Solution) -> Result<()> {
+        let mut first_error = None;
+        for sink in &mut self.sinks {
+            if let Err(error) = sink.submit(solution) {
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let mut first_error = None;
+        for sink in &mut self.sinks {
+            if let Err(error) = sink.flush() {
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+/// Open a sink from a spec: `stdout`, `json`, `file:<PATH>`,
+/// `stratum:<HOST:PORT>` or, with the `sqlite` feature, `sqlite:<PATH>`
+pub fn open_sink(spec: &str) -> Result<Box<dyn SolutionSink>> {
+    let (kind, target) = spec.split_once(':').unwrap_or((spec, ""));
+    match (kind, target) {
+        ("stdout", "") => Ok(Box::new(StdoutSink::text())),
+        ("json", "") => Ok(Box::new(StdoutSink::json())),
+        ("file", path) if !path.is_empty() => Ok(Box::new(FileSink::open(Path::new(path))?)),
+        ("stratum", address) if !address.is_empty() => Ok(Box::new(StratumSink::connect(address)?)),
+        #[cfg(feature = "sqlite")]
+        ("sqlite", path) if !path.is_empty() => Ok(Box::new(SqliteSink::open(Path::new(path))?)),
+        _ => Err(CuckatooError::InternalError(format!(
+            "Unknown solution sink: {} (expected stdout, json, file:<PATH>, stratum:<HOST:PORT> or sqlite:<PATH>)", spec
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll, Waker};
+
+    fn solution() -> Solution {
+        Solution { job_id: 7, height: 100, nonce: 42, edge_bits: 12, proof: vec![1, 2, 3] }
+    }
+
+    #[test]
+    fn test_sinks_deliver_solutions() {
+        let path = std::env::temp_dir().join(format!("cuckatoo-sink-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut sinks = SinkSet::new();
+        sinks.push(open_sink(&format!("file:{}", path.display())).unwrap());
+        sinks.push(Box::new(StdoutSink::json()));
+        assert_eq!(sinks.len(), 2);
+        sinks.submit(&solution()).unwrap();
+        sinks.submit(&solution()).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text.lines().collect::<Vec<_>>(), [solution().to_json(), solution().to_json()]);
+        assert!(sinks.name().starts_with("file:"));
+
+        let mut stratum = StratumSink::new("stratum:test", Vec::new());
+        stratum.submit(&solution()).unwrap();
+        let request = String::from_utf8(stratum.get_ref().clone()).unwrap();
+        assert_eq!(
+            request,
+            "{\"id\":\"0\",\"jsonrpc\":\"2.0\",\"method\":\"submit\",\"params\":{\"edge_bits\":12,\"height\":100,\"job_id\":7,\"nonce\":42,\"pow\":[1,2,3]}}\n"
+        );
+
+        // Sync sinks complete their async submission at once
+        let solution = solution();
+        let mut future = std::pin::pin!(stratum.submit_async(&solution));
+        let mut context = Context::from_waker(Waker::noop());
+        assert!(matches!(future.as_mut().poll(&mut context), Poll::Ready(Ok(()))));
+
+        assert!(open_sink("carrier-pigeon").is_err());
+        assert!(open_sink("file:").is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_sink() {
+        let path = std::env::temp_dir().join(format!("cuckatoo-sink-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut sink = SqliteSink::open(&path).unwrap();
+        sink.submit(&solution()).unwrap();
+        let (nonce, proof): (i64, String) = sink
+            .connection
+            .query_row("SELECT nonce, proof FROM solutions", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        drop(sink);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((nonce, proof.as_str()), (42, "1,2,3"));
+    }
+}
@@ -0,0 +1,157 @@
+//! SipHash keys with their derivation
+//!
+//! The four SipHash keys select the graph. Grin, the C++ reference miner and
+//! the tests each derive them differently, and keys from one schedule used
+//! where another is expected give a valid-looking but different graph.
+//! `SipKeys` records how its keys were derived, so such mix-ups fail
+//! comparisons and show up in error messages.
+
+use crate::blake2b::{blake2b_bytes, blake2b_digest};
+use crate::endian::{keys_from_le_bytes, keys_to_le_bytes, KEYS_SIZE};
+use std::fmt;
+
+/// How a set of SipHash keys was derived
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyProvenance {
+    /// Blake2b of the header with the nonce appended as a big-endian u64,
+    /// the way Grin's `pre_pow` serializes them
+    Grin,
+    /// Blake2b of the header with the nonce written over its last four
+    /// bytes as a little-endian u32, as the C++ reference miner does
+    ReferenceMiner,
+    /// Fixed keys of a test graph or known-answer vector
+    TestSeed,
+}
+
+impl fmt::Display for KeyProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyProvenance::Grin => write!(f, "grin-blake2b"),
+            KeyProvenance::ReferenceMiner => write!(f, "reference-miner"),
+            KeyProvenance::TestSeed => write!(f, "test-seed"),
+        }
+    }
+}
+
+impl std::str::FromStr for KeyProvenance {
+    type Err = crate::CuckatooError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grin-blake2b" => Ok(KeyProvenance::Grin),
+            "reference-miner" => Ok(KeyProvenance::ReferenceMiner),
+            "test-seed" => Ok(KeyProvenance::TestSeed),
+            _ => Err(crate::CuckatooError::InternalError(format!("Unknown key derivation: {}", s))),
+        }
+    }
+}
+
+/// SipHash keys of a graph and how they were derived
+///
+/// Keys only compare equal if they were derived the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SipKeys {
+    words: [u64; 4],
+    provenance: KeyProvenance,
+}
+
+impl SipKeys {
+    /// Derive the keys of a header and nonce the way Grin does
+    pub fn from_header(header: &[u8], nonce: u64) -> Self {
+        Self {
+            words: keys_from_le_bytes(&blake2b_digest(header, nonce)),
+            provenance: KeyProvenance::Grin,
+        }
+    }
+
+    /// Derive the keys of a header and nonce the way the C++ reference
+    /// miner does
+    ///
+    /// Headers shorter than four bytes are zero-padded first.
+    pub fn from_header_reference(header: &[u8], nonce: u64) -> Self {
+        let mut bytes = header.to_vec();
+        if bytes.len() < 4 {
+            bytes.resize(4, 0);
+        }
+        let nonce_offset = bytes.len() - 4;
+        bytes[nonce_offset..].copy_from_slice(&(nonce as u32).to_le_bytes());
+
+        Self {
+            words: keys_from_le_bytes(&blake2b_bytes(&bytes)),
+            provenance: KeyProvenance::ReferenceMiner,
+        }
+    }
+
+    /// Fixed keys of a test graph
+    pub const fn test_seed(words: [u64; 4]) -> Self {
+        Self { words, provenance: KeyProvenance::TestSeed }
+    }
+
+    /// Keys read back from their words (for example from a replay record),
+    /// tagged with how they were originally derived
+    pub const fn from_words(words: [u64; 4], provenance: KeyProvenance) -> Self {
+        Self { words, provenance }
+    }
+
+    /// Keys read back from their 32-byte form (for example from a pool or
+    /// a saved graph), tagged with how they were originally derived
+    pub fn from_le_bytes(bytes: &[u8; KEYS_SIZE], provenance: KeyProvenance) -> Self {
+        Self::from_words(keys_from_le_bytes(bytes), provenance)
+    }
+
+    /// The four key words
+    pub fn words(&self) -> [u64; 4] {
+        self.words
+    }
+
+    /// How the keys were derived
+    pub fn provenance(&self) -> KeyProvenance {
+        self.provenance
+    }
+
+    /// The keys in their 32-byte little-endian form
+    pub fn to_le_bytes(&self) -> [u8; KEYS_SIZE] {
+        keys_to_le_bytes(&self.words)
+    }
+}
+
+impl fmt::Display for SipKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [k0, k1, k2, k3] = self.words;
+        write!(f, "[0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:016x}] ({})", k0, k1, k2, k3, self.provenance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grin_vector() {
+        // Keys of Grin's cuckatoo31 test proof: 80 zero bytes but for a
+        // little-endian 99 at the end, which is a zero 72-byte pre_pow
+        // with the big-endian nonce 99 << 24 appended
+        let words = [0x8785f61f3e087286, 0x91b57e6072a0cdaa, 0x8035f9ee251a77a0, 0x0de03da786148f07];
+        assert_eq!(SipKeys::from_header(&[0; 72], 99 << 24).words(), words);
+        assert_eq!(SipKeys::from_header_reference(&[0; 80], 99).words(), words);
+    }
+
+    #[test]
+    fn test_provenance_separates_schedules() {
+        let header = [7u8; 16];
+        let grin = SipKeys::from_header(&header, 5);
+        let reference = SipKeys::from_header_reference(&header, 5);
+        assert_eq!(grin.provenance(), KeyProvenance::Grin);
+        assert_ne!(grin.words(), reference.words());
+
+        // The same words with a different provenance are different keys
+        let seed = SipKeys::test_seed(grin.words());
+        assert_ne!(seed, grin);
+        assert_eq!(SipKeys::from_le_bytes(&grin.to_le_bytes(), KeyProvenance::Grin), grin);
+
+        // The reference miner only uses the low 32 bits of the nonce
+        assert_eq!(SipKeys::from_header_reference(&header, 5 | 1 << 32), reference);
+        assert!(grin.to_string().ends_with("(grin-blake2b)"));
+        assert_eq!("reference-miner".parse::<KeyProvenance>().unwrap(), KeyProvenance::ReferenceMiner);
+    }
+}
@@ -0,0 +1,62 @@
+//! WASM-SIMD SipHash-2-4 for batched hashing
+//!
+//! Hashes two nonces at once in the 64-bit lanes of a `v128`. Only compiled
+//! for wasm32 with the `simd128` target feature enabled.
+
+use core::arch::wasm32::*;
+
+/// Rotate both 64-bit lanes left by `bits`
+#[inline(always)]
+fn rotate_left(value: v128, bits: u32) -> v128 {
+    v128_or(i64x2_shl(value, bits), u64x2_shr(value, 64 - bits))
+}
+
+/// SipRound on both lanes (same steps as `SipHash::sip_round`)
+#[inline(always)]
+fn sip_round(states: &mut [v128; 4]) {
+    states[0] = i64x2_add(states[0], states[1]);
+    states[2] = i64x2_add(states[2], states[3]);
+    states[1] = rotate_left(states[1], 13);
+    states[3] = rotate_left(states[3], 16);
+    states[1] = v128_xor(states[1], states[0]);
+    states[3] = v128_xor(states[3], states[2]);
+    states[0] = rotate_left(states[0], 32);
+    states[2] = i64x2_add(states[2], states[1]);
+    states[0] = i64x2_add(states[0], states[3]);
+    states[1] = rotate_left(states[1], 17);
+    states[3] = rotate_left(states[3], 21);
+    states[1] = v128_xor(states[1], states[2]);
+    states[3] = v128_xor(states[3], states[0]);
+    states[2] = rotate_left(states[2], 32);
+}
+
+/// Hash nonces two at a time into masked nodes
+///
+/// Returns the number of nonces hashed; an odd last nonce is left to the
+/// caller's scalar path.
+pub(crate) fn hash_nonce_pairs(keys: &[u64; 4], nonces: &[u64], node_mask: u64, nodes: &mut [u64]) -> usize {
+    let mask = u64x2_splat(node_mask);
+    let pairs = nonces.len() / 2;
+
+    for (pair, output) in nonces.chunks_exact(2).zip(nodes.chunks_exact_mut(2)) {
+        let nonce = u64x2(pair[0], pair[1]);
+        let mut states = keys.map(u64x2_splat);
+
+        states[3] = v128_xor(states[3], nonce);
+        sip_round(&mut states);
+        sip_round(&mut states);
+        states[0] = v128_xor(states[0], nonce);
+        states[2] = v128_xor(states[2], u64x2_splat(255));
+        sip_round(&mut states);
+        sip_round(&mut states);
+        sip_round(&mut states);
+        sip_round(&mut states);
+
+        let hash = v128_xor(v128_xor(states[0], states[1]), v128_xor(states[2], states[3]));
+        let node = v128_and(hash, mask);
+        output[0] = u64x2_extract_lane::<0>(node);
+        output[1] = u64x2_extract_lane::<1>(node);
+    }
+
+    pairs * 2
+}
@@ -0,0 +1,433 @@
+//! Reusable graph solver
+//!
+//! A `Solver` owns the trimmer buffers and cycle finder for one graph size.
+//! Allocating them is the expensive part of setting up a graph, so when a
+//! new job (or just a new nonce) arrives the solver is rebound to it with
+//! `rebind`, which only derives the new SipHash keys. The miner's jobs
+//! file mode keeps one solver across consecutive jobs of the same size and
+//! the stress test one per thread. A `Dispatcher` keeps one solver per
+//! algorithm for pools that mix algorithms.
+
+use crate::metrics::{MetricsRegistry, WorkerMetrics, DEFAULT_TIME_BUCKETS};
+use crate::{
+    Algorithm, BitmapTrimmer, CancelToken, Config, CuckatooError, CycleVerifier, Edge, EndpointCache, Header, KeyDerivation, MiningJob,
+    Result, SipHash, SipHasher, SipKeys, TrimmerPool, TrimmingMode, recover_proof,
+};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+/// Trims and searches graphs of one configuration, reusing its buffers
+pub struct Solver {
+    config: Config,
+    trimmer: BitmapTrimmer,
+    verifier: CycleVerifier,
+    /// Hasher of the bound job
+    siphash: SipHash,
+    /// Cache of the bound job's endpoints used while trimming
+    endpoint_cache: Option<EndpointCache<SipHash>>,
+    /// Time taken to allocate the buffers in `new`
+    setup_time: Duration,
+    registry: Option<MetricsRegistry>,
+    /// Metrics of the worker thread or device running this solver
+    worker: Option<WorkerMetrics>,
+}
+
+impl Solver {
+    /// Create a solver, allocating the buffers for the configured graph size
+    ///
+    /// The solver starts bound to the default SipHash keys; call `rebind`
+    /// before solving a job. Fails for configurations it can't honour (see
+    /// `check_supported`).
+    pub fn new(config: Config) -> Result<Self> {
+        Self::with_trimmer(config, BitmapTrimmer::for_config)
+    }
+
+    /// Create a solver with a trimmer from `pool`, reusing the buffers of
+    /// an earlier solver of the same graph size if one was released
+    pub fn from_pool(config: Config, pool: &TrimmerPool) -> Result<Self> {
+        Self::with_trimmer(config, |config| pool.take_for_config(config))
+    }
+
+    /// Return the solver's trimmer to `pool` for the next solver
+    pub fn release(self, pool: &TrimmerPool) {
+        pool.put(self.trimmer);
+    }
+
+    /// Create a solver around the trimmer `trimmer` returns for the
+    /// configuration
+    fn with_trimmer(config: Config, trimmer: impl FnOnce(&Config) -> Result<BitmapTrimmer>) -> Result<Self> {
+        let start = Instant::now();
+        config.validate()?;
+        check_supported(&config)?;
+        let trimmer = trimmer(&config)?.with_threads(config.threads).with_numa_policy(config.numa_policy);
+        let endpoint_cache = config.endpoint_cache
+            .map(|bytes| EndpointCache::new(SipHash::default(), bytes))
+            .transpose()?;
+        let verifier = CycleVerifier::new().with_threads(config.threads).with_solver_strategy(config.solver_strategy);
+
+        Ok(Self {
+            config,
+            trimmer,
+            verifier,
+            siphash: SipHash::default(),
+            endpoint_cache,
+            setup_time: start.elapsed(),
+            registry: None,
+            worker: None,
+        })
+    }
+
+    /// Record solver, trimming and search metrics into the given registry
+    pub fn with_metrics(mut self, registry: MetricsRegistry) -> Self {
+        registry.histogram("cuckatoo_solver_setup_seconds", &DEFAULT_TIME_BUCKETS)
+            .observe(self.setup_time.as_secs_f64());
+        self.trimmer = self.trimmer.with_metrics(registry.clone());
+        self.verifier = self.verifier.with_metrics(registry.clone());
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Stop trimming and searching with `Cancelled` soon after `token` is
+    /// cancelled, e.g. when a new job makes the bound graph stale
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.trimmer = self.trimmer.with_cancel_token(token.clone());
+        self.verifier = self.verifier.with_cancel_token(token);
+        self
+    }
+
+    /// Record graphs, solutions and trim and search times per worker
+    /// (see `MetricsRegistry::worker`)
+    pub fn with_worker(mut self, worker: WorkerMetrics) -> Self {
+        self.worker = Some(worker);
+        self
+    }
+
+    /// Bind the solver to a job header and nonce, keeping its buffers
+    ///
+    /// The keys are derived with the configured `key_schedule`. Returns the
+    /// time the rebind took.
+    pub fn rebind(&mut self, header: &Header) -> Duration {
+        let start = Instant::now();
+        self.siphash = SipHash::with_key(self.config.key_schedule.derive_keys(header.as_bytes(), header.nonce()));
+        self.rebind_cache();
+        let elapsed = start.elapsed();
+
+        if let Some(registry) = &self.registry {
+            registry.histogram("cuckatoo_solver_rebind_seconds", &DEFAULT_TIME_BUCKETS)
+                .observe(elapsed.as_secs_f64());
+        }
+        elapsed
+    }
+
+    /// Bind the solver to the graph of the given SipHash keys
+    pub fn rebind_keys(&mut self, keys: SipKeys) {
+        self.siphash = SipHash::with_key(keys);
+        self.rebind_cache();
+    }
+
+    /// Point the endpoint cache at the bound graph
+    fn rebind_cache(&mut self) {
+        if let Some(cache) = &mut self.endpoint_cache {
+            cache.rebind(SipHash::with_key(self.siphash.get_key()));
+        }
+    }
+
+    /// SipHash keys of the bound graph
+    pub fn keys(&self) -> SipKeys {
+        self.siphash.get_key()
+    }
+
+    /// Time taken to allocate the buffers when the solver was created
+    pub fn setup_time(&self) -> Duration {
+        self.setup_time
+    }
+
+    /// Configuration the solver was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Name of the cycle finder searching the graphs
+    pub fn finder_name(&self) -> &'static str {
+        CycleVerifier::NAME
+    }
+
+    /// Trim and search the bound graph
+    ///
+    /// Returns the proof nonces of a 42-cycle, sorted ascending. Callers
+    /// should re-verify the proof (`reverify_solution`) before reporting it.
+    /// A panic while trimming or searching is returned as `InternalError`
+    /// with the graph seed, and the solver stays usable for the next graph.
+    pub fn solve(&mut self) -> Result<Option<Vec<u64>>> {
+        let (keys, edge_bits) = (self.keys(), self.config.edge_bits);
+        isolate_panics(&keys, edge_bits, || self.solve_bound_graph())
+    }
+
+    /// Trim and search the bound graph (see `solve`)
+    fn solve_bound_graph(&mut self) -> Result<Option<Vec<u64>>> {
+        let trim_start = Instant::now();
+        let survivors = match &self.endpoint_cache {
+            Some(cache) => {
+                let survivors = trim(&mut self.trimmer, &self.config, cache)?;
+                if let Some(registry) = &self.registry {
+                    let stats = cache.stats();
+                    registry.counter("cuckatoo_endpoint_cache_hits_total").add(stats.hits);
+                    registry.counter("cuckatoo_endpoint_cache_misses_total").add(stats.misses);
+                }
+                cache.reset_stats();
+                survivors
+            }
+            None => trim(&mut self.trimmer, &self.config, &self.siphash)?,
+        };
+
+        let trim_time = trim_start.elapsed();
+
+        let search_start = Instant::now();
+        let cycle = self.verifier.find_42_cycle(&survivors)?;
+        if let Some(worker) = &self.worker {
+            worker.record_graph(trim_time, search_start.elapsed(), cycle.is_some() as u64);
+        }
+        // Only the cycle is held in memory while the graph is re-hashed,
+        // even for graphs above EDGE_BITS=32
+        cycle
+            .map(|cycle| recover_proof(&self.siphash.get_key(), self.config.edge_bits, &cycle, self.config.threads))
+            .transpose()
+    }
+}
+
+/// Run `solve` on a graph, turning a panic into an `InternalError` with the
+/// graph seed so one pathological graph doesn't take down a long-running
+/// miner
+fn isolate_panics<T>(keys: &SipKeys, edge_bits: u32, solve: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(solve)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        Err(CuckatooError::InternalError(format!(
+            "Solving panicked ({}); graph seed: EDGE_BITS={} keys={}", message, edge_bits, keys
+        )))
+    })
+}
+
+/// Check a solver can solve graphs the way `config` asks
+///
+/// A solver trims every graph with the lean `BitmapTrimmer` and returns
+/// the first cycle it finds, so other trimming modes, a node buffer (only
+/// `ExactTrimmer` has one) and submission policies wanting more than one
+/// solution per graph are rejected instead of being ignored.
+fn check_supported(config: &Config) -> Result<()> {
+    if config.mode != TrimmingMode::Lean {
+        return Err(CuckatooError::InternalError(format!("the solver only trims in lean mode, not {}", config.mode)));
+    }
+    if config.node_buffer.is_some() {
+        return Err(CuckatooError::InternalError("the solver's lean trimmer has no node buffer".to_string()));
+    }
+    if !config.submission_policy.stops_at_first() {
+        return Err(CuckatooError::InternalError(format!(
+            "the solver returns one solution per graph, not for the {} submission policy", config.submission_policy
+        )));
+    }
+    Ok(())
+}
+
+/// Trim a graph as configured, hashing endpoints with `hasher`
+fn trim<H: SipHasher>(trimmer: &mut BitmapTrimmer, config: &Config, hasher: &H) -> Result<Vec<Edge>> {
+    match config.round_controller() {
+        Some(mut controller) => Ok(trimmer.trim_edges_adaptive(hasher, &mut controller)?.edges),
+        None => trimmer.trim_edges(hasher, config.trimming_rounds),
+    }
+}
+
+/// Solves jobs with the solver of each job's algorithm
+///
+/// Solvers are created on the first job of their algorithm and reused for
+/// later jobs, so switching algorithms doesn't need a restart.
+pub struct Dispatcher {
+    /// Configuration of new solvers, with the edge bits of their algorithm
+    template: Config,
+    solvers: HashMap<Algorithm, Solver>,
+    registry: Option<MetricsRegistry>,
+    cancel: Option<CancelToken>,
+}
+
+impl Dispatcher {
+    /// Create a dispatcher whose solvers use `template` (apart from the
+    /// edge bits, which come from each algorithm)
+    pub fn new(template: Config) -> Self {
+        Self { template, solvers: HashMap::new(), registry: None, cancel: None }
+    }
+
+    /// Record the metrics of every solver into the given registry
+    pub fn with_metrics(mut self, registry: MetricsRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Give every solver `token` (see `Solver::with_cancel_token`)
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Get the solver of an algorithm, creating it on first use
+    ///
+    /// Fails for algorithms this crate can't solve.
+    pub fn solver(&mut self, algorithm: Algorithm) -> Result<&mut Solver> {
+        algorithm.ensure_supported()?;
+        if !self.solvers.contains_key(&algorithm) {
+            let mut config = self.template.clone();
+            config.edge_bits = algorithm.edge_bits();
+            let mut solver = Solver::new(config)?;
+            if let Some(registry) = &self.registry {
+                solver = solver.with_metrics(registry.clone());
+            }
+            if let Some(token) = &self.cancel {
+                solver = solver.with_cancel_token(token.clone());
+            }
+            self.solvers.insert(algorithm, solver);
+        }
+        Ok(self.solvers.get_mut(&algorithm).expect("solver was just inserted"))
+    }
+
+    /// Solve a job's graph for `nonce`
+    ///
+    /// Returns the proof nonces of a 42-cycle; callers should verify it
+    /// with `MiningJob::verify` before submitting it.
+    pub fn solve(&mut self, job: &MiningJob, nonce: u64) -> Result<Option<Vec<u64>>> {
+        let solver = self.solver(job.algorithm)?;
+        solver.rebind(&job.header(nonce));
+        solver.solve()
+    }
+
+    /// Algorithms that have a solver
+    pub fn algorithms(&self) -> Vec<Algorithm> {
+        self.solvers.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{KNOWN_PROOF, KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_HEADER, KNOWN_PROOF_KEYS, KNOWN_PROOF_NONCE};
+    use crate::SubmissionPolicy;
+
+    #[test]
+    fn test_rebind_keeps_buffers() {
+        let registry = MetricsRegistry::new();
+        let mut solver = Solver::new(Config::new(12)).unwrap().with_metrics(registry.clone());
+
+        let header = Header::new_with_nonce(b"job", 1);
+        solver.rebind(&header);
+        let first_keys = solver.keys();
+        assert_eq!(first_keys, crate::blake2b(b"job", 1));
+        let first = solver.solve().unwrap();
+
+        // Only the nonce changes within a job
+        solver.rebind(&Header::new_with_nonce(b"job", 2));
+        assert_ne!(solver.keys(), first_keys);
+        solver.solve().unwrap();
+
+        // Rebinding back gives the same result as the first solve
+        solver.rebind(&header);
+        assert_eq!(solver.solve().unwrap(), first);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.histogram("cuckatoo_solver_setup_seconds").unwrap().count, 1);
+        assert_eq!(snapshot.histogram("cuckatoo_solver_rebind_seconds").unwrap().count, 3);
+        assert_eq!(snapshot.counter("cuckatoo_trimmed_graphs_total"), Some(3));
+    }
+
+    #[test]
+    fn test_worker_metrics() {
+        let registry = MetricsRegistry::new();
+        let mut solver = Solver::new(Config::new(10)).unwrap().with_worker(registry.worker("cpu-0"));
+        for nonce in 0..3 {
+            solver.rebind(&Header::new_with_nonce(b"worker", nonce));
+            solver.solve().unwrap();
+        }
+
+        let workers = registry.snapshot().workers();
+        assert_eq!(workers.len(), 1);
+        assert_eq!(workers[0].graphs, 3);
+        assert!(workers[0].trim_seconds > 0.0);
+    }
+
+    #[test]
+    fn test_endpoint_cache_matches_uncached() {
+        let header = Header::new_with_nonce(b"cached job", 3);
+        let mut uncached = Solver::new(Config::new(12)).unwrap();
+        uncached.rebind(&header);
+
+        let registry = MetricsRegistry::new();
+        let mut config = Config::new(12);
+        config.endpoint_cache = Some(1 << 17);
+        let mut cached = Solver::new(config).unwrap().with_metrics(registry.clone());
+        cached.rebind(&header);
+
+        assert_eq!(cached.solve().unwrap(), uncached.solve().unwrap());
+        let snapshot = registry.snapshot();
+        assert!(snapshot.counter("cuckatoo_endpoint_cache_hits_total").unwrap() > 0);
+        assert!(snapshot.counter("cuckatoo_endpoint_cache_misses_total").unwrap() > 0);
+    }
+
+    #[test]
+    fn test_panics_become_errors() {
+        let keys = SipKeys::from_header(b"panic", 1);
+        let error = isolate_panics::<()>(&keys, 12, || panic!("bad graph")).unwrap_err();
+        match error {
+            CuckatooError::InternalError(message) => {
+                assert!(message.contains("bad graph"), "{}", message);
+                assert!(message.contains(&format!("EDGE_BITS=12 keys={}", keys)), "{}", message);
+            },
+            other => panic!("Expected an internal error, got {:?}", other),
+        }
+        assert_eq!(isolate_panics(&keys, 12, || Ok(7)).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_solves_known_job() {
+        let mut solver = Solver::new(Config::new(KNOWN_PROOF_EDGE_BITS)).unwrap();
+        solver.rebind(&Header::new_with_nonce(KNOWN_PROOF_HEADER, KNOWN_PROOF_NONCE));
+        assert_eq!(solver.keys(), KNOWN_PROOF_KEYS);
+        let proof = solver.solve().unwrap().unwrap();
+        assert_eq!(proof, KNOWN_PROOF);
+        assert!(crate::verify_proof(KNOWN_PROOF_HEADER, KNOWN_PROOF_NONCE, &proof, KNOWN_PROOF_EDGE_BITS).is_ok());
+
+        // Not the next nonce's graph
+        solver.rebind(&Header::new_with_nonce(KNOWN_PROOF_HEADER, KNOWN_PROOF_NONCE + 1));
+        assert_eq!(solver.solve().unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        assert!(Solver::new(Config::new(8)).is_err());
+
+        // Settings the solver would otherwise ignore
+        let mut mean = Config::new(12);
+        mean.mode = TrimmingMode::Mean;
+        let mut buffered = Config::new(12);
+        buffered.node_buffer = Some(1 << 13);
+        let mut all = Config::new(12);
+        all.submission_policy = SubmissionPolicy::All;
+        for config in [mean, buffered, all] {
+            assert!(matches!(Solver::new(config), Err(CuckatooError::InternalError(_))));
+        }
+    }
+
+    #[test]
+    fn test_dispatcher_rejects_unsupported_algorithms() {
+        let mut dispatcher = Dispatcher::new(Config::new(12));
+        let job = MiningJob {
+            job_id: 1,
+            height: 1,
+            pre_pow: vec![0; 8],
+            algorithm: Algorithm::Cuckaroo29,
+            difficulty: 1,
+        };
+        assert!(matches!(dispatcher.solve(&job, 0), Err(crate::CuckatooError::InternalError(_))));
+        assert!(dispatcher.algorithms().is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+//! Solution submission policy
+//!
+//! A graph can contain more than one 42-cycle. The policy decides whether
+//! the miner stops at the first one, submits every one, or only submits
+//! the ones that meet the pool's share difficulty.
+
+use crate::blake2b::blake2b_bytes;
+use crate::endian::pack_proof;
+use crate::CuckatooError;
+use std::fmt;
+
+/// Blake2b-256 hash of a proof, packed `edge_bits` bits per nonce as on
+/// chain
+pub fn proof_hash(nonces: &[u64], edge_bits: u32) -> [u8; 32] {
+    blake2b_bytes(&pack_proof(nonces, edge_bits))
+}
+
+/// Difficulty of a proof: `u64::MAX` divided by the first eight bytes
+/// (big-endian) of the hash of the packed proof
+///
+/// This is the unscaled difficulty, before any graph weight is applied.
+pub fn proof_difficulty(nonces: &[u64], edge_bits: u32) -> u64 {
+    let hash = proof_hash(nonces, edge_bits);
+    let leading = u64::from_be_bytes(hash[..8].try_into().expect("8-byte prefix"));
+    u64::MAX / leading.max(1)
+}
+
+/// A verified solution of a job, as handed to a `SolutionSink`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    /// Job id assigned by the pool
+    pub job_id: u64,
+    /// Height of the block being mined
+    pub height: u64,
+    /// Header nonce of the graph
+    pub nonce: u64,
+    /// Edge bits of the graph
+    pub edge_bits: u32,
+    /// Proof nonces (edge indices of the cycle), sorted
+    pub proof: Vec<u64>,
+}
+
+impl Solution {
+    /// Difficulty of the proof (see `proof_difficulty`)
+    pub fn difficulty(&self) -> u64 {
+        proof_difficulty(&self.proof, self.edge_bits)
+    }
+
+    /// Check if the proof's difficulty reaches `target`, so the solution
+    /// is worth submitting as a share
+    pub fn meets_target(&self, target: u64) -> bool {
+        self.difficulty() >= target
+    }
+
+    /// Format the solution as a single-line JSON object
+    pub fn to_json(&self) -> String {
+        let proof: Vec<String> = self.proof.iter().map(u64::to_string).collect();
+        format!(
+            "{{\"job_id\":{},\"height\":{},\"nonce\":{},\"edge_bits\":{},\"proof\":[{}]}}",
+            self.job_id, self.height, self.nonce, self.edge_bits, proof.join(",")
+        )
+    }
+}
+
+/// Which solutions of a graph are submitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubmissionPolicy {
+    /// Submit the first solution and stop searching the graph
+    #[default]
+    First,
+    /// Submit every solution found
+    All,
+    /// Submit every solution whose difficulty meets the share difficulty
+    ShareDifficulty,
+}
+
+impl SubmissionPolicy {
+    /// Check if the search of a graph should stop after its first solution
+    pub fn stops_at_first(&self) -> bool {
+        *self == SubmissionPolicy::First
+    }
+
+    /// Choose the solutions to submit, in the order they were found
+    pub fn select(&self, solutions: Vec<Vec<u64>>, edge_bits: u32, share_difficulty: u64) -> Vec<Vec<u64>> {
+        match self {
+            SubmissionPolicy::First => solutions.into_iter().take(1).collect(),
+            SubmissionPolicy::All => solutions,
+            SubmissionPolicy::ShareDifficulty => solutions
+                .into_iter()
+                .filter(|nonces| proof_difficulty(nonces, edge_bits) >= share_difficulty)
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for SubmissionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmissionPolicy::First => write!(f, "first"),
+            SubmissionPolicy::All => write!(f, "all"),
+            SubmissionPolicy::ShareDifficulty => write!(f, "difficulty"),
+        }
+    }
+}
+
+/// Whether a solution's job is still worth submitting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareFreshness {
+    /// The solution is for the current job
+    Current,
+    /// The solution is for the previous job, within the stale-share grace
+    /// window after it was replaced
+    Stale,
+    /// The solution is for an older or unknown job, or the grace window
+    /// has passed; it is discarded
+    Expired,
+}
+
+impl ShareFreshness {
+    /// Check if a solution with this freshness is submitted
+    pub fn is_submittable(&self) -> bool {
+        *self != ShareFreshness::Expired
+    }
+}
+
+impl fmt::Display for ShareFreshness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareFreshness::Current => write!(f, "current"),
+            ShareFreshness::Stale => write!(f, "stale"),
+            ShareFreshness::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+impl std::str::FromStr for SubmissionPolicy {
+    type Err = CuckatooError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "first" => Ok(SubmissionPolicy::First),
+            "all" => Ok(SubmissionPolicy::All),
+            "difficulty" => Ok(SubmissionPolicy::ShareDifficulty),
+            _ => Err(CuckatooError::InternalError(format!("Unknown submission policy: {}", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_by_policy() {
+        let solutions: Vec<Vec<u64>> = (0..4u64).map(|offset| (offset..offset + 42).collect()).collect();
+        let difficulties: Vec<u64> = solutions.iter().map(|nonces| proof_difficulty(nonces, 12)).collect();
+
+        assert_eq!(SubmissionPolicy::First.select(solutions.clone(), 12, 0), solutions[..1]);
+        assert_eq!(SubmissionPolicy::All.select(solutions.clone(), 12, u64::MAX).len(), 4);
+
+        // Only solutions at or above the share difficulty are kept
+        let share_difficulty = difficulties.iter().copied().max().unwrap();
+        let selected = SubmissionPolicy::ShareDifficulty.select(solutions.clone(), 12, share_difficulty);
+        assert_eq!(selected.len(), difficulties.iter().filter(|&&d| d >= share_difficulty).count());
+        assert!(selected.iter().all(|nonces| proof_difficulty(nonces, 12) >= share_difficulty));
+        assert_eq!(SubmissionPolicy::ShareDifficulty.select(solutions, 12, 0).len(), 4);
+
+        assert!(SubmissionPolicy::First.stops_at_first());
+        assert_eq!("difficulty".parse::<SubmissionPolicy>().unwrap(), SubmissionPolicy::ShareDifficulty);
+        assert!("best".parse::<SubmissionPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_solution_difficulty() {
+        let proof: Vec<u64> = (100..142).collect();
+        let solution = Solution { job_id: 1, height: 2, nonce: 3, edge_bits: 12, proof: proof.clone() };
+        let hash = proof_hash(&proof, 12);
+        assert_eq!(hash, blake2b_bytes(&pack_proof(&proof, 12)));
+        assert_ne!(hash, proof_hash(&proof, 13));
+
+        let leading = u64::from_be_bytes(hash[..8].try_into().unwrap());
+        assert_eq!(solution.difficulty(), u64::MAX / leading);
+        assert!(solution.meets_target(solution.difficulty()));
+        assert!(solution.meets_target(1));
+        assert!(!solution.meets_target(solution.difficulty() + 1));
+    }
+
+    #[test]
+    fn test_known_proof_difficulty() {
+        use crate::self_test::{GRIN_PROOF, GRIN_PROOF_EDGE_BITS};
+
+        // Grin's cuckatoo31 test proof packs to a hash starting 0x5c29174b...,
+        // so u64::MAX / 0x5c29174bce6ea65a rounds down to 2
+        let hash = proof_hash(&GRIN_PROOF, GRIN_PROOF_EDGE_BITS);
+        assert_eq!(hash[..8], [0x5c, 0x29, 0x17, 0x4b, 0xce, 0x6e, 0xa6, 0x5a]);
+        assert_eq!(proof_difficulty(&GRIN_PROOF, GRIN_PROOF_EDGE_BITS), 2);
+    }
+}
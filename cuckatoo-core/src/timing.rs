@@ -3,16 +3,148 @@
 //! This module provides utilities for measuring performance and
 //! benchmarking different components of the Cuckatoo miner.
 
-use crate::{PerformanceMetrics, Result, CuckatooError};
+use crate::{ArrayCycleFinder, BitmapTrimmer, CounterTrimmer, Edge, HashCycleFinder, PerformanceMetrics, Result, CuckatooError, SipHash, SipKeys};
+use crate::metrics::MetricsRegistry;
+use crate::report::format_duration;
+use crate::alloc_tracking::{StageAllocations, StageTracker};
 use std::time::{Instant, Duration};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Resource usage of the whole process at a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// User plus system CPU time consumed so far
+    pub cpu_time: Duration,
+    /// Peak resident set size so far, in bytes
+    pub peak_rss_bytes: u64,
+}
+
+impl ResourceUsage {
+    /// Sample the process resource usage
+    /// 
+    /// Uses getrusage on Unix; returns None where it isn't available.
+    #[cfg(all(unix, feature = "resource-usage"))]
+    pub fn sample() -> Option<Self> {
+        // SAFETY: getrusage only writes into the zero-initialized struct we pass
+        let usage = unsafe {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            if libc::getrusage(libc::RUSAGE_SELF, &mut usage) != 0 {
+                return None;
+            }
+            usage
+        };
+        
+        let timeval = |tv: libc::timeval| {
+            Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+        };
+        
+        // ru_maxrss is in bytes on macOS and in kilobytes elsewhere
+        let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+        
+        Some(Self {
+            cpu_time: timeval(usage.ru_utime) + timeval(usage.ru_stime),
+            peak_rss_bytes: usage.ru_maxrss as u64 * rss_unit,
+        })
+    }
+    
+    /// Sample the process resource usage
+    #[cfg(not(all(unix, feature = "resource-usage")))]
+    pub fn sample() -> Option<Self> {
+        None
+    }
+}
+
+/// Wall time, CPU time and peak RSS of a measured piece of work
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceMeasurement {
+    /// Wall-clock time
+    pub wall_time: Duration,
+    /// Process CPU time consumed (None if unavailable)
+    pub cpu_time: Option<Duration>,
+    /// Process peak RSS after the work, in bytes (None if unavailable)
+    pub peak_rss_bytes: Option<u64>,
+}
+
+impl ResourceMeasurement {
+    /// Ratio of CPU time to wall time
+    /// 
+    /// Values well below the number of busy threads suggest the work is
+    /// waiting on memory or I/O rather than computing.
+    pub fn cpu_utilization(&self) -> Option<f64> {
+        let wall = self.wall_time.as_secs_f64();
+        self.cpu_time
+            .filter(|_| wall > 0.0)
+            .map(|cpu| cpu.as_secs_f64() / wall)
+    }
+}
+
+/// Separator between phase names in a phase path (`trimming > round 17 > step two`)
+pub const PHASE_SEPARATOR: &str = " > ";
+
+/// A timed phase in the phase tree
+#[derive(Debug, Clone)]
+struct PhaseNode {
+    /// Phase name
+    name: String,
+    /// Child phases in the order they were first started
+    children: Vec<usize>,
+    /// Start time if the phase is currently running
+    started: Option<Instant>,
+    /// Accumulated time of all completed runs
+    total: Duration,
+    /// Number of completed runs
+    count: u32,
+    /// Resource usage when the running phase started
+    usage_started: Option<ResourceUsage>,
+    /// Accumulated CPU time of all completed runs
+    cpu_time: Option<Duration>,
+    /// Highest process peak RSS seen at the end of a run
+    peak_rss_bytes: Option<u64>,
+    /// Allocation tracker of the running phase (only with `alloc-track`)
+    alloc_started: Option<StageTracker>,
+    /// Accumulated allocations of all completed runs
+    allocations: Option<StageAllocations>,
+}
+
+/// Timing of a single phase in a phase report
+#[derive(Debug, Clone)]
+pub struct PhaseRecord {
+    /// Phase name
+    pub name: String,
+    /// Full path of the phase (names joined with `PHASE_SEPARATOR`)
+    pub path: String,
+    /// Nesting depth (0 for top-level phases)
+    pub depth: usize,
+    /// Accumulated time of all completed runs
+    pub total: Duration,
+    /// Number of completed runs
+    pub count: u32,
+    /// Accumulated CPU time (only with resource sampling enabled)
+    pub cpu_time: Option<Duration>,
+    /// Process peak RSS at the end of the phase (only with resource sampling enabled)
+    pub peak_rss_bytes: Option<u64>,
+    /// Bytes allocated and highest peak live bytes (only with the `alloc-track` feature)
+    pub allocations: Option<StageAllocations>,
+}
 
 /// Performance timer for measuring execution time
+/// 
+/// Phases started while another phase is running become its sub-phases,
+/// so timings form a tree (`trimming > round 17 > step two`). Starting a
+/// phase that already exists under the same parent accumulates into it.
 pub struct PerformanceTimer {
     /// Start time
     start_time: Instant,
-    /// Checkpoints for measuring different phases
-    checkpoints: HashMap<String, Instant>,
+    /// All phases, indexed by the tree links
+    phases: Vec<PhaseNode>,
+    /// Top-level phases
+    roots: Vec<usize>,
+    /// Currently running phases, innermost last
+    open_phases: Vec<usize>,
+    /// Whether to sample CPU time and peak RSS per phase
+    sample_resources: bool,
     /// Total metrics
     metrics: PerformanceMetrics,
 }
@@ -22,32 +154,207 @@ impl PerformanceTimer {
     pub fn new() -> Self {
         Self {
             start_time: Instant::now(),
-            checkpoints: HashMap::new(),
+            phases: Vec::new(),
+            roots: Vec::new(),
+            open_phases: Vec::new(),
+            sample_resources: false,
             metrics: PerformanceMetrics::new(),
         }
     }
     
+    /// Also record process CPU time and peak RSS for every phase
+    pub fn with_resource_sampling(mut self) -> Self {
+        self.sample_resources = true;
+        self
+    }
+    
     /// Start timing a specific phase
+    /// 
+    /// The phase becomes a sub-phase of the innermost running phase.
     pub fn start_phase(&mut self, phase: &str) {
-        self.checkpoints.insert(phase.to_string(), Instant::now());
+        let parent = self.open_phases.last().copied();
+        let index = self.child_phase(parent, phase);
+        
+        if self.sample_resources {
+            self.phases[index].usage_started = ResourceUsage::sample();
+        }
+        self.phases[index].alloc_started = StageTracker::start();
+        self.phases[index].started = Some(Instant::now());
+        self.open_phases.push(index);
+    }
+    
+    /// Record a completed run of a phase measured elsewhere
+    /// 
+    /// `path` is a full phase path from the top level (`trimming > round 1`),
+    /// independent of which phases are currently running.
+    pub fn record_phase(&mut self, path: &str, duration: Duration) {
+        let mut parent = None;
+        for name in path.split(PHASE_SEPARATOR) {
+            parent = Some(self.child_phase(parent, name));
+        }
+        
+        if let Some(index) = parent {
+            self.phases[index].total += duration;
+            self.phases[index].count += 1;
+        }
+    }
+    
+    /// Find or create the phase with the given name under a parent
+    fn child_phase(&mut self, parent: Option<usize>, phase: &str) -> usize {
+        let siblings = match parent {
+            Some(parent) => &self.phases[parent].children,
+            None => &self.roots,
+        };
+        
+        if let Some(index) = siblings.iter().copied().find(|&i| self.phases[i].name == phase) {
+            return index;
+        }
+        
+        let index = self.phases.len();
+        self.phases.push(PhaseNode {
+            name: phase.to_string(),
+            children: Vec::new(),
+            started: None,
+            total: Duration::ZERO,
+            count: 0,
+            usage_started: None,
+            cpu_time: None,
+            peak_rss_bytes: None,
+            alloc_started: None,
+            allocations: None,
+        });
+        match parent {
+            Some(parent) => self.phases[parent].children.push(index),
+            None => self.roots.push(index),
+        }
+        index
     }
     
     /// End timing a specific phase
+    /// 
+    /// Any sub-phases still running inside it are ended as well.
     pub fn end_phase(&mut self, phase: &str) -> Result<Duration> {
-        if let Some(start_time) = self.checkpoints.get(phase) {
-            let duration = start_time.elapsed();
-            println!("Phase '{}' completed in {:?}", phase, duration);
-            Ok(duration)
-        } else {
-            Err(CuckatooError::InternalError(
+        let position = self.open_phases
+            .iter()
+            .rposition(|&i| self.phases[i].name == phase)
+            .ok_or_else(|| CuckatooError::InternalError(
                 format!("Phase '{}' was not started", phase)
-            ))
+            ))?;
+        
+        let usage = if self.sample_resources { ResourceUsage::sample() } else { None };
+        let mut duration = Duration::ZERO;
+        for index in self.open_phases.drain(position..).rev() {
+            let node = &mut self.phases[index];
+            if let Some(started) = node.started.take() {
+                duration = started.elapsed();
+                node.total += duration;
+                node.count += 1;
+            }
+            if let (Some(start), Some(end)) = (node.usage_started.take(), usage) {
+                let cpu_time = end.cpu_time.saturating_sub(start.cpu_time);
+                node.cpu_time = Some(node.cpu_time.unwrap_or_default() + cpu_time);
+                node.peak_rss_bytes = node.peak_rss_bytes.max(Some(end.peak_rss_bytes));
+            }
+            if let Some(tracker) = node.alloc_started.take() {
+                let run = tracker.finish();
+                let total = node.allocations.get_or_insert_with(StageAllocations::default);
+                total.allocated_bytes += run.allocated_bytes;
+                total.allocations += run.allocations;
+                total.peak_live_bytes = total.peak_live_bytes.max(run.peak_live_bytes);
+            }
+        }
+        
+        if position == 0 {
+            tracing::debug!(phase, ?duration, "Phase completed");
         }
+        Ok(duration)
     }
     
     /// Get duration for a specific phase
+    /// 
+    /// `phase` is either a full path (`trimming > round 1`) or the name of
+    /// a phase anywhere in the tree. Includes the time of a running phase.
     pub fn get_phase_duration(&self, phase: &str) -> Option<Duration> {
-        self.checkpoints.get(phase).map(|start| start.elapsed())
+        self.find_phase(phase).map(|index| {
+            let node = &self.phases[index];
+            node.total + node.started.map_or(Duration::ZERO, |started| started.elapsed())
+        })
+    }
+    
+    /// Get the number of completed runs of a specific phase
+    pub fn get_phase_count(&self, phase: &str) -> Option<u32> {
+        self.find_phase(phase).map(|index| self.phases[index].count)
+    }
+    
+    /// Find a phase by path, falling back to the first phase with that name
+    fn find_phase(&self, phase: &str) -> Option<usize> {
+        let mut siblings = &self.roots;
+        let mut found = None;
+        for name in phase.split(PHASE_SEPARATOR) {
+            found = siblings.iter().copied().find(|&i| self.phases[i].name == name);
+            match found {
+                Some(index) => siblings = &self.phases[index].children,
+                None => break,
+            }
+        }
+        
+        found.or_else(|| self.phases.iter().position(|node| node.name == phase))
+    }
+    
+    /// Get the phase tree as a flat list in depth-first order
+    pub fn phase_report(&self) -> Vec<PhaseRecord> {
+        let mut records = Vec::with_capacity(self.phases.len());
+        let mut stack: Vec<(usize, usize, String)> = self.roots
+            .iter()
+            .rev()
+            .map(|&i| (i, 0, self.phases[i].name.clone()))
+            .collect();
+        
+        while let Some((index, depth, path)) = stack.pop() {
+            let node = &self.phases[index];
+            for &child in node.children.iter().rev() {
+                let child_path = format!("{}{}{}", path, PHASE_SEPARATOR, self.phases[child].name);
+                stack.push((child, depth + 1, child_path));
+            }
+            records.push(PhaseRecord {
+                name: node.name.clone(),
+                path,
+                depth,
+                total: node.total,
+                count: node.count,
+                cpu_time: node.cpu_time,
+                peak_rss_bytes: node.peak_rss_bytes,
+                allocations: node.allocations,
+            });
+        }
+        
+        records
+    }
+    
+    /// Print the phase tree with indented sub-phases
+    pub fn print_phase_report(&self) {
+        println!("\n=== Phase Timings ===");
+        for record in self.phase_report() {
+            let label = format!("{}{}", "  ".repeat(record.depth), record.name);
+            let mut line = format!("{:<32} {}", label, format_duration(record.total));
+            if let Some(cpu_time) = record.cpu_time {
+                line.push_str(&format!(" cpu {}", format_duration(cpu_time)));
+            }
+            if let Some(peak_rss) = record.peak_rss_bytes {
+                line.push_str(&format!(" peak RSS {:.1} MiB", peak_rss as f64 / (1024.0 * 1024.0)));
+            }
+            if let Some(allocations) = record.allocations {
+                line.push_str(&format!(
+                    " alloc {:.1} MiB peak live {:.1} MiB",
+                    allocations.allocated_bytes as f64 / (1024.0 * 1024.0),
+                    allocations.peak_live_bytes as f64 / (1024.0 * 1024.0)
+                ));
+            }
+            if record.count > 1 {
+                line.push_str(&format!(" ({} runs)", record.count));
+            }
+            println!("{}", line);
+        }
     }
     
     /// Get total elapsed time
@@ -93,10 +400,20 @@ impl PerformanceTimer {
         &mut self.metrics
     }
     
+    /// Publish the performance metrics as gauges in the given registry
+    pub fn record_metrics(&self, registry: &MetricsRegistry) {
+        registry.gauge("cuckatoo_searching_seconds").set(self.metrics.searching_time);
+        registry.gauge("cuckatoo_trimming_seconds_last").set(self.metrics.trimming_time);
+        registry.gauge("cuckatoo_graphs_processed").set(self.metrics.graphs_processed as f64);
+        registry.gauge("cuckatoo_mining_rate").set(self.metrics.mining_rate);
+    }
+    
     /// Reset the timer
     pub fn reset(&mut self) {
         self.start_time = Instant::now();
-        self.checkpoints.clear();
+        self.phases.clear();
+        self.roots.clear();
+        self.open_phases.clear();
         self.metrics = PerformanceMetrics::new();
     }
 }
@@ -107,21 +424,130 @@ impl Default for PerformanceTimer {
     }
 }
 
+/// Thread-safe handle to a shared performance timer
+/// 
+/// Clones share the same timer, so parallel workers can each hold a handle
+/// and record phases and metrics concurrently. Phases are measured on the
+/// worker without holding the lock and recorded by full path.
+#[derive(Clone, Default)]
+pub struct SharedPerformanceTimer {
+    /// Shared timer
+    inner: Arc<Mutex<PerformanceTimer>>,
+}
+
+impl SharedPerformanceTimer {
+    /// Create a new shared performance timer
+    pub fn new() -> Self {
+        Self::from_timer(PerformanceTimer::new())
+    }
+    
+    /// Share an existing performance timer
+    pub fn from_timer(timer: PerformanceTimer) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(timer)),
+        }
+    }
+    
+    /// Lock the underlying timer
+    /// 
+    /// A worker that panicked while holding the lock doesn't invalidate the
+    /// recorded timings, so a poisoned lock is recovered.
+    pub fn lock(&self) -> MutexGuard<'_, PerformanceTimer> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    /// Measure a function and record it as a run of the phase at `path`
+    pub fn time_phase<F, R>(&self, path: &str, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let (result, duration) = measure_time(f);
+        self.record_phase(path, duration);
+        result
+    }
+    
+    /// Record a completed run of the phase at `path`
+    pub fn record_phase(&self, path: &str, duration: Duration) {
+        self.lock().record_phase(path, duration);
+    }
+    
+    /// Add to the searching time
+    pub fn add_searching_time(&self, duration: Duration) {
+        self.lock().metrics.searching_time += duration.as_secs_f64();
+    }
+    
+    /// Add to the trimming time
+    pub fn add_trimming_time(&self, duration: Duration) {
+        self.lock().metrics.trimming_time += duration.as_secs_f64();
+    }
+    
+    /// Add to the graphs processed
+    pub fn add_graphs_processed(&self, count: u64) {
+        self.lock().metrics.graphs_processed += count;
+    }
+    
+    /// Add to the solutions found
+    pub fn add_solutions_found(&self, count: u64) {
+        self.lock().metrics.solutions_found += count;
+    }
+    
+    /// Get a snapshot of the performance metrics
+    pub fn metrics(&self) -> PerformanceMetrics {
+        self.lock().metrics.clone()
+    }
+    
+    /// Get a snapshot of the phase tree
+    pub fn phase_report(&self) -> Vec<PhaseRecord> {
+        self.lock().phase_report()
+    }
+    
+    /// Take the timer back if this is the last handle
+    pub fn try_into_inner(self) -> std::result::Result<PerformanceTimer, Self> {
+        Arc::try_unwrap(self.inner)
+            .map(|mutex| mutex.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()))
+            .map_err(|inner| Self { inner })
+    }
+}
+
 /// Benchmark runner for comparing different implementations
 pub struct BenchmarkRunner {
-    /// Benchmark results
-    results: HashMap<String, BenchmarkResult>,
+    /// Benchmark results by name
+    results: BTreeMap<String, BenchmarkResult>,
+    /// Warm-up iterations (None uses a tenth of the measured iterations)
+    warmup_iterations: Option<usize>,
+    /// Fraction of samples dropped from each end before computing statistics
+    outlier_fraction: f64,
 }
 
+/// Fraction of samples dropped from each end when outlier rejection is enabled
+pub const DEFAULT_OUTLIER_FRACTION: f64 = 0.05;
+
 impl BenchmarkRunner {
     /// Create a new benchmark runner
     pub fn new() -> Self {
         Self {
-            results: HashMap::new(),
+            results: BTreeMap::new(),
+            warmup_iterations: None,
+            outlier_fraction: 0.0,
         }
     }
     
+    /// Set the number of warm-up iterations run before measuring
+    pub fn with_warmup(mut self, iterations: usize) -> Self {
+        self.warmup_iterations = Some(iterations);
+        self
+    }
+    
+    /// Drop the fastest and slowest `fraction` of samples (e.g. 0.05 for
+    /// the top and bottom 5%) before computing statistics
+    pub fn with_outlier_rejection(mut self, fraction: f64) -> Self {
+        self.outlier_fraction = fraction.clamp(0.0, 0.49);
+        self
+    }
+    
     /// Run a benchmark
+    /// 
+    /// At least one iteration is always measured.
     pub fn run_benchmark<F, R>(
         &mut self,
         name: &str,
@@ -131,29 +557,37 @@ impl BenchmarkRunner {
     where
         F: Fn() -> R,
     {
-        let mut times = Vec::with_capacity(iterations);
-        let mut total_time = Duration::ZERO;
+        let iterations = iterations.max(1);
+        let mut samples = Vec::with_capacity(iterations);
         
         // Warm up
-        for _ in 0..iterations / 10 {
+        for _ in 0..self.warmup_iterations.unwrap_or(iterations / 10) {
             benchmark_fn();
         }
         
         // Run benchmark
+        let benchmark_start = Instant::now();
         for _ in 0..iterations {
             let start = Instant::now();
             benchmark_fn();
-            let duration = start.elapsed();
-            times.push(duration);
-            total_time += duration;
+            samples.push(BenchmarkSample {
+                offset: start.duration_since(benchmark_start),
+                duration: start.elapsed(),
+            });
         }
         
-        // Calculate statistics
+        // Reject outliers from both ends
+        let mut times: Vec<Duration> = samples.iter().map(|sample| sample.duration).collect();
         times.sort();
+        let rejected = (iterations as f64 * self.outlier_fraction) as usize;
+        let times = &times[rejected..iterations - rejected];
+        
+        // Calculate statistics
+        let total_time: Duration = times.iter().sum();
         let min_time = times[0];
-        let max_time = times[iterations - 1];
-        let avg_time = total_time / iterations as u32;
-        let median_time = times[iterations / 2];
+        let max_time = times[times.len() - 1];
+        let avg_time = total_time / times.len() as u32;
+        let median_time = times[times.len() / 2];
         
         let result = BenchmarkResult {
             name: name.to_string(),
@@ -163,6 +597,8 @@ impl BenchmarkRunner {
             avg_time,
             median_time,
             total_time,
+            outliers_rejected: rejected * 2,
+            samples,
         };
         
         self.results.insert(name.to_string(), result.clone());
@@ -189,6 +625,59 @@ impl BenchmarkRunner {
         })
     }
     
+    /// Rank all benchmarks from fastest to slowest average time
+    pub fn rank(&self) -> Vec<BenchmarkRank> {
+        let mut results: Vec<&BenchmarkResult> = self.results.values().collect();
+        results.sort_by(|a, b| a.avg_time.cmp(&b.avg_time).then_with(|| a.name.cmp(&b.name)));
+        
+        let fastest = results.first().map_or(0.0, |result| result.avg_time.as_secs_f64());
+        let slowest = results.last().map_or(0.0, |result| result.avg_time.as_secs_f64());
+        let ratio = |numerator: f64, denominator: f64| {
+            if denominator > 0.0 {
+                numerator / denominator
+            } else {
+                1.0
+            }
+        };
+        
+        results
+            .into_iter()
+            .enumerate()
+            .map(|(i, result)| {
+                let avg = result.avg_time.as_secs_f64();
+                BenchmarkRank {
+                    rank: i + 1,
+                    result: result.clone(),
+                    relative_to_fastest: ratio(avg, fastest),
+                    speedup_over_slowest: ratio(slowest, avg),
+                }
+            })
+            .collect()
+    }
+    
+    /// Format the ranked benchmarks as a markdown table
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from(
+            "| Rank | Benchmark | Iterations | Average | Median | Min | Max | vs fastest |\n\
+             |-----:|-----------|-----------:|--------:|-------:|----:|----:|-----------:|\n",
+        );
+        for entry in self.rank() {
+            let result = &entry.result;
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {:?} | {:?} | {:?} | {:?} | {:.2}x |\n",
+                entry.rank,
+                result.name,
+                result.iterations,
+                result.avg_time,
+                result.median_time,
+                result.min_time,
+                result.max_time,
+                entry.relative_to_fastest,
+            ));
+        }
+        markdown
+    }
+    
     /// Print all benchmark results
     pub fn print_results(&self) {
         println!("\n=== Benchmark Results ===");
@@ -200,6 +689,9 @@ impl BenchmarkRunner {
             println!("  Min: {:?}", result.min_time);
             println!("  Max: {:?}", result.max_time);
             println!("  Total: {:?}", result.total_time);
+            if result.outliers_rejected > 0 {
+                println!("  Outliers rejected: {}", result.outliers_rejected);
+            }
             println!();
         }
     }
@@ -226,8 +718,34 @@ pub struct BenchmarkResult {
     pub avg_time: Duration,
     /// Median execution time
     pub median_time: Duration,
-    /// Total execution time
+    /// Total execution time (of the samples kept after outlier rejection)
     pub total_time: Duration,
+    /// Number of samples dropped as outliers
+    pub outliers_rejected: usize,
+    /// Every measured iteration in run order
+    pub samples: Vec<BenchmarkSample>,
+}
+
+/// A single measured benchmark iteration
+#[derive(Debug, Clone, Copy)]
+pub struct BenchmarkSample {
+    /// Time the iteration started, relative to the first measured iteration
+    pub offset: Duration,
+    /// Execution time of the iteration
+    pub duration: Duration,
+}
+
+/// Position of a benchmark in a ranking
+#[derive(Debug, Clone)]
+pub struct BenchmarkRank {
+    /// Position in the ranking (1 is fastest)
+    pub rank: usize,
+    /// Benchmark result
+    pub result: BenchmarkResult,
+    /// Average time relative to the fastest benchmark (1.0 for the fastest)
+    pub relative_to_fastest: f64,
+    /// Speedup over the slowest benchmark (1.0 for the slowest)
+    pub speedup_over_slowest: f64,
 }
 
 /// Comparison between two benchmarks
@@ -243,6 +761,42 @@ pub struct BenchmarkComparison {
     pub improvement: String,
 }
 
+/// Timings and survivors of the two lean trimmers on the same graph
+#[derive(Debug)]
+pub struct LeanTrimmerComparison {
+    /// `counter` (`CounterTrimmer`) timed against `bitmap` (`BitmapTrimmer`)
+    pub timing: BenchmarkComparison,
+    /// Edges surviving the bitmap trimmer
+    pub bitmap_survivors: usize,
+    /// Edges surviving the counter trimmer
+    pub counter_survivors: usize,
+}
+
+/// Benchmark `BitmapTrimmer` against `CounterTrimmer`, each trimming the
+/// graph of `keys` for `rounds` rounds `iterations` times
+pub fn compare_lean_trimmers(keys: SipKeys, edge_bits: u32, rounds: u32, iterations: usize) -> Result<LeanTrimmerComparison> {
+    let siphash = SipHash::with_key(keys);
+    let bitmap = RefCell::new(BitmapTrimmer::try_new(edge_bits)?);
+    let counter = RefCell::new(CounterTrimmer::try_new(edge_bits)?);
+    let bitmap_survivors = bitmap.borrow_mut().trim(&siphash, rounds)?.surviving_edges();
+    let counter_survivors = counter.borrow_mut().trim(&siphash, rounds)?.surviving_edges();
+
+    let mut runner = BenchmarkRunner::new().with_warmup(0);
+    runner.run_benchmark("bitmap", iterations, || bitmap.borrow_mut().trim_edges(&siphash, rounds));
+    runner.run_benchmark("counter", iterations, || counter.borrow_mut().trim_edges(&siphash, rounds));
+    let timing = runner.compare("bitmap", "counter").expect("both trimmers were benchmarked");
+    Ok(LeanTrimmerComparison { timing, bitmap_survivors, counter_survivors })
+}
+
+/// Benchmark `HashCycleFinder` against `ArrayCycleFinder`, each searching
+/// `edges` `iterations` times
+pub fn compare_cycle_finders(edges: &[Edge], iterations: usize) -> BenchmarkComparison {
+    let mut runner = BenchmarkRunner::new().with_warmup(0);
+    runner.run_benchmark("hash", iterations, || HashCycleFinder::new().find_cycle(edges));
+    runner.run_benchmark("array", iterations, || ArrayCycleFinder::new().find_cycle(edges));
+    runner.compare("hash", "array").expect("both cycle finders were benchmarked")
+}
+
 /// Utility for measuring execution time of a function
 pub fn measure_time<F, R>(f: F) -> (R, Duration)
 where
@@ -259,12 +813,33 @@ pub fn measure_time_logged<F, R>(name: &str, f: F) -> (R, Duration)
 where
     F: FnOnce() -> R,
 {
-    println!("Starting {}", name);
+    tracing::info!(name, "Starting");
     let (result, duration) = measure_time(f);
-    println!("{} completed in {:?}", name, duration);
+    tracing::info!(name, ?duration, "Completed");
     (result, duration)
 }
 
+/// Utility for measuring wall time, process CPU time and peak RSS
+pub fn measure_time_with_resources<F, R>(f: F) -> (R, ResourceMeasurement)
+where
+    F: FnOnce() -> R,
+{
+    let usage_before = ResourceUsage::sample();
+    let (result, wall_time) = measure_time(f);
+    let usage_after = ResourceUsage::sample();
+    
+    let cpu_time = match (usage_before, usage_after) {
+        (Some(before), Some(after)) => Some(after.cpu_time.saturating_sub(before.cpu_time)),
+        _ => None,
+    };
+    
+    (result, ResourceMeasurement {
+        wall_time,
+        cpu_time,
+        peak_rss_bytes: usage_after.map(|usage| usage.peak_rss_bytes),
+    })
+}
+
 /// Utility for measuring execution time and updating metrics
 pub fn measure_time_with_metrics<F, R>(
     timer: &mut PerformanceTimer,
@@ -300,6 +875,131 @@ mod tests {
         assert!(retrieved_duration <= Duration::from_millis(15)); // Allow some variance
     }
     
+    #[test]
+    fn test_nested_phases() {
+        let mut timer = PerformanceTimer::new();
+        
+        timer.start_phase("trimming");
+        for round in 1..=3 {
+            let round_phase = format!("round {}", round);
+            timer.start_phase(&round_phase);
+            timer.start_phase("step two");
+            timer.end_phase("step two").unwrap();
+            timer.end_phase(&round_phase).unwrap();
+        }
+        timer.end_phase("trimming").unwrap();
+        
+        assert!(timer.get_phase_duration("trimming > round 2 > step two").is_some());
+        assert!(timer.get_phase_duration("round 3").is_some());
+        assert!(timer.get_phase_duration("trimming > round 4").is_none());
+        assert_eq!(timer.get_phase_count("trimming > round 1 > step two"), Some(1));
+        
+        let report = timer.phase_report();
+        assert_eq!(report.len(), 7);
+        assert_eq!(report[0].path, "trimming");
+        assert_eq!(report[1].path, "trimming > round 1");
+        assert_eq!(report[2].path, "trimming > round 1 > step two");
+        assert_eq!(report[2].depth, 2);
+    }
+    
+    #[test]
+    fn test_repeated_phase_accumulates() {
+        let mut timer = PerformanceTimer::new();
+        
+        timer.start_phase("search");
+        for _ in 0..3 {
+            timer.start_phase("step");
+            thread::sleep(Duration::from_millis(1));
+            timer.end_phase("step").unwrap();
+        }
+        timer.end_phase("search").unwrap();
+        
+        assert_eq!(timer.get_phase_count("search > step"), Some(3));
+        assert!(timer.get_phase_duration("search > step").unwrap() >= Duration::from_millis(3));
+    }
+    
+    #[test]
+    fn test_end_phase_closes_sub_phases() {
+        let mut timer = PerformanceTimer::new();
+        
+        timer.start_phase("outer");
+        timer.start_phase("inner");
+        timer.end_phase("outer").unwrap();
+        
+        assert_eq!(timer.get_phase_count("outer > inner"), Some(1));
+        assert!(timer.end_phase("inner").is_err());
+    }
+    
+    #[test]
+    fn test_shared_timer_concurrent_recording() {
+        let timer = SharedPerformanceTimer::new();
+        
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                let timer = timer.clone();
+                scope.spawn(move || {
+                    for _ in 0..25 {
+                        timer.time_phase("trimming > worker", thread::yield_now);
+                        timer.add_graphs_processed(1);
+                    }
+                });
+            }
+        });
+        
+        assert_eq!(timer.metrics().graphs_processed, 100);
+        let timer = timer.try_into_inner().ok().unwrap();
+        assert_eq!(timer.get_phase_count("trimming > worker"), Some(100));
+        assert_eq!(timer.get_phase_count("trimming"), Some(0));
+    }
+    
+    #[test]
+    fn test_timer_record_metrics() {
+        let mut timer = PerformanceTimer::new();
+        timer.set_graphs_processed(3);
+        timer.set_trimming_time(Duration::from_secs(2));
+        
+        let registry = MetricsRegistry::new();
+        timer.record_metrics(&registry);
+        
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.gauge("cuckatoo_graphs_processed"), Some(3.0));
+        assert_eq!(snapshot.gauge("cuckatoo_trimming_seconds_last"), Some(2.0));
+    }
+    
+    #[test]
+    fn test_measure_time_with_resources() {
+        let (sum, measurement) = measure_time_with_resources(|| {
+            (0..2_000_000u64).fold(0u64, |acc, x| acc.wrapping_add(x * x))
+        });
+        
+        assert!(sum > 0);
+        if cfg!(all(unix, feature = "resource-usage")) {
+            assert!(measurement.cpu_time.is_some());
+            assert!(measurement.peak_rss_bytes.unwrap() > 0);
+        }
+    }
+    
+    #[test]
+    fn test_phase_resource_sampling() {
+        let mut timer = PerformanceTimer::new().with_resource_sampling();
+        
+        timer.start_phase("outer");
+        timer.start_phase("inner");
+        timer.end_phase("inner").unwrap();
+        timer.end_phase("outer").unwrap();
+        
+        let report = timer.phase_report();
+        assert_eq!(report.len(), 2);
+        if cfg!(all(unix, feature = "resource-usage")) {
+            assert!(report.iter().all(|r| r.cpu_time.is_some() && r.peak_rss_bytes.is_some()));
+        }
+        
+        let mut plain = PerformanceTimer::new();
+        plain.start_phase("phase");
+        plain.end_phase("phase").unwrap();
+        assert!(plain.phase_report()[0].cpu_time.is_none());
+    }
+    
     #[test]
     fn test_benchmark_runner() {
         let mut runner = BenchmarkRunner::new();
@@ -313,6 +1013,61 @@ mod tests {
         assert!(result.avg_time >= Duration::from_millis(1));
     }
     
+    #[test]
+    fn test_benchmark_runner_few_iterations() {
+        let mut runner = BenchmarkRunner::new();
+        
+        // Fewer than 10 iterations (and zero) must not panic
+        let result = runner.run_benchmark("tiny", 3, || 1 + 1);
+        assert_eq!(result.iterations, 3);
+        assert_eq!(result.samples.len(), 3);
+        
+        let result = runner.run_benchmark("empty", 0, || 1 + 1);
+        assert_eq!(result.iterations, 1);
+    }
+    
+    #[test]
+    fn test_benchmark_runner_outlier_rejection() {
+        let mut runner = BenchmarkRunner::new()
+            .with_warmup(2)
+            .with_outlier_rejection(DEFAULT_OUTLIER_FRACTION);
+        
+        let result = runner.run_benchmark("test", 40, thread::yield_now);
+        assert_eq!(result.outliers_rejected, 4);
+        assert_eq!(result.samples.len(), 40);
+        assert!(result.samples.windows(2).all(|pair| pair[0].offset <= pair[1].offset));
+        assert!(result.min_time <= result.median_time && result.median_time <= result.max_time);
+    }
+    
+    #[test]
+    fn test_benchmark_ranking() {
+        let mut runner = BenchmarkRunner::new().with_warmup(0);
+        runner.run_benchmark("slow", 3, || thread::sleep(Duration::from_millis(4)));
+        runner.run_benchmark("fast", 3, || thread::sleep(Duration::from_millis(1)));
+        
+        let ranking = runner.rank();
+        assert_eq!(ranking.len(), 2);
+        assert_eq!(ranking[0].result.name, "fast");
+        assert_eq!(ranking[0].rank, 1);
+        assert_eq!(ranking[0].relative_to_fastest, 1.0);
+        assert!(ranking[0].speedup_over_slowest > 1.0);
+        assert!(ranking[1].relative_to_fastest > 1.0);
+        
+        let markdown = runner.to_markdown();
+        let lines: Vec<&str> = markdown.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with("| 1 | fast |"));
+        assert!(lines[3].starts_with("| 2 | slow |"));
+    }
+    
+    #[test]
+    fn test_compare_lean_trimmers() {
+        let comparison = compare_lean_trimmers(SipKeys::from_header(b"lean trimmers", 1), 10, 4, 2).unwrap();
+        assert_eq!((comparison.timing.baseline.name.as_str(), comparison.timing.comparison.name.as_str()), ("bitmap", "counter"));
+        assert_eq!(comparison.timing.comparison.iterations, 2);
+        assert!(comparison.counter_survivors >= comparison.bitmap_survivors);
+    }
+    
     #[test]
     fn test_measure_time() {
         let (_, duration) = measure_time(|| {
@@ -0,0 +1,99 @@
+//! Structured trimming results
+//!
+//! `trim_edges` hands back the surviving edges and nothing else; what the
+//! trim did along the way was only visible in stdout. A `TrimOutput` keeps
+//! the survivors as edge indices together with the rounds run, the edges
+//! left after each of them and the time taken, so miners, pools and
+//! benchmarks can use the numbers directly.
+
+use crate::{Edge, Node, SipHasher};
+use std::time::Duration;
+
+/// Edge indices hashed per `hash_nonces` call by `TrimOutput::edges`
+const EDGES_PER_BATCH: usize = 1024;
+
+/// Survivors and statistics of a trim
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrimOutput {
+    /// Indices of the surviving edges, in increasing order
+    pub surviving_edge_indices: Vec<u64>,
+    /// Number of rounds the trim ran
+    pub rounds_run: u32,
+    /// Surviving edges after each round
+    pub per_round_survivors: Vec<u64>,
+    /// Time taken by the trim
+    pub elapsed: Duration,
+}
+
+impl TrimOutput {
+    /// Number of surviving edges
+    pub fn surviving_edges(&self) -> usize {
+        self.surviving_edge_indices.len()
+    }
+
+    /// Hash the surviving edges of the graph of `hasher`, in index order
+    /// like `trim_edges` returns them
+    pub fn edges<H: SipHasher>(&self, hasher: &H, edge_bits: u32) -> Vec<Edge> {
+        let mut edges = Vec::with_capacity(self.surviving_edge_indices.len());
+        let mut nonces = Vec::with_capacity(EDGES_PER_BATCH * 2);
+        let mut nodes = [0u64; EDGES_PER_BATCH * 2];
+        for indices in self.surviving_edge_indices.chunks(EDGES_PER_BATCH) {
+            // Edge i uses nonces 2i and 2i + 1
+            nonces.clear();
+            nonces.extend(indices.iter().flat_map(|&index| [index * 2, index * 2 + 1]));
+            hasher.hash_nonces(&nonces, edge_bits, &mut nodes);
+            edges.extend(nodes[..nonces.len()].chunks_exact(2).map(|pair| Edge::new(Node::new(pair[0]), Node::new(pair[1]))));
+        }
+        edges
+    }
+
+    /// Format the statistics (not the survivors) as a JSON object
+    pub fn to_json(&self) -> String {
+        let curve: Vec<String> = self.per_round_survivors.iter().map(u64::to_string).collect();
+        format!(
+            "{{\"surviving_edges\":{},\"rounds_run\":{},\"per_round_survivors\":[{}],\"elapsed\":{:.6}}}",
+            self.surviving_edges(), self.rounds_run, curve.join(","), self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Indices of the edges set in an edges bitmap
+pub(crate) fn edge_indices(edges_bitmap: &[u64]) -> Vec<u64> {
+    let mut indices = Vec::with_capacity(edges_bitmap.iter().map(|word| word.count_ones() as usize).sum());
+    for (word_index, &word) in edges_bitmap.iter().enumerate() {
+        let mut unit = word;
+        while unit != 0 {
+            indices.push(word_index as u64 * 64 + u64::from(unit.trailing_zeros()));
+            unit &= unit - 1;
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BitmapTrimmer, CounterTrimmer, ExactTrimmer, SipHash, SipKeys};
+
+    #[test]
+    fn test_trim_outputs_match_trimmed_edges() {
+        let siphash = SipHash::with_key(SipKeys::from_header(b"trim output", 3));
+        let edges = BitmapTrimmer::new(12).trim_edges(&siphash, 8).unwrap();
+
+        let mut trimmer = BitmapTrimmer::new(12);
+        let output = trimmer.trim(&siphash, 8).unwrap();
+        assert_eq!(output.edges(&siphash, 12), edges);
+        assert_eq!(output.rounds_run, 8);
+        assert_eq!(output.per_round_survivors.len(), 8);
+        assert_eq!(output.per_round_survivors.last(), Some(&(edges.len() as u64)));
+        assert!(output.surviving_edge_indices.windows(2).all(|pair| pair[0] < pair[1]));
+        // Counting for the output doesn't leave a survivor curve behind
+        assert!(trimmer.survivor_curve().is_empty());
+
+        let exact = ExactTrimmer::new(12).trim(&siphash, 8).unwrap();
+        assert_eq!((exact.surviving_edge_indices, exact.per_round_survivors), (output.surviving_edge_indices, output.per_round_survivors));
+
+        let counter = CounterTrimmer::new(12).trim(&siphash, 8).unwrap();
+        assert_eq!(counter.edges(&siphash, 12), CounterTrimmer::new(12).trim_edges(&siphash, 8).unwrap());
+        assert!(counter.to_json().starts_with(&format!("{{\"surviving_edges\":{},\"rounds_run\":8,", counter.surviving_edges())));
+    }
+}
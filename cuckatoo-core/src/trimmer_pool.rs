@@ -0,0 +1,121 @@
+//! Pool of reusable trimmers
+//!
+//! A trimmer's bitmaps take hundreds of megabytes at mining sizes, and
+//! allocating them (and faulting their pages in) for every job costs more
+//! than rebinding a trimmer that already has them. A `TrimmerPool` keeps
+//! the trimmers of finished work by graph size and hands them out again,
+//! so consecutive jobs and nonces reuse the same memory.
+
+use crate::{BitmapTrimmer, Config, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Shared pool of idle trimmers, keyed by edge bits
+///
+/// Clones share the same pool.
+#[derive(Clone, Default)]
+pub struct TrimmerPool {
+    inner: Arc<Mutex<PoolInner>>,
+}
+
+#[derive(Default)]
+struct PoolInner {
+    /// Idle trimmers by edge bits
+    idle: HashMap<u32, Vec<BitmapTrimmer>>,
+    /// Trimmers allocated because none were idle
+    allocations: u64,
+    /// Trimmers handed out again
+    reuses: u64,
+}
+
+impl TrimmerPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock the pool, recovering from a poisoned lock
+    fn lock(&self) -> MutexGuard<'_, PoolInner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Take an idle trimmer of `edge_bits`, allocating one if there is none
+    ///
+    /// Reused trimmers come back with default options, like new ones.
+    pub fn take(&self, edge_bits: u32) -> Result<BitmapTrimmer> {
+        self.take_or_allocate(edge_bits, || BitmapTrimmer::try_new(edge_bits))
+    }
+
+    /// Take an idle trimmer of the configuration's edge bits, allocating
+    /// one within its bitmap memory budget if there is none
+    ///
+    /// Idle trimmers are handed out whether or not their bitmaps are
+    /// disk-backed, so a pool should serve configurations with the same
+    /// budget.
+    pub fn take_for_config(&self, config: &Config) -> Result<BitmapTrimmer> {
+        self.take_or_allocate(config.edge_bits, || BitmapTrimmer::for_config(config))
+    }
+
+    /// Take an idle trimmer of `edge_bits`, or the one `allocate` creates
+    fn take_or_allocate(&self, edge_bits: u32, allocate: impl FnOnce() -> Result<BitmapTrimmer>) -> Result<BitmapTrimmer> {
+        let mut inner = self.lock();
+        if let Some(trimmer) = inner.idle.get_mut(&edge_bits).and_then(Vec::pop) {
+            inner.reuses += 1;
+            return Ok(trimmer);
+        }
+        inner.allocations += 1;
+        drop(inner);
+        allocate()
+    }
+
+    /// Return a trimmer for later `take`s, keeping only its bitmaps
+    pub fn put(&self, trimmer: BitmapTrimmer) {
+        let trimmer = trimmer.recycle();
+        self.lock().idle.entry(trimmer.edge_bits()).or_default().push(trimmer);
+    }
+
+    /// Number of idle trimmers of `edge_bits`
+    pub fn idle(&self, edge_bits: u32) -> usize {
+        self.lock().idle.get(&edge_bits).map_or(0, Vec::len)
+    }
+
+    /// Number of trimmers allocated by `take`
+    pub fn allocations(&self) -> u64 {
+        self.lock().allocations
+    }
+
+    /// Number of trimmers `take` handed out again
+    pub fn reuses(&self) -> u64 {
+        self.lock().reuses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SipHash, SipHasher, SipKeys};
+
+    #[test]
+    fn test_trimmers_are_reused() {
+        let pool = TrimmerPool::new();
+        let siphash = SipHash::with_key(SipKeys::from_header(b"pool", 1));
+        let expected = BitmapTrimmer::new(10).trim_edges(&siphash, 4).unwrap();
+
+        let mut trimmer = pool.take(10).unwrap().with_survivor_curve();
+        trimmer.trim_edges(&siphash, 4).unwrap();
+        pool.put(trimmer);
+        assert_eq!(pool.idle(10), 1);
+        assert_eq!(pool.idle(11), 0);
+
+        // The recycled trimmer keeps its bitmaps but none of its options
+        let mut trimmer = pool.take(10).unwrap();
+        assert!(trimmer.survivor_curve().is_empty());
+        trimmer.reset_for_keys(siphash.keys());
+        assert_eq!(trimmer.resume_trim_edges(&siphash, 4).unwrap(), expected);
+        assert_eq!(trimmer.trim_edges(&siphash, 4).unwrap(), expected);
+        assert_eq!((pool.allocations(), pool.reuses()), (1, 1));
+
+        pool.take(11).unwrap();
+        assert_eq!(pool.allocations(), 2);
+    }
+}
@@ -3,7 +3,9 @@
 //! This implements the lean trimming algorithm using bitmap-based approach
 //! as specified in the C++ reference miner.
 
-use crate::{Edge, Node, Result, PerformanceMetrics};
+use crate::{format_seconds, CancelToken, EarlyExit, Edge, Node, Result, PerformanceMetrics};
+use crate::cancel::check_cancelled;
+use crate::progress::{report_round, ProgressObserver};
 use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 
@@ -14,6 +16,14 @@ use std::time::Instant;
 pub struct LeanTrimmer {
     /// Number of trimming rounds
     trimming_rounds: u32,
+    /// Conditions ending trims before `trimming_rounds`
+    early_exit: EarlyExit,
+    /// Rounds the last trim ran
+    rounds_run: u32,
+    /// Receiver of per-round progress
+    progress: Option<Box<dyn ProgressObserver>>,
+    /// Token stopping trims between rounds
+    cancel: Option<CancelToken>,
     /// Performance metrics
     metrics: PerformanceMetrics,
 }
@@ -23,6 +33,10 @@ impl LeanTrimmer {
     pub fn new(_edge_bits: u32) -> Self {
         Self {
             trimming_rounds: 90, // Default from C++ miner
+            early_exit: EarlyExit::default(),
+            rounds_run: 0,
+            progress: None,
+            cancel: None,
             metrics: PerformanceMetrics::new(),
         }
     }
@@ -31,10 +45,42 @@ impl LeanTrimmer {
     pub fn with_rounds(_edge_bits: u32, trimming_rounds: u32) -> Self {
         Self {
             trimming_rounds,
+            early_exit: EarlyExit::default(),
+            rounds_run: 0,
+            progress: None,
+            cancel: None,
             metrics: PerformanceMetrics::new(),
         }
     }
     
+    /// End trims early once any of the `early_exit` conditions is met
+    /// 
+    /// Trimming always stops once a round removes no edges, so only the
+    /// surviving-edge threshold changes anything here.
+    pub fn with_early_exit(mut self, early_exit: EarlyExit) -> Self {
+        self.early_exit = early_exit;
+        self
+    }
+    
+    /// Number of rounds the last trim ran, not counting a final round
+    /// that found nothing to remove
+    pub fn rounds_run(&self) -> u32 {
+        self.rounds_run
+    }
+    
+    /// Report every round that removed edges to `observer`
+    pub fn with_progress(mut self, observer: impl ProgressObserver + 'static) -> Self {
+        self.progress = Some(Box::new(observer));
+        self
+    }
+    
+    /// Stop trimming with `Cancelled` at the next round once `token` is
+    /// cancelled
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+    
     /// Trim edges using lean trimming algorithm
     /// 
     /// This implements the same algorithm as the C++ reference miner:
@@ -53,7 +99,10 @@ impl LeanTrimmer {
         let mut node_bitmap = NodeBitmap::new(edges);
         
         // Perform trimming rounds
+        self.rounds_run = 0;
         for round in 0..rounds {
+            crate::profile_scope!(crate::profiling::TRIM_ROUND, round = round + 1);
+            check_cancelled(self.cancel.as_ref())?;
             let round_start = Instant::now();
             
             // Find nodes with degree 1 (leaf nodes)
@@ -73,7 +122,14 @@ impl LeanTrimmer {
             }
             
             let round_time = round_start.elapsed().as_secs_f64();
-            println!("Round {}: removed {} edges in {:.6}s", round + 1, edges_removed, round_time);
+            tracing::debug!(round = round + 1, edges_removed, round_time = %format_seconds(round_time), "Trimmed round");
+            
+            self.rounds_run = round + 1;
+            let survivors = edge_bitmap.active_count() as u64;
+            report_round(&mut self.progress, self.rounds_run, survivors, start_time)?;
+            if self.early_exit.should_stop(self.rounds_run, survivors + edges_removed as u64, survivors) {
+                break;
+            }
         }
         
         // Extract surviving edges
@@ -82,9 +138,14 @@ impl LeanTrimmer {
         let trimming_time = start_time.elapsed().as_secs_f64();
         self.metrics.trimming_time = trimming_time;
         self.metrics.graphs_processed = 1; // One graph processed
+        self.metrics.trimming_rounds = self.rounds_run as u64;
         
-        println!("Lean trimming completed in {:.6}s", trimming_time);
-        println!("Surviving edges: {}/{}", surviving_edges.len(), edges.len());
+        tracing::info!(
+            trimming_time = %format_seconds(trimming_time),
+            surviving_edges = surviving_edges.len(),
+            edges = edges.len(),
+            "Lean trimming completed"
+        );
         
         Ok(surviving_edges)
     }
@@ -187,8 +248,7 @@ impl EdgeBitmap {
         self.active_edges.iter().copied().collect()
     }
     
-    /// Get number of active edges (for testing)
-    #[allow(dead_code)]
+    /// Get number of active edges
     fn active_count(&self) -> usize {
         self.active_edges.len()
     }
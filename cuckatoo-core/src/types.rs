@@ -6,9 +6,45 @@ use std::fmt;
 /// Solution size (42-cycle)
 pub const SOLUTION_SIZE: usize = 42;
 
+/// Longest cycle the cycle finders can search for (their walks count
+/// edges in a `u8`)
+pub const MAX_CYCLE_LENGTH: usize = 254;
+
+/// Check a cycle finder's cycle length: cycles alternate between the two
+/// partitions, so they have an even number of edges, at least 4
+#[cfg(not(feature = "verify-only"))]
+pub(crate) fn assert_cycle_length(length: usize) {
+    assert!(
+        (4..=MAX_CYCLE_LENGTH).contains(&length) && length.is_multiple_of(2),
+        "Cycle length must be even and between 4 and {}, got {}", MAX_CYCLE_LENGTH, length
+    );
+}
+
 /// Edge number of components (C++ uses 3: [edge_index, node_u, node_v])
 pub const EDGE_NUMBER_OF_COMPONENTS: usize = 3;
 
+/// Edge bits of consensus graph sizes
+pub const CONSENSUS_EDGE_BITS: std::ops::RangeInclusive<u32> = 10..=32;
+
+/// Edge bits the pipeline works with, from tiny test graphs to oversized
+/// research graphs with 64-bit nodes; whether a graph fits in memory is
+/// left to its allocations
+pub const SUPPORTED_EDGE_BITS: std::ops::RangeInclusive<u32> = 4..=63;
+
+/// Largest edge bits whose edge lists can be addressed on this target
+/// (graphs above 2^30 edges need a 64-bit address space)
+pub const MAX_ADDRESSABLE_EDGE_BITS: u32 = if usize::BITS >= 64 { 32 } else { 30 };
+
+/// Convert a 64-bit count or size to `usize`
+/// 
+/// Returns a memory error naming `what` if the value can't be addressed
+/// on this target (only possible on 32-bit targets).
+pub fn checked_usize(value: u64, what: &str) -> Result<usize, crate::CuckatooError> {
+    usize::try_from(value).map_err(|_| crate::CuckatooError::MemoryError(format!(
+        "{} of {} cannot be addressed on a {}-bit target", what, value, usize::BITS
+    )))
+}
+
 /// Edge in the Cuckatoo graph
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Edge {
@@ -127,6 +163,81 @@ impl Header {
     }
 }
 
+/// A 42-cycle proof: the sorted edge indices (nonces) of the cycle's edges
+/// in a graph of `edge_bits`
+///
+/// On chain a proof is stored bit-packed, `edge_bits` bits per nonce
+/// (least significant bit first); `to_bytes` and `to_hex` produce that
+/// form and `from_bytes` and `from_hex` read it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Proof {
+    /// Edge bits of the graph
+    pub edge_bits: u32,
+    /// Edge indices of the cycle
+    pub nonces: [u64; SOLUTION_SIZE],
+}
+
+impl Proof {
+    /// Create a proof, checking there are `SOLUTION_SIZE` nonces that fit
+    /// in `edge_bits` bits
+    pub fn new(edge_bits: u32, nonces: &[u64]) -> Result<Self, crate::CuckatooError> {
+        if edge_bits == 0 || edge_bits >= u64::BITS {
+            return Err(crate::CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let nonces: [u64; SOLUTION_SIZE] = nonces.try_into().map_err(|_| crate::CuckatooError::VerificationError(format!(
+            "Proof has {} nonces, expected {}", nonces.len(), SOLUTION_SIZE
+        )))?;
+        if let Some(nonce) = nonces.iter().find(|&&nonce| nonce >> edge_bits != 0) {
+            return Err(crate::CuckatooError::VerificationError(format!("Nonce {} is too big", nonce)));
+        }
+        Ok(Self { edge_bits, nonces })
+    }
+
+    /// Number of bytes of the packed proof
+    pub fn packed_size(edge_bits: u32) -> usize {
+        (SOLUTION_SIZE * edge_bits as usize).div_ceil(8)
+    }
+
+    /// Pack the nonces, `edge_bits` bits each
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::endian::pack_proof(&self.nonces, self.edge_bits)
+    }
+
+    /// Read a proof packed by `to_bytes`, which must be exactly
+    /// `packed_size(edge_bits)` bytes
+    pub fn from_bytes(edge_bits: u32, bytes: &[u8]) -> Result<Self, crate::CuckatooError> {
+        if edge_bits == 0 || edge_bits >= u64::BITS {
+            return Err(crate::CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        if bytes.len() != Self::packed_size(edge_bits) {
+            return Err(crate::CuckatooError::VerificationError(format!(
+                "Packed proof has {} bytes, expected {}", bytes.len(), Self::packed_size(edge_bits)
+            )));
+        }
+        Self::new(edge_bits, &crate::endian::unpack_proof(bytes, edge_bits, SOLUTION_SIZE)?)
+    }
+
+    /// Packed proof as lowercase hex
+    pub fn to_hex(&self) -> String {
+        self.to_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Read a proof from the hex of `to_hex`
+    pub fn from_hex(edge_bits: u32, hex: &str) -> Result<Self, crate::CuckatooError> {
+        let hex = hex.trim();
+        let invalid = || crate::CuckatooError::VerificationError(format!("Invalid proof hex: {}", hex));
+        if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        let bytes = hex
+            .as_bytes()
+            .chunks_exact(2)
+            .map(|digits| u8::from_str_radix(std::str::from_utf8(digits).expect("ASCII digits"), 16).map_err(|_| invalid()))
+            .collect::<Result<Vec<u8>, _>>()?;
+        Self::from_bytes(edge_bits, &bytes)
+    }
+}
+
 /// Configuration for Cuckatoo mining
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -138,6 +249,53 @@ pub struct Config {
     pub mode: TrimmingMode,
     /// Whether to run in tuning mode (offline)
     pub tuning: bool,
+    /// Minimum per-round edge reduction for adaptive trimming
+    /// (None uses the fixed `trimming_rounds`, otherwise it is the round cap)
+    pub adaptive_threshold: Option<f64>,
+    /// Consecutive rounds below `adaptive_threshold` that stop adaptive
+    /// trimming
+    pub plateau_rounds: u32,
+    /// Trim until at most this multiple of the cycle length survives
+    /// (`trimming_rounds` is the round cap)
+    pub target_survivors: Option<u64>,
+    /// Disable time- and scheduling-dependent behavior so runs with the same
+    /// seed give identical results (adaptive trimming is turned off)
+    pub deterministic: bool,
+    /// Which edge bits `validate` accepts
+    pub validation_policy: ValidationPolicy,
+    /// How the SipHash keys of a header and nonce are derived
+    pub key_schedule: crate::KeySchedule,
+    /// How the nonces to mine are chosen
+    pub nonce_schedule: crate::NonceSchedule,
+    /// Maximum memory in bytes a run may use (None for no limit)
+    pub max_memory: Option<u64>,
+    /// Bytes of RAM the lean trimmer's bitmaps may take before they are
+    /// backed by temp files (None keeps them in RAM)
+    pub bitmap_memory: Option<u64>,
+    /// Directory of the disk-backed bitmaps' temp files (None uses the
+    /// system temp directory)
+    pub spill_dir: Option<std::path::PathBuf>,
+    /// Put the lean trimmer's bitmaps on 2 MiB huge pages where the system
+    /// has them (ordinary pages otherwise)
+    pub use_huge_pages: bool,
+    /// Which solutions of a graph are submitted
+    pub submission_policy: crate::SubmissionPolicy,
+    /// How long after a job change solutions of the previous job are still
+    /// submitted (zero discards them at once)
+    pub stale_share_grace: std::time::Duration,
+    /// Bytes of the endpoint cache in front of the trimming hasher
+    /// (None hashes every endpoint)
+    pub endpoint_cache: Option<u64>,
+    /// Bytes of the buffer `ExactTrimmer` keeps the nodes hashed by one step
+    /// in for the next (None hashes them again)
+    pub node_buffer: Option<u64>,
+    /// Threads generating edges and trimming (1 runs both on the calling
+    /// thread)
+    pub threads: usize,
+    /// Placement of the trimming threads and their bitmaps on NUMA hosts
+    pub numa_policy: NumaPolicy,
+    /// Algorithm searching the trimmed graph for cycles
+    pub solver_strategy: SolverStrategy,
 }
 
 impl Config {
@@ -148,27 +306,72 @@ impl Config {
             trimming_rounds: 90, // Default from C++ Makefile
             mode: TrimmingMode::Lean,
             tuning: false,
+            adaptive_threshold: None,
+            plateau_rounds: 1,
+            target_survivors: None,
+            deterministic: false,
+            validation_policy: ValidationPolicy::Consensus,
+            key_schedule: crate::KeySchedule::Grin,
+            nonce_schedule: crate::NonceSchedule::Sequential,
+            max_memory: None,
+            bitmap_memory: None,
+            spill_dir: None,
+            use_huge_pages: false,
+            submission_policy: crate::SubmissionPolicy::First,
+            stale_share_grace: std::time::Duration::ZERO,
+            endpoint_cache: None,
+            node_buffer: None,
+            threads: 1,
+            numa_policy: NumaPolicy::Off,
+            solver_strategy: SolverStrategy::Cpp,
         }
     }
     
     /// Create a new configuration with C++ Makefile defaults
     pub fn new_cuckatoo31() -> Self {
-        Self {
-            edge_bits: 31, // From C++ Makefile: EDGE_BITS = 31
-            trimming_rounds: 90, // From C++ Makefile: TRIMMING_ROUNDS = 90
-            mode: TrimmingMode::Lean,
-            tuning: false,
-        }
+        Self::new(31) // From C++ Makefile: EDGE_BITS = 31
     }
     
     /// Validate the configuration
+    /// 
+    /// Edge bits must be in the range of the validation policy, and the
+    /// graph must be addressable on this target.
     pub fn validate(&self) -> Result<(), crate::CuckatooError> {
-        if self.edge_bits < 10 || self.edge_bits > 32 {
+        if !self.validation_policy.edge_bits_range().contains(&self.edge_bits) {
             return Err(crate::CuckatooError::InvalidEdgeBits(self.edge_bits));
         }
+        if self.validation_policy == ValidationPolicy::Consensus && self.edge_bits > MAX_ADDRESSABLE_EDGE_BITS {
+            return Err(crate::CuckatooError::MemoryError(format!(
+                "EDGE_BITS={} graphs cannot be addressed on a {}-bit target (maximum is {})",
+                self.edge_bits, usize::BITS, MAX_ADDRESSABLE_EDGE_BITS
+            )));
+        }
+        if self.threads == 0 {
+            return Err(crate::CuckatooError::InternalError("threads must be at least 1".to_string()));
+        }
         Ok(())
     }
     
+    /// Build the round controller for adaptive or target-survivor trimming
+    /// 
+    /// Returns None when a fixed number of trimming rounds is configured,
+    /// or in deterministic mode.
+    #[cfg(not(feature = "verify-only"))]
+    pub fn round_controller(&self) -> Option<crate::AdaptiveRoundController> {
+        if self.deterministic || (self.adaptive_threshold.is_none() && self.target_survivors.is_none()) {
+            return None;
+        }
+        
+        let mut controller = crate::AdaptiveRoundController::new(
+            self.adaptive_threshold.unwrap_or(0.0),
+            self.trimming_rounds,
+        ).plateau_rounds(self.plateau_rounds);
+        if let Some(multiple) = self.target_survivors {
+            controller = controller.target_survivors(multiple * SOLUTION_SIZE as u64);
+        }
+        Some(controller)
+    }
+    
     /// Calculate the number of edges based on edge bits
     pub fn edge_count(&self) -> u64 {
         1 << self.edge_bits
@@ -180,6 +383,122 @@ impl Config {
     }
 }
 
+/// Edge bits accepted by `Config::validate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationPolicy {
+    /// Consensus graph sizes (10-32 edge bits)
+    #[default]
+    Consensus,
+    /// Also allow tiny (4-9) and oversized (33-63) graphs for tests and
+    /// research; the allocations then decide whether a graph fits in memory
+    Research,
+}
+
+impl ValidationPolicy {
+    /// Range of edge bits accepted by the policy
+    pub fn edge_bits_range(&self) -> std::ops::RangeInclusive<u32> {
+        match self {
+            ValidationPolicy::Consensus => CONSENSUS_EDGE_BITS,
+            ValidationPolicy::Research => SUPPORTED_EDGE_BITS,
+        }
+    }
+}
+
+impl fmt::Display for ValidationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationPolicy::Consensus => write!(f, "consensus"),
+            ValidationPolicy::Research => write!(f, "research"),
+        }
+    }
+}
+
+impl std::str::FromStr for ValidationPolicy {
+    type Err = crate::CuckatooError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "consensus" => Ok(ValidationPolicy::Consensus),
+            "research" => Ok(ValidationPolicy::Research),
+            _ => Err(crate::CuckatooError::InternalError(
+                format!("Unknown validation policy: {}", s)
+            )),
+        }
+    }
+}
+
+/// Placement of the parallel trimmer's workers and bitmaps on NUMA hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumaPolicy {
+    /// Leave threads and pages where the OS puts them
+    #[default]
+    Off,
+    /// Pin each worker to a node's CPUs and have it touch its own range of
+    /// the bitmaps first, so the pages it walks are local (Linux only; a
+    /// single-node host is left alone)
+    Local,
+}
+
+impl fmt::Display for NumaPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumaPolicy::Off => write!(f, "off"),
+            NumaPolicy::Local => write!(f, "local"),
+        }
+    }
+}
+
+impl std::str::FromStr for NumaPolicy {
+    type Err = crate::CuckatooError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(NumaPolicy::Off),
+            "local" => Ok(NumaPolicy::Local),
+            _ => Err(crate::CuckatooError::InternalError(
+                format!("Unknown NUMA policy: {}", s)
+            )),
+        }
+    }
+}
+
+/// Algorithm `CycleVerifier` searches the trimmed graph with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SolverStrategy {
+    /// The C++ reference search, walking connection lists from every edge
+    /// (`ArrayCycleFinder`)
+    #[default]
+    Cpp,
+    /// Tromp's path-following solver, adding edges to a forest and
+    /// measuring the cycles they close (`PathSolver`; single-threaded).
+    /// It misses the cycles whose edges close other cycles over node
+    /// pairs first, so it finds fewer solutions than `Cpp`
+    Path,
+}
+
+impl fmt::Display for SolverStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverStrategy::Cpp => write!(f, "cpp"),
+            SolverStrategy::Path => write!(f, "path"),
+        }
+    }
+}
+
+impl std::str::FromStr for SolverStrategy {
+    type Err = crate::CuckatooError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cpp" => Ok(SolverStrategy::Cpp),
+            "path" => Ok(SolverStrategy::Path),
+            _ => Err(crate::CuckatooError::InternalError(
+                format!("Unknown solver strategy: {}", s)
+            )),
+        }
+    }
+}
+
 /// Trimming mode for edge trimming
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrimmingMode {
@@ -189,6 +508,8 @@ pub enum TrimmingMode {
     Mean,
     /// Slean trimming (balanced)
     Slean,
+    /// Lean trimming with 2-bit node degree counters (`CounterTrimmer`)
+    LeanCounter,
 }
 
 impl fmt::Display for TrimmingMode {
@@ -197,6 +518,7 @@ impl fmt::Display for TrimmingMode {
             TrimmingMode::Lean => write!(f, "lean"),
             TrimmingMode::Mean => write!(f, "mean"),
             TrimmingMode::Slean => write!(f, "slean"),
+            TrimmingMode::LeanCounter => write!(f, "lean-counter"),
         }
     }
 }
@@ -209,6 +531,7 @@ impl std::str::FromStr for TrimmingMode {
             "lean" => Ok(TrimmingMode::Lean),
             "mean" => Ok(TrimmingMode::Mean),
             "slean" => Ok(TrimmingMode::Slean),
+            "lean-counter" => Ok(TrimmingMode::LeanCounter),
             _ => Err(crate::CuckatooError::InternalError(
                 format!("Unknown trimming mode: {}", s)
             )),
@@ -223,6 +546,8 @@ pub struct PerformanceMetrics {
     pub searching_time: f64,
     /// Time spent trimming (GPU/CPU)
     pub trimming_time: f64,
+    /// Trimming rounds run
+    pub trimming_rounds: u64,
     /// Total graphs processed
     pub graphs_processed: u64,
     /// Solutions found
@@ -233,12 +558,19 @@ pub struct PerformanceMetrics {
     pub nodes_processed: u64,
 }
 
+impl Default for PerformanceMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PerformanceMetrics {
     /// Create new performance metrics
     pub fn new() -> Self {
         Self {
             searching_time: 0.0,
             trimming_time: 0.0,
+            trimming_rounds: 0,
             graphs_processed: 0,
             solutions_found: 0,
             mining_rate: 0.0,
@@ -260,3 +592,51 @@ impl PerformanceMetrics {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_validation_policy() {
+        let mut config = Config::new(8);
+        assert!(config.validate().is_err());
+        assert!(Config::new(33).validate().is_err());
+        assert!(Config::new(12).validate().is_ok());
+        
+        config.validation_policy = ValidationPolicy::Research;
+        assert!(config.validate().is_ok());
+        config.edge_bits = 3;
+        assert!(config.validate().is_err());
+        config.edge_bits = 64;
+        assert!(config.validate().is_err());
+        
+        assert_eq!("research".parse::<ValidationPolicy>().unwrap(), ValidationPolicy::Research);
+        assert!("lenient".parse::<ValidationPolicy>().is_err());
+    }
+    
+    #[test]
+    fn test_proof_round_trips() {
+        let nonces: Vec<u64> = (0..SOLUTION_SIZE as u64).map(|i| i * 97 + (i % 2) * (1 << 28)).collect();
+        let proof = Proof::new(29, &nonces).unwrap();
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), Proof::packed_size(29));
+        assert_eq!(Proof::from_bytes(29, &bytes).unwrap(), proof);
+        let hex = proof.to_hex();
+        assert_eq!(hex.len(), bytes.len() * 2);
+        assert_eq!(Proof::from_hex(29, &hex).unwrap(), proof);
+        assert_eq!(Proof::from_hex(29, &hex.to_uppercase()).unwrap(), proof);
+        
+        // The first nonce takes the lowest bits of the first bytes
+        let mut first = [0u64; SOLUTION_SIZE];
+        first[0] = 0x1ff;
+        assert_eq!(Proof::new(12, &first).unwrap().to_hex()[..4], *"ff01");
+        
+        assert!(Proof::new(29, &nonces[1..]).is_err());
+        assert!(Proof::new(12, &nonces).is_err());
+        assert!(Proof::new(64, &nonces).is_err());
+        assert!(Proof::from_bytes(29, &bytes[1..]).is_err());
+        assert!(Proof::from_hex(29, &hex[1..]).is_err());
+        assert!(Proof::from_hex(29, &hex.replace('0', "g")).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Union-find pre-filter for the cycle search
+//!
+//! Tromp's reference solver joins the two endpoints of every edge in a
+//! disjoint set forest and knows a cycle exists as soon as an edge joins
+//! two nodes that are already connected. The same pass also tells which
+//! connected components can hold a 42-cycle at all: one needs at least as
+//! many edges as vertices (otherwise it's a tree) and at least 42 vertices
+//! (or however long the cycles searched for are).
+//! `cyclic_edges` keeps the edges of such components, so the path search
+//! only walks the parts of the trimmed graph where a solution can be.
+//!
+//! The forest joins node pairs (U pair `u >> 1` and V pair `v >> 1`)
+//! rather than nodes, so it keeps the cycles of the trimmers' node pair
+//! graph as well as those of edges sharing nodes.
+
+use crate::Edge;
+use std::collections::{HashMap, HashSet};
+
+/// Disjoint set forest over dense vertex ids, with path halving and union
+/// by size
+pub(crate) struct DisjointSet {
+    parents: Vec<u32>,
+    sizes: Vec<u32>,
+}
+
+impl DisjointSet {
+    /// Forest of `vertices` singleton sets
+    pub(crate) fn new(vertices: usize) -> Self {
+        Self { parents: (0..vertices as u32).collect(), sizes: vec![1; vertices] }
+    }
+
+    /// Root of the set holding `vertex`
+    pub(crate) fn find(&mut self, mut vertex: u32) -> u32 {
+        while self.parents[vertex as usize] != vertex {
+            let grandparent = self.parents[self.parents[vertex as usize] as usize];
+            self.parents[vertex as usize] = grandparent;
+            vertex = grandparent;
+        }
+        vertex
+    }
+
+    /// Join the sets of `a` and `b`, returning false if they already were
+    /// one set (the joining edge closes a cycle)
+    pub(crate) fn union(&mut self, a: u32, b: u32) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.sizes[a as usize] < self.sizes[b as usize] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parents[b as usize] = a;
+        self.sizes[a as usize] += self.sizes[b as usize];
+        true
+    }
+
+    /// Number of vertices in the set holding `vertex`
+    pub(crate) fn size(&mut self, vertex: u32) -> u32 {
+        let root = self.find(vertex);
+        self.sizes[root as usize]
+    }
+}
+
+/// Indices (in order) of the edges lying in components that can hold a
+/// cycle of `cycle_length` edges
+pub(crate) fn cyclic_edges(edges: &[Edge], cycle_length: usize) -> Vec<usize> {
+    // Dense ids for the node pairs, U pairs and V pairs kept apart
+    let mut ids: HashMap<(u64, u64), u32> = HashMap::with_capacity(edges.len() * 2);
+    let mut endpoints = Vec::with_capacity(edges.len());
+    for edge in edges {
+        let mut id = |pair| {
+            let next = ids.len() as u32;
+            *ids.entry(pair).or_insert(next)
+        };
+        endpoints.push((id((0, edge.u.value() >> 1)), id((1, edge.v.value() >> 1))));
+    }
+
+    let mut forest = DisjointSet::new(ids.len());
+    let closing: Vec<u32> = endpoints.iter().filter(|&&(u, v)| !forest.union(u, v)).map(|&(u, _)| u).collect();
+    if closing.is_empty() {
+        return Vec::new();
+    }
+
+    // A component with a closing edge holds a cycle; it's only worth
+    // searching if it has the vertices of a whole cycle
+    let mut cyclic_roots = HashSet::new();
+    for vertex in closing {
+        if forest.size(vertex) as usize >= cycle_length {
+            cyclic_roots.insert(forest.find(vertex));
+        }
+    }
+    endpoints
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(u, _))| cyclic_roots.contains(&forest.find(u)))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::{KNOWN_PROOF, KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_KEYS};
+    use crate::{CycleVerifier, Node, SipHash, Strategy, SOLUTION_SIZE};
+
+    /// 42-cycle through U pairs and V pairs 0 to 20, each vertex reached
+    /// at both of its nodes
+    fn cuckatoo_cycle() -> Vec<Edge> {
+        let half = SOLUTION_SIZE as u64 / 2;
+        (0..half)
+            .flat_map(|pair| {
+                let next = (pair + 1) % half;
+                [
+                    Edge::new(Node::new(2 * pair), Node::new(2 * pair)),
+                    Edge::new(Node::new(2 * next + 1), Node::new(2 * pair + 1)),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_union_find_prefilter() {
+        let mut forest = DisjointSet::new(4);
+        assert!(forest.union(0, 1) && forest.union(2, 1));
+        assert!(!forest.union(0, 2));
+        assert_eq!((forest.size(2), forest.size(3)), (3, 1));
+
+        // A tree, a 4-cycle (too small) and a 42-cycle with a dangling edge
+        let mut edges = vec![Edge::new(Node::new(1000), Node::new(1000)), Edge::new(Node::new(1001), Node::new(1002))];
+        edges.extend([(500, 500), (502, 501), (503, 503), (501, 502)].map(|(u, v)| Edge::new(Node::new(u), Node::new(v))));
+        let first_cycle_edge = edges.len();
+        edges.extend(cuckatoo_cycle());
+        edges.push(Edge::new(Node::new(4), Node::new(900)));
+        assert_eq!(cyclic_edges(&edges, SOLUTION_SIZE), (first_cycle_edge..edges.len()).collect::<Vec<_>>());
+        assert!(cyclic_edges(&edges[..first_cycle_edge], SOLUTION_SIZE).is_empty());
+        assert_eq!(cyclic_edges(&edges[..first_cycle_edge], 4), (2..first_cycle_edge).collect::<Vec<_>>());
+
+        // The planted solution's edges all make it through the filter
+        let siphash = SipHash::with_key(KNOWN_PROOF_KEYS);
+        let graph = siphash.edges_for_indices(&(0..1 << KNOWN_PROOF_EDGE_BITS).collect::<Vec<_>>(), KNOWN_PROOF_EDGE_BITS);
+        let kept = cyclic_edges(&graph, SOLUTION_SIZE);
+        assert!(kept.len() < graph.len());
+        assert!(KNOWN_PROOF.iter().all(|&index| kept.binary_search(&(index as usize)).is_ok()));
+
+        // Without a candidate component there's nothing to search
+        let mut verifier = CycleVerifier::new().with_strategy(Strategy::UnionFind);
+        assert_eq!(verifier.find_42_cycle(&edges[..first_cycle_edge + 40]).unwrap(), None);
+        assert_eq!(verifier.search_stats().candidate_pairs, 0);
+        assert_eq!(verifier.find_42_cycle(&graph).unwrap(), CycleVerifier::new().find_42_cycle(&graph).unwrap());
+    }
+}
@@ -1,12 +1,35 @@
 //! Cycle verification for Cuckatoo
 //! 
 //! This implements the exact same 42-cycle verification algorithm as the C++ reference miner.
-//! Uses hash table-based cycle finding with node pair logic.
+//! Searches with `ArrayCycleFinder`, or `PathSolver` with
+//! `SolverStrategy::Path`, optionally after a union-find pre-filter.
+//! Every search first prunes the graph to its 2-core: a node with only one
+//! edge can't be on a cycle, and dropping its edge can leave another node
+//! with only one, so trees hanging off the graph go away entirely.
 
-use crate::{Edge, Node, Result, PerformanceMetrics, HashCycleFinder};
-use std::collections::{HashMap, HashSet};
+use crate::{ArrayCycleFinder, CancelToken, CuckatooError, Edge, Node, PathSolver, Result, PerformanceMetrics, CycleSearchStats, SearchBudget, SolverStrategy, SOLUTION_SIZE};
+use crate::cancel::check_cancelled;
+use crate::metrics::{MetricsRegistry, DEFAULT_TIME_BUCKETS};
+use crate::report::format_seconds;
+use crate::graph_stats::two_core;
+use crate::union_find::cyclic_edges;
+use std::collections::{BTreeMap, HashSet};
 use std::time::Instant;
 
+/// Bucket bounds of the longest chain a search reached, in edges
+const CHAIN_LENGTH_BUCKETS: [f64; 7] = [2.0, 4.0, 8.0, 16.0, 24.0, 32.0, 42.0];
+
+/// How `CycleVerifier` searches the trimmed graph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Path search over every edge (the C++ algorithm)
+    #[default]
+    AllEdges,
+    /// Union-find pass first: skip the search when no component can hold
+    /// a 42-cycle, otherwise search only the edges of those that can
+    UnionFind,
+}
+
 /// Cycle verifier for Cuckatoo
 /// 
 /// Implements the 42-cycle verification algorithm used in the
@@ -14,16 +37,107 @@ use std::time::Instant;
 pub struct CycleVerifier {
     /// Performance metrics
     metrics: PerformanceMetrics,
+    /// Registry receiving cycle search metrics
+    registry: Option<MetricsRegistry>,
+    /// Work done by the last search
+    search_stats: CycleSearchStats,
+    /// Token stopping searches
+    cancel: Option<CancelToken>,
+    /// Search strategy
+    strategy: Strategy,
+    /// Algorithm searching the graph
+    solver: SolverStrategy,
+    /// Number of edges in the cycles searched for
+    cycle_length: usize,
+    /// Threads walking the graph
+    threads: usize,
+    /// Limits on the work of each search
+    budget: SearchBudget,
 }
 
 impl CycleVerifier {
+    /// Name of this cycle finder in replay records
+    pub const NAME: &'static str = "cycle-verifier";
+    
     /// Create a new cycle verifier
     pub fn new() -> Self {
         Self {
             metrics: PerformanceMetrics::new(),
+            registry: None,
+            search_stats: CycleSearchStats::default(),
+            cancel: None,
+            strategy: Strategy::default(),
+            solver: SolverStrategy::default(),
+            cycle_length: SOLUTION_SIZE,
+            threads: 1,
+            budget: SearchBudget::default(),
         }
     }
     
+    /// Search with the given strategy
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+    
+    /// Search with the given algorithm (`PathSolver` ignores `with_threads`)
+    pub fn with_solver_strategy(mut self, solver: SolverStrategy) -> Self {
+        self.solver = solver;
+        self
+    }
+    
+    /// Search for cycles of `length` edges instead of 42 (see
+    /// `ArrayCycleFinder::with_cycle_length`)
+    pub fn with_cycle_length(mut self, length: usize) -> Self {
+        crate::types::assert_cycle_length(length);
+        self.cycle_length = length;
+        self
+    }
+    
+    /// Search on `threads` threads (see `ArrayCycleFinder::find_cycle_parallel`)
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+    
+    /// Stop searching with `Cancelled` soon after `token` is cancelled
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+    
+    /// Fail searches with `BudgetExceeded` once they go over `budget`
+    /// (see `ArrayCycleFinder::with_budget`)
+    pub fn with_budget(mut self, budget: SearchBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+    
+    /// Record cycle search metrics into the given registry
+    pub fn with_metrics(mut self, registry: MetricsRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+    
+    /// Record a completed cycle search in the metrics registry, if any
+    fn record_search(&self) {
+        if let Some(registry) = &self.registry {
+            registry.counter("cuckatoo_cycle_searches_total").inc();
+            registry.counter("cuckatoo_solutions_found_total").add(self.metrics.solutions_found);
+            registry.histogram("cuckatoo_cycle_search_seconds", &DEFAULT_TIME_BUCKETS)
+                .observe(self.metrics.searching_time);
+            registry.counter("cuckatoo_cycle_candidate_pairs_total").add(self.search_stats.candidate_pairs);
+            registry.counter("cuckatoo_cycle_traversal_steps_total").add(self.search_stats.traversal_steps);
+            registry.histogram("cuckatoo_cycle_max_chain_length", &CHAIN_LENGTH_BUCKETS)
+                .observe(self.search_stats.max_chain_length as f64);
+        }
+    }
+    
+    /// Candidate pairs, traversal steps and longest chain of the last search
+    pub fn search_stats(&self) -> CycleSearchStats {
+        self.search_stats
+    }
+    
     /// Find a 42-cycle in the given edges
     /// 
     /// This is the main method used by the CLI
@@ -33,32 +147,81 @@ impl CycleVerifier {
     
     /// Verify if edges contain a 42-cycle
     /// 
-    /// This implements the exact same algorithm as the C++ reference miner:
-    /// 1. Use hash table-based cycle finding with node pair logic
+    /// This implements the exact same algorithm as the C++ reference miner,
+    /// on the 2-core of the graph:
+    /// 1. Walk paths from every edge over array-indexed connection lists
+    ///    (or follow `PathSolver`'s forest with `SolverStrategy::Path`)
     /// 2. Return the first valid 42-cycle found
     pub fn verify_cycle(&mut self, edges: &[Edge]) -> Result<Option<Vec<Edge>>> {
+        self.search_cycle(edges).inspect_err(|error| {
+            if let Some(registry) = &self.registry {
+                registry.record_error(error);
+            }
+        })
+    }
+    
+    /// Search for a 42-cycle (see `verify_cycle`)
+    fn search_cycle(&mut self, edges: &[Edge]) -> Result<Option<Vec<Edge>>> {
+        crate::profile_scope!(crate::profiling::CYCLE_SEARCH, edges = edges.len());
         let start_time = Instant::now();
+        check_cancelled(self.cancel.as_ref())?;
         
-        if edges.len() < 42 {
-            // Not enough edges for a 42-cycle
+        if edges.len() < self.cycle_length {
+            // Not enough edges for a cycle
             return Ok(None);
         }
         
-        // Use the hash table-based cycle finder (matches C++ algorithm)
-        let mut finder = HashCycleFinder::new();
-        if let Some(solution_indices) = finder.find_cycle(edges)? {
+        // Only the 2-core can hold a cycle
+        let core: Vec<Edge> = two_core(edges).into_iter().map(|idx| edges[idx]).collect();
+        tracing::debug!(edges = edges.len(), core = core.len(), "2-core pre-pass");
+        
+        // Keep only the components that can hold a 42-cycle
+        let candidates: Vec<Edge>;
+        let search_edges = match self.strategy {
+            Strategy::AllEdges => &core,
+            Strategy::UnionFind => {
+                candidates = cyclic_edges(&core, self.cycle_length).into_iter().map(|idx| core[idx]).collect();
+                tracing::debug!(edges = core.len(), candidates = candidates.len(), "Union-find pre-filter");
+                &candidates
+            }
+        };
+        
+        let too_small = search_edges.len() < self.cycle_length;
+        let found = match self.solver {
+            SolverStrategy::Cpp => {
+                // Search with the C++ algorithm over array-indexed connection lists
+                let mut finder = ArrayCycleFinder::new().with_cycle_length(self.cycle_length).with_budget(self.budget);
+                if let Some(token) = &self.cancel {
+                    finder = finder.with_cancel_token(token.clone());
+                }
+                let found = if too_small { None } else { finder.find_cycle_parallel(search_edges, self.threads)? };
+                self.search_stats = finder.stats();
+                found
+            }
+            SolverStrategy::Path => {
+                let mut solver = PathSolver::new().with_cycle_length(self.cycle_length).with_budget(self.budget);
+                if let Some(token) = &self.cancel {
+                    solver = solver.with_cancel_token(token.clone());
+                }
+                let found = if too_small { None } else { solver.find_cycle(search_edges)? };
+                self.search_stats = solver.stats();
+                found
+            }
+        };
+        if let Some(solution_indices) = found {
             // Convert edge indices back to edges
             let solution_edges: Vec<Edge> = solution_indices
                 .iter()
-                .map(|&idx| edges[idx])
+                .map(|&idx| search_edges[idx])
                 .collect();
             
             let searching_time = start_time.elapsed().as_secs_f64();
             self.metrics.searching_time = searching_time;
             self.metrics.solutions_found = 1;
+            self.record_search();
             
-            println!("42-cycle found in {:.6}s", searching_time);
-            println!("Cycle edges: {:?}", solution_edges);
+            tracing::info!(searching_time = %format_seconds(searching_time), cycle_length = self.cycle_length, "Cycle found");
+            tracing::debug!(?solution_edges, "Cycle edges");
             
             return Ok(Some(solution_edges));
         }
@@ -66,8 +229,9 @@ impl CycleVerifier {
         let searching_time = start_time.elapsed().as_secs_f64();
         self.metrics.searching_time = searching_time;
         self.metrics.solutions_found = 0;
+        self.record_search();
         
-        println!("No 42-cycle found in {:.6}s", searching_time);
+        tracing::info!(searching_time = %format_seconds(searching_time), cycle_length = self.cycle_length, "No cycle found");
         
         Ok(None)
     }
@@ -129,7 +293,6 @@ impl CycleVerifier {
             return false;
         }
         
-        println!("Checking incident edges in cycle:");
         let mut all_incident = true;
         
         for i in 0..cycle_edges.len() {
@@ -141,31 +304,23 @@ impl CycleVerifier {
             
             if is_incident {
                 // Find the shared node
-                let shared_node = if current_edge.u == next_edge.u {
+                let shared_node = if current_edge.u == next_edge.u || current_edge.u == next_edge.v {
                     current_edge.u
-                } else if current_edge.u == next_edge.v {
-                    current_edge.u
-                } else if current_edge.v == next_edge.u {
-                    current_edge.v
-                } else if current_edge.v == next_edge.v {
+                } else if current_edge.v == next_edge.u || current_edge.v == next_edge.v {
                     current_edge.v
                 } else {
                     // This shouldn't happen if edges_are_properly_connected is correct
                     Node::new(0)
                 };
                 
-                println!("  Edge {} -> Edge {}: ✅ INCIDENT (shared node: {})", i, (i + 1) % cycle_edges.len(), shared_node);
+                tracing::trace!(edge = i, next = (i + 1) % cycle_edges.len(), %shared_node, "Edges incident");
             } else {
-                println!("  Edge {} -> Edge {}: ❌ NOT INCIDENT", i, (i + 1) % cycle_edges.len());
+                tracing::debug!(edge = i, next = (i + 1) % cycle_edges.len(), "Edges not incident");
                 all_incident = false;
             }
         }
         
-        if all_incident {
-            println!("✅ All consecutive edges in the cycle are properly incident!");
-        } else {
-            println!("❌ Some consecutive edges in the cycle are not incident!");
-        }
+        tracing::debug!(all_incident, "Checked incident edges in cycle");
         
         all_incident
     }
@@ -191,6 +346,10 @@ impl Default for CycleVerifier {
 pub struct OptimizedCycleVerifier {
     /// Performance metrics
     metrics: PerformanceMetrics,
+    /// Longest cycle searched for, if limited
+    max_depth: Option<usize>,
+    /// Node visits allowed per search, if limited
+    visit_budget: Option<u64>,
 }
 
 impl OptimizedCycleVerifier {
@@ -198,122 +357,143 @@ impl OptimizedCycleVerifier {
     pub fn new() -> Self {
         Self {
             metrics: PerformanceMetrics::new(),
+            max_depth: None,
+            visit_budget: None,
         }
     }
     
+    /// Refuse to search for cycles longer than `depth` nodes
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+    
+    /// Give up a search after `visits` node visits
+    /// 
+    /// The search walks every simple path of the cycle length from every
+    /// node, which on full-size trimmed graphs can take practically forever.
+    pub fn with_visit_budget(mut self, visits: u64) -> Self {
+        self.visit_budget = Some(visits);
+        self
+    }
+    
     /// Find all cycles of specified length
+    /// 
+    /// Every cycle is found once per node and direction. Fails with
+    /// `VerificationError` if `cycle_length` is above the depth limit or
+    /// the visit budget runs out.
     pub fn find_all_cycles(&mut self, edges: &[Edge], cycle_length: usize) -> Result<Vec<Vec<Node>>> {
+        crate::profile_scope!(crate::profiling::CYCLE_SEARCH, edges = edges.len());
         let start_time = Instant::now();
         
+        if let Some(max_depth) = self.max_depth.filter(|&max_depth| cycle_length > max_depth) {
+            return Err(CuckatooError::VerificationError(format!(
+                "Cycle length {} is above the search depth limit of {}", cycle_length, max_depth
+            )));
+        }
         if edges.len() < cycle_length {
             return Ok(vec![]);
         }
         
         let adjacency = self.build_adjacency_list(edges);
         let mut all_cycles = Vec::new();
+        let mut visits = 0;
         
         // Try to find cycles starting from each node
         for &start_node in adjacency.keys() {
-            if let Some(cycles) = self.find_cycles_from_node(start_node, &adjacency, cycle_length) {
-                all_cycles.extend(cycles);
-            }
+            self.dfs_all_cycles(start_node, &adjacency, cycle_length, &mut visits, &mut all_cycles)?;
         }
         
         let searching_time = start_time.elapsed().as_secs_f64();
         self.metrics.searching_time = searching_time;
         self.metrics.solutions_found = all_cycles.len() as u64;
         
-                println!("Found {} cycles of length {} in {:.6}s", 
-                    all_cycles.len(), cycle_length, searching_time);
+        tracing::info!(
+            cycles = all_cycles.len(),
+            cycle_length,
+            searching_time = %format_seconds(searching_time),
+            "Found cycles"
+        );
         
         Ok(all_cycles)
     }
     
     /// Build adjacency list from edges
+    /// 
+    /// Nodes are ordered so cycles are always found in the same order.
     #[allow(dead_code)]
-    fn build_adjacency_list(&self, edges: &[Edge]) -> HashMap<Node, Vec<Node>> {
-        let mut adjacency: HashMap<Node, Vec<Node>> = HashMap::new();
+    fn build_adjacency_list(&self, edges: &[Edge]) -> BTreeMap<Node, Vec<Node>> {
+        let mut adjacency: BTreeMap<Node, Vec<Node>> = BTreeMap::new();
         
         for edge in edges {
-            adjacency.entry(edge.u).or_insert_with(Vec::new).push(edge.v);
-            adjacency.entry(edge.v).or_insert_with(Vec::new).push(edge.u);
+            adjacency.entry(edge.u).or_default().push(edge.v);
+            adjacency.entry(edge.v).or_default().push(edge.u);
         }
         
         adjacency
     }
     
-    /// Find cycles starting from a specific node
-    fn find_cycles_from_node(
-        &self,
-        start_node: Node,
-        adjacency: &HashMap<Node, Vec<Node>>,
-        cycle_length: usize,
-    ) -> Option<Vec<Vec<Node>>> {
-        let mut visited = HashSet::new();
-        let mut path = Vec::new();
-        let mut cycles = Vec::new();
-        
-        self.dfs_all_cycles(
-            start_node,
-            start_node,
-            adjacency,
-            &mut visited,
-            &mut path,
-            cycle_length,
-            &mut cycles,
-        );
-        
-        if cycles.is_empty() {
-            None
-        } else {
-            Some(cycles)
-        }
-    }
-    
-    /// DFS to find all cycles
+    /// DFS to find all cycles through `start`
+    /// 
+    /// Walks every simple path of `target_length` nodes from `start` and
+    /// records those that close back to it. The path is kept on an explicit
+    /// stack with the next neighbor to try at each node, rather than on the
+    /// call stack, so long target cycles can't overflow it.
     fn dfs_all_cycles(
         &self,
-        current: Node,
         start: Node,
-        adjacency: &HashMap<Node, Vec<Node>>,
-        visited: &mut HashSet<Node>,
-        path: &mut Vec<Node>,
+        adjacency: &BTreeMap<Node, Vec<Node>>,
         target_length: usize,
+        visits: &mut u64,
         cycles: &mut Vec<Vec<Node>>,
-    ) {
-        path.push(current);
-        
-        if path.len() == target_length {
-            if let Some(neighbors) = adjacency.get(&current) {
-                if neighbors.contains(&start) {
-                    // Found a cycle!
-                    cycles.push(path.clone());
+    ) -> Result<()> {
+        let no_neighbors = Vec::new();
+        let neighbors_of = |node| adjacency.get(&node).unwrap_or(&no_neighbors);
+        let mut visited = HashSet::new();
+        let mut path: Vec<Node> = Vec::with_capacity(target_length);
+        // Index of the next neighbor to try, per node on the path
+        let mut next_neighbor: Vec<usize> = Vec::with_capacity(target_length);
+        
+        let mut entering = Some(start);
+        loop {
+            if let Some(current) = entering.take() {
+                *visits += 1;
+                if let Some(budget) = self.visit_budget.filter(|&budget| *visits > budget) {
+                    return Err(CuckatooError::VerificationError(format!(
+                        "Cycle search gave up after {} node visits", budget
+                    )));
+                }
+                path.push(current);
+                if path.len() == target_length {
+                    if neighbors_of(current).contains(&start) {
+                        // Found a cycle!
+                        cycles.push(path.clone());
+                    }
+                    path.pop();
+                } else {
+                    visited.insert(current);
+                    next_neighbor.push(0);
                 }
             }
-            path.pop();
-            return;
-        }
-        
-        visited.insert(current);
-        
-        if let Some(neighbors) = adjacency.get(&current) {
-            for &neighbor in neighbors {
-                if !visited.contains(&neighbor) {
-                    self.dfs_all_cycles(
-                        neighbor,
-                        start,
-                        adjacency,
-                        visited,
-                        path,
-                        target_length,
-                        cycles,
-                    );
+            
+            // Go on to the next unvisited neighbor of the deepest node, or
+            // back up once it has none left
+            let (Some(&current), Some(index)) = (path.last(), next_neighbor.last_mut()) else {
+                return Ok(());
+            };
+            let neighbors = neighbors_of(current);
+            match neighbors[*index..].iter().position(|neighbor| !visited.contains(neighbor)) {
+                Some(offset) => {
+                    *index += offset + 1;
+                    entering = Some(neighbors[*index - 1]);
+                }
+                None => {
+                    next_neighbor.pop();
+                    visited.remove(&current);
+                    path.pop();
                 }
             }
         }
-        
-        visited.remove(&current);
-        path.pop();
     }
     
     /// Get performance metrics
@@ -406,6 +586,43 @@ mod tests {
         assert!(edges.len() == 1024); // 2^10 edges
     }
     
+    #[test]
+    fn test_search_stats_are_recorded() {
+        let registry = MetricsRegistry::new();
+        let mut verifier = CycleVerifier::new().with_metrics(registry.clone());
+        let siphash = crate::SipHash::with_key(crate::self_test::KNOWN_PROOF_KEYS);
+        let edges = siphash.edges_for_indices(&(0..1 << 12).collect::<Vec<_>>(), 12);
+        verifier.verify_cycle(&edges).unwrap();
+        
+        let stats = verifier.search_stats();
+        assert!(stats.candidate_pairs > 0);
+        assert!(stats.traversal_steps >= stats.candidate_pairs);
+        assert!(stats.max_chain_length >= 1 && stats.max_chain_length < crate::SOLUTION_SIZE as u32);
+        
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("cuckatoo_cycle_candidate_pairs_total"), Some(stats.candidate_pairs));
+        assert_eq!(snapshot.counter("cuckatoo_cycle_traversal_steps_total"), Some(stats.traversal_steps));
+        assert_eq!(snapshot.histogram("cuckatoo_cycle_max_chain_length").unwrap().count, 1);
+    }
+    
+    #[test]
+    fn test_two_core_pre_pass() {
+        // A tree hanging off a 4-cycle, with an edge of the tree that has
+        // other edges at both ends added before the cycle
+        let edges: Vec<Edge> = [(1, 7), (4, 6), (0, 0), (3, 1), (2, 2), (1, 3)]
+            .map(|(u, v)| Edge::new(Node::new(u), Node::new(v)))
+            .to_vec();
+        let mut verifier = CycleVerifier::new().with_cycle_length(4);
+        assert_eq!(verifier.verify_cycle(&edges).unwrap(), Some(edges[2..].to_vec()));
+        // Only the edge closing the cycle starts a walk
+        assert_eq!(verifier.search_stats().candidate_pairs, 1);
+        
+        // A tree alone has no 2-core to search
+        let tree = [(1, 7), (4, 6), (9, 6), (4, 8)].map(|(u, v)| Edge::new(Node::new(u), Node::new(v)));
+        assert_eq!(verifier.verify_cycle(&tree).unwrap(), None);
+        assert_eq!(verifier.search_stats(), CycleSearchStats::default());
+    }
+    
     #[test]
     fn test_synthetic_42_cycle() {
         let mut verifier = CycleVerifier::new();
@@ -528,11 +745,28 @@ mod tests {
         assert!(result.is_ok());
         
         let cycles = result.unwrap();
-        assert!(cycles.len() >= 1); // At least one 3-cycle (may find duplicates with different starting points)
+        assert!(!cycles.is_empty()); // At least one 3-cycle (may find duplicates with different starting points)
         
         let cycle = &cycles[0];
         assert_eq!(cycle.len(), 3);
     }
+    
+    #[test]
+    fn test_optimized_cycle_verifier_limits() {
+        // A 300-cycle is found from each of its nodes in both directions
+        let edges = test_fixtures::create_synthetic_cycle_graph(300);
+        let cycles = OptimizedCycleVerifier::new().find_all_cycles(&edges, 300).unwrap();
+        assert_eq!(cycles.len(), 600);
+        assert!(cycles.iter().all(|cycle| cycle.iter().collect::<HashSet<_>>().len() == 300));
+        assert!(OptimizedCycleVerifier::new().find_all_cycles(&edges, 299).unwrap().is_empty());
+        
+        let mut limited = OptimizedCycleVerifier::new().with_max_depth(299);
+        assert!(matches!(limited.find_all_cycles(&edges, 300), Err(CuckatooError::VerificationError(_))));
+        let mut limited = OptimizedCycleVerifier::new().with_visit_budget(1000);
+        assert!(matches!(limited.find_all_cycles(&edges, 300), Err(CuckatooError::VerificationError(_))));
+        let mut limited = OptimizedCycleVerifier::new().with_visit_budget(300 * 600);
+        assert_eq!(limited.find_all_cycles(&edges, 300).unwrap().len(), 600);
+    }
 }
 
 /// Synthetic test fixtures for cycle verification
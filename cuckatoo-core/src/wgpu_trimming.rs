@@ -0,0 +1,578 @@
+//! Portable GPU lean trimming through wgpu
+//!
+//! `WgpuLeanTrimmer` runs the four lean trimming steps as WGSL compute
+//! shaders, so the same code trims on Vulkan, Metal and DX12 devices. WGSL
+//! has no 64-bit integers, so SipHash-2-4 works on `vec2<u32>` (low, high)
+//! pairs. Both bitmaps stay on the device between rounds; dispatches are
+//! recorded into one command buffer per trim (one per round with early
+//! exit conditions or a progress observer), and the surviving edges bitmap
+//! is copied into a staging buffer the host maps asynchronously.
+
+use crate::{checked_usize, CancelToken, CuckatooError, EarlyExit, Result, SipKeys};
+use crate::bitmap_trimming::round_side;
+use crate::cancel::check_cancelled;
+use crate::hashing::node_mask;
+use crate::progress::{report_round, ProgressObserver};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+/// Edges per bitmap word on the device
+const WORD_EDGES: u64 = 32;
+
+/// Invocations per workgroup, as in `@workgroup_size` of the shaders
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Most workgroups per dispatch dimension every backend supports
+const MAX_WORKGROUPS_PER_DIMENSION: u32 = 65535;
+
+/// Lean trimming shaders, one invocation per edges bitmap word
+///
+/// SipHash-2-4 matches `hashing::siphash24`, including the C++ rotation of
+/// 21 in the second half of a round. Nodes are below 2^32 for the edge bits
+/// the trimmer accepts, so only their low word indexes the nodes bitmap.
+const SHADERS: &str = r#"
+struct Params {
+    keys: array<vec4<u32>, 2>,
+    node_mask: vec2<u32>,
+    words: u32,
+    padding: u32,
+}
+
+struct SipState {
+    v0: vec2<u32>,
+    v1: vec2<u32>,
+    v2: vec2<u32>,
+    v3: vec2<u32>,
+}
+
+@group(0) @binding(0) var<storage, read_write> edges: array<u32>;
+@group(0) @binding(1) var<storage, read_write> nodes: array<atomic<u32>>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+fn add64(a: vec2<u32>, b: vec2<u32>) -> vec2<u32> {
+    let low = a.x + b.x;
+    return vec2<u32>(low, a.y + b.y + select(0u, 1u, low < a.x));
+}
+
+fn rotl64(a: vec2<u32>, n: u32) -> vec2<u32> {
+    var v = a;
+    var s = n;
+    if (s >= 32u) {
+        v = v.yx;
+        s = s - 32u;
+    }
+    if (s == 0u) {
+        return v;
+    }
+    return vec2<u32>((v.x << s) | (v.y >> (32u - s)), (v.y << s) | (v.x >> (32u - s)));
+}
+
+fn sip_round(state: SipState) -> SipState {
+    var s = state;
+    s.v0 = add64(s.v0, s.v1); s.v2 = add64(s.v2, s.v3);
+    s.v1 = rotl64(s.v1, 13u); s.v3 = rotl64(s.v3, 16u);
+    s.v1 = s.v1 ^ s.v0; s.v3 = s.v3 ^ s.v2;
+    s.v0 = rotl64(s.v0, 32u);
+    s.v2 = add64(s.v2, s.v1); s.v0 = add64(s.v0, s.v3);
+    s.v1 = rotl64(s.v1, 17u); s.v3 = rotl64(s.v3, 21u);
+    s.v1 = s.v1 ^ s.v2; s.v3 = s.v3 ^ s.v0;
+    s.v2 = rotl64(s.v2, 32u);
+    return s;
+}
+
+fn sip_node(nonce: vec2<u32>) -> u32 {
+    var s = SipState(params.keys[0].xy, params.keys[0].zw, params.keys[1].xy, params.keys[1].zw ^ nonce);
+    s = sip_round(s); s = sip_round(s);
+    s.v0 = s.v0 ^ nonce;
+    s.v2 = s.v2 ^ vec2<u32>(255u, 0u);
+    s = sip_round(s); s = sip_round(s); s = sip_round(s); s = sip_round(s);
+    return ((s.v0 ^ s.v1 ^ s.v2 ^ s.v3) & params.node_mask).x;
+}
+
+fn edge_node(word: u32, bit: u32, uorv: u32) -> u32 {
+    let edge = word * 32u + bit;
+    return sip_node(vec2<u32>((edge << 1u) | uorv, edge >> 31u));
+}
+
+fn word_index(workgroup: vec3<u32>, workgroups: vec3<u32>, local: vec3<u32>) -> u32 {
+    return (workgroup.y * workgroups.x + workgroup.x) * 64u + local.x;
+}
+
+/* Set the node bit of endpoint uorv of every live edge of the word */
+fn mark_nodes(word: u32, uorv: u32) {
+    if (word >= params.words) {
+        return;
+    }
+    var unit = edges[word];
+    while (unit != 0u) {
+        let node = edge_node(word, countTrailingZeros(unit), uorv);
+        atomicOr(&nodes[node >> 5u], 1u << (node & 31u));
+        unit = unit & (unit - 1u);
+    }
+}
+
+/* Keep the edges of the word whose endpoint uorv has its partner node set */
+fn keep_paired_edges(word: u32, uorv: u32) {
+    if (word >= params.words) {
+        return;
+    }
+    var unit = edges[word];
+    var kept = 0u;
+    while (unit != 0u) {
+        let bit = countTrailingZeros(unit);
+        let partner = edge_node(word, bit, uorv) ^ 1u;
+        if (((atomicLoad(&nodes[partner >> 5u]) >> (partner & 31u)) & 1u) != 0u) {
+            kept = kept | (1u << bit);
+        }
+        unit = unit & (unit - 1u);
+    }
+    edges[word] = kept;
+}
+
+@compute @workgroup_size(64)
+fn trim_edges_step_one(@builtin(workgroup_id) workgroup: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>,
+                       @builtin(local_invocation_id) local: vec3<u32>) {
+    mark_nodes(word_index(workgroup, workgroups, local), 0u);
+}
+
+@compute @workgroup_size(64)
+fn trim_edges_step_two(@builtin(workgroup_id) workgroup: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>,
+                       @builtin(local_invocation_id) local: vec3<u32>) {
+    keep_paired_edges(word_index(workgroup, workgroups, local), 0u);
+}
+
+@compute @workgroup_size(64)
+fn trim_edges_step_three(@builtin(workgroup_id) workgroup: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>,
+                         @builtin(local_invocation_id) local: vec3<u32>) {
+    mark_nodes(word_index(workgroup, workgroups, local), 1u);
+}
+
+@compute @workgroup_size(64)
+fn trim_edges_step_four(@builtin(workgroup_id) workgroup: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>,
+                        @builtin(local_invocation_id) local: vec3<u32>) {
+    keep_paired_edges(word_index(workgroup, workgroups, local), 1u);
+}
+"#;
+
+/// Entry points of `SHADERS`, in step order
+const STEPS: [&str; 4] = ["trim_edges_step_one", "trim_edges_step_two", "trim_edges_step_three", "trim_edges_step_four"];
+
+/// Lean trimmer running on a wgpu adapter
+pub struct WgpuLeanTrimmer {
+    edge_bits: u32,
+    adapter_name: String,
+    /// Words of the edges and of the nodes bitmap
+    words: u32,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    steps: [wgpu::ComputePipeline; 4],
+    bind_group: wgpu::BindGroup,
+    edges_bitmap: wgpu::Buffer,
+    nodes_bitmap: wgpu::Buffer,
+    params: wgpu::Buffer,
+    /// Host-mappable copy of the edges bitmap
+    readback: wgpu::Buffer,
+    early_exit: EarlyExit,
+    rounds_run: u32,
+    progress: Option<Box<dyn ProgressObserver>>,
+    cancel: Option<CancelToken>,
+}
+
+impl WgpuLeanTrimmer {
+    /// Names of the adapters of every backend, in the order `new` indexes
+    /// them
+    pub fn adapters() -> Vec<String> {
+        all_adapters().iter().map(|adapter| adapter.get_info().name).collect()
+    }
+
+    /// Create a trimmer for `edge_bits` graphs on the adapter at
+    /// `adapter_index` (see `adapters`), compiling the shaders and
+    /// allocating both bitmaps
+    ///
+    /// Graphs are limited to 32 edge bits, and both bitmaps must fit the
+    /// adapter's largest storage buffer binding.
+    pub fn new(edge_bits: u32, adapter_index: usize) -> Result<Self> {
+        if edge_bits > u32::BITS {
+            return Err(CuckatooError::InvalidEdgeBits(edge_bits));
+        }
+        let words = (1u64 << edge_bits).div_ceil(WORD_EDGES);
+        let bitmap_bytes = words * std::mem::size_of::<u32>() as u64;
+
+        let adapters = all_adapters();
+        let adapter = adapters.get(adapter_index).ok_or_else(|| {
+            CuckatooError::DeviceError(format!("No wgpu adapter {} ({} found)", adapter_index, adapters.len()))
+        })?;
+        let adapter_name = adapter.get_info().name;
+        let limits = adapter.limits();
+        if bitmap_bytes > u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size) {
+            return Err(CuckatooError::MemoryError(format!(
+                "{} byte bitmaps exceed the storage buffer limit of {}", bitmap_bytes, adapter_name
+            )));
+        }
+        let descriptor = wgpu::DeviceDescriptor {
+            label: Some("cuckatoo trimming"),
+            required_limits: limits,
+            ..Default::default()
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None)).map_err(device_error)?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("lean trimming"),
+            source: wgpu::ShaderSource::Wgsl(SHADERS.into()),
+        });
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let uniform = wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("lean trimming"),
+            entries: &[storage(0), storage(1), uniform],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("lean trimming"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let steps = STEPS.map(|entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        });
+
+        let buffer = |label, size, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size, usage, mapped_at_creation: false })
+        };
+        let edges_bitmap = buffer(
+            "edges bitmap",
+            bitmap_bytes,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        );
+        let nodes_bitmap = buffer("nodes bitmap", bitmap_bytes, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
+        let params = buffer("trimming params", PARAMS_BYTES as u64, wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST);
+        let readback = buffer("edges readback", bitmap_bytes, wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("lean trimming"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: edges_bitmap.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: nodes_bitmap.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: params.as_entire_binding() },
+            ],
+        });
+
+        Ok(Self {
+            edge_bits,
+            adapter_name,
+            words: words as u32,
+            device,
+            queue,
+            steps,
+            bind_group,
+            edges_bitmap,
+            nodes_bitmap,
+            params,
+            readback,
+            early_exit: EarlyExit::default(),
+            rounds_run: 0,
+            progress: None,
+            cancel: None,
+        })
+    }
+
+    /// End trims early once any of the `early_exit` conditions is met
+    ///
+    /// The surviving edges are counted on the host, so each round then
+    /// ends with a readback of the edges bitmap.
+    pub fn with_early_exit(mut self, early_exit: EarlyExit) -> Self {
+        self.early_exit = early_exit;
+        self
+    }
+
+    /// Stop trimming with `Cancelled` after the round running when `token`
+    /// is cancelled
+    ///
+    /// Dispatches can't be interrupted, so with a token each round is
+    /// submitted on its own and awaited before checking it.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Name of the adapter the trimmer runs on
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter_name
+    }
+
+    /// Number of rounds the last trim ran
+    pub fn rounds_run(&self) -> u32 {
+        self.rounds_run
+    }
+
+    /// Report every finished round to `observer`
+    ///
+    /// Like early exit conditions, this ends each round with a readback of
+    /// the edges bitmap.
+    pub fn with_progress(mut self, observer: impl ProgressObserver + 'static) -> Self {
+        self.progress = Some(Box::new(observer));
+        self
+    }
+
+    /// Trim the graph of `keys` for `trimming_rounds` rounds and return the
+    /// indices of the surviving edges, blocking until the device is done
+    pub fn trim(&mut self, keys: &SipKeys, trimming_rounds: u32) -> Result<Vec<u64>> {
+        pollster::block_on(self.trim_async(keys, trimming_rounds))
+    }
+
+    /// Trim the graph of `keys` for `trimming_rounds` rounds and resolve to
+    /// the indices of the surviving edges, in order
+    ///
+    /// Even rounds run steps one and two (U endpoints) and odd rounds steps
+    /// three and four (V endpoints), alternating sides like `BitmapTrimmer`. The
+    /// future polls the device each time it is polled, so it makes progress
+    /// on any executor without a dedicated polling thread.
+    pub async fn trim_async(&mut self, keys: &SipKeys, trimming_rounds: u32) -> Result<Vec<u64>> {
+        let words = checked_usize(u64::from(self.words), "GPU bitmap size")?;
+        let mut edges = vec![u32::MAX; words];
+        if self.edge_bits < WORD_EDGES.trailing_zeros() {
+            edges[0] = (1u32 << (1u32 << self.edge_bits)) - 1;
+        }
+        self.queue.write_buffer(&self.edges_bitmap, 0, &to_bytes(&edges));
+        self.queue.write_buffer(&self.params, 0, &self.params_bytes(keys));
+
+        let start_time = Instant::now();
+        let mut survivors = 1u64 << self.edge_bits;
+        self.rounds_run = 0;
+        let mut encoder = self.encoder();
+        for round in 0..trimming_rounds {
+            // Steps one and two trim the U endpoints, three and four the V ones
+            let first_step = if round_side(round) == 0 { 0 } else { 2 };
+            encoder.clear_buffer(&self.nodes_bitmap, 0, None);
+            self.record_step(&mut encoder, first_step);
+            self.record_step(&mut encoder, first_step + 1);
+            self.rounds_run = round + 1;
+
+            if self.cancel.is_some() {
+                self.queue.submit(Some(std::mem::replace(&mut encoder, self.encoder()).finish()));
+                DeviceFuture::work_done(&self.device, &self.queue).await?;
+                check_cancelled(self.cancel.as_ref())?;
+            }
+
+            if self.early_exit.is_enabled() || self.progress.is_some() {
+                self.queue.submit(Some(std::mem::replace(&mut encoder, self.encoder()).finish()));
+                let count = self.read_edges_bitmap().await?.iter().map(|word| u64::from(word.count_ones())).sum();
+                let previous = std::mem::replace(&mut survivors, count);
+                report_round(&mut self.progress, self.rounds_run, survivors, start_time)?;
+                if self.early_exit.should_stop(self.rounds_run, previous, survivors) {
+                    break;
+                }
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        let bitmap = self.read_edges_bitmap().await?;
+        Ok(surviving_indices(&bitmap))
+    }
+
+    /// Create an encoder for trimming commands
+    fn encoder(&self) -> wgpu::CommandEncoder {
+        self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("lean trimming") })
+    }
+
+    /// Copy the edges bitmap to the host once the submitted work is done
+    async fn read_edges_bitmap(&self) -> Result<Vec<u32>> {
+        let mut encoder = self.encoder();
+        encoder.copy_buffer_to_buffer(&self.edges_bitmap, 0, &self.readback, 0, self.readback.size());
+        self.queue.submit(Some(encoder.finish()));
+
+        DeviceFuture::map_read(&self.device, &self.readback).await?;
+        let bitmap = {
+            let mapped = self.readback.slice(..).get_mapped_range();
+            mapped.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect::<Vec<_>>()
+        };
+        self.readback.unmap();
+        Ok(bitmap)
+    }
+
+    /// Record one trimming step over every edges bitmap word, in its own
+    /// compute pass so it sees the previous step's bitmaps
+    fn record_step(&self, encoder: &mut wgpu::CommandEncoder, step: usize) {
+        let (x, y) = workgroups(self.words);
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(STEPS[step]), timestamp_writes: None });
+        pass.set_pipeline(&self.steps[step]);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(x, y, 1);
+    }
+
+    /// `Params` uniform of the shaders: the keys and node mask as (low,
+    /// high) word pairs, then the bitmap words
+    fn params_bytes(&self, keys: &SipKeys) -> [u8; PARAMS_BYTES] {
+        let mask = node_mask(self.edge_bits);
+        let mut values = Vec::with_capacity(PARAMS_BYTES / 4);
+        for word in keys.words().into_iter().chain([mask]) {
+            values.extend([word as u32, (word >> 32) as u32]);
+        }
+        values.extend([self.words, 0]);
+        let mut bytes = [0; PARAMS_BYTES];
+        bytes.copy_from_slice(&to_bytes(&values));
+        bytes
+    }
+}
+
+/// Size of the `Params` uniform
+const PARAMS_BYTES: usize = 48;
+
+/// Every adapter of every backend wgpu was built with
+fn all_adapters() -> Vec<wgpu::Adapter> {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor::default()).enumerate_adapters(wgpu::Backends::all())
+}
+
+/// Workgroups covering `words` invocations, spread over two dimensions so
+/// neither exceeds `MAX_WORKGROUPS_PER_DIMENSION`
+fn workgroups(words: u32) -> (u32, u32) {
+    let groups = words.div_ceil(WORKGROUP_SIZE);
+    let y = groups.div_ceil(MAX_WORKGROUPS_PER_DIMENSION);
+    (groups.div_ceil(y), y)
+}
+
+/// Waits for a device callback: `map_async` of a whole buffer for reading,
+/// or the submitted work being done
+struct DeviceFuture<'a> {
+    device: &'a wgpu::Device,
+    state: Arc<Mutex<MapState>>,
+}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<std::result::Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+impl<'a> DeviceFuture<'a> {
+    fn map_read(device: &'a wgpu::Device, buffer: &wgpu::Buffer) -> Self {
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let callback_state = Arc::clone(&state);
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| complete(&callback_state, result));
+        Self { device, state }
+    }
+
+    fn work_done(device: &'a wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let callback_state = Arc::clone(&state);
+        queue.on_submitted_work_done(move || complete(&callback_state, Ok(())));
+        Self { device, state }
+    }
+}
+
+/// Store a callback's result and wake the future waiting for it
+fn complete(state: &Mutex<MapState>, result: std::result::Result<(), wgpu::BufferAsyncError>) {
+    let mut state = state.lock().unwrap();
+    state.result = Some(result);
+    if let Some(waker) = state.waker.take() {
+        waker.wake();
+    }
+}
+
+impl Future for DeviceFuture<'_> {
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Callbacks only run while the device is polled
+        self.device.poll(wgpu::Maintain::Poll);
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result.map_err(device_error)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            },
+        }
+    }
+}
+
+fn to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// Indices of the set bits of a bitmap of 32-bit words
+fn surviving_indices(bitmap: &[u32]) -> Vec<u64> {
+    let mut indices = Vec::new();
+    for (word_index, &word) in bitmap.iter().enumerate() {
+        let mut unit = word;
+        while unit != 0 {
+            indices.push(word_index as u64 * WORD_EDGES + unit.trailing_zeros() as u64);
+            unit &= unit - 1;
+        }
+    }
+    indices
+}
+
+fn device_error(error: impl std::fmt::Display) -> CuckatooError {
+    CuckatooError::DeviceError(format!("wgpu: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitmapTrimmer, SipHash, SipHasher};
+
+    #[test]
+    fn test_workgroups_cover_bitmap() {
+        assert_eq!(workgroups(1), (1, 1));
+        assert_eq!(workgroups(1 << 20), (1 << 14, 1));
+        let (x, y) = workgroups(1 << 27);
+        assert!(x <= MAX_WORKGROUPS_PER_DIMENSION && u64::from(x * y) * 64 >= 1 << 27);
+        assert_eq!(surviving_indices(&[0b101, 0, 1 << 31]), [0, 2, 95]);
+    }
+
+    #[test]
+    fn test_wgpu_trimming_matches_cpu() {
+        // Machines without a GPU adapter only run the host-side test
+        if WgpuLeanTrimmer::adapters().is_empty() {
+            return;
+        }
+        let keys = SipKeys::from_header(b"wgpu", 5);
+        let siphash = SipHash::with_key(keys);
+        let mut gpu = WgpuLeanTrimmer::new(12, 0).unwrap();
+        for rounds in [1, 2, 7] {
+            let expected = BitmapTrimmer::new(12).trim_edges(&siphash, rounds).unwrap();
+            let survivors: Vec<_> = gpu.trim(&keys, rounds).unwrap().into_iter().map(|index| siphash.hash_pair(index, 12)).collect();
+            assert_eq!(survivors, expected, "{} rounds on {}", rounds, gpu.adapter_name());
+        }
+
+        let mut gpu = gpu.with_early_exit(EarlyExit::new().stop_on_plateau());
+        let survivors: Vec<_> = gpu.trim(&keys, 90).unwrap().into_iter().map(|index| siphash.hash_pair(index, 12)).collect();
+        assert!(gpu.rounds_run() < 90);
+        assert_eq!(survivors, BitmapTrimmer::new(12).trim_edges(&siphash, 90).unwrap());
+
+        let token = CancelToken::new();
+        let mut gpu = gpu.with_cancel_token(token.clone());
+        token.cancel();
+        assert!(matches!(gpu.trim(&keys, 5), Err(CuckatooError::Cancelled)));
+        assert_eq!(gpu.rounds_run(), 1);
+    }
+}
@@ -0,0 +1,346 @@
+//! Jobs file batch mode
+//!
+//! Mines a list of jobs read from a JSON Lines file, one after another,
+//! for offline reprocessing and research sweeps across many headers. Each
+//! line names a header (hex pre-PoW bytes), a nonce range and optionally
+//! the edge bits:
+//!
+//! ```text
+//! {"header":"0102ab","start_nonce":0,"end_nonce":100,"edge_bits":16}
+//! ```
+//!
+//! `end_nonce` is exclusive and `edge_bits` defaults to `--edge-bits`.
+//! Blank lines and lines starting with `#` are skipped.
+//!
+//! Each line is a job, identified by its line number or an optional
+//! `job_id`: a job counts as received when mining a line of it first
+//! starts, and every verified solution as a share submitted for it, so
+//! `cuckatoo_share_latency_seconds` measures job receipt to share. A line
+//! going back to an earlier job after another one was received stands for
+//! work finishing after a job change: its shares are only submitted within
+//! `--stale-grace` of the change, and discarded after that.
+
+use cuckatoo_core::prelude::*;
+use cuckatoo_core::{format_seconds, json_seconds, reverify_solution, MetricsRegistry, Result, ShareLatencyTracker, Solver, TrimmerPool};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Instant;
+
+/// One line of a jobs file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchJob {
+    /// Line of the jobs file, from 1
+    pub line: usize,
+    /// Job the line belongs to (its line number unless given)
+    pub job_id: u64,
+    /// Pre-PoW header bytes
+    pub header: Vec<u8>,
+    /// Nonces to mine
+    pub nonces: Range<u64>,
+    /// Edge bits of the job's graphs
+    pub edge_bits: u32,
+}
+
+impl BatchJob {
+    /// Parse a jobs file line, using `default_edge_bits` if it has none
+    pub fn parse(line: usize, text: &str, default_edge_bits: u32) -> Result<Self> {
+        let invalid = |message: String| CuckatooError::InternalError(format!("Invalid job on line {}: {}", line, message));
+        let body = text
+            .trim()
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+            .ok_or_else(|| invalid("expected a JSON object".to_string()))?;
+
+        let mut fields = HashMap::new();
+        for field in body.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+            let (name, value) = field.split_once(':').ok_or_else(|| invalid(format!("no ':' in {:?}", field)))?;
+            fields.insert(name.trim().trim_matches('"'), value.trim().trim_matches('"'));
+        }
+        let field = |name: &str| fields.get(name).copied().ok_or_else(|| invalid(format!("missing {}", name)));
+        let number = |name: &str| field(name)?.parse::<u64>().map_err(|_| invalid(format!("bad {}", name)));
+
+        let hex = field("header")?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(invalid("header must be an even number of hex digits".to_string()));
+        }
+        let header = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<std::result::Result<Vec<u8>, _>>()
+            .map_err(|_| invalid("bad header".to_string()))?;
+
+        let nonces = number("start_nonce")?..number("end_nonce")?;
+        if nonces.is_empty() {
+            return Err(invalid(format!("empty nonce range {}..{}", nonces.start, nonces.end)));
+        }
+        let edge_bits = match fields.get("edge_bits") {
+            Some(bits) => bits.parse().map_err(|_| invalid("bad edge_bits".to_string()))?,
+            None => default_edge_bits,
+        };
+        let job_id = match fields.get("job_id") {
+            Some(id) => id.parse().map_err(|_| invalid("bad job_id".to_string()))?,
+            None => line as u64,
+        };
+        Ok(Self { line, job_id, header, nonces, edge_bits })
+    }
+}
+
+/// Read every job of a jobs file
+pub fn load_jobs(path: &str, default_edge_bits: u32) -> Result<Vec<BatchJob>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| CuckatooError::InternalError(format!("Can't read jobs file {}: {}", path, error)))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| BatchJob::parse(index + 1, line, default_edge_bits))
+        .collect()
+}
+
+/// What mining one job found
+struct JobSummary {
+    job: BatchJob,
+    /// Nonces mined
+    graphs: u64,
+    /// Verified solutions as (nonce, proof)
+    solutions: Vec<(u64, Vec<u64>)>,
+    /// Graphs that failed with an error or an invalid proof
+    failures: u64,
+    trim_seconds: f64,
+    search_seconds: f64,
+    elapsed: f64,
+}
+
+impl JobSummary {
+    /// Format the summary as a single-line JSON object
+    ///
+    /// Timings are left out in deterministic mode.
+    fn to_json(&self, deterministic: bool) -> String {
+        let solutions: Vec<String> = self
+            .solutions
+            .iter()
+            .map(|(nonce, proof)| {
+                let proof: Vec<String> = proof.iter().map(u64::to_string).collect();
+                format!("{{\"nonce\":{},\"proof\":[{}]}}", nonce, proof.join(","))
+            })
+            .collect();
+        let mut fields = vec![
+            format!("\"line\":{}", self.job.line),
+            format!("\"edge_bits\":{}", self.job.edge_bits),
+            format!("\"start_nonce\":{}", self.job.nonces.start),
+            format!("\"end_nonce\":{}", self.job.nonces.end),
+            format!("\"graphs\":{}", self.graphs),
+            format!("\"solutions\":[{}]", solutions.join(",")),
+            format!("\"failures\":{}", self.failures),
+        ];
+        if !deterministic {
+            fields.push(format!("\"trimming_time\":{}", json_seconds(self.trim_seconds)));
+            fields.push(format!("\"searching_time\":{}", json_seconds(self.search_seconds)));
+            fields.push(format!("\"elapsed\":{}", json_seconds(self.elapsed)));
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+/// Mine every job of a jobs file in order, printing a summary per job
+///
+/// Each job runs with `config`, apart from its own edge bits, and records
+/// its graphs into `registry` as worker `line-<N>`. With `json_output`,
+/// the summaries are also written there as JSON Lines. Returns false if
+/// any graph failed or yielded an invalid proof.
+pub fn run_jobs(path: &str, config: &Config, json_output: Option<&str>, registry: &MetricsRegistry) -> Result<bool> {
+    let jobs = load_jobs(path, config.edge_bits)?;
+    println!("Jobs file {}: {} job(s)", path, jobs.len());
+
+    // Consecutive jobs of the same size keep the solver and only rebind it;
+    // the pool keeps the buffers of other sizes for later jobs
+    let pool = TrimmerPool::new();
+    let mut solver = None;
+    let shares = ShareLatencyTracker::new(registry.clone()).with_stale_grace(config.stale_share_grace);
+    let mut lines = Vec::new();
+    let mut passed = true;
+    for job in jobs {
+        let summary = run_job(job, config, &pool, registry, &shares, &mut solver)?;
+        println!("Job on line {}: EDGE_BITS={} nonces {}..{}: {} graph(s), {} solution(s), {} failure(s) in {} (trim {}, search {})",
+                 summary.job.line, summary.job.edge_bits, summary.job.nonces.start, summary.job.nonces.end,
+                 summary.graphs, summary.solutions.len(), summary.failures, format_seconds(summary.elapsed),
+                 format_seconds(summary.trim_seconds), format_seconds(summary.search_seconds));
+        for (nonce, proof) in &summary.solutions {
+            println!("  Solution at nonce {}: {:?}", nonce, proof);
+        }
+        passed &= summary.failures == 0;
+        lines.push(summary.to_json(config.deterministic));
+    }
+    if let Some(solver) = solver {
+        solver.release(&pool);
+    }
+
+    if let Some(path) = json_output {
+        let mut text = lines.join("\n");
+        text.push('\n');
+        std::fs::write(path, text)
+            .map_err(|error| CuckatooError::InternalError(format!("Can't write {}: {}", path, error)))?;
+        println!("Wrote job summaries to {}", path);
+    }
+    Ok(passed)
+}
+
+/// Mine one job's nonce range, submitting its solutions as shares
+///
+/// `current` is the previous job's solver, rebound to this job if it
+/// solves graphs of the same size and replaced otherwise.
+fn run_job(
+    job: BatchJob,
+    template: &Config,
+    pool: &TrimmerPool,
+    registry: &MetricsRegistry,
+    shares: &ShareLatencyTracker,
+    current: &mut Option<Solver>,
+) -> Result<JobSummary> {
+    let mut config = template.clone();
+    config.edge_bits = job.edge_bits;
+    config.validate()?;
+    shares.job_received(job.job_id);
+
+    let solver = match current.take() {
+        Some(solver) if solver.config().edge_bits == job.edge_bits => solver,
+        previous => {
+            if let Some(previous) = previous {
+                previous.release(pool);
+            }
+            Solver::from_pool(config, pool)?.with_metrics(registry.clone())
+        },
+    };
+    let worker_name = format!("line-{}", job.line);
+    let solver = current.insert(solver.with_worker(registry.worker(&worker_name)));
+    let start = Instant::now();
+    let mut solutions = Vec::new();
+    let mut failures = 0;
+    for nonce in job.nonces.clone() {
+        solver.rebind(&Header::new_with_nonce(&job.header, nonce));
+        let keys = solver.keys();
+        match solver.solve() {
+            Ok(None) => {},
+            Ok(Some(proof)) => match reverify_solution(&keys, job.edge_bits, &proof) {
+                Ok(()) => {
+                    if shares.freshness(job.job_id).is_submittable() {
+                        shares.share_submitted(job.job_id);
+                    } else {
+                        println!("  Solution at nonce {} is for expired job {}, not submitted", nonce, job.job_id);
+                        shares.share_discarded();
+                    }
+                    solutions.push((nonce, proof));
+                },
+                Err(error) => {
+                    eprintln!("{}", error);
+                    registry.record_error(&error);
+                    failures += 1;
+                },
+            },
+            Err(error) => {
+                eprintln!("Job on line {} failed at nonce {}: {}", job.line, nonce, error);
+                registry.record_error(&error);
+                failures += 1;
+            },
+        }
+    }
+
+    let workers = registry.snapshot().workers();
+    let (trim_seconds, search_seconds) = workers
+        .iter()
+        .find(|worker| worker.worker == worker_name)
+        .map_or((0.0, 0.0), |worker| (worker.trim_seconds, worker.search_seconds));
+    Ok(JobSummary {
+        graphs: job.nonces.end - job.nonces.start,
+        job,
+        solutions,
+        failures,
+        trim_seconds,
+        search_seconds,
+        elapsed: start.elapsed().as_secs_f64(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuckatoo_core::self_test::{KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_HEADER, KNOWN_PROOF_NONCE};
+
+    /// Write a jobs file with one job per line and return its path
+    fn jobs_file(name: &str, lines: &[String]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("cuckatoo-jobs-{}-{}.jsonl", name, std::process::id()));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    /// Jobs file line mining the known proof's header from `start`
+    fn known_proof_job(start: u64, end: u64) -> String {
+        let header: String = KNOWN_PROOF_HEADER.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("{{\"header\":\"{}\",\"start_nonce\":{},\"end_nonce\":{}}}", header, start, end)
+    }
+
+    #[test]
+    fn test_jobs_record_share_latency() {
+        let path = jobs_file("latency", &[known_proof_job(KNOWN_PROOF_NONCE, KNOWN_PROOF_NONCE + 1)]);
+        let registry = MetricsRegistry::new();
+        assert!(run_jobs(path.to_str().unwrap(), &Config::new(KNOWN_PROOF_EDGE_BITS), None, &registry).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        // The known proof is a share of the file's only job
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("cuckatoo_shares_submitted_total"), Some(1));
+        assert_eq!(snapshot.counter("cuckatoo_shares_unknown_job_total"), None);
+        assert_eq!(snapshot.histogram("cuckatoo_share_latency_seconds").unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_jobs_reuse_solver() {
+        // Two entries of the same job, the second holding the known proof
+        let path = jobs_file("reuse", &[
+            known_proof_job(KNOWN_PROOF_NONCE + 1, KNOWN_PROOF_NONCE + 3),
+            known_proof_job(KNOWN_PROOF_NONCE, KNOWN_PROOF_NONCE + 1),
+        ]);
+        let registry = MetricsRegistry::new();
+        assert!(run_jobs(path.to_str().unwrap(), &Config::new(KNOWN_PROOF_EDGE_BITS), None, &registry).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        // One solver set up, rebound for every nonce of both entries, still
+        // finding the proof in the second
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.histogram("cuckatoo_solver_setup_seconds").unwrap().count, 1);
+        assert_eq!(snapshot.histogram("cuckatoo_solver_rebind_seconds").unwrap().count, 3);
+        assert_eq!(snapshot.counter("cuckatoo_shares_submitted_total"), Some(1));
+        let graphs: Vec<u64> = snapshot.workers().iter().map(|worker| worker.graphs).collect();
+        assert_eq!(graphs, [2, 1]);
+    }
+
+    #[test]
+    fn test_jobs_after_job_change() {
+        // Job 1 finds its solution only after job 2 was received
+        let lines = [
+            known_proof_job(KNOWN_PROOF_NONCE + 1, KNOWN_PROOF_NONCE + 2).replace('{', "{\"job_id\":1,"),
+            known_proof_job(KNOWN_PROOF_NONCE + 1, KNOWN_PROOF_NONCE + 2).replace('{', "{\"job_id\":2,"),
+            known_proof_job(KNOWN_PROOF_NONCE, KNOWN_PROOF_NONCE + 1).replace('{', "{\"job_id\":1,"),
+        ];
+        let path = jobs_file("stale", &lines);
+        assert_eq!(load_jobs(path.to_str().unwrap(), 12).unwrap()[2].job_id, 1);
+
+        // Within the grace window the share is still submitted, as stale
+        let mut config = Config::new(KNOWN_PROOF_EDGE_BITS);
+        config.stale_share_grace = std::time::Duration::from_secs(600);
+        let registry = MetricsRegistry::new();
+        assert!(run_jobs(path.to_str().unwrap(), &config, None, &registry).unwrap());
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("cuckatoo_stale_shares_submitted_total"), Some(1));
+        assert_eq!(snapshot.counter("cuckatoo_shares_submitted_total"), None);
+
+        // Without one it is discarded
+        config.stale_share_grace = std::time::Duration::ZERO;
+        let registry = MetricsRegistry::new();
+        assert!(run_jobs(path.to_str().unwrap(), &config, None, &registry).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counter("cuckatoo_stale_shares_discarded_total"), Some(1));
+        assert_eq!(snapshot.counter("cuckatoo_stale_shares_submitted_total"), None);
+        assert!(snapshot.histogram("cuckatoo_share_latency_seconds").is_none());
+    }
+}
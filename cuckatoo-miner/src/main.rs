@@ -3,71 +3,299 @@
 //! This implements the CLI interface for the Cuckatoo Reference Miner
 //! with parity to the C++ version as specified in Milestone 1.
 
-use cuckatoo_core::{
-    Config, TrimmingMode, CycleVerifier,
-    hashing::SipHash, Header,
-    blake2b, Edge, Node
-};
+use cuckatoo_core::prelude::*;
+use cuckatoo_core::{duplicate_edges, format_memory_size, CapabilityReport, EdgeIterator, EdgeList, EdgeListFormat, entropy_start_nonce, EndpointCache, parse_memory_size, EdgeThroughput, EnergyReport, format_duration, format_rate, json_seconds, set_time_unit, TimeUnit, FixedPower, MemoryBudget, MemoryEstimate, KeyDerivation, KeySchedule, NonceSchedule, PowerSource, Preset, Graph, RaplPower, ShareLatencyTracker, SubmissionPolicy, Solution, SinkSet, SolutionSink, open_sink, proof_difficulty, ValidationPolicy, StageAllocations, StageTracker, GraphSeed, ReplayRecord, ReplayRecorder, Solver, profiling, recover_proof, reverify_solution, self_test, Delta, RoundProgress, RunBaseline, TrimCheckpoint, HistogramSnapshot, ConfigWarning, CounterTrimmer, NumaPolicy, Preflight, SolverStrategy, warnings_to_json, DEFAULT_MIN_REDUCTION, DEFAULT_PLATEAU_ROUNDS, MetricsServer};
+use std::ops::ControlFlow;
 use std::time::Instant;
 use std::env;
 
+mod jobs;
+mod stress;
+
+/// Job id of the locally built header, for share latency tracking
+const LOCAL_JOB_ID: u64 = 0;
+
+/// Trimming rounds between checkpoints written with `--checkpoint`
+const CHECKPOINT_ROUNDS: u32 = 10;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Cuckatoo Reference Miner v0.1.0 (Rust)");
     
     // Parse command line arguments
     let args: Vec<String> = env::args().collect();
-    let config = parse_args(&args)?;
+    if args.get(1).map(String::as_str) == Some("self-test") {
+        if !run_self_test() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("replay") {
+        let source = args.get(2).ok_or("Missing replay record path or graph seed")?;
+        run_replay(source)?;
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("verify-edges") {
+        let path = args.get(2).ok_or("Missing edge list path")?;
+        let format = args.get(3).map_or(Ok(EdgeListFormat::Text), |format| format.parse())?;
+        run_verify_edges(path, format)?;
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("stress") {
+        let stress_args = stress::parse_stress_args(&args[2..])?;
+        set_time_unit(stress_args.time_unit);
+        if !stress::run_stress(&stress_args)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let cli = parse_args(&args)?;
+    set_time_unit(cli.time_unit);
+    let registry = MetricsRegistry::new();
+    let _metrics_server = cli.metrics_addr.as_deref().map(|address| serve_metrics(address, &registry)).transpose()?;
+    if let Some(path) = &cli.jobs_file {
+        if !jobs::run_jobs(path, &cli.config, cli.json_output.as_deref(), &registry)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let mut config = cli.config;
+    let replay = cli.replay_dir.as_deref().map(ReplayRecorder::new).transpose()?;
+    let mut sinks = SinkSet::new();
+    for spec in &cli.sinks {
+        sinks.push(open_sink(spec)?);
+    }
     
-    println!("Configuration: EDGE_BITS={}, mode={}, rounds={}, tuning={}", 
-             config.edge_bits, config.mode, config.trimming_rounds, config.tuning);
+    println!("Configuration: EDGE_BITS={}, mode={}, rounds={}, tuning={}, keys={}, nonce schedule={}, submission={}", 
+             config.edge_bits, config.mode, config.trimming_rounds, config.tuning, config.key_schedule,
+             config.nonce_schedule, config.submission_policy);
+    if config.deterministic {
+        println!("Deterministic mode: adaptive trimming disabled, timings omitted from JSON output");
+    }
+    if let Some(threshold) = config.adaptive_threshold {
+        println!("Adaptive trimming: stop below {:.2}% reduction per round for {} rounds (max {} rounds)", 
+                 threshold * 100.0, config.plateau_rounds, config.trimming_rounds);
+    }
+    if let Some(multiple) = config.target_survivors {
+        println!("Target survivors: {}x cycle length (max {} rounds)", 
+                 multiple, config.trimming_rounds);
+    }
     
     // Validate configuration
     config.validate()?;
     
+    // Keep the run within the memory budget, falling back to leaner trimming
+    if let Some(budget) = MemoryBudget::for_config(&config) {
+        let mode = budget.select_mode(config.edge_bits, config.mode)?;
+        if mode != config.mode {
+            println!("Memory budget: {} trimming doesn't fit, using {}", config.mode, mode);
+            config.mode = mode;
+        }
+        println!("Memory budget: {} of {}", MemoryEstimate::for_config(&config), format_memory_size(budget.max_bytes));
+    }
+    
+    // Flag configurations that will run, but probably not as intended
+    // (host memory is left out in deterministic mode to keep output stable)
+    let mut preflight = if config.deterministic { Preflight::new() } else { Preflight::for_host() };
+    if let Some(preset) = cli.preset {
+        preflight = preflight.with_preset(preset);
+    }
+    let warnings = preflight.check(&config);
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    registry.record_config_warnings(&warnings);
+    
+    // Report what this run will use, after budget fallbacks
+    match cli.banner {
+        BannerFormat::Text => println!("{}", CapabilityReport::new(&config).with_warnings(warnings.clone())),
+        BannerFormat::Json => println!("{}", CapabilityReport::new(&config).with_warnings(warnings.clone()).to_json()),
+        BannerFormat::None => {},
+    }
+    
+    // Collect profiling scopes (only with the profiling feature)
+    profiling::enable();
+    
+    // Measure the power draw over the whole pipeline
+    let mut power_source = cli.power_source;
+    if let Some(source) = power_source.as_mut() {
+        source.start();
+    }
+    let run_start = Instant::now();
+    
     // Test header (simple test data for tuning mode)
     // C++ HEADER_SIZE is 238 bytes: 2 + 8 + 8 + 32*5 + 32 + 8*3 + 4 = 238
     let mut header_data = [0u8; 238];
     header_data[0] = 0x01; // Add some non-zero data
     header_data[1] = 0x02;
     let header = Header::new(&header_data);
-    let nonce = 12345u64; // Use non-zero nonce
+    // The header stands in for a stratum job; a verified solution is its share
+    let share_latency = ShareLatencyTracker::new(registry.clone()).with_stale_grace(config.stale_share_grace);
+    share_latency.job_received(LOCAL_JOB_ID);
+    let start_nonce = match cli.start_nonce {
+        StartNonce::Fixed(nonce) => nonce,
+        StartNonce::Entropy => {
+            let nonce = entropy_start_nonce();
+            println!("Start nonce: {} (from OS entropy, rerun with --start-nonce {} to reproduce)", nonce, nonce);
+            nonce
+        },
+    };
+    let mut nonce_scheduler = config.nonce_schedule.scheduler(start_nonce);
+    let nonce = nonce_scheduler.next_nonce();
+    println!("Nonce: {}", nonce);
     
-    // Generate SipHash keys using Blake2b (exact C++ approach)
-    println!("Generating SipHash keys using exact C++ implementation...");
+    // Generate SipHash keys with the configured derivation
+    println!("Generating SipHash keys ({})...", config.key_schedule);
     let start_time = Instant::now();
-    let keys = blake2b(header.as_bytes(), nonce);
+    let keys = config.key_schedule.derive_keys(header.as_bytes(), nonce);
     let siphash = SipHash::with_key(keys);
     let generation_time = start_time.elapsed();
     
-    println!("Generated SipHash keys in {:.6}s", generation_time.as_secs_f64());
-    println!("SipHash keys: [0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:016x}]", 
-             keys[0], keys[1], keys[2], keys[3]);
+    println!("Generated SipHash keys in {}", format_duration(generation_time));
+    println!("SipHash keys: {}", keys);
     
     // Generate edges using SipHash (matching C++ exactly)
     println!("Generating edges using SipHash (C++ method)...");
     let edge_start = Instant::now();
-    let edges = generate_edges_cpp_style(&keys, config.edge_bits);
+    let edge_allocations = StageTracker::start();
+    let edges = generate_edges_cpp_style(&siphash, config.edge_bits, config.threads);
+    let edge_allocations = edge_allocations.map(StageTracker::finish);
     let edge_time = edge_start.elapsed();
     
-    println!("Generated {} edges in {:.6}s", edges.len(), edge_time.as_secs_f64());
+    println!("Generated {} edges in {}", edges.len(), format_duration(edge_time));
     
     // Print timing information as specified in requirements
-    println!("Edge generation time: {:.6}s", edge_time.as_secs_f64());
+    println!("Edge generation time: {}", format_duration(edge_time));
+    
+    // Edges are stored as [edge_index, node_u, node_v] u64 triples
+    let edge_throughput = EdgeThroughput::new(
+        (edges.len() / 3) as u64,
+        std::mem::size_of_val(edges.as_slice()) as u64,
+        edge_time.as_secs_f64(),
+    );
+    registry.record_edge_throughput(&edge_throughput);
+    println!("Edge generation throughput: {}, {}",
+             format_rate(edge_throughput.megaedges_per_second(), "Medges"),
+             format_rate(edge_throughput.bytes_per_second() / 1e6, "MB"));
+    
+    // Optionally look for multi-edges, which can fool simple cycle checkers
+    if cli.check_duplicates {
+        let duplicates = duplicate_edges(&flat_edges(&edges));
+        registry.gauge("cuckatoo_duplicate_edges").set(duplicates.len() as f64);
+        println!("Duplicate edges: {}", duplicates.len());
+        for (first, duplicate) in duplicates.iter().take(10) {
+            println!("  Edge {} duplicates edge {}", duplicate, first);
+        }
+    }
+    
+    // Optionally spot check the generated endpoints against the core hasher
+    if let Some(sample_rate) = cli.audit_rate {
+        let graph = Graph::from_edges(keys, config.edge_bits, flat_edges(&edges))?;
+        let report = graph.audit(sample_rate);
+        println!("Edge audit: {} of {} sampled edges mismatched", report.mismatches.len(), report.checked);
+        if let Some(&index) = report.mismatches.first() {
+            graph.check_edge(index)?;
+        }
+    }
+    
+    // Trim edges using bitmap-based lean trimming
+    println!("Trimming edges ({} mode)...", config.mode);
+    let trim_start = Instant::now();
+    let trim_allocations = StageTracker::start();
+    let (surviving_edges, rounds_run, survivor_curve) = if config.mode == TrimmingMode::LeanCounter {
+        // 2-bit node degree counters; no progress or checkpoints
+        let output = CounterTrimmer::try_new(config.edge_bits)?.trim(&siphash, config.trimming_rounds)?;
+        (output.edges(&siphash, config.edge_bits), output.rounds_run, output.per_round_survivors)
+    } else {
+        let mut trimmer = BitmapTrimmer::for_config(&config)?
+            .with_metrics(registry.clone())
+            .with_survivor_curve()
+            .with_threads(config.threads)
+            .with_numa_policy(config.numa_policy);
+        if config.numa_policy == NumaPolicy::Local {
+            match trimmer.numa_nodes() {
+                1 => println!("Single NUMA node, trimming threads are not pinned"),
+                nodes => println!("Trimming threads pinned across {} NUMA nodes", nodes),
+            }
+        }
+        if trimmer.is_disk_backed() {
+            println!("Trimming bitmaps are disk-backed in {}",
+                     config.spill_dir.clone().unwrap_or_else(std::env::temp_dir).display());
+        } else if config.use_huge_pages && !trimmer.is_huge_page_backed() {
+            println!("Huge pages unavailable, trimming bitmaps use ordinary pages");
+        }
+        if cli.progress {
+            trimmer = trimmer.with_progress(|progress: &RoundProgress| {
+                eprintln!("Round {}: {} edges survive after {}",
+                          progress.round, progress.surviving_edges, format_duration(progress.elapsed));
+                ControlFlow::Continue(())
+            });
+        }
+        let mut resume = false;
+        if let Some(path) = &cli.checkpoint {
+            let sink_path = path.clone();
+            trimmer = trimmer.with_checkpoints(CHECKPOINT_ROUNDS, move |bytes: &[u8]| {
+                if let Err(error) = write_checkpoint(&sink_path, bytes) {
+                    eprintln!("Cannot write checkpoint {}: {}", sink_path, error);
+                }
+            });
+            if let Ok(bytes) = std::fs::read(path) {
+                match TrimCheckpoint::from_bytes(&bytes) {
+                    Ok(checkpoint) if checkpoint.keys == keys && checkpoint.edge_bits == config.edge_bits && config.round_controller().is_none() => {
+                        trimmer.restore_checkpoint(&bytes)?;
+                        println!("Resuming trim after round {} from checkpoint {}", checkpoint.rounds_run, path);
+                        resume = true;
+                    },
+                    Ok(_) => println!("Ignoring checkpoint {} of another graph", path),
+                    Err(error) => println!("Ignoring checkpoint {}: {}", path, error),
+                }
+            }
+        }
+        let (surviving_edges, rounds_run) = match config.endpoint_cache {
+            Some(bytes) => {
+                let cache = EndpointCache::new(SipHash::with_key(keys), bytes)?;
+                let trimmed = trim_graph(&mut trimmer, &config, &cache, resume)?;
+                let stats = cache.stats();
+                registry.counter("cuckatoo_endpoint_cache_hits_total").add(stats.hits);
+                registry.counter("cuckatoo_endpoint_cache_misses_total").add(stats.misses);
+                println!("Endpoint cache ({}): {} hits, {} misses ({:.1}% hit rate)",
+                         format_memory_size(cache.size_bytes()), stats.hits, stats.misses, stats.hit_rate() * 100.0);
+                trimmed
+            },
+            None => trim_graph(&mut trimmer, &config, &siphash, resume)?,
+        };
+        if let Some(path) = &cli.checkpoint {
+            // The trim is done, a later run must not resume it
+            let _ = std::fs::remove_file(path);
+        }
+        let survivor_curve = trimmer.survivor_curve().to_vec();
+        (surviving_edges, rounds_run, survivor_curve)
+    };
+    let trim_allocations = trim_allocations.map(StageTracker::finish);
+    let trim_time = trim_start.elapsed();
+    
+    println!("Trimmed to {} survivors in {} ({} rounds)", 
+             surviving_edges.len(), format_duration(trim_time), rounds_run);
+    // The counter trimmer always runs the fixed rounds
+    let auto_rounds = cli.auto_rounds && config.round_controller().is_some() && config.mode != TrimmingMode::LeanCounter;
+    if auto_rounds {
+        println!("Auto trimming chose {} rounds (pass --trimming-rounds {} to fix it)", rounds_run, rounds_run);
+    }
     
     // Test SipHash implementation correctness
     println!("Testing SipHash implementation correctness...");
     let verify_start = Instant::now();
+    let verify_allocations = StageTracker::start();
     
     // Test with known values to verify SipHash matches C++
-    let test_keys = [0x736f6d6570736575, 0x646f72616e646f6d, 0x6c7967656e657261, 0x7465646279746573];
+    let test_siphash = SipHash::with_key(self_test::SIPHASH_TEST_KEYS);
     let test_nonce = 0x123456789abcdef0;
     
     // Test SipHash with our implementation
-    let test_node = siphash24_single(&test_keys, test_nonce, 12);
+    let test_node = test_siphash.hash_node(test_nonce, 12).value();
     println!("SipHash test result: 0x{:016x}", test_node);
     
     // Test edge generation
-    let test_edges = generate_edges_cpp_style(&test_keys, 10);
+    let test_edges = generate_edges_cpp_style(&test_siphash, 10, 1);
     println!("Generated {} test edges", test_edges.len());
     
     // Print first few edges for verification
@@ -77,25 +305,97 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                  i, test_edges[edge_idx], test_edges[edge_idx + 1], test_edges[edge_idx + 2]);
     }
     
-    let found_solution = false; // Temporarily disabled
+    // Search the trimmed graph; solutions are only reported after the
+    // consensus verifier independently accepted them
+    let failed_graph = |failure: &CuckatooError, proof: Option<Vec<u64>>| ReplayRecord {
+        keys,
+        edge_bits: config.edge_bits,
+        trimming_rounds: rounds_run,
+        finder: CycleVerifier::NAME.to_string(),
+        failure: failure.to_string(),
+        proof,
+    };
+    // One-line seed of the graph, printed with each failure for bug reports
+    let seed = GraphSeed::new(header.as_bytes(), nonce, config.edge_bits, rounds_run, config.mode);
+    let mut verifier = CycleVerifier::new().with_threads(config.threads).with_solver_strategy(config.solver_strategy).with_metrics(registry.clone());
+    let search = verifier.find_42_cycle(&surviving_edges);
+    if let Err(error) = &search {
+        registry.record_error(error);
+        eprintln!("Graph seed: {}", seed.to_json());
+        if let Some(replay) = &replay {
+            record_replay(replay, &failed_graph(error, None));
+        }
+    }
+    let solution = match search? {
+        Some(cycle) => {
+            // An unrecoverable cycle fails reverification like any bad proof
+            let nonces = recover_proof(&keys, config.edge_bits, &cycle, config.threads).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                Vec::new()
+            });
+            match reverify_solution(&keys, config.edge_bits, &nonces) {
+                Ok(()) => {
+                    let difficulty = proof_difficulty(&nonces, config.edge_bits);
+                    println!("Solution difficulty: {}", difficulty);
+                    registry.record_proof_difficulty(difficulty);
+                    let mut submitted = config.submission_policy.select(vec![nonces], config.edge_bits, cli.share_difficulty);
+                    let freshness = share_latency.freshness(LOCAL_JOB_ID);
+                    if !submitted.is_empty() && !freshness.is_submittable() {
+                        println!("Solution is for an expired job, not submitted");
+                        share_latency.share_discarded();
+                        submitted.clear();
+                    } else if submitted.is_empty() {
+                        println!("Solution below share difficulty {}, not submitted", cli.share_difficulty);
+                    } else if let Some(latency) = share_latency.share_submitted(LOCAL_JOB_ID) {
+                        println!("Job to share latency: {}", format_duration(latency));
+                    }
+                    for proof in &submitted {
+                        let solution = Solution { job_id: LOCAL_JOB_ID, height: 0, nonce, edge_bits: config.edge_bits, proof: proof.clone() };
+                        if let Err(error) = sinks.submit(&solution) {
+                            eprintln!("{}", error);
+                        }
+                    }
+                    submitted.into_iter().next()
+                },
+                Err(error) => {
+                    registry.record_error(&error);
+                    eprintln!("{}", error);
+                    eprintln!("Graph seed: {}", seed.to_json());
+                    if let Some(replay) = &replay {
+                        record_replay(replay, &failed_graph(&error, Some(nonces)));
+                    }
+                    None
+                },
+            }
+        },
+        None => None,
+    };
+    let found_solution = solution.is_some();
     
+    let verify_allocations = verify_allocations.map(StageTracker::finish);
     let verify_time = verify_start.elapsed();
+    profiling::finish_frame();
+    let energy = power_source.as_deref_mut().and_then(|source| {
+        EnergyReport::measure(source, run_start.elapsed().as_secs_f64(), found_solution as u64, cli.price_per_kwh)
+    });
+    if power_source.is_some() && energy.is_none() {
+        eprintln!("Warning: no power reading available, energy report skipped");
+    }
     
     // Handle cycle result
     if found_solution {
-        println!("Found 42-cycle in {:.6}s", verify_time.as_secs_f64());
-        // println!("Solution: {:?}", solution); // Temporarily disabled
+        println!("Found 42-cycle in {}", format_duration(verify_time));
+        println!("Solution: {:?}", solution.as_deref().unwrap_or_default());
         
         // Print SipHash keys for verification
         let keys = siphash.get_key();
-        println!("SipHash keys: [0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:016x}]", 
-                 keys[0], keys[1], keys[2], keys[3]);
+        println!("SipHash keys: {}", keys);
     } else {
-        println!("No 42-cycle found in {:.6}s", verify_time.as_secs_f64());
+        println!("No 42-cycle found in {}", format_duration(verify_time));
     }
     
-    println!("Performance metrics: solutions_found={}, searching_time={:.6}s", 
-             if found_solution { 1 } else { 0 }, verify_time.as_secs_f64());
+    println!("Performance metrics: solutions_found={}, searching_time={}", 
+             if found_solution { 1 } else { 0 }, format_duration(verify_time));
     
     // Test with a known cycle to verify the algorithm works
     println!("\nTesting with a known 42-cycle...");
@@ -119,7 +419,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("  Edge {}: {} -> {}", i, edge.u.0, edge.v.0);
     }
     
-    let mut test_verifier = CycleVerifier::new();
+    let mut test_verifier = CycleVerifier::new().with_metrics(registry.clone());
     let test_result = test_verifier.verify_cycle(&test_edges)?;
     
     match test_result {
@@ -136,24 +436,419 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // In tuning mode, keep output minimal like C++ reference
     if config.tuning {
         println!("Pipeline stages:");
-        println!("\tSearching time:\t {:.6} second(s)", verify_time.as_secs_f64());
+        println!("\tEdge generation:\t {}, {}",
+                 format_rate(edge_throughput.megaedges_per_second(), "Medges"),
+                 format_rate(edge_throughput.bytes_per_second(), "bytes"));
+        println!("\tTrimming time:\t {}", format_duration(trim_time));
+        println!("\tTrimming rounds:\t {}", rounds_run);
+        println!("\tSearching time:\t {}", format_duration(verify_time));
+        print_stage_allocations("Edge generation", edge_allocations);
+        print_stage_allocations("Trimming", trim_allocations);
+        print_stage_allocations("Searching", verify_allocations);
+        print_energy(energy.as_ref());
     } else {
         println!("Mining completed!");
     }
     
+    if let Some((path, baseline)) = &cli.baseline {
+        print_baseline_comparison(path, baseline, &config, &[
+            baseline.compare_time("Edge generation time", "edge_generation_time", edge_throughput.seconds),
+            baseline.compare_time("Trimming time", "trimming_time", trim_time.as_secs_f64()),
+            baseline.compare_time("Searching time", "searching_time", verify_time.as_secs_f64()),
+            baseline.compare_count("Trimming rounds", "trimming_rounds", rounds_run as u64),
+            baseline.compare_count("Surviving edges", "surviving_edges", surviving_edges.len() as u64),
+        ]);
+    }
+    
+    if let Some(path) = &cli.json_output {
+        let summary = RunSummary {
+            edge_bits: config.edge_bits,
+            mode: config.mode,
+            start_nonce,
+            nonce,
+            keys,
+            edges: edges.len() / 3,
+            trimming_rounds: rounds_run,
+            auto_rounds,
+            survivor_curve,
+            proof_difficulty: registry.snapshot().proof_difficulty().map(HistogramSnapshot::to_json),
+            surviving_edges: surviving_edges.len(),
+            solution_found: found_solution,
+            test_cycle_found: test_result.is_some(),
+            edge_throughput,
+            trimming_time: trim_time.as_secs_f64(),
+            searching_time: verify_time.as_secs_f64(),
+            energy,
+            warnings,
+        };
+        std::fs::write(path, summary.to_json(config.deterministic))?;
+        println!("Wrote run summary to {}", path);
+    }
+    
+    if cli.print_metrics {
+        println!("\n=== Metrics ===");
+        print!("{}", registry.to_prometheus());
+    }
+    
+    Ok(())
+}
+
+/// Run the known-answer self-test, printing a line per check
+/// 
+/// Returns true if every check passed.
+fn run_self_test() -> bool {
+    println!("Running self-test...");
+    let checks = self_test::run_self_test();
+    for check in &checks {
+        if check.passed {
+            println!("  PASS  {}", check.name);
+        } else {
+            println!("  FAIL  {}: {}", check.name, check.detail);
+        }
+    }
+    
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    if failed == 0 {
+        println!("Self-test passed ({} checks)", checks.len());
+    } else {
+        println!("Self-test FAILED ({} of {} checks)", failed, checks.len());
+    }
+    failed == 0
+}
+
+/// Solve the graph of a replay record again and verify any solution
+fn run_replay(source: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // A graph seed can be given inline or in a file
+    let text = if source.trim_start().starts_with('{') {
+        source.to_string()
+    } else {
+        std::fs::read_to_string(source).map_err(|error| format!("Cannot read {}: {}", source, error))?
+    };
+    if text.trim_start().starts_with('{') {
+        let seed = GraphSeed::from_json(&text)?;
+        println!("Replaying graph seed: EDGE_BITS={} nonce={} keys={} rounds={} mode={}",
+                 seed.edge_bits, seed.nonce, seed.keys(), seed.rounds, seed.mode);
+        return replay_graph(seed.keys(), seed.edge_bits, seed.rounds, seed.mode);
+    }
+
+    let record = ReplayRecord::parse(&text)?;
+    println!("Replaying EDGE_BITS={} keys={} rounds={} finder={}",
+             record.edge_bits, record.keys, record.trimming_rounds, record.finder);
+    println!("Recorded failure: {}", record.failure);
+    if record.finder != CycleVerifier::NAME {
+        println!("Warning: {} is not available, searching with {}", record.finder, CycleVerifier::NAME);
+    }
+    replay_graph(record.keys, record.edge_bits, record.trimming_rounds, TrimmingMode::Lean)
+}
+
+/// Trim and search a graph again, reporting what happened
+fn replay_graph(keys: SipKeys, edge_bits: u32, rounds: u32, mode: TrimmingMode) -> Result<(), Box<dyn std::error::Error>> {
+    // The graph size was already accepted when it was mined
+    let mut config = Config::new(edge_bits);
+    config.trimming_rounds = rounds;
+    config.mode = mode;
+    config.validation_policy = ValidationPolicy::Research;
+    let mut solver = Solver::new(config)?;
+    solver.rebind_keys(keys);
+    
+    match solver.solve() {
+        Ok(Some(nonces)) => match reverify_solution(&keys, edge_bits, &nonces) {
+            Ok(()) => println!("Found a valid solution: {:?}", nonces),
+            Err(error) => println!("Reproduced: {}", error),
+        },
+        Ok(None) => println!("No solution found"),
+        Err(error) => println!("Reproduced: {}", error),
+    }
+    Ok(())
+}
+
+/// Search another miner's edge dump for a 42-cycle with this crate's verifier
+fn run_verify_edges(path: &str, format: EdgeListFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let list = EdgeList::load(std::path::Path::new(path), format)?;
+    println!("Loaded {} edges from {} ({})", list.len(), path, format);
+    
+    let mut verifier = CycleVerifier::new();
+    match verifier.verify_cycle(&list.edges)? {
+        Some(cycle) => {
+            println!("Found a 42-cycle: {:?}", cycle);
+            if let Some(indices) = &list.indices {
+                let nonces: Vec<u64> = cycle
+                    .iter()
+                    .filter_map(|edge| list.edges.iter().position(|listed| listed == edge))
+                    .map(|position| indices[position])
+                    .collect();
+                println!("Edge indices: {:?}", nonces);
+            }
+        },
+        None => println!("No 42-cycle found"),
+    }
     Ok(())
 }
 
+/// Serve `registry` at `address` until the returned server is dropped,
+/// reporting where
+fn serve_metrics(address: &str, registry: &MetricsRegistry) -> cuckatoo_core::Result<MetricsServer> {
+    let server = MetricsServer::bind(address, registry.clone())?;
+    println!("Serving live metrics at http://{}/metrics", server.local_addr());
+    Ok(server)
+}
+
+/// Write a replay record of a failed graph, reporting where it went
+fn record_replay(recorder: &ReplayRecorder, record: &ReplayRecord) {
+    match recorder.record(record) {
+        Ok(path) => eprintln!("Replay record written to {}", path.display()),
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+/// Convert the flat edge list ([edge_index, node_u, node_v] triples) into
+/// edges indexed by edge index
+fn flat_edges(edges: &[u64]) -> Vec<Edge> {
+    edges
+        .chunks_exact(3)
+        .map(|chunk| Edge::new(Node::new(chunk[1]), Node::new(chunk[2])))
+        .collect()
+}
+
+/// Print the allocations of a pipeline stage (only with the `alloc-track` feature)
+fn print_stage_allocations(stage: &str, allocations: Option<StageAllocations>) {
+    if let Some(allocations) = allocations {
+        println!("\t{} allocations:\t {} bytes in {} allocation(s), peak live {} bytes",
+                 stage, allocations.allocated_bytes, allocations.allocations, allocations.peak_live_bytes);
+    }
+}
+
+/// Print the energy report (only with --power-watts or --power-rapl)
+fn print_energy(energy: Option<&EnergyReport>) {
+    if let Some(energy) = energy {
+        println!("\tPower draw:\t {:.1} W", energy.watts);
+        println!("\tEnergy:\t {:.6} Wh", energy.energy_kwh() * 1000.0);
+        println!("\tSolutions per kWh:\t {:.3}", energy.solutions_per_kwh());
+        match energy.cost_per_solution() {
+            Some(cost) => println!("\tCost per solution:\t {:.6}", cost),
+            None if energy.price_per_kwh.is_some() => println!("\tCost per solution:\t n/a (no solutions)"),
+            None => {},
+        }
+    }
+}
+
+/// Print how this run compares with a baseline run summary
+/// 
+/// Stages the baseline has no value for (timings of deterministic runs)
+/// are skipped.
+fn print_baseline_comparison(path: &str, baseline: &RunBaseline, config: &Config, deltas: &[Option<Delta>]) {
+    println!("Compared to baseline {}:", path);
+    let edge_bits = config.edge_bits.to_string();
+    let mode = config.mode.to_string();
+    for (name, current) in [("edge_bits", edge_bits.as_str()), ("mode", mode.as_str())] {
+        if let Some(previous) = baseline.field(name).filter(|&previous| previous != current) {
+            println!("\tWarning: baseline {} was {}, this run {}", name, previous, current);
+        }
+    }
+    for delta in deltas.iter().flatten() {
+        println!("\t{}", delta);
+    }
+}
+
+/// Parsed command line arguments
+struct CliArgs {
+    /// Mining configuration
+    config: Config,
+    /// Chain preset selected with `--preset`
+    preset: Option<Preset>,
+    /// Let adaptive trimming choose the rounds (`--trimming-rounds auto`)
+    auto_rounds: bool,
+    /// Print the metrics registry in Prometheus text format at exit
+    print_metrics: bool,
+    /// Serve the metrics registry over HTTP at this address while mining
+    metrics_addr: Option<String>,
+    /// Write a JSON run summary to this path
+    json_output: Option<String>,
+    /// Run summary of an earlier run to compare this one with, and its path
+    baseline: Option<(String, RunBaseline)>,
+    /// Write trimming checkpoints to this path and resume from it
+    checkpoint: Option<String>,
+    /// Count duplicate edges in the generated graph
+    check_duplicates: bool,
+    /// Print the surviving edges after every trimming round
+    progress: bool,
+    /// Fraction of the generated edges to recompute with the core hasher
+    audit_rate: Option<f64>,
+    /// Power draw used for the energy report
+    power_source: Option<Box<dyn PowerSource>>,
+    /// Electricity price per kWh for the cost per solution
+    price_per_kwh: Option<f64>,
+    /// Directory receiving replay records of failed graphs
+    replay_dir: Option<String>,
+    /// Share difficulty for the `difficulty` submission policy
+    share_difficulty: u64,
+    /// How the startup capability report is printed
+    banner: BannerFormat,
+    /// Where the nonce schedule starts
+    start_nonce: StartNonce,
+    /// Mine the jobs of this JSON Lines file instead of the test header
+    jobs_file: Option<String>,
+    /// Unit of the durations in the text output
+    time_unit: TimeUnit,
+    /// Specs of the sinks receiving submitted solutions
+    sinks: Vec<String>,
+}
+
+/// Start of the nonce schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StartNonce {
+    /// A nonce given on the command line (or the default)
+    Fixed(u64),
+    /// A nonce drawn from OS entropy at startup
+    Entropy,
+}
+
+/// Format of the startup capability report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BannerFormat {
+    Text,
+    Json,
+    None,
+}
+
+impl std::str::FromStr for BannerFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "none" => Ok(Self::None),
+            _ => Err(format!("Invalid banner format: {} (expected text, json or none)", s)),
+        }
+    }
+}
+
+/// Results of a run, written with `--json`
+struct RunSummary {
+    edge_bits: u32,
+    mode: TrimmingMode,
+    start_nonce: u64,
+    nonce: u64,
+    keys: SipKeys,
+    edges: usize,
+    trimming_rounds: u32,
+    /// Whether adaptive trimming chose `trimming_rounds`
+    auto_rounds: bool,
+    survivor_curve: Vec<u64>,
+    proof_difficulty: Option<String>,
+    surviving_edges: usize,
+    solution_found: bool,
+    test_cycle_found: bool,
+    edge_throughput: EdgeThroughput,
+    trimming_time: f64,
+    searching_time: f64,
+    energy: Option<EnergyReport>,
+    warnings: Vec<ConfigWarning>,
+}
+
+impl RunSummary {
+    /// Format the summary as a JSON object
+    /// 
+    /// Timings are left out in deterministic mode so runs with the same
+    /// seed produce byte-identical output.
+    fn to_json(&self, deterministic: bool) -> String {
+        let keys: Vec<String> = self.keys.words().iter().map(|key| format!("\"0x{:016x}\"", key)).collect();
+        let curve: Vec<String> = self.survivor_curve.iter().map(u64::to_string).collect();
+        let mut fields = vec![
+            format!("\"edge_bits\":{}", self.edge_bits),
+            format!("\"mode\":\"{}\"", self.mode),
+            format!("\"deterministic\":{}", deterministic),
+            format!("\"warnings\":{}", warnings_to_json(&self.warnings)),
+            format!("\"start_nonce\":{}", self.start_nonce),
+            format!("\"nonce\":{}", self.nonce),
+            format!("\"keys\":[{}]", keys.join(",")),
+            format!("\"key_derivation\":\"{}\"", self.keys.provenance()),
+            format!("\"edges\":{}", self.edges),
+            format!("\"trimming_rounds\":{}", self.trimming_rounds),
+            format!("\"auto_trimming_rounds\":{}", self.auto_rounds),
+            format!("\"survivor_curve\":[{}]", curve.join(",")),
+            format!("\"surviving_edges\":{}", self.surviving_edges),
+            format!("\"solution_found\":{}", self.solution_found),
+            format!("\"test_cycle_found\":{}", self.test_cycle_found),
+            format!("\"proof_difficulty\":{}", self.proof_difficulty.as_deref().unwrap_or("null")),
+        ];
+        if !deterministic {
+            fields.push(format!("\"edge_generation_time\":{}", json_seconds(self.edge_throughput.seconds)));
+            fields.push(format!("\"edge_generation_medges_per_second\":{:.6}", self.edge_throughput.megaedges_per_second()));
+            fields.push(format!("\"edge_generation_bytes_per_second\":{:.0}", self.edge_throughput.bytes_per_second()));
+            fields.push(format!("\"trimming_time\":{}", json_seconds(self.trimming_time)));
+            fields.push(format!("\"searching_time\":{}", json_seconds(self.searching_time)));
+            if let Some(energy) = &self.energy {
+                fields.push(format!("\"power_watts\":{:.3}", energy.watts));
+                fields.push(format!("\"energy_kwh\":{:.9}", energy.energy_kwh()));
+                fields.push(format!("\"solutions_per_kwh\":{:.3}", energy.solutions_per_kwh()));
+                if let Some(cost) = energy.cost_per_solution() {
+                    fields.push(format!("\"cost_per_solution\":{:.6}", cost));
+                }
+            }
+        }
+        format!("{{{}}}\n", fields.join(","))
+    }
+}
+
 /// Parse command line arguments
-fn parse_args(args: &[String]) -> Result<Config, Box<dyn std::error::Error>> {
+fn parse_args(args: &[String]) -> Result<CliArgs, Box<dyn std::error::Error>> {
     let mut edge_bits = 12; // Default to small edge bits for testing
     let mut mode = TrimmingMode::Lean;
     let mut trimming_rounds = 90;
     let mut tuning = false;
+    let mut auto_rounds = false;
+    let mut adaptive_threshold = None;
+    let mut plateau_rounds = None;
+    let mut target_survivors = None;
+    let mut print_metrics = false;
+    let mut metrics_addr = None;
+    let mut deterministic = false;
+    let mut json_output = None;
+    let mut baseline = None;
+    let mut checkpoint = None;
+    let mut power_source: Option<Box<dyn PowerSource>> = None;
+    let mut price_per_kwh = None;
+    let mut replay_dir = None;
+    let mut submission_policy = SubmissionPolicy::First;
+    let mut share_difficulty = 1;
+    let mut stale_share_grace = std::time::Duration::ZERO;
+    let mut validation_policy = ValidationPolicy::Consensus;
+    let mut key_schedule = KeySchedule::Grin;
+    let mut nonce_schedule = NonceSchedule::Sequential;
+    let mut check_duplicates = false;
+    let mut progress = false;
+    let mut audit_rate = None;
+    let mut preset = None;
+    let mut max_memory = None;
+    let mut bitmap_memory = None;
+    let mut spill_dir = None;
+    let mut use_huge_pages = false;
+    let mut endpoint_cache = None;
+    let mut banner = BannerFormat::Text;
+    let mut threads = 1;
+    let mut numa_policy = NumaPolicy::Off;
+    let mut solver_strategy = SolverStrategy::Cpp;
+    let mut start_nonce = StartNonce::Fixed(12345); // Use non-zero nonce
+    let mut jobs_file = None;
+    let mut time_unit = TimeUnit::default();
+    let mut sinks = Vec::new();
     
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
+            "--preset" => {
+                i += 1;
+                if i < args.len() {
+                    let chosen: Preset = args[i].parse()?;
+                    edge_bits = chosen.edge_bits;
+                    trimming_rounds = chosen.trimming_rounds;
+                    preset = Some(chosen);
+                } else {
+                    return Err("Missing value for --preset".into());
+                }
+            },
             "--edge-bits" => {
                 i += 1;
                 if i < args.len() {
@@ -173,14 +868,275 @@ fn parse_args(args: &[String]) -> Result<Config, Box<dyn std::error::Error>> {
             "--tuning" => {
                 tuning = true;
             },
+            "--metrics" => {
+                print_metrics = true;
+            },
+            "--metrics-addr" => {
+                i += 1;
+                if i < args.len() {
+                    metrics_addr = Some(args[i].clone());
+                } else {
+                    return Err("Missing value for --metrics-addr".into());
+                }
+            },
+            "--check-duplicate-edges" => {
+                check_duplicates = true;
+            },
+            "--progress" => {
+                progress = true;
+            },
+            "--deterministic" => {
+                deterministic = true;
+            },
+            "--json" => {
+                i += 1;
+                if i < args.len() {
+                    json_output = Some(args[i].clone());
+                } else {
+                    return Err("Missing value for --json".into());
+                }
+            },
+            "--baseline" => {
+                i += 1;
+                if i < args.len() {
+                    baseline = Some((args[i].clone(), RunBaseline::load(std::path::Path::new(&args[i]))?));
+                } else {
+                    return Err("Missing value for --baseline".into());
+                }
+            },
+            "--checkpoint" => {
+                i += 1;
+                if i < args.len() {
+                    checkpoint = Some(args[i].clone());
+                } else {
+                    return Err("Missing value for --checkpoint".into());
+                }
+            },
+            "--audit-edges" => {
+                i += 1;
+                if i < args.len() {
+                    audit_rate = Some(args[i].parse()?);
+                } else {
+                    return Err("Missing value for --audit-edges".into());
+                }
+            },
+            "--submission-policy" => {
+                i += 1;
+                if i < args.len() {
+                    submission_policy = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --submission-policy".into());
+                }
+            },
+            "--share-difficulty" => {
+                i += 1;
+                if i < args.len() {
+                    share_difficulty = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --share-difficulty".into());
+                }
+            },
+            "--stale-grace" => {
+                i += 1;
+                if i < args.len() {
+                    let seconds: f64 = args[i].parse()?;
+                    stale_share_grace = std::time::Duration::try_from_secs_f64(seconds)
+                        .map_err(|_| format!("Invalid --stale-grace: {}", args[i]))?;
+                } else {
+                    return Err("Missing value for --stale-grace".into());
+                }
+            },
+            "--record-replay" => {
+                i += 1;
+                if i < args.len() {
+                    replay_dir = Some(args[i].clone());
+                } else {
+                    return Err("Missing value for --record-replay".into());
+                }
+            },
+            "--validation-policy" => {
+                i += 1;
+                if i < args.len() {
+                    validation_policy = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --validation-policy".into());
+                }
+            },
+            "--endpoint-cache" => {
+                i += 1;
+                if i < args.len() {
+                    endpoint_cache = Some(parse_memory_size(&args[i])?);
+                } else {
+                    return Err("Missing value for --endpoint-cache".into());
+                }
+            },
+            "--threads" => {
+                i += 1;
+                if i < args.len() {
+                    threads = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --threads".into());
+                }
+            },
+            "--numa" => {
+                i += 1;
+                if i < args.len() {
+                    numa_policy = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --numa".into());
+                }
+            },
+            "--solver" => {
+                i += 1;
+                if i < args.len() {
+                    solver_strategy = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --solver".into());
+                }
+            },
+            "--banner" => {
+                i += 1;
+                if i < args.len() {
+                    banner = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --banner".into());
+                }
+            },
+            "--max-memory" => {
+                i += 1;
+                if i < args.len() {
+                    max_memory = Some(parse_memory_size(&args[i])?);
+                } else {
+                    return Err("Missing value for --max-memory".into());
+                }
+            },
+            "--bitmap-memory" => {
+                i += 1;
+                if i < args.len() {
+                    bitmap_memory = Some(parse_memory_size(&args[i])?);
+                } else {
+                    return Err("Missing value for --bitmap-memory".into());
+                }
+            },
+            "--spill-dir" => {
+                i += 1;
+                if i < args.len() {
+                    spill_dir = Some(std::path::PathBuf::from(&args[i]));
+                } else {
+                    return Err("Missing value for --spill-dir".into());
+                }
+            },
+            "--huge-pages" => {
+                use_huge_pages = true;
+            },
+            "--jobs-file" => {
+                i += 1;
+                if i < args.len() {
+                    jobs_file = Some(args[i].clone());
+                } else {
+                    return Err("Missing value for --jobs-file".into());
+                }
+            },
+            "--sink" => {
+                i += 1;
+                if i < args.len() {
+                    sinks.push(args[i].clone());
+                } else {
+                    return Err("Missing value for --sink".into());
+                }
+            },
+            "--time-unit" => {
+                i += 1;
+                if i < args.len() {
+                    time_unit = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --time-unit".into());
+                }
+            },
+            "--start-nonce" => {
+                i += 1;
+                if i < args.len() {
+                    start_nonce = StartNonce::Fixed(args[i].parse()?);
+                } else {
+                    return Err("Missing value for --start-nonce".into());
+                }
+            },
+            "--random-start-nonce" => {
+                start_nonce = StartNonce::Entropy;
+            },
+            "--key-derivation" => {
+                i += 1;
+                if i < args.len() {
+                    key_schedule = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --key-derivation".into());
+                }
+            },
+            "--nonce-schedule" => {
+                i += 1;
+                if i < args.len() {
+                    nonce_schedule = args[i].parse()?;
+                } else {
+                    return Err("Missing value for --nonce-schedule".into());
+                }
+            },
+            "--power-watts" => {
+                i += 1;
+                if i < args.len() {
+                    power_source = Some(Box::new(FixedPower::new(args[i].parse()?)));
+                } else {
+                    return Err("Missing value for --power-watts".into());
+                }
+            },
+            "--power-rapl" => {
+                power_source = Some(Box::new(RaplPower::default()));
+            },
+            "--price-per-kwh" => {
+                i += 1;
+                if i < args.len() {
+                    price_per_kwh = Some(args[i].parse()?);
+                } else {
+                    return Err("Missing value for --price-per-kwh".into());
+                }
+            },
             "--trimming-rounds" => {
                 i += 1;
                 if i < args.len() {
-                    trimming_rounds = args[i].parse()?;
+                    // "auto" keeps the round count as the cap
+                    auto_rounds = args[i] == "auto";
+                    if !auto_rounds {
+                        trimming_rounds = args[i].parse()?;
+                    }
                 } else {
                     return Err("Missing value for --trimming-rounds".into());
                 }
             },
+            "--adaptive-threshold" => {
+                i += 1;
+                if i < args.len() {
+                    // Given in percent per round (e.g. 0.5 = 0.5%)
+                    let percent: f64 = args[i].parse()?;
+                    adaptive_threshold = Some(percent / 100.0);
+                } else {
+                    return Err("Missing value for --adaptive-threshold".into());
+                }
+            },
+            "--plateau-rounds" => {
+                i += 1;
+                if i < args.len() {
+                    plateau_rounds = Some(args[i].parse()?);
+                } else {
+                    return Err("Missing value for --plateau-rounds".into());
+                }
+            },
+            "--target-survivors" => {
+                i += 1;
+                if i < args.len() {
+                    target_survivors = Some(args[i].parse()?);
+                } else {
+                    return Err("Missing value for --target-survivors".into());
+                }
+            },
             "--help" | "-h" => {
                 print_usage();
                 std::process::exit(0);
@@ -194,11 +1150,58 @@ fn parse_args(args: &[String]) -> Result<Config, Box<dyn std::error::Error>> {
         i += 1;
     }
     
-    Ok(Config {
+    // Auto rounds stop once the reduction stays low for a few rounds
+    if auto_rounds {
+        adaptive_threshold = adaptive_threshold.or(Some(DEFAULT_MIN_REDUCTION));
+        plateau_rounds = plateau_rounds.or(Some(DEFAULT_PLATEAU_ROUNDS));
+    }
+    
+    let config = Config {
         edge_bits,
         trimming_rounds,
         mode,
         tuning,
+        adaptive_threshold,
+        plateau_rounds: plateau_rounds.unwrap_or(1),
+        target_survivors,
+        deterministic,
+        validation_policy,
+        key_schedule,
+        nonce_schedule,
+        max_memory,
+        bitmap_memory,
+        spill_dir,
+        use_huge_pages,
+        submission_policy,
+        stale_share_grace,
+        endpoint_cache,
+        node_buffer: None,
+        threads,
+        numa_policy,
+        solver_strategy,
+    };
+    
+    Ok(CliArgs {
+        config,
+        preset,
+        auto_rounds,
+        print_metrics,
+        metrics_addr,
+        json_output,
+        baseline,
+        checkpoint,
+        check_duplicates,
+        progress,
+        audit_rate,
+        power_source,
+        price_per_kwh,
+        replay_dir,
+        share_difficulty,
+        banner,
+        start_nonce,
+        jobs_file,
+        time_unit,
+        sinks,
     })
 }
 
@@ -207,166 +1210,173 @@ fn print_usage() {
     println!("Cuckatoo Reference Miner v0.1.0 (Rust)");
     println!();
     println!("Usage: cuckatoo-miner [OPTIONS]");
+    println!("       cuckatoo-miner self-test");
+    println!("       cuckatoo-miner stress [--edge-bits <BITS>] [--graphs <N>] [--threads <N>]");
+    println!("                             [--max-memory <SIZE>] [--record-replay <DIR>]");
+    println!("                             [--time-unit <UNIT>]");
+    println!("       cuckatoo-miner replay <FILE|SEED>");
+    println!("       cuckatoo-miner verify-edges <FILE> [text|u32|u64]");
+    println!();
+    println!("Commands:");
+    println!("  self-test              Check hashing, trimming, cycle search and proof verification");
+    println!("                         against built-in known answers (exits 1 on mismatch)");
+    println!("  stress                 Solve many random graphs (default: 10000 at EDGE_BITS=16,");
+    println!("                         one thread per core), verifying every solution; exits 1 on");
+    println!("                         panics, invalid proofs or leaks (leaks need alloc-track)");
+    println!("  replay                 Solve the graph of a --record-replay record, or of a");
+    println!("                         graph seed (JSON, inline or in a file), again");
+    println!("  verify-edges           Search another miner's edge dump for a 42-cycle: text with");
+    println!("                         `u v` or `index u v` per line (default), or little-endian");
+    println!("                         u32 or u64 pairs");
     println!();
     println!("Options:");
     println!("  --edge-bits <BITS>     Number of edge bits (10-32, default: 12)");
-    println!("  --mode <MODE>          Trimming mode: lean, mean, slean (default: lean)");
-    println!("  --trimming-rounds <N>  Number of trimming rounds (default: 90)");
+    println!("  --preset <NAME>        Network preset setting the edge bits and trimming rounds:");
+    println!("                         grin-c31, grin-c32 or mwc-c31 (later options override it)");
+    println!("  --mode <MODE>          Trimming mode: lean, mean, slean or lean-counter (2-bit");
+    println!("                         node pair degree counters) (default: lean)");
+    println!("  --trimming-rounds <N>  Number of trimming rounds, or auto to stop once {} rounds", DEFAULT_PLATEAU_ROUNDS);
+    println!("                         in a row remove less than {}% of the edges (default: 90)", DEFAULT_MIN_REDUCTION * 100.0);
+    println!("  --adaptive-threshold <PCT>");
+    println!("                         Stop trimming when a round removes less than PCT% of");
+    println!("                         the edges (--trimming-rounds becomes the maximum)");
+    println!("  --plateau-rounds <N>   Rounds in a row below the adaptive threshold that stop");
+    println!("                         trimming (default: 1, {} with --trimming-rounds auto)", DEFAULT_PLATEAU_ROUNDS);
+    println!("  --target-survivors <K> Trim until at most K x 42 edges survive");
+    println!("                         (--trimming-rounds becomes the maximum)");
+    println!("  --validation-policy <POLICY>");
+    println!("                         consensus (10-32 edge bits, default) or research");
+    println!("                         (4-63 edge bits, for tests and experiments)");
+    println!("  --max-memory <SIZE>    Memory budget such as 8G or 512M; leaner trimming is used");
+    println!("                         if needed, and runs that can't fit fail up front");
+    println!("  --bitmap-memory <SIZE> RAM the trimming bitmaps may take; beyond it they are kept");
+    println!("                         in memory-mapped temp files (for edge bits 33 and up)");
+    println!("  --spill-dir <DIR>      Directory of those temp files (default: system temp dir)");
+    println!("  --huge-pages           Put the trimming bitmaps on 2 MiB huge pages where available");
+    println!("  --endpoint-cache <SIZE>");
+    println!("                         Cache trimming's SipHash endpoints in SIZE of memory");
+    println!("                         (e.g. 64M) instead of rehashing them every round");
+    println!("  --threads <N>          Threads generating edges, trimming and searching (default: 1)");
+    println!("  --numa <POLICY>        off, or local to pin the trimming threads to NUMA nodes and");
+    println!("                         keep their bitmap ranges on them (default: off)");
+    println!("  --solver <STRATEGY>    Cycle search: cpp (connection lists, multi-threaded) or path");
+    println!("                         (Tromp's path-following solver) (default: cpp)");
+    println!("  --start-nonce <N>      First nonce of the schedule (default: 12345)");
+    println!("  --random-start-nonce   Start at a nonce drawn from OS entropy (printed, so the run");
+    println!("                         can be reproduced with --start-nonce) so solo miners on");
+    println!("                         the same header don't duplicate each other's work");
+    println!("  --key-derivation <KEYS>");
+    println!("                         grin-blake2b (default), reference-miner (the C++ miner's");
+    println!("                         nonce placement) or test-seed:<K0>,<K1>,<K2>,<K3> (fixed");
+    println!("                         hex keys for every nonce)");
+    println!("  --nonce-schedule <SCHEDULE>");
+    println!("                         sequential (default), strided:<OFFSET>/<STRIDE> so rig");
+    println!("                         OFFSET of STRIDE rigs never repeats another rig's nonces,");
+    println!("                         random or random:<SEED>");
+    println!("  --submission-policy <POLICY>");
+    println!("                         first (stop at the first solution of a graph, default),");
+    println!("                         all, or difficulty (only solutions meeting the share");
+    println!("                         difficulty)");
+    println!("  --share-difficulty <D> Share difficulty for the difficulty policy (default: 1)");
+    println!("  --stale-grace <SECONDS>");
+    println!("                         Keep submitting the previous job's solutions for SECONDS");
+    println!("                         after a job change (default: 0, discard them)");
+    println!("  --banner <FORMAT>      Startup capability report (CPU features, hash backend,");
+    println!("                         memory estimate, effective parameters): text (default),");
+    println!("                         json or none");
     println!("  --tuning               Run in tuning mode (offline)");
+    println!("  --metrics              Print metrics in Prometheus text format at exit");
+    println!("  --metrics-addr <ADDR>  Serve live metrics at http://ADDR/metrics while mining,");
+    println!("                         e.g. 127.0.0.1:9100 (also for stress and --jobs-file), and");
+    println!("                         error counts and per-worker totals as JSON at");
+    println!("                         http://ADDR/status");
+    println!("  --progress             Print the surviving edges and elapsed time after every");
+    println!("                         trimming round to stderr");
+    println!("  --check-duplicate-edges");
+    println!("                         Count edges with the same endpoints as an earlier edge");
+    println!("  --audit-edges <RATE>   Recompute a RATE fraction of the generated edges (1.0 for all)");
+    println!("                         and fail if any endpoints differ");
+    println!("  --deterministic        Disable adaptive trimming and omit timings from --json");
+    println!("                         so runs with the same seed give identical output");
+    println!("  --json <PATH>          Write a JSON run summary to PATH");
+    println!("  --baseline <PATH>      Compare stage times, rounds and survivors with the --json");
+    println!("                         summary of an earlier run, e.g. after a config change");
+    println!("  --checkpoint <PATH>    Save the trim to PATH every {} rounds and resume from it", CHECKPOINT_ROUNDS);
+    println!("                         after a restart (fixed-round trims only)");
+    println!("  --sink <SPEC>          Also deliver submitted solutions to stdout, json (JSON lines");
+    println!("                         on stdout), file:<PATH> (JSON Lines), stratum:<HOST:PORT>");
+    println!("                         or sqlite:<PATH> (sqlite feature); repeat for several");
+    println!("  --time-unit <UNIT>     Unit of the durations in the text output: s (default), ms");
+    println!("                         or us; JSON output always uses seconds");
+    println!("  --jobs-file <PATH>     Mine the jobs of a JSON Lines file in order, one per line:");
+    println!("                         {{\"header\":\"<HEX>\",\"start_nonce\":N,\"end_nonce\":M,\"edge_bits\":B}}");
+    println!("                         (end exclusive, edge_bits optional), printing a summary per");
+    println!("                         job (written to --json as JSON Lines); exits 1 on failures");
+    println!("  --record-replay <DIR>  Write a replay record to DIR for each graph that fails or");
+    println!("                         yields an invalid solution (rerun with `replay <FILE>`)");
+    println!("  --power-watts <W>      Power draw in watts, for solutions/kWh in the tuning report");
+    println!("  --power-rapl           Measure the power draw with the Linux RAPL package counter");
+    println!("  --price-per-kwh <P>    Electricity price per kWh, for the cost per solution");
     println!("  --help, -h             Show this help message");
     println!();
     println!("Examples:");
     println!("  cuckatoo-miner --tuning --edge-bits 12 --mode lean");
     println!("  cuckatoo-miner --edge-bits 16 --mode lean");
+    println!("  cuckatoo-miner --tuning --edge-bits 16 --adaptive-threshold 0.5");
+    println!("  cuckatoo-miner --tuning --edge-bits 16 --target-survivors 100");
+}
+
+/// Trim a graph as configured, returning the survivors and rounds run
+fn trim_graph(trimmer: &mut BitmapTrimmer, config: &Config, hasher: &impl SipHasher, resume: bool) -> cuckatoo_core::Result<(Vec<Edge>, u32)> {
+    match config.round_controller() {
+        Some(mut controller) => {
+            let result = trimmer.trim_edges_adaptive(hasher, &mut controller)?;
+            Ok((result.edges, result.rounds))
+        },
+        None if resume => Ok((trimmer.resume_trim_edges(hasher, config.trimming_rounds)?, config.trimming_rounds)),
+        None => Ok((trimmer.trim_edges(hasher, config.trimming_rounds)?, config.trimming_rounds)),
+    }
+}
+
+/// Replace the checkpoint at `path`, writing to a temporary file first so
+/// a crash mid-write leaves the previous checkpoint intact
+fn write_checkpoint(path: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let temporary = format!("{}.tmp", path);
+    std::fs::write(&temporary, bytes)?;
+    std::fs::rename(&temporary, path)
 }
 
 /// Generate edges using the exact C++ method
-fn generate_edges_cpp_style(keys: &[u64; 4], edge_bits: u32) -> Vec<u32> {
-    let number_of_edges = 1u64 << edge_bits;
-    let mut edges = Vec::new();
+/// 
+/// Nonces are hashed in batches so hashers with SIMD paths can use them.
+/// Indices and nodes are stored as `u64` (C++ uses `u32`) so graphs above
+/// EDGE_BITS=32 keep their full nodes.
+fn generate_edges_cpp_style(hasher: &(impl SipHasher + Sync), edge_bits: u32, threads: usize) -> Vec<u64> {
+    cuckatoo_core::profile_scope!(cuckatoo_core::profiling::EDGE_GEN);
+    
+    let number_of_edges = 1usize << edge_bits;
+    let mut edges = vec![0u64; 3 * number_of_edges];
     
     // Generate edges exactly like C++ does - flat array format [edge_index, node_u, node_v]
-    for edge_index in 0..number_of_edges {
-        // C++ uses: nonces = {edgeIndex * 2, edgeIndex * 2 | 1}
-        let nonce_u = edge_index * 2;
-        let nonce_v = (edge_index * 2) | 1;
-        
-        // Generate nodes using SipHash-2-4
-        let node_u = siphash24_single(keys, nonce_u, edge_bits);
-        let node_v = siphash24_single(keys, nonce_v, edge_bits);
-        
-        // C++ format: [edge_index, node_u, node_v]
-        edges.push(edge_index as u32);
-        edges.push(node_u as u32);
-        edges.push(node_v as u32);
-    }
+    // (C++ uses nonces = {edgeIndex * 2, edgeIndex * 2 | 1}, which EdgeIterator hashes in batches).
+    // Each thread fills a disjoint range of edges.
+    let per_thread = number_of_edges.div_ceil(threads.max(1));
+    std::thread::scope(|scope| {
+        for (index, slice) in edges.chunks_mut(3 * per_thread).enumerate() {
+            let first_edge = (index * per_thread) as u64;
+            scope.spawn(move || {
+                let range = first_edge..first_edge + (slice.len() / 3) as u64;
+                let generated = range.clone().zip(EdgeIterator::with_range(hasher, edge_bits, range));
+                for ((edge_index, edge), triple) in generated.zip(slice.chunks_exact_mut(3)) {
+                    triple.copy_from_slice(&[edge_index, edge.u.value(), edge.v.value()]);
+                }
+            });
+        }
+    });
     
     edges
 }
 
-/// SipHash-2-4 implementation matching C++ exactly
-fn siphash24_single(keys: &[u64; 4], nonce: u64, edge_bits: u32) -> u64 {
-    let mut v0 = keys[0];
-    let mut v1 = keys[1];
-    let mut v2 = keys[2];
-    let mut v3 = keys[3];
-    
-    // Initialization
-    v3 ^= nonce;
-    
-    // SipRound 1
-    v0 = v0.wrapping_add(v1);
-    v2 = v2.wrapping_add(v3);
-    v1 = v1.rotate_left(13);
-    v3 = v3.rotate_left(16);
-    v1 ^= v0;
-    v3 ^= v2;
-    v0 = v0.rotate_left(32);
-    v2 = v2.wrapping_add(v1);
-    v0 = v0.wrapping_add(v3);
-    v1 = v1.rotate_left(17);
-    v3 = v3.rotate_left(21);
-    v1 ^= v2;
-    v3 ^= v0;
-    v2 = v2.rotate_left(32);
-    
-    // SipRound 2
-    v0 = v0.wrapping_add(v1);
-    v2 = v2.wrapping_add(v3);
-    v1 = v1.rotate_left(13);
-    v3 = v3.rotate_left(16);
-    v1 ^= v0;
-    v3 ^= v2;
-    v0 = v0.rotate_left(32);
-    v2 = v2.wrapping_add(v1);
-    v0 = v0.wrapping_add(v3);
-    v1 = v1.rotate_left(17);
-    v3 = v3.rotate_left(21);
-    v1 ^= v2;
-    v3 ^= v0;
-    v2 = v2.rotate_left(32);
-    
-    // Finalization
-    v2 ^= 0xff;
-    
-    // SipRound 3
-    v0 = v0.wrapping_add(v1);
-    v2 = v2.wrapping_add(v3);
-    v1 = v1.rotate_left(13);
-    v3 = v3.rotate_left(16);
-    v1 ^= v0;
-    v3 ^= v2;
-    v0 = v0.rotate_left(32);
-    v2 = v2.wrapping_add(v1);
-    v0 = v0.wrapping_add(v3);
-    v1 = v1.rotate_left(17);
-    v3 = v3.rotate_left(21);
-    v1 ^= v2;
-    v3 ^= v0;
-    v2 = v2.rotate_left(32);
-    
-    // SipRound 4
-    v0 = v0.wrapping_add(v1);
-    v2 = v2.wrapping_add(v3);
-    v1 = v1.rotate_left(13);
-    v3 = v3.rotate_left(16);
-    v1 ^= v0;
-    v3 ^= v2;
-    v0 = v0.rotate_left(32);
-    v2 = v2.wrapping_add(v1);
-    v0 = v0.wrapping_add(v3);
-    v1 = v1.rotate_left(17);
-    v3 = v3.rotate_left(21);
-    v1 ^= v2;
-    v3 ^= v0;
-    v2 = v2.rotate_left(32);
-    
-    // SipRound 5
-    v0 = v0.wrapping_add(v1);
-    v2 = v2.wrapping_add(v3);
-    v1 = v1.rotate_left(13);
-    v3 = v3.rotate_left(16);
-    v1 ^= v0;
-    v3 ^= v2;
-    v0 = v0.rotate_left(32);
-    v2 = v2.wrapping_add(v1);
-    v0 = v0.wrapping_add(v3);
-    v1 = v1.rotate_left(17);
-    v3 = v3.rotate_left(21);
-    v1 ^= v2;
-    v3 ^= v0;
-    v2 = v2.rotate_left(32);
-    
-    // SipRound 6
-    v0 = v0.wrapping_add(v1);
-    v2 = v2.wrapping_add(v3);
-    v1 = v1.rotate_left(13);
-    v3 = v3.rotate_left(16);
-    v1 ^= v0;
-    v3 ^= v2;
-    v0 = v0.rotate_left(32);
-    v2 = v2.wrapping_add(v1);
-    v0 = v0.wrapping_add(v3);
-    v1 = v1.rotate_left(17);
-    v3 = v3.rotate_left(21);
-    v1 ^= v2;
-    v3 ^= v0;
-    v2 = v2.rotate_left(32);
-    
-    // Final XOR
-    v0 ^= v1;
-    v2 ^= v3;
-    v0 ^= v2;
-    
-    // Apply node mask if edge_bits < 32
-    if edge_bits < 32 {
-        let node_mask = (1u64 << edge_bits) - 1;
-        v0 & node_mask
-    } else {
-        v0
-    }
-}
-
 /// Create a test 42-cycle to verify the algorithm works
 fn create_test_42_cycle() -> Vec<u32> {
     let mut edges = Vec::new();
@@ -401,3 +1411,25 @@ fn create_test_42_cycle() -> Vec<u32> {
     
     edges
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_rounds_choose_plateau() {
+        let args: Vec<String> = ["cuckatoo-miner", "--edge-bits", "14", "--trimming-rounds", "auto"].map(String::from).to_vec();
+        let cli = parse_args(&args).unwrap();
+        assert!(cli.auto_rounds);
+        let siphash = SipHash::with_key(SipKeys::from_header(b"auto rounds", 1));
+        let mut trimmer = BitmapTrimmer::for_config(&cli.config).unwrap();
+        let (edges, rounds) = trim_graph(&mut trimmer, &cli.config, &siphash, false).unwrap();
+
+        // Trimming keeps removing edges well past the first rounds, and
+        // stops before the cap once it levels off
+        assert!(rounds > 10 && rounds < cli.config.trimming_rounds, "auto trimming chose {} rounds", rounds);
+        assert_eq!(edges, BitmapTrimmer::new(14).trim_edges(&siphash, rounds).unwrap());
+        let more = BitmapTrimmer::new(14).trim_edges(&siphash, rounds + 10).unwrap();
+        assert!(more.len() * 10 >= edges.len() * 9);
+    }
+}
@@ -0,0 +1,331 @@
+//! Stress test subcommand
+//!
+//! Solves many small graphs on several threads and checks every reported
+//! solution with the consensus verifier. Panics, invalid proofs, no
+//! solutions at all over enough graphs to expect several and (with the
+//! `alloc-track` feature) memory still live after the run are failures.
+
+use cuckatoo_core::prelude::*;
+use cuckatoo_core::{allocation_stats, entropy_start_nonce, format_rate, format_seconds, parse_memory_size, proof_difficulty, reverify_solution, MemoryBudget, MemoryEstimate, MetricsRegistry, ReplayRecord, ReplayRecorder, Result, Solver, TimeUnit, WorkerMetrics};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Bytes that may stay live after the run without counting as a leak
+/// (lazily initialized runtime state such as stdout buffers)
+const LEAK_TOLERANCE_BYTES: u64 = 64 * 1024;
+
+/// Graphs per solution, well above the measured rates: about one graph in
+/// 42 has a 42-cycle, but fewer small ones do (one in 70 at EDGE_BITS=12,
+/// one in 280 at EDGE_BITS=10)
+const GRAPHS_PER_SOLUTION: u64 = 1000;
+
+/// Solutions expected at `GRAPHS_PER_SOLUTION` from which a run finding
+/// none fails
+const MIN_EXPECTED_SOLUTIONS: u64 = 5;
+
+/// Options of the stress subcommand
+pub struct StressArgs {
+    /// Edge bits of every graph
+    pub edge_bits: u32,
+    /// Number of graphs to solve
+    pub graphs: u64,
+    /// Number of worker threads
+    pub threads: usize,
+    /// Trimming rounds per graph
+    pub trimming_rounds: u32,
+    /// Memory budget in bytes, limiting how many graphs are solved at once
+    pub max_memory: Option<u64>,
+    /// Directory receiving replay records of failed graphs
+    pub replay_dir: Option<String>,
+    /// Unit of the durations in the report
+    pub time_unit: TimeUnit,
+    /// Serve the metrics registry over HTTP at this address during the run
+    pub metrics_addr: Option<String>,
+}
+
+/// Parse the arguments following `stress`
+pub fn parse_stress_args(args: &[String]) -> std::result::Result<StressArgs, Box<dyn std::error::Error>> {
+    let mut stress = StressArgs {
+        edge_bits: 16,
+        graphs: 10_000,
+        threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        trimming_rounds: 90,
+        max_memory: None,
+        replay_dir: None,
+        time_unit: TimeUnit::default(),
+        metrics_addr: None,
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        let option = args[i].as_str();
+        i += 1;
+        let value = args.get(i).ok_or_else(|| format!("Missing value for {}", option));
+        match option {
+            "--edge-bits" => stress.edge_bits = value?.parse()?,
+            "--graphs" => stress.graphs = value?.parse()?,
+            "--threads" => stress.threads = value?.parse()?,
+            "--trimming-rounds" => stress.trimming_rounds = value?.parse()?,
+            "--max-memory" => stress.max_memory = Some(parse_memory_size(value?)?),
+            "--record-replay" => stress.replay_dir = Some(value?.clone()),
+            "--time-unit" => stress.time_unit = value?.parse()?,
+            "--metrics-addr" => stress.metrics_addr = Some(value?.clone()),
+            _ => return Err(format!("Unknown stress option: {}", option).into()),
+        }
+        i += 1;
+    }
+
+    if stress.threads == 0 {
+        return Err("--threads must be at least 1".into());
+    }
+    Config::new(stress.edge_bits).validate()?;
+    
+    // Each thread works on its own graph
+    if let Some(max_memory) = stress.max_memory {
+        let estimate = MemoryEstimate::new(stress.edge_bits, TrimmingMode::Lean);
+        let threads = MemoryBudget::new(max_memory).concurrent_graphs(&estimate, stress.threads)?;
+        if threads < stress.threads {
+            println!("Memory budget fits {} graphs of {} at once, using {} thread(s)", threads, estimate, threads);
+            stress.threads = threads;
+        }
+    }
+    Ok(stress)
+}
+
+/// Counters shared by the stress workers
+#[derive(Default)]
+struct StressCounters {
+    /// Graphs solved (with or without a solution)
+    solved: AtomicU64,
+    /// Solutions accepted by the consensus verifier
+    solutions: AtomicU64,
+    /// Solutions rejected by the consensus verifier
+    invalid_proofs: AtomicU64,
+    /// Graphs that failed with an error
+    errors: AtomicU64,
+    /// Graphs whose solve panicked
+    panics: AtomicU64,
+    /// Solvers created (once per thread, again after a panic)
+    setups: AtomicU64,
+    /// Nanoseconds spent creating solvers
+    setup_nanos: AtomicU64,
+    /// Nanoseconds spent rebinding solvers to the next graph
+    rebind_nanos: AtomicU64,
+}
+
+/// Run the stress test, printing a summary
+///
+/// Returns true if no graph panicked, failed or produced an invalid proof,
+/// some solution was found if enough graphs were solved to expect one (see
+/// `missing_solutions`), and no memory leaked.
+pub fn run_stress(stress: &StressArgs) -> Result<bool> {
+    let replay = stress.replay_dir.as_deref().map(ReplayRecorder::new).transpose()?;
+    // Random start so repeated runs cover different graphs
+    let first_nonce = entropy_start_nonce();
+    println!("Stress test: {} graphs at EDGE_BITS={} on {} thread(s), first nonce {}",
+             stress.graphs, stress.edge_bits, stress.threads, first_nonce);
+
+    let header = Header::new(&[0u8; 238]);
+    let counters = StressCounters::default();
+    let registry = MetricsRegistry::new();
+    let _metrics_server = stress.metrics_addr.as_deref().map(|address| crate::serve_metrics(address, &registry)).transpose()?;
+    let next_graph = AtomicU64::new(0);
+    let live_before = allocation_stats().map(|stats| stats.live_bytes);
+    let start = Instant::now();
+
+    std::thread::scope(|scope| {
+        for thread in 0..stress.threads {
+            let worker = registry.worker(&format!("thread-{}", thread));
+            let (counters, next_graph, replay, header, registry) = (&counters, &next_graph, replay.as_ref(), &header, &registry);
+            scope.spawn(move || {
+                // Each thread keeps its solver's buffers across graphs
+                let mut solver = None;
+                let mut header = header.clone();
+                loop {
+                    let graph = next_graph.fetch_add(1, Ordering::Relaxed);
+                    if graph >= stress.graphs {
+                        break;
+                    }
+                    header.nonce = first_nonce.wrapping_add(graph);
+                    stress_graph(stress, &mut solver, &worker, registry, &header, counters, replay);
+
+                    let solved = counters.solved.fetch_add(1, Ordering::Relaxed) + 1;
+                    if solved % 1000 == 0 {
+                        println!("  {} graphs solved", solved);
+                    }
+                }
+            });
+        }
+    });
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let leaked = live_before
+        .zip(allocation_stats())
+        .map(|(before, after)| after.live_bytes.saturating_sub(before));
+
+    let solved = counters.solved.load(Ordering::Relaxed);
+    let invalid_proofs = counters.invalid_proofs.load(Ordering::Relaxed);
+    let errors = counters.errors.load(Ordering::Relaxed);
+    let panics = counters.panics.load(Ordering::Relaxed);
+    println!("Solved {} graphs in {} ({})", solved, format_seconds(elapsed), format_rate(solved as f64 / elapsed, "graphs"));
+    let solutions = counters.solutions.load(Ordering::Relaxed);
+    println!("  Solutions:      {}", solutions);
+    let missing = missing_solutions(solved, solutions);
+    if missing {
+        println!("  No solutions in {} graphs, where at least {} were expected: the trimmers or cycle finder lose cycles",
+                 solved, solved / GRAPHS_PER_SOLUTION);
+    }
+    println!("  Invalid proofs: {}", invalid_proofs);
+    println!("  Errors:         {}", errors);
+    println!("  Panics:         {}", panics);
+    let setups = counters.setups.load(Ordering::Relaxed);
+    if setups > 0 && solved > 0 {
+        println!("  Solver setup:   {} cold ({} solvers), {} per rebind",
+                 format_seconds(counters.setup_nanos.load(Ordering::Relaxed) as f64 / setups as f64 / 1e9), setups,
+                 format_seconds(counters.rebind_nanos.load(Ordering::Relaxed) as f64 / solved as f64 / 1e9));
+    }
+    if let Some(difficulty) = registry.snapshot().proof_difficulty() {
+        // About 1/d of the solutions should be above difficulty d
+        println!("  Solution difficulty:");
+        for &(bound, at_or_below) in difficulty.buckets.iter().filter(|(bound, _)| bound.is_finite()) {
+            let (above, expected) = (difficulty.count - at_or_below, difficulty.count as f64 / bound);
+            if above > 0 || expected >= 1.0 {
+                println!("    above {:>10}: {} (expected {:.1})", bound, above, expected);
+            }
+        }
+    }
+    println!("  Workers:");
+    for worker in registry.snapshot().workers() {
+        println!("    {}", worker);
+    }
+    match leaked {
+        Some(bytes) => println!("  Leaked bytes:   {}", bytes),
+        None => println!("  Leaked bytes:   not tracked (build with --features alloc-track)"),
+    }
+
+    let passed = invalid_proofs == 0 && errors == 0 && panics == 0 && !missing
+        && leaked.is_none_or(|bytes| bytes <= LEAK_TOLERANCE_BYTES);
+    if let Some(replay) = &replay {
+        println!("  Replay records: {}", replay.written());
+    }
+    println!("Stress test {}", if passed { "passed" } else { "FAILED" });
+    Ok(passed)
+}
+
+/// Whether finding `solutions` in `graphs` graphs means cycles are lost,
+/// because at least `MIN_EXPECTED_SOLUTIONS` were expected and none found
+fn missing_solutions(graphs: u64, solutions: u64) -> bool {
+    solutions == 0 && graphs / GRAPHS_PER_SOLUTION >= MIN_EXPECTED_SOLUTIONS
+}
+
+/// Solve one graph and count the outcome
+///
+/// The thread's solver is created on first use and rebound for later
+/// graphs. It is dropped after a panic, since its buffers may be
+/// half-written. Failed graphs are written to `replay`, if set.
+fn stress_graph(
+    stress: &StressArgs,
+    solver: &mut Option<Solver>,
+    worker: &WorkerMetrics,
+    registry: &MetricsRegistry,
+    header: &Header,
+    counters: &StressCounters,
+    replay: Option<&ReplayRecorder>,
+) {
+    let keys = blake2b(header.as_bytes(), header.nonce());
+    let failed_graph = |failure: String, proof: Option<Vec<u64>>| {
+        if let Some(replay) = replay {
+            crate::record_replay(replay, &ReplayRecord {
+                keys,
+                edge_bits: stress.edge_bits,
+                trimming_rounds: stress.trimming_rounds,
+                finder: CycleVerifier::NAME.to_string(),
+                failure,
+                proof,
+            });
+        }
+    };
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        if solver.is_none() {
+            let mut config = Config::new(stress.edge_bits);
+            config.trimming_rounds = stress.trimming_rounds;
+            let created = Solver::new(config)?.with_worker(worker.clone());
+            counters.setups.fetch_add(1, Ordering::Relaxed);
+            counters.setup_nanos.fetch_add(created.setup_time().as_nanos() as u64, Ordering::Relaxed);
+            *solver = Some(created);
+        }
+        let solver = solver.as_mut().expect("solver was just created");
+        let rebind_time = solver.rebind(header);
+        counters.rebind_nanos.fetch_add(rebind_time.as_nanos() as u64, Ordering::Relaxed);
+        solver.solve()
+    }));
+
+    match outcome {
+        Ok(Ok(None)) => {},
+        Ok(Ok(Some(nonces))) => match reverify_solution(&keys, stress.edge_bits, &nonces) {
+            Ok(()) => {
+                counters.solutions.fetch_add(1, Ordering::Relaxed);
+                registry.record_proof_difficulty(proof_difficulty(&nonces, stress.edge_bits));
+            },
+            Err(error) => {
+                eprintln!("{}", error);
+                registry.record_error(&error);
+                counters.invalid_proofs.fetch_add(1, Ordering::Relaxed);
+                failed_graph(error.to_string(), Some(nonces));
+            },
+        },
+        Ok(Err(error)) => {
+            eprintln!("Graph with keys {} failed: {}", keys, error);
+            registry.record_error(&error);
+            counters.errors.fetch_add(1, Ordering::Relaxed);
+            failed_graph(error.to_string(), None);
+        },
+        Err(_) => {
+            eprintln!("Graph with keys {} panicked", keys);
+            counters.panics.fetch_add(1, Ordering::Relaxed);
+            *solver = None;
+            failed_graph("panicked".to_string(), None);
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuckatoo_core::self_test::{KNOWN_PROOF_EDGE_BITS, KNOWN_PROOF_HEADER, KNOWN_PROOF_NONCE};
+
+    #[test]
+    fn test_stress_graph_finds_known_proof() {
+        let stress = StressArgs {
+            edge_bits: KNOWN_PROOF_EDGE_BITS,
+            graphs: 2,
+            threads: 1,
+            trimming_rounds: 90,
+            max_memory: None,
+            replay_dir: None,
+            time_unit: TimeUnit::default(),
+            metrics_addr: None,
+        };
+        let (counters, registry, mut solver) = (StressCounters::default(), MetricsRegistry::new(), None);
+        let worker = registry.worker("thread-0");
+        for nonce in [KNOWN_PROOF_NONCE, KNOWN_PROOF_NONCE + 1] {
+            let header = Header::new_with_nonce(KNOWN_PROOF_HEADER, nonce);
+            stress_graph(&stress, &mut solver, &worker, &registry, &header, &counters, None);
+        }
+
+        // Found, and accepted by the consensus verifier, in the first graph only
+        assert_eq!(counters.solutions.load(Ordering::Relaxed), 1);
+        assert_eq!(counters.invalid_proofs.load(Ordering::Relaxed), 0);
+        assert_eq!(counters.errors.load(Ordering::Relaxed), 0);
+        assert_eq!(counters.panics.load(Ordering::Relaxed), 0);
+        assert_eq!(counters.setups.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_missing_solutions() {
+        assert!(missing_solutions(10_000, 0));
+        assert!(!missing_solutions(10_000, 1));
+        assert!(!missing_solutions(4_999, 0));
+    }
+}